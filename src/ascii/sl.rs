@@ -360,8 +360,8 @@ fn add_smoke(x: i32, y: i32) -> Result<()> {
         for i in 0..sum {
             let smoke = &mut smokes[i];
             move_print(smoke.x, smoke.y, ERASER[smoke.ptrn])?;
-            smoke.y -= DY[smoke.ptrn] as i32;
-            smoke.x += DX[smoke.ptrn] as i32;
+            smoke.y -= DY[smoke.ptrn];
+            smoke.x += DX[smoke.ptrn];
             smoke.ptrn += if smoke.ptrn < SMOKEPTNS - 1 { 1 } else { 0 };
             move_print(smoke.x, smoke.y, SMOKE[smoke.kind][smoke.ptrn])?;
         }
@@ -369,8 +369,8 @@ fn add_smoke(x: i32, y: i32) -> Result<()> {
         move_print(x, y, SMOKE[sum % 2][0])?;
 
         let smoke = &mut smokes[sum];
-        smoke.y = y as i32;
-        smoke.x = x as i32;
+        smoke.y = y;
+        smoke.x = x;
         smoke.ptrn = 0;
         smoke.kind = sum % 2;
         SUM.fetch_add(1, atomic::Ordering::Relaxed);
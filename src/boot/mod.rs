@@ -1,5 +1,6 @@
 use crate::terminal::Terminal;
 
+#[allow(clippy::module_inception)]
 pub mod boot;
 
 impl Terminal {
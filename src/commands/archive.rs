@@ -0,0 +1,1457 @@
+//! `tar` and `gzip`. Archives are ustar, compressed data is DEFLATE inside
+//! the gzip container, both written byte-for-byte as the real tools do,
+//! so a `.tar.gz` made here unpacks anywhere else.
+
+use std::collections::HashMap;
+
+use crate::commands::filesystem::{
+    normalize_path, Children, Filesystem, FsError, Inode, InodeId, Node, CURRENT_PATH, FILESYSTEM,
+};
+use crate::commands::permissions::{self, Access};
+use crate::commands::text;
+use crate::commands::users::{self, Session};
+
+/// Where modification ticks start counting: 2025-01-01 12:00 UTC, the
+/// same moment `stat` shows for tick 0.
+const EPOCH: u64 = 1_735_732_800;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW: usize = 32 * 1024;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash are tried per match.
+const MAX_CHAIN: usize = 64;
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Packs bits least significant first, as DEFLATE streams are laid out.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, len: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += len;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes go out most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+
+    /// A literal/length symbol from the fixed Huffman table.
+    fn symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn copy(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .unwrap_or(0);
+        self.symbol(257 + code as u32);
+        self.write(
+            (length - LENGTH_BASE[code] as usize) as u32,
+            LENGTH_EXTRA[code] as u32,
+        );
+
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap_or(0);
+        self.write_code(code as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASE[code] as usize) as u32,
+            DISTANCE_EXTRA[code] as u32,
+        );
+    }
+}
+
+/// Compresses `data` as a single DEFLATE block with the fixed Huffman
+/// codes, finding repeats through hash chains over three-byte prefixes.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & 0x7fff
+    };
+    let mut head = vec![usize::MAX; 1 << 15];
+    let mut previous = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut Vec<usize>, previous: &mut Vec<usize>| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut writer = BitWriter::default();
+    writer.write(1, 1); // final block
+    writer.write(1, 2); // fixed Huffman codes
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        if best.0 >= 3 {
+            writer.copy(best.0, best.1);
+            for position in i..i + best.0 {
+                insert(position, &mut head, &mut previous);
+            }
+            i += best.0;
+        } else {
+            writer.symbol(data[i] as u32);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    writer.symbol(256);
+    writer.finish()
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, &'static str> {
+        let mut value = 0;
+        for shift in 0..count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("unexpected end of file")?;
+            value |= ((byte >> self.bit) as u32 & 1) << shift;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code given by the bit length of each symbol.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<(u8, u16)> = lengths
+            .iter()
+            .enumerate()
+            .filter(|(_, &length)| length != 0)
+            .map(|(symbol, &length)| (length, symbol as u16))
+            .collect();
+        symbols.sort();
+        Huffman {
+            counts,
+            symbols: symbols.into_iter().map(|(_, symbol)| symbol).collect(),
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, &'static str> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid compressed data--format violated")
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, returning the data and how many
+/// input bytes it took up.
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), &'static str> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    const CORRUPT: &str = "invalid compressed data--format violated";
+
+    let mut reader = BitReader {
+        data,
+        position: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        let (literals, distances) = match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.position..reader.position + 4)
+                    .ok_or("unexpected end of file")?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(CORRUPT);
+                }
+                let start = reader.position + 4;
+                let stored = data
+                    .get(start..start + length as usize)
+                    .ok_or("unexpected end of file")?;
+                out.extend_from_slice(stored);
+                reader.position = start + length as usize;
+                if last {
+                    break;
+                }
+                continue;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+            }
+            2 => {
+                let literal_count = reader.bits(5)? as usize + 257;
+                let distance_count = reader.bits(5)? as usize + 1;
+                let code_count = reader.bits(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for &symbol in &ORDER[..code_count] {
+                    code_lengths[symbol] = reader.bits(3)? as u8;
+                }
+                let codes = Huffman::new(&code_lengths);
+
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (value, repeat) = match codes.decode(&mut reader)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 => (*lengths.last().ok_or(CORRUPT)?, 3 + reader.bits(2)?),
+                        17 => (0, 3 + reader.bits(3)?),
+                        _ => (0, 11 + reader.bits(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                }
+                if lengths.len() != literal_count + distance_count {
+                    return Err(CORRUPT);
+                }
+                (
+                    Huffman::new(&lengths[..literal_count]),
+                    Huffman::new(&lengths[literal_count..]),
+                )
+            }
+            _ => return Err(CORRUPT),
+        };
+
+        loop {
+            let symbol = literals.decode(&mut reader)? as usize;
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let code = symbol - 257;
+                    if code >= LENGTH_BASE.len() {
+                        return Err(CORRUPT);
+                    }
+                    let length = LENGTH_BASE[code] as usize
+                        + reader.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                    let code = distances.decode(&mut reader)? as usize;
+                    if code >= DISTANCE_BASE.len() {
+                        return Err(CORRUPT);
+                    }
+                    let distance = DISTANCE_BASE[code] as usize
+                        + reader.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err(CORRUPT);
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+        if last {
+            break;
+        }
+    }
+    reader.align();
+    Ok((out, reader.position))
+}
+
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+/// Wraps `data` in a gzip member, recording `name` as the original name.
+pub fn gzip_compress(data: &[u8], name: Option<&str>) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, if name.is_some() { 0x08 } else { 0 }];
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 3]); // no mtime, no extra flags, Unix
+    if let Some(name) = name {
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+    }
+    out.extend(deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Unpacks every gzip member in `data`, checking each one's CRC.
+pub fn gzip_decompress(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if !is_gzip(data) {
+        return Err("not in gzip format");
+    }
+    let mut out = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 18 || !is_gzip(rest) || rest[2] != 8 {
+            return Err("not in gzip format");
+        }
+        let flags = rest[3];
+        let mut position = 10;
+        if flags & 0x04 != 0 {
+            let extra = u16::from_le_bytes([rest[position], rest[position + 1]]) as usize;
+            position += 2 + extra;
+        }
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                position += rest
+                    .get(position..)
+                    .and_then(|tail| tail.iter().position(|&b| b == 0))
+                    .ok_or("unexpected end of file")?
+                    + 1;
+            }
+        }
+        if flags & 0x02 != 0 {
+            position += 2;
+        }
+
+        let (member, used) = inflate(rest.get(position..).ok_or("unexpected end of file")?)?;
+        let trailer = rest
+            .get(position + used..position + used + 8)
+            .ok_or("unexpected end of file")?;
+        if u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != crc32(&member) {
+            return Err("invalid compressed data--crc error");
+        }
+        out.extend(member);
+        rest = &rest[position + used + 8..];
+    }
+    Ok(out)
+}
+
+/// One member of a tar archive.
+struct Entry {
+    name: String,
+    mode: u16,
+    owner: String,
+    group: String,
+    mtime: u64,
+    kind: u8,
+    link: String,
+    data: Vec<u8>,
+}
+
+const BLOCK: usize = 512;
+
+fn put(header: &mut [u8], offset: usize, len: usize, value: &[u8]) {
+    let len = value.len().min(len);
+    header[offset..offset + len].copy_from_slice(&value[..len]);
+}
+
+fn put_octal(header: &mut [u8], offset: usize, len: usize, value: u64) {
+    put(
+        header,
+        offset,
+        len,
+        format!("{:0width$o}\0", value, width = len - 1).as_bytes(),
+    );
+}
+
+fn field(header: &[u8], offset: usize, len: usize) -> String {
+    let raw = &header[offset..offset + len];
+    let end = raw.iter().position(|&b| b == 0).unwrap_or(len);
+    String::from_utf8_lossy(&raw[..end]).into_owned()
+}
+
+fn octal_field(header: &[u8], offset: usize, len: usize) -> Option<u64> {
+    let text = field(header, offset, len);
+    let text = text.trim_matches(|c| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum()
+}
+
+/// Serialises `entries` as a ustar archive. Names longer than 100 bytes
+/// are split into the prefix field.
+fn pack(fs: &Filesystem, entries: &[Entry]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    for entry in entries {
+        let (prefix, name) = if entry.name.len() <= 100 {
+            ("", entry.name.as_str())
+        } else {
+            entry
+                .name
+                .char_indices()
+                .filter(|&(i, c)| c == '/' && i <= 155 && entry.name.len() - i - 1 <= 100)
+                .map(|(i, _)| (&entry.name[..i], &entry.name[i + 1..]))
+                .find(|(_, name)| !name.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "tar: {}: file name is too long (max 255); not dumped",
+                        entry.name
+                    )
+                })?
+        };
+
+        let id = |name: &str, lookup: fn(&Filesystem, &str) -> Option<u32>| {
+            lookup(fs, name).unwrap_or(0) as u64
+        };
+        let mut header = [0u8; BLOCK];
+        put(&mut header, 0, 100, name.as_bytes());
+        put_octal(&mut header, 100, 8, entry.mode as u64);
+        put_octal(
+            &mut header,
+            108,
+            8,
+            id(&entry.owner, |fs, name| {
+                users::find_user(fs, name).map(|user| user.uid)
+            }),
+        );
+        put_octal(
+            &mut header,
+            116,
+            8,
+            id(&entry.group, |fs, name| {
+                users::find_group(fs, name).map(|group| group.gid)
+            }),
+        );
+        put_octal(&mut header, 124, 12, entry.data.len() as u64);
+        put_octal(&mut header, 136, 12, entry.mtime);
+        header[156] = entry.kind;
+        put(&mut header, 157, 100, entry.link.as_bytes());
+        put(&mut header, 257, 8, b"ustar\x0000");
+        put(&mut header, 265, 32, entry.owner.as_bytes());
+        put(&mut header, 297, 32, entry.group.as_bytes());
+        put_octal(&mut header, 329, 8, 0);
+        put_octal(&mut header, 337, 8, 0);
+        put(&mut header, 345, 155, prefix.as_bytes());
+        let sum = checksum(&header);
+        put(&mut header, 148, 8, format!("{:06o}\0 ", sum).as_bytes());
+
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&entry.data);
+        out.resize(out.len().div_ceil(BLOCK) * BLOCK, 0);
+    }
+    // Two empty blocks end the archive.
+    out.resize(out.len() + 2 * BLOCK, 0);
+    Ok(out)
+}
+
+fn unpack(data: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut position = 0;
+    while position + BLOCK <= data.len() {
+        let header = &data[position..position + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            return Ok(entries);
+        }
+        if octal_field(header, 148, 8) != Some(checksum(header)) {
+            return Err(if entries.is_empty() {
+                "tar: This does not look like a tar archive".to_string()
+            } else {
+                "tar: Skipping to next header".to_string()
+            });
+        }
+
+        let prefix = field(header, 345, 155);
+        let name = field(header, 0, 100);
+        let size = octal_field(header, 124, 12).unwrap_or(0) as usize;
+        let start = position + BLOCK;
+        let content = data
+            .get(start..start + size)
+            .ok_or("tar: Unexpected EOF in archive")?;
+        entries.push(Entry {
+            name: if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            },
+            mode: octal_field(header, 100, 8).unwrap_or(0o644) as u16 & 0o7777,
+            owner: field(header, 265, 32),
+            group: field(header, 297, 32),
+            mtime: octal_field(header, 136, 12).unwrap_or(0),
+            kind: header[156],
+            link: field(header, 157, 100),
+            data: content.to_vec(),
+        });
+        position = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    if entries.is_empty() && !data.is_empty() {
+        return Err("tar: This does not look like a tar archive".to_string());
+    }
+    Ok(entries)
+}
+
+/// Gathers the archive entries for `node` and everything below it.
+/// `hard_links` remembers the first member name of each inode, so later
+/// names for it become hard links instead of second copies.
+struct Collector<'a> {
+    fs: &'a Filesystem,
+    session: &'a Session,
+    /// The archive being written, which must not archive itself.
+    archive: Option<InodeId>,
+    hard_links: HashMap<InodeId, String>,
+    entries: Vec<Entry>,
+    errors: Vec<String>,
+}
+
+impl Collector<'_> {
+    fn visit(&mut self, name: String, node: &Node) {
+        let fs = self.fs;
+        let entry = |kind: u8, link: String, data: Vec<u8>, mtime: u64| Entry {
+            name: name.clone(),
+            mode: node.get_permissions(&fs.inodes),
+            owner: node.get_owner(&fs.inodes).to_string(),
+            group: node.get_group(&fs.inodes).to_string(),
+            mtime: EPOCH + mtime,
+            kind,
+            link,
+            data,
+        };
+
+        match node {
+            Node::Directory { children, .. } => {
+                self.entries.push(Entry {
+                    name: format!("{}/", name.trim_end_matches('/')),
+                    ..entry(b'5', String::new(), Vec::new(), 0)
+                });
+                if !permissions::check(fs, node, self.session, Access::Read)
+                    || !permissions::check(fs, node, self.session, Access::Execute)
+                {
+                    self.errors.push(format!(
+                        "tar: {}: Cannot open: {}",
+                        name,
+                        FsError::PermissionDenied
+                    ));
+                    return;
+                }
+                for (child_name, child) in children {
+                    self.visit(
+                        format!("{}/{}", name.trim_end_matches('/'), child_name),
+                        child,
+                    );
+                }
+            }
+            Node::Symlink { target, .. } => {
+                self.entries
+                    .push(entry(b'2', target.clone(), Vec::new(), 0))
+            }
+            Node::File { inode } if Some(*inode) == self.archive => self
+                .errors
+                .push(format!("tar: {}: file is the archive; not dumped", name)),
+            Node::File { inode } if fs.inodes.generator(*inode).is_some_and(|g| g.device) => {
+                self.entries.push(entry(b'3', String::new(), Vec::new(), 0))
+            }
+            Node::File { inode } => {
+                let mtime = fs.inodes.modified(*inode);
+                if let Some(first) = self.hard_links.get(inode) {
+                    self.entries
+                        .push(entry(b'1', first.clone(), Vec::new(), mtime));
+                } else if !permissions::check(fs, node, self.session, Access::Read) {
+                    self.errors.push(format!(
+                        "tar: {}: Cannot open: {}",
+                        name,
+                        FsError::PermissionDenied
+                    ));
+                } else {
+                    self.hard_links.insert(*inode, name.clone());
+                    self.entries
+                        .push(entry(b'0', String::new(), fs.read_bytes(*inode), mtime));
+                }
+            }
+        }
+    }
+}
+
+/// Packs `node` and everything below it as a ustar archive whose members
+/// start with `name`. Entries that could not be read come back as
+/// messages in tar's wording, the archive is written without them.
+pub fn archive_tree(
+    fs: &Filesystem,
+    name: &str,
+    node: &Node,
+    session: &Session,
+) -> Result<(Vec<u8>, Vec<String>), String> {
+    let mut collector = Collector {
+        fs,
+        session,
+        archive: None,
+        hard_links: HashMap::new(),
+        entries: Vec::new(),
+        errors: Vec::new(),
+    };
+    collector.visit(name.to_string(), node);
+    Ok((pack(fs, &collector.entries)?, collector.errors))
+}
+
+/// Removes whatever non-directory is at `path`, as tar does before
+/// putting an extracted member in its place.
+fn unlink(fs: &mut Filesystem, path: &[String], session: &Session) -> Result<(), FsError> {
+    let canonical = permissions::can_remove(fs, path, session)?;
+    let (name, parent) = canonical.split_last().ok_or(FsError::PermissionDenied)?;
+    if let Some((Node::Directory { children, .. }, inodes)) = fs.split_mut(parent) {
+        if let Some(removed) = children.remove(name) {
+            inodes.release(&removed);
+        }
+    }
+    Ok(())
+}
+
+/// Adds `node` as `path`, which must not exist yet.
+fn insert(
+    fs: &mut Filesystem,
+    path: &[String],
+    session: &Session,
+    make: impl FnOnce(&mut Filesystem) -> Node,
+) -> Result<(), FsError> {
+    let (name, parent) = path.split_last().ok_or(FsError::AlreadyExists)?;
+    let parent = permissions::can_create(fs, parent, session)?;
+    let node = make(fs);
+    match fs.split_mut(&parent) {
+        Some((Node::Directory { children, .. }, _)) => {
+            children.insert(name.clone(), node);
+            Ok(())
+        }
+        _ => {
+            fs.inodes.release(&node);
+            Err(FsError::NotFound)
+        }
+    }
+}
+
+fn directory(mode: u16, session: &Session) -> Node {
+    Node::Directory {
+        children: Children::new(),
+        permissions: mode,
+        owner: session.user.clone(),
+        group: session.primary_group(),
+    }
+}
+
+/// Writes one member below `base`, creating missing parent directories.
+/// Root keeps the archived owners where they exist here.
+fn extract(
+    fs: &mut Filesystem,
+    base: &[String],
+    entry: &Entry,
+    session: &Session,
+) -> Result<(), String> {
+    let fail = |err: FsError| format!("tar: {}: Cannot open: {}", entry.name, err);
+    let parts: Vec<&str> = entry
+        .name
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.contains(&"..") {
+        return Err(format!("tar: {}: Member name contains '..'", entry.name));
+    }
+    let mut path = base.to_vec();
+    path.extend(parts.iter().map(|part| part.to_string()));
+    if path.len() == base.len() {
+        return Ok(());
+    }
+
+    for depth in base.len() + 1..path.len() {
+        match permissions::traverse(fs, &path[..depth], session) {
+            Ok(_) => {}
+            Err(FsError::NotFound) => {
+                insert(fs, &path[..depth], session, |_| directory(0o755, session)).map_err(fail)?
+            }
+            Err(err) => return Err(fail(err)),
+        }
+    }
+
+    let existing = match permissions::traverse(fs, &path, session) {
+        Ok(Node::Directory { .. }) => Some(true),
+        Ok(_) => Some(false),
+        Err(FsError::NotFound) => None,
+        Err(err) => return Err(fail(err)),
+    };
+    match (entry.kind, existing) {
+        (b'5', Some(true)) => return Ok(()),
+        (_, Some(true)) => return Err(fail(FsError::IsADirectory)),
+        (_, Some(false)) => unlink(fs, &path, session).map_err(fail)?,
+        (_, None) => {}
+    }
+
+    match entry.kind {
+        b'5' => insert(fs, &path, session, |_| directory(entry.mode, session)),
+        b'2' => insert(fs, &path, session, |_| Node::Symlink {
+            target: entry.link.clone(),
+            owner: session.user.clone(),
+        }),
+        b'1' => {
+            let mut target = base.to_vec();
+            target.extend(
+                entry
+                    .link
+                    .split('/')
+                    .filter(|part| !part.is_empty() && *part != ".")
+                    .map(str::to_string),
+            );
+            let inode = match permissions::traverse(fs, &target, session) {
+                Ok(Node::File { inode }) => *inode,
+                _ => {
+                    return Err(format!(
+                        "tar: {}: Cannot hard link to '{}': {}",
+                        entry.name,
+                        entry.link,
+                        FsError::NotFound
+                    ))
+                }
+            };
+            insert(fs, &path, session, |fs| fs.inodes.link(inode))
+        }
+        b'3' | b'4' | b'6' => {
+            return Err(format!(
+                "tar: {}: Cannot mknod: Operation not permitted",
+                entry.name
+            ))
+        }
+        _ => insert(fs, &path, session, |fs| {
+            fs.inodes.create(Inode {
+                content: entry.data.clone(),
+                permissions: entry.mode,
+                owner: session.user.clone(),
+                group: session.primary_group(),
+            })
+        }),
+    }
+    .map_err(fail)?;
+
+    if session.is_root() && entry.kind != b'2' {
+        let owner = users::find_user(fs, &entry.owner).map(|user| user.name);
+        let group = users::find_group(fs, &entry.group).map(|group| group.name);
+        if let Some((node, inodes)) = fs.split_mut(&path) {
+            if let Some(owner) = owner {
+                node.set_owner(inodes, &owner);
+            }
+            if let Some(group) = group {
+                node.set_group(inodes, &group);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A `tar -tv` line: mode, owner/group, size, date and name.
+fn describe(entry: &Entry) -> String {
+    let kind = match entry.kind {
+        b'5' => 'd',
+        b'1' => 'h',
+        b'2' => 'l',
+        b'3' => 'c',
+        _ => '-',
+    };
+    let mut mode = String::from(kind);
+    for shift in [6, 3, 0] {
+        let bits = entry.mode >> shift;
+        mode.push(if bits & 4 != 0 { 'r' } else { '-' });
+        mode.push(if bits & 2 != 0 { 'w' } else { '-' });
+        mode.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    let tick = entry.mtime.saturating_sub(EPOCH);
+    let name = match entry.kind {
+        b'2' => format!("{} -> {}", entry.name, entry.link),
+        b'1' => format!("{} link to {}", entry.name, entry.link),
+        _ => entry.name.clone(),
+    };
+    format!(
+        "{} {}/{} {:>8} 2025-01-01 12:{:02} {}",
+        mode,
+        entry.owner,
+        entry.group,
+        entry.data.len(),
+        (tick / 60) % 60,
+        name
+    )
+}
+
+/// `tar -c|-x|-t [-v] [-z] -f ARCHIVE [-C DIR] [FILE...]`. Flags may be
+/// bundled with or without the dash (`tar czf out.tgz dir`). Reading
+/// detects gzip by itself; `-z` only matters when creating.
+pub fn tar(args: &[&str]) -> String {
+    let mut action = None;
+    let mut verbose = false;
+    let mut compress = false;
+    let mut archive: Option<String> = None;
+    let mut directory: Option<String> = None;
+    let mut operands: Vec<&str> = Vec::new();
+
+    let mut rest = args;
+    let mut first = true;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let bundle = match arg {
+            "--create" => "c",
+            "--extract" | "--get" => "x",
+            "--list" => "t",
+            "--verbose" => "v",
+            "--gzip" => "z",
+            long if long.starts_with("--file=") => {
+                archive = Some(long["--file=".len()..].to_string());
+                continue;
+            }
+            long if long.starts_with("--directory=") => {
+                directory = Some(long["--directory=".len()..].to_string());
+                continue;
+            }
+            long if long.starts_with("--") => {
+                return format!("tar: unrecognized option '{}'", long)
+            }
+            short if short.starts_with('-') && short.len() > 1 => &short[1..],
+            // Only the first word may be a bundle without a dash.
+            old if first && !old.is_empty() => old,
+            operand => {
+                operands.push(operand);
+                continue;
+            }
+        };
+        let dashed = arg.starts_with('-');
+        first = false;
+
+        for (index, flag) in bundle.char_indices() {
+            match flag {
+                'c' | 'x' | 't' => {
+                    if action.is_some_and(|action| action != flag) {
+                        return "tar: You may not specify more than one '-Acdtrux', '--delete' or  '--test-label' option".to_string();
+                    }
+                    action = Some(flag);
+                }
+                'v' => verbose = true,
+                'z' => compress = true,
+                'f' | 'C' => {
+                    let attached = &bundle[index + 1..];
+                    let value = if dashed && !attached.is_empty() {
+                        attached.to_string()
+                    } else if let Some((&next, tail)) = rest.split_first() {
+                        rest = tail;
+                        next.to_string()
+                    } else {
+                        return format!("tar: option requires an argument -- '{}'", flag);
+                    };
+                    if flag == 'f' {
+                        archive = Some(value);
+                    } else {
+                        directory = Some(value);
+                    }
+                    if dashed {
+                        break;
+                    }
+                }
+                _ => return format!("tar: invalid option -- '{}'", flag),
+            }
+        }
+    }
+
+    let action = match action {
+        Some(action) => action,
+        None => {
+            return "tar: You must specify one of the '-Acdtrux', '--delete' or '--test-label' options".to_string()
+        }
+    };
+    let archive = match archive {
+        Some(archive) => archive,
+        None if action == 'c' => {
+            return "tar: Refusing to write archive contents to terminal (missing -f option?)"
+                .to_string()
+        }
+        None => {
+            return "tar: Refusing to read archive contents from terminal (missing -f option?)"
+                .to_string()
+        }
+    };
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap().clone();
+    let session = users::current_session();
+    let archive_path = normalize_path(&archive, &current_path);
+    let base = match &directory {
+        Some(directory) => normalize_path(directory, &current_path),
+        None => current_path.clone(),
+    };
+
+    let mut output = Vec::new();
+    if action == 'c' {
+        if operands.is_empty() {
+            return "tar: Cowardly refusing to create an empty archive".to_string();
+        }
+        let mut collector = Collector {
+            fs: &filesystem,
+            session: &session,
+            archive: match permissions::resolve(&filesystem, &archive_path, &session) {
+                Ok((_, Node::File { inode })) => Some(*inode),
+                _ => None,
+            },
+            hard_links: HashMap::new(),
+            entries: Vec::new(),
+            errors: Vec::new(),
+        };
+        let mut stripped = false;
+        for operand in &operands {
+            let path = normalize_path(operand, &base);
+            let name = match operand.trim_start_matches('/') {
+                _ if operand.trim_matches('/').is_empty() => ".".to_string(),
+                name => {
+                    stripped |= name.len() != operand.len();
+                    name.trim_end_matches('/').to_string()
+                }
+            };
+            match permissions::traverse(&filesystem, &path, &session) {
+                Ok(node) => collector.visit(name, node),
+                Err(err) => collector
+                    .errors
+                    .push(format!("tar: {}: Cannot stat: {}", operand, err)),
+            }
+        }
+        if stripped {
+            output.push("tar: Removing leading `/' from member names".to_string());
+        }
+        let Collector {
+            entries, errors, ..
+        } = collector;
+        if verbose {
+            output.extend(entries.iter().map(|entry| entry.name.clone()));
+        }
+        output.extend(errors);
+
+        let packed = match pack(&filesystem, &entries) {
+            Ok(packed) => packed,
+            Err(err) => return err,
+        };
+        let bytes = if compress {
+            gzip_compress(&packed, None)
+        } else {
+            packed
+        };
+        if let Err(err) = text::write_file(&mut filesystem, &archive_path, bytes, &session) {
+            output.push(format!("tar: {}: Cannot open: {}", archive, err));
+        }
+        return output.join("\n");
+    }
+
+    let mut data = match text::read_bytes(&filesystem, &archive_path, &session) {
+        Ok(data) => data,
+        Err(err) => return format!("tar: {}: Cannot open: {}", archive, err),
+    };
+    if is_gzip(&data) {
+        data = match gzip_decompress(&data) {
+            Ok(data) => data,
+            Err(err) => {
+                return format!(
+                    "gzip: stdin: {}\ntar: Child returned status 1\ntar: Error is not recoverable: exiting now",
+                    err
+                )
+            }
+        };
+    }
+    let entries = match unpack(&data) {
+        Ok(entries) => entries,
+        Err(err) => {
+            return format!(
+                "{}\ntar: Exiting with failure status due to previous errors",
+                err
+            )
+        }
+    };
+
+    let wanted = |entry: &Entry| {
+        let name = entry.name.trim_end_matches('/');
+        operands.is_empty()
+            || operands.iter().any(|operand| {
+                let operand = operand.trim_end_matches('/');
+                name == operand || name.starts_with(&format!("{}/", operand))
+            })
+    };
+    let mut failed = false;
+    for entry in entries.iter().filter(|entry| wanted(entry)) {
+        if action == 't' {
+            output.push(if verbose {
+                describe(entry)
+            } else {
+                entry.name.clone()
+            });
+            continue;
+        }
+        if verbose {
+            output.push(entry.name.clone());
+        }
+        if let Err(err) = extract(&mut filesystem, &base, entry, &session) {
+            output.push(err);
+            failed = true;
+        }
+    }
+    for operand in &operands {
+        if !entries.iter().any(|entry| {
+            let name = entry.name.trim_end_matches('/');
+            let operand = operand.trim_end_matches('/');
+            name == operand || name.starts_with(&format!("{}/", operand))
+        }) {
+            output.push(format!("tar: {}: Not found in archive", operand));
+            failed = true;
+        }
+    }
+    if failed {
+        output.push("tar: Exiting with failure status due to previous errors".to_string());
+    }
+    output.join("\n")
+}
+
+const NOT_TO_TERMINAL: &str = "gzip: compressed data not written to a terminal. Use -f to force compression.\nFor help, type: gzip -h\n";
+
+/// Adds one line of messages to gzip's output.
+fn say(output: &mut Vec<u8>, message: String) {
+    output.extend(message.into_bytes());
+    output.push(b'\n');
+}
+
+/// `gzip [-d] [-c] [-k] [-f] [-l] [-v] FILE...`: replaces each file with
+/// a compressed `FILE.gz` (or back again with `-d`), keeping its mode.
+/// What it writes to stdout is bytes, as `-dc` may unpack binary data.
+/// Compressed data only goes to the terminal with `-f`.
+pub fn gzip(args: &[&str], stdin: Option<&[u8]>, to_terminal: bool) -> Vec<u8> {
+    let mut decompress = false;
+    let mut to_stdout = false;
+    let mut keep = false;
+    let mut force = false;
+    let mut list = false;
+    let mut verbose = false;
+    let mut operands = Vec::new();
+    for &arg in args {
+        let flags = match arg {
+            "--decompress" | "--uncompress" => "d",
+            "--stdout" => "c",
+            "--keep" => "k",
+            "--force" => "f",
+            "--list" => "l",
+            "--verbose" => "v",
+            long if long.starts_with("--") => {
+                return format!("gzip: unrecognized option '{}'\n", long).into_bytes()
+            }
+            short if short.starts_with('-') && short.len() > 1 => &short[1..],
+            operand => {
+                operands.push(operand);
+                continue;
+            }
+        };
+        for flag in flags.chars() {
+            match flag {
+                'd' => decompress = true,
+                'c' => to_stdout = true,
+                'k' => keep = true,
+                'f' => force = true,
+                'l' => list = true,
+                'v' => verbose = true,
+                '1'..='9' => {}
+                _ => return format!("gzip: invalid option -- '{}'\n", flag).into_bytes(),
+            }
+        }
+    }
+
+    let compressing_to_stdout = !decompress && !list && (to_stdout || operands.is_empty());
+    if compressing_to_stdout && to_terminal && !force {
+        return NOT_TO_TERMINAL.as_bytes().to_vec();
+    }
+    if operands.is_empty() {
+        if compressing_to_stdout {
+            return gzip_compress(stdin.unwrap_or(b""), None);
+        }
+        return match gzip_decompress(stdin.unwrap_or(b"")) {
+            Ok(data) => data,
+            Err(err) => format!("gzip: stdin: {}\n", err).into_bytes(),
+        };
+    }
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap().clone();
+    let session = users::current_session();
+
+    let mut output = Vec::new();
+    if list {
+        say(
+            &mut output,
+            "         compressed        uncompressed  ratio uncompressed_name".to_string(),
+        );
+    }
+    for operand in operands {
+        let path = normalize_path(operand, &current_path);
+        let (canonical, mode) = match permissions::resolve(&filesystem, &path, &session) {
+            Ok((canonical, node @ Node::File { .. })) => {
+                (canonical, node.get_permissions(&filesystem.inodes))
+            }
+            Ok(_) => {
+                say(
+                    &mut output,
+                    format!("gzip: {} is a directory -- ignored", operand),
+                );
+                continue;
+            }
+            Err(err) => {
+                say(&mut output, format!("gzip: {}: {}", operand, err));
+                continue;
+            }
+        };
+        let data = match text::read_bytes(&filesystem, &canonical, &session) {
+            Ok(data) => data,
+            Err(err) => {
+                say(&mut output, format!("gzip: {}: {}", operand, err));
+                continue;
+            }
+        };
+
+        let (target, converted) = if decompress || list {
+            let target = if let Some(stem) = operand.strip_suffix(".gz") {
+                stem.to_string()
+            } else if let Some(stem) = operand.strip_suffix(".tgz") {
+                format!("{}.tar", stem)
+            } else {
+                say(
+                    &mut output,
+                    format!("gzip: {}: unknown suffix -- ignored", operand),
+                );
+                continue;
+            };
+            match gzip_decompress(&data) {
+                Ok(plain) => (target, plain),
+                Err(err) => {
+                    say(&mut output, format!("gzip: {}: {}", operand, err));
+                    continue;
+                }
+            }
+        } else {
+            if operand.ends_with(".gz") || operand.ends_with(".tgz") {
+                say(
+                    &mut output,
+                    format!("gzip: {} already has .gz suffix -- unchanged", operand),
+                );
+                continue;
+            }
+            let name = canonical.last().map(String::as_str);
+            (format!("{}.gz", operand), gzip_compress(&data, name))
+        };
+
+        let (compressed, plain) = if decompress || list {
+            (data.len(), converted.len())
+        } else {
+            (converted.len(), data.len())
+        };
+        let ratio = if plain == 0 {
+            0.0
+        } else {
+            100.0 * (1.0 - compressed as f64 / plain as f64)
+        };
+        if list {
+            say(
+                &mut output,
+                format!(
+                    "{:>19} {:>19} {:>5.1}% {}",
+                    compressed, plain, ratio, target
+                ),
+            );
+            continue;
+        }
+        if to_stdout {
+            output.extend(converted);
+            continue;
+        }
+
+        let target_path = normalize_path(&target, &current_path);
+        if permissions::traverse(&filesystem, &target_path, &session).is_ok() {
+            if !force {
+                say(
+                    &mut output,
+                    format!("gzip: {} already exists; not overwritten", target),
+                );
+                continue;
+            }
+            if let Err(err) = unlink(&mut filesystem, &target_path, &session) {
+                say(&mut output, format!("gzip: {}: {}", target, err));
+                continue;
+            }
+        }
+        if let Err(err) = text::write_file(&mut filesystem, &target_path, converted, &session) {
+            say(&mut output, format!("gzip: {}: {}", target, err));
+            continue;
+        }
+        if let Some((node, inodes)) = filesystem.split_mut(&target_path) {
+            node.set_permissions(inodes, mode);
+        }
+        if !keep {
+            if let Err(err) = unlink(&mut filesystem, &path, &session) {
+                say(&mut output, format!("gzip: {}: {}", operand, err));
+            }
+        }
+        if verbose {
+            let verb = if keep { "created" } else { "replaced with" };
+            say(
+                &mut output,
+                format!("{}:\t{:5.1}% -- {} {}", operand, ratio, verb, target),
+            );
+        }
+    }
+    output
+}
+
+/// `gunzip FILE...`, the same as `gzip -d`.
+pub fn gunzip(args: &[&str], stdin: Option<&[u8]>, to_terminal: bool) -> Vec<u8> {
+    let mut args = args.to_vec();
+    args.insert(0, "-d");
+    gzip(&args, stdin, to_terminal)
+}
+
+/// `zcat FILE...`, the same as `gzip -dc`.
+pub fn zcat(args: &[&str], stdin: Option<&[u8]>, to_terminal: bool) -> Vec<u8> {
+    let mut args = args.to_vec();
+    args.insert(0, "-dc");
+    gzip(&args, stdin, to_terminal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that compress poorly, from a small linear congruential generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn deflate_round_trips() {
+        let text: String = (0..500)
+            .map(|i| format!("row {} row {}\n", i % 7, i))
+            .collect();
+        let inputs = vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabcabcabcabc".to_vec(),
+            vec![0; 100_000],
+            text.into_bytes(),
+            noise(70_000),
+        ];
+        for input in inputs {
+            let compressed = deflate(&input);
+            assert_eq!(inflate(&compressed), Ok((input.clone(), compressed.len())));
+            assert_eq!(
+                gzip_decompress(&gzip_compress(&input, Some("x"))),
+                Ok(input)
+            );
+        }
+        assert!(deflate(&[0; 100_000]).len() < 1_000);
+    }
+
+    #[test]
+    fn inflates_what_zlib_writes() {
+        // A fixed-Huffman block and a dynamic one, both from zlib at level 9.
+        let fixed = [75, 76, 42, 74, 76, 78, 76, 73, 4, 82, 10, 137, 216, 217, 0];
+        assert_eq!(
+            inflate(&fixed).unwrap().0,
+            b"abracadabra abracadabra abracadabra"
+        );
+
+        let text: String = (0..200)
+            .map(|i| format!("line {} of the test\n", i))
+            .collect();
+        let dynamic = unhex("6dd7394e04411444419f53f4112a6baf030d02690406737f214cd4e17eab43b3e4ebe7e7d7e32ad7f7fbf5fa785cafc7cfebedf977cafd54efa7763ff5fb69dc4ff37e5af7d3be9f0e1e558f8fe70f0081202004860011280246e0a870547d0e7054382a1c158e0a4785a3c251e1687034389abe507034381a1c0d8e064783a3c1d1e1e8707438ba7e197074383a1c1d8e0e478763c031e018700c38867ee2700c38061c038e01c78463c231e198704c38a6feabe098704c38261c0b8e05c78263c1b1e058702cfde9c2b1e058706c38361c1b8e0dc78663c3b1e1d85a0f38361c078e03c781e3c071e038701c380e1c4733c81dd410162d61d114166d61d11816ad61d11c16ed61d12016893ced1271dcb9ee9c77ee3b079e0bcf89d7c647239fca5a91483b1f0d7db4f4d1d4475b1f8d7db4f6d1dc477b9fc6009348931f6d7e34fad1ea47b31fed7e34fcd1f247d39fcea69448eb1fcd7fb4ff51004405102540d4005104441590c14c9648211095409402510b443110d5409403510f444190c9f297484d10454154055116445d1085415406511a446d90c59719899407511f44811015429408512344911055429409d97c3f9348a510a542d40a512c44b510e542d40b513044c590c357ceffa25f");
+        assert_eq!(
+            inflate(&dynamic).unwrap(),
+            (text.into_bytes(), dynamic.len())
+        );
+    }
+
+    #[test]
+    fn reads_gzip_members() {
+        let member = [
+            31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 215, 81, 200, 64, 162, 20,
+            210, 171, 50, 11, 184, 0, 42, 8, 68, 166, 25, 0, 0, 0,
+        ];
+        let expected = b"hello, hello, hello gzip\n";
+        assert_eq!(gzip_decompress(&member).unwrap(), expected);
+
+        // Concatenated members decompress to the concatenation.
+        let twice = [&member[..], &member[..]].concat();
+        assert_eq!(
+            gzip_decompress(&twice).unwrap(),
+            [&expected[..], &expected[..]].concat()
+        );
+
+        let mut corrupt = member.to_vec();
+        corrupt[member.len() - 8] ^= 1;
+        assert_eq!(
+            gzip_decompress(&corrupt),
+            Err("invalid compressed data--crc error")
+        );
+        assert!(gzip_decompress(&member[..20]).is_err());
+        assert!(gzip_decompress(b"plain text").is_err());
+    }
+
+    fn entry(name: &str, kind: u8, data: &[u8]) -> Entry {
+        Entry {
+            name: name.to_string(),
+            mode: 0o640,
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mtime: 1_700_000_000,
+            kind,
+            link: if kind == b'2' {
+                "target".to_string()
+            } else {
+                String::new()
+            },
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn tar_round_trips() {
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let entries = vec![
+            entry("dir/", b'5', b""),
+            entry("dir/file.txt", b'0', b"hello\n"),
+            entry("dir/link", b'2', b""),
+            entry(&long, b'0', &noise(1_500)),
+        ];
+        let fs = FILESYSTEM.lock().unwrap();
+        let packed = pack(&fs, &entries).unwrap();
+        drop(fs);
+        assert_eq!(packed.len() % BLOCK, 0);
+        assert_eq!(&packed[257..263], b"ustar\0");
+
+        let unpacked = unpack(&packed).unwrap();
+        assert_eq!(unpacked.len(), entries.len());
+        for (got, want) in unpacked.iter().zip(&entries) {
+            assert_eq!(got.name, want.name);
+            assert_eq!(got.mode, want.mode);
+            assert_eq!((&got.owner, &got.group), (&want.owner, &want.group));
+            assert_eq!(got.mtime, want.mtime);
+            assert_eq!(got.kind, want.kind);
+            assert_eq!(got.link, want.link);
+            assert_eq!(got.data, want.data);
+        }
+    }
+
+    #[test]
+    fn tar_rejects_what_it_cannot_hold_or_read() {
+        let fs = FILESYSTEM.lock().unwrap();
+        let too_long = entry(&"n".repeat(300), b'0', b"");
+        assert!(pack(&fs, &[too_long]).is_err());
+        drop(fs);
+
+        assert_eq!(
+            unpack(&[1; BLOCK * 2]).err().unwrap(),
+            "tar: This does not look like a tar archive"
+        );
+        assert!(unpack(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,2240 @@
+use std::collections::HashMap;
+
+use crate::commands::regex::{Regex, Syntax};
+use crate::commands::text;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+    /// A builtin function name; `length` may also appear without parens.
+    Builtin(String),
+    Sym(&'static str),
+    Newline,
+    Eof,
+}
+
+const KEYWORDS: &[&str] = &[
+    "BEGIN", "END", "if", "else", "while", "for", "do", "break", "continue", "next", "exit",
+    "print", "printf", "delete", "in", "getline",
+];
+
+const BUILTINS: &[&str] = &[
+    "length", "substr", "index", "split", "sub", "gsub", "match", "sprintf", "tolower", "toupper",
+    "int", "sqrt", "exp", "log", "sin", "cos", "atan2",
+];
+
+// Longest first, so that `**=` wins over `**` and `*`.
+const SYMBOLS: &[&str] = &[
+    "**=", "&&", "||", "==", "!=", "<=", ">=", "!~", "++", "--", "+=", "-=", "*=", "/=", "%=",
+    "^=", "**", "{", "}", "(", ")", "[", "]", ";", ",", "+", "-", "*", "/", "%", "^", "!", "<",
+    ">", "~", "?", ":", "=", "$",
+];
+
+fn lex(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\r' => i += 1,
+            '\\' if chars.get(i + 1) == Some(&'\n') => {
+                i += 2;
+                line += 1;
+            }
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+                i += 1;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(format!("awk: line {}: non-terminated string", line))
+                        }
+                        Some('"') => break,
+                        Some('\\') => {
+                            i += 1;
+                            match chars.get(i) {
+                                Some('n') => text.push('\n'),
+                                Some('t') => text.push('\t'),
+                                Some('r') => text.push('\r'),
+                                Some('\\') => text.push('\\'),
+                                Some('"') => text.push('"'),
+                                Some('/') => text.push('/'),
+                                Some(&other) => {
+                                    text.push('\\');
+                                    text.push(other);
+                                }
+                                None => continue,
+                            }
+                        }
+                        Some(&other) => text.push(other),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Str(text), line));
+            }
+            '/' if regex_allowed(tokens.last().map(|(token, _)| token)) => {
+                let mut pattern = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => {
+                            return Err(format!(
+                                "awk: line {}: non-terminated regular expression",
+                                line
+                            ))
+                        }
+                        Some('/') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                            pattern.push('/');
+                            i += 1;
+                        }
+                        Some('\\') => {
+                            pattern.push('\\');
+                            if let Some(&next) = chars.get(i + 1) {
+                                pattern.push(next);
+                                i += 1;
+                            }
+                        }
+                        Some(&other) => pattern.push(other),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                tokens.push((Token::Regex(pattern), line));
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let value = literal
+                    .parse()
+                    .map_err(|_| format!("awk: line {}: bad number '{}'", line, literal))?;
+                tokens.push((Token::Number(value), line));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = if BUILTINS.contains(&word.as_str()) {
+                    Token::Builtin(word)
+                } else {
+                    Token::Name(word)
+                };
+                tokens.push((token, line));
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| rest.starts_with(*symbol))
+                    .ok_or_else(|| format!("awk: line {}: syntax error at or near {}", line, c))?;
+                i += symbol.chars().count();
+                let symbol = match *symbol {
+                    "**" => "^",
+                    "**=" => "^=",
+                    other => other,
+                };
+                tokens.push((Token::Sym(symbol), line));
+            }
+        }
+    }
+
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+/// A `/` starts a regex unless it follows something that ends an operand.
+fn regex_allowed(previous: Option<&Token>) -> bool {
+    match previous {
+        Some(Token::Number(_) | Token::Str(_) | Token::Regex(_) | Token::Builtin(_)) => false,
+        Some(Token::Name(name)) => KEYWORDS.contains(&name.as_str()),
+        Some(Token::Sym(symbol)) => !matches!(*symbol, ")" | "]" | "$" | "++" | "--"),
+        _ => true,
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(f64),
+    Str(String),
+    /// A regex literal; on its own it means `$0 ~ /re/`.
+    Regex(String),
+    Var(String),
+    Index(String, Vec<Expr>),
+    Field(Box<Expr>),
+    /// `(a, b)`, only meaningful before `in` or as print's argument list.
+    Group(Vec<Expr>),
+    Assign(Box<Expr>, Option<&'static str>, Box<Expr>),
+    IncDec {
+        target: Box<Expr>,
+        delta: f64,
+        prefix: bool,
+    },
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    Match(bool, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    In(Vec<Expr>, String),
+    Call(String, Vec<Expr>),
+    /// `getline [var] [< file]`.
+    Getline(Option<Box<Expr>>, Option<Box<Expr>>),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Expr(Expr),
+    Print(Vec<Expr>),
+    Printf(Vec<Expr>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    DoWhile(Box<Stmt>, Expr),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    ForIn(String, String, Box<Stmt>),
+    Block(Vec<Stmt>),
+    Delete(String, Option<Vec<Expr>>),
+    Next,
+    Exit(Option<Expr>),
+    Break,
+    Continue,
+}
+
+enum Pattern {
+    Begin,
+    End,
+    All,
+    Expr(Expr),
+    Range(Expr, Expr),
+}
+
+struct Rule {
+    pattern: Pattern,
+    /// None means `{ print }`.
+    action: Option<Vec<Stmt>>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Set while parsing print's arguments, where a bare `>` would be an
+    /// output redirection rather than a comparison.
+    in_print: bool,
+}
+
+type Parsed<T> = Result<T, String>;
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self) -> Parsed<T> {
+        let (token, line) = &self.tokens[self.pos];
+        let near = match token {
+            Token::Number(n) => format_number(*n),
+            Token::Str(s) => format!("\"{}\"", s),
+            Token::Regex(r) => format!("/{}/", r),
+            Token::Name(n) | Token::Builtin(n) => n.clone(),
+            Token::Sym(s) => s.to_string(),
+            Token::Newline => "end of line".to_string(),
+            Token::Eof => "end of program".to_string(),
+        };
+        Err(format!(
+            "awk: line {}: syntax error at or near {}",
+            line, near
+        ))
+    }
+
+    fn is_sym(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Token::Sym(s) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Name(n) if n == keyword)
+    }
+
+    fn eat_sym(&mut self, symbol: &str) -> bool {
+        if self.is_sym(symbol) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, symbol: &str) -> Parsed<()> {
+        if self.eat_sym(symbol) {
+            Ok(())
+        } else {
+            self.error()
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Token::Newline {
+            self.advance();
+        }
+    }
+
+    fn skip_terminators(&mut self) {
+        while *self.peek() == Token::Newline || self.is_sym(";") {
+            self.advance();
+        }
+    }
+
+    fn program(&mut self) -> Parsed<Vec<Rule>> {
+        let mut rules = Vec::new();
+        loop {
+            self.skip_terminators();
+            if *self.peek() == Token::Eof {
+                return Ok(rules);
+            }
+
+            let pattern = if self.is_keyword("BEGIN") {
+                self.advance();
+                Pattern::Begin
+            } else if self.is_keyword("END") {
+                self.advance();
+                Pattern::End
+            } else if self.is_sym("{") {
+                Pattern::All
+            } else {
+                let first = self.expr()?;
+                if self.eat_sym(",") {
+                    self.skip_newlines();
+                    Pattern::Range(first, self.expr()?)
+                } else {
+                    Pattern::Expr(first)
+                }
+            };
+
+            let action = if self.is_sym("{") {
+                Some(self.block()?)
+            } else if matches!(pattern, Pattern::Begin | Pattern::End) {
+                return self.error();
+            } else {
+                None
+            };
+            rules.push(Rule { pattern, action });
+        }
+    }
+
+    fn block(&mut self) -> Parsed<Vec<Stmt>> {
+        self.expect_sym("{")?;
+        let mut statements = Vec::new();
+        loop {
+            self.skip_terminators();
+            if self.eat_sym("}") {
+                return Ok(statements);
+            }
+            statements.push(self.statement()?);
+        }
+    }
+
+    /// Ends a simple statement: `;`, a newline, or a following `}`.
+    fn end_simple(&mut self) -> Parsed<()> {
+        match self.peek() {
+            Token::Newline | Token::Eof => Ok(()),
+            Token::Sym(";") => {
+                self.advance();
+                Ok(())
+            }
+            Token::Sym("}") => Ok(()),
+            _ => self.error(),
+        }
+    }
+
+    fn body(&mut self) -> Parsed<Box<Stmt>> {
+        self.skip_newlines();
+        if self.eat_sym(";") {
+            return Ok(Box::new(Stmt::Block(Vec::new())));
+        }
+        Ok(Box::new(self.statement()?))
+    }
+
+    fn condition(&mut self) -> Parsed<Expr> {
+        self.expect_sym("(")?;
+        let condition = self.expr()?;
+        self.expect_sym(")")?;
+        Ok(condition)
+    }
+
+    fn statement(&mut self) -> Parsed<Stmt> {
+        if self.is_sym("{") {
+            return Ok(Stmt::Block(self.block()?));
+        }
+
+        let keyword = match self.peek() {
+            // `getline` is an expression, so it starts a simple statement.
+            Token::Name(name) if KEYWORDS.contains(&name.as_str()) && name != "getline" => {
+                name.clone()
+            }
+            _ => {
+                let expr = self.expr()?;
+                self.end_simple()?;
+                return Ok(Stmt::Expr(expr));
+            }
+        };
+        self.advance();
+
+        let statement = match keyword.as_str() {
+            "if" => {
+                let condition = self.condition()?;
+                let then = self.body()?;
+                // `else` may follow on a later line or after a `;`.
+                let saved = self.pos;
+                self.skip_terminators();
+                if self.is_keyword("else") {
+                    self.advance();
+                    let otherwise = self.body()?;
+                    return Ok(Stmt::If(condition, then, Some(otherwise)));
+                }
+                self.pos = saved;
+                return Ok(Stmt::If(condition, then, None));
+            }
+            "while" => {
+                let condition = self.condition()?;
+                return Ok(Stmt::While(condition, self.body()?));
+            }
+            "do" => {
+                let body = self.body()?;
+                self.skip_terminators();
+                if !self.is_keyword("while") {
+                    return self.error();
+                }
+                self.advance();
+                let condition = self.condition()?;
+                Stmt::DoWhile(body, condition)
+            }
+            "for" => {
+                self.expect_sym("(")?;
+                if let (
+                    Token::Name(var),
+                    Token::Name(keyword),
+                    Token::Name(array),
+                    Token::Sym(")"),
+                ) = (
+                    self.peek().clone(),
+                    self.tokens[self.pos + 1].0.clone(),
+                    self.tokens
+                        .get(self.pos + 2)
+                        .map_or(Token::Eof, |t| t.0.clone()),
+                    self.tokens
+                        .get(self.pos + 3)
+                        .map_or(Token::Eof, |t| t.0.clone()),
+                ) {
+                    if keyword == "in" {
+                        self.pos += 4;
+                        return Ok(Stmt::ForIn(var, array, self.body()?));
+                    }
+                }
+                let init = if self.is_sym(";") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.expect_sym(";")?;
+                self.skip_newlines();
+                let condition = if self.is_sym(";") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.expect_sym(";")?;
+                self.skip_newlines();
+                let step = if self.is_sym(")") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.expect_sym(")")?;
+                return Ok(Stmt::For(init, condition, step, self.body()?));
+            }
+            "print" | "printf" => {
+                let mut args = Vec::new();
+                if !matches!(
+                    self.peek(),
+                    Token::Newline | Token::Eof | Token::Sym(";" | "}")
+                ) {
+                    self.in_print = true;
+                    let parsed = self.expr_list();
+                    self.in_print = false;
+                    args = parsed?;
+                }
+                // `print (a, b)` is the same as `print a, b`.
+                if let [Expr::Group(items)] = args.as_slice() {
+                    args = items.clone();
+                }
+                if self.is_sym(">") || self.is_sym("|") {
+                    let line = self.tokens[self.pos].1;
+                    return Err(format!(
+                        "awk: line {}: output redirection is not supported",
+                        line
+                    ));
+                }
+                if keyword == "print" {
+                    Stmt::Print(args)
+                } else if args.is_empty() {
+                    return self.error();
+                } else {
+                    Stmt::Printf(args)
+                }
+            }
+            "delete" => {
+                let name = match self.advance() {
+                    Token::Name(name) => name,
+                    _ => return self.error(),
+                };
+                let keys = if self.eat_sym("[") {
+                    let keys = self.expr_list()?;
+                    self.expect_sym("]")?;
+                    Some(keys)
+                } else {
+                    None
+                };
+                Stmt::Delete(name, keys)
+            }
+            "next" => Stmt::Next,
+            "break" => Stmt::Break,
+            "continue" => Stmt::Continue,
+            "exit" => {
+                if matches!(
+                    self.peek(),
+                    Token::Newline | Token::Eof | Token::Sym(";" | "}")
+                ) {
+                    Stmt::Exit(None)
+                } else {
+                    Stmt::Exit(Some(self.expr()?))
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                return self.error();
+            }
+        };
+        self.end_simple()?;
+        Ok(statement)
+    }
+
+    fn expr_list(&mut self) -> Parsed<Vec<Expr>> {
+        let mut list = vec![self.expr()?];
+        while self.eat_sym(",") {
+            self.skip_newlines();
+            list.push(self.expr()?);
+        }
+        Ok(list)
+    }
+
+    fn expr(&mut self) -> Parsed<Expr> {
+        let target = self.ternary()?;
+        let operator = match self.peek() {
+            Token::Sym(op @ ("=" | "+=" | "-=" | "*=" | "/=" | "%=" | "^=")) => *op,
+            _ => return Ok(target),
+        };
+        if !matches!(target, Expr::Var(_) | Expr::Index(..) | Expr::Field(_)) {
+            return self.error();
+        }
+        self.advance();
+        self.skip_newlines();
+        let value = self.expr()?;
+        let operator = operator.strip_suffix('=').filter(|op| !op.is_empty());
+        let operator = operator.map(|op| match op {
+            "+" => "+",
+            "-" => "-",
+            "*" => "*",
+            "/" => "/",
+            "%" => "%",
+            _ => "^",
+        });
+        Ok(Expr::Assign(Box::new(target), operator, Box::new(value)))
+    }
+
+    fn ternary(&mut self) -> Parsed<Expr> {
+        let condition = self.or()?;
+        if !self.eat_sym("?") {
+            return Ok(condition);
+        }
+        self.skip_newlines();
+        let then = self.expr()?;
+        self.skip_newlines();
+        self.expect_sym(":")?;
+        self.skip_newlines();
+        let otherwise = self.expr()?;
+        Ok(Expr::Ternary(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn or(&mut self) -> Parsed<Expr> {
+        let mut left = self.and()?;
+        while self.eat_sym("||") {
+            self.skip_newlines();
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Parsed<Expr> {
+        let mut left = self.membership()?;
+        while self.eat_sym("&&") {
+            self.skip_newlines();
+            left = Expr::And(Box::new(left), Box::new(self.membership()?));
+        }
+        Ok(left)
+    }
+
+    fn membership(&mut self) -> Parsed<Expr> {
+        let mut left = self.matching()?;
+        while self.is_keyword("in") {
+            self.advance();
+            let array = match self.advance() {
+                Token::Name(name) => name,
+                _ => return self.error(),
+            };
+            let keys = match left {
+                Expr::Group(keys) => keys,
+                key => vec![key],
+            };
+            left = Expr::In(keys, array);
+        }
+        Ok(left)
+    }
+
+    fn matching(&mut self) -> Parsed<Expr> {
+        let mut left = self.comparison()?;
+        loop {
+            let negated = if self.eat_sym("~") {
+                false
+            } else if self.eat_sym("!~") {
+                true
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Match(negated, Box::new(left), Box::new(self.comparison()?));
+        }
+    }
+
+    fn comparison(&mut self) -> Parsed<Expr> {
+        let left = self.concatenation()?;
+        let operator = match self.peek() {
+            Token::Sym(">") if self.in_print => return Ok(left),
+            Token::Sym(op @ ("<" | "<=" | ">" | ">=" | "==" | "!=")) => *op,
+            _ => return Ok(left),
+        };
+        self.advance();
+        let right = self.concatenation()?;
+        Ok(Expr::Binary(operator, Box::new(left), Box::new(right)))
+    }
+
+    /// Whether the next token can begin the right-hand side of an implicit
+    /// concatenation.
+    fn starts_operand(&self) -> bool {
+        match self.peek() {
+            Token::Number(_) | Token::Str(_) | Token::Builtin(_) => true,
+            Token::Name(name) => !KEYWORDS.contains(&name.as_str()),
+            Token::Sym(symbol) => matches!(*symbol, "$" | "(" | "!" | "++" | "--"),
+            _ => false,
+        }
+    }
+
+    fn concatenation(&mut self) -> Parsed<Expr> {
+        let mut left = self.additive()?;
+        while self.starts_operand() {
+            // `a !~ b` and `a != b` were taken above; a lone `!` here
+            // would be `a (!b)`, which awk rejects too.
+            if self.is_sym("!") {
+                return self.error();
+            }
+            left = Expr::Concat(Box::new(left), Box::new(self.additive()?));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Parsed<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Sym(op @ ("+" | "-")) => *op,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Parsed<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Sym(op @ ("*" | "/" | "%")) => *op,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Parsed<Expr> {
+        let operator = match self.peek() {
+            Token::Sym(op @ ("!" | "-" | "+")) => *op,
+            _ => return self.power(),
+        };
+        self.advance();
+        Ok(Expr::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn power(&mut self) -> Parsed<Expr> {
+        let base = self.postfix()?;
+        if self.eat_sym("^") {
+            // Right-associative, and the exponent may carry a sign.
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary("^", Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Parsed<Expr> {
+        if let Token::Sym(op @ ("++" | "--")) = self.peek() {
+            let delta = if *op == "++" { 1.0 } else { -1.0 };
+            self.advance();
+            let target = self.postfix()?;
+            if !matches!(target, Expr::Var(_) | Expr::Index(..) | Expr::Field(_)) {
+                return self.error();
+            }
+            return Ok(Expr::IncDec {
+                target: Box::new(target),
+                delta,
+                prefix: true,
+            });
+        }
+
+        let primary = self.primary()?;
+        if matches!(primary, Expr::Var(_) | Expr::Index(..) | Expr::Field(_)) {
+            if let Token::Sym(op @ ("++" | "--")) = self.peek() {
+                let delta = if *op == "++" { 1.0 } else { -1.0 };
+                self.advance();
+                return Ok(Expr::IncDec {
+                    target: Box::new(primary),
+                    delta,
+                    prefix: false,
+                });
+            }
+        }
+        Ok(primary)
+    }
+
+    fn primary(&mut self) -> Parsed<Expr> {
+        let start = self.pos;
+        match self.advance() {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Regex(r) => Ok(Expr::Regex(r)),
+            Token::Sym("$") => {
+                let index = match self.peek() {
+                    Token::Sym("++" | "--") => self.postfix()?,
+                    Token::Sym("-") => {
+                        self.advance();
+                        Expr::Unary("-", Box::new(self.primary()?))
+                    }
+                    _ => self.primary()?,
+                };
+                Ok(Expr::Field(Box::new(index)))
+            }
+            Token::Sym("(") => {
+                let in_print = std::mem::replace(&mut self.in_print, false);
+                let result = self.expr_list();
+                self.in_print = in_print;
+                let mut list = result?;
+                self.expect_sym(")")?;
+                if list.len() == 1 {
+                    Ok(list.pop().unwrap())
+                } else {
+                    Ok(Expr::Group(list))
+                }
+            }
+            Token::Name(name) if name == "getline" => {
+                let target = match self.peek() {
+                    Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => {
+                        Some(Box::new(self.primary()?))
+                    }
+                    Token::Sym("$") => Some(Box::new(self.primary()?)),
+                    _ => None,
+                };
+                let source = if self.eat_sym("<") {
+                    Some(Box::new(self.primary()?))
+                } else {
+                    None
+                };
+                Ok(Expr::Getline(target, source))
+            }
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => {
+                if self.eat_sym("[") {
+                    let keys = self.expr_list()?;
+                    self.expect_sym("]")?;
+                    return Ok(Expr::Index(name, keys));
+                }
+                Ok(Expr::Var(name))
+            }
+            Token::Builtin(name) => {
+                if !self.is_sym("(") {
+                    if name == "length" {
+                        return Ok(Expr::Call(name, Vec::new()));
+                    }
+                    self.pos = start;
+                    return self.error();
+                }
+                self.advance();
+                let args = if self.is_sym(")") {
+                    Vec::new()
+                } else {
+                    let in_print = std::mem::replace(&mut self.in_print, false);
+                    let result = self.expr_list();
+                    self.in_print = in_print;
+                    result?
+                };
+                self.expect_sym(")")?;
+                Ok(Expr::Call(name, args))
+            }
+            _ => {
+                self.pos = start;
+                self.error()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Uninit,
+    Num(f64),
+    Str(String),
+    /// Input data (fields, `-v` assignments, split results): a string that
+    /// compares as a number when it looks like one.
+    StrNum(String),
+}
+
+impl Value {
+    fn to_num(&self) -> f64 {
+        match self {
+            Value::Uninit => 0.0,
+            Value::Num(n) => *n,
+            Value::Str(s) | Value::StrNum(s) => leading_number(s),
+        }
+    }
+
+    fn to_str(&self) -> String {
+        match self {
+            Value::Uninit => String::new(),
+            Value::Num(n) => format_number(*n),
+            Value::Str(s) | Value::StrNum(s) => s.clone(),
+        }
+    }
+
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Uninit => false,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::StrNum(s) => match looks_numeric(s) {
+                Some(n) => n != 0.0,
+                None => !s.is_empty(),
+            },
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Value::Uninit | Value::Num(_) => true,
+            Value::Str(_) => false,
+            Value::StrNum(s) => looks_numeric(s).is_some(),
+        }
+    }
+}
+
+/// The value of a whole string if it is a number (surrounding blanks
+/// allowed), as awk decides for input data.
+fn looks_numeric(text: &str) -> Option<f64> {
+    let trimmed = text.trim_matches([' ', '\t', '\n']);
+    if trimmed.is_empty() || trimmed.contains(|c: char| c.is_alphabetic() && c != 'e' && c != 'E') {
+        return None;
+    }
+    trimmed.parse().ok()
+}
+
+/// strtod-style: the longest numeric prefix, or 0.
+fn leading_number(text: &str) -> f64 {
+    let text = text.trim_start_matches([' ', '\t', '\n']);
+    let bytes = text.as_bytes();
+    let mut end = 0;
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
+    }
+    let digits_start = end;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        end += 1;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+    }
+    if end == digits_start || (end == digits_start + 1 && bytes[digits_start] == b'.') {
+        return 0.0;
+    }
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exponent = end + 1;
+        if exponent < bytes.len() && (bytes[exponent] == b'+' || bytes[exponent] == b'-') {
+            exponent += 1;
+        }
+        if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+            end = exponent;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+        }
+    }
+    text[..end].parse().unwrap_or(0.0)
+}
+
+/// Number to string: integers print whole, everything else as `%.6g`.
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e18 {
+        format!("{}", n as i64)
+    } else if n.is_nan() {
+        "nan".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format_float('g', n, 6, false)
+    }
+}
+
+/// `%e`, `%f` and `%g` as C's printf does them. `alternate` keeps the
+/// trailing zeros `%g` would otherwise trim.
+fn format_float(conversion: char, n: f64, precision: usize, alternate: bool) -> String {
+    let exponential = |n: f64, precision: usize| {
+        let formatted = format!("{:.*e}", precision, n);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap();
+        let exponent: i32 = exponent.parse().unwrap();
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    };
+
+    match conversion {
+        'e' | 'E' => {
+            let text = exponential(n, precision);
+            if conversion == 'E' {
+                text.to_uppercase()
+            } else {
+                text
+            }
+        }
+        'f' | 'F' => format!("{:.*}", precision, n),
+        _ => {
+            let precision = precision.max(1);
+            if n == 0.0 {
+                return if alternate {
+                    format!("{:.*}", precision - 1, 0.0)
+                } else {
+                    "0".to_string()
+                };
+            }
+            let exponent: i32 = format!("{:.*e}", precision - 1, n)
+                .split_once('e')
+                .unwrap()
+                .1
+                .parse()
+                .unwrap();
+            let mut text = if exponent < -4 || exponent >= precision as i32 {
+                exponential(n, precision - 1)
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - exponent) as usize, n)
+            };
+            if !alternate {
+                let (mantissa, suffix) = match text.find('e') {
+                    Some(e) => text.split_at(e),
+                    None => (text.as_str(), ""),
+                };
+                let mantissa = if mantissa.contains('.') {
+                    mantissa.trim_end_matches('0').trim_end_matches('.')
+                } else {
+                    mantissa
+                };
+                text = format!("{}{}", mantissa, suffix);
+            }
+            if conversion == 'G' {
+                text.to_uppercase()
+            } else {
+                text
+            }
+        }
+    }
+}
+
+/// printf/sprintf formatting: `%c %d %i %o %x %X %u %e %f %g %s %%` with
+/// flags, width and precision (`*` takes them from the arguments).
+fn sprintf(format: &str, args: &[Value]) -> String {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut flags = String::new();
+        while let Some(&flag) = chars.peek() {
+            if !"-+ #0".contains(flag) {
+                break;
+            }
+            flags.push(flag);
+            chars.next();
+        }
+        let mut number = |chars: &mut std::iter::Peekable<std::str::Chars>| -> Option<usize> {
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                return Some(args.next().map_or(0.0, Value::to_num) as usize);
+            }
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            digits.parse().ok()
+        };
+        let width = number(&mut chars).unwrap_or(0);
+        let precision = if chars.peek() == Some(&'.') {
+            chars.next();
+            Some(number(&mut chars).unwrap_or(0))
+        } else {
+            None
+        };
+
+        let conversion = match chars.next() {
+            Some(conversion) => conversion,
+            None => {
+                output.push('%');
+                break;
+            }
+        };
+        if conversion == '%' {
+            output.push('%');
+            continue;
+        }
+
+        let arg = args.next().cloned().unwrap_or(Value::Uninit);
+        let left = flags.contains('-');
+        let zero = flags.contains('0') && !left;
+        let sign = |n: f64| {
+            if n < 0.0 {
+                "-"
+            } else if flags.contains('+') {
+                "+"
+            } else if flags.contains(' ') {
+                " "
+            } else {
+                ""
+            }
+        };
+
+        let (prefix, body) = match conversion {
+            'c' => {
+                let text = match arg {
+                    Value::Num(n) => char::from_u32(n as u32).map_or(String::new(), String::from),
+                    other => other
+                        .to_str()
+                        .chars()
+                        .next()
+                        .map_or(String::new(), String::from),
+                };
+                (String::new(), text)
+            }
+            's' => {
+                let text = arg.to_str();
+                let text = match precision {
+                    Some(p) => text.chars().take(p).collect(),
+                    None => text,
+                };
+                (String::new(), text)
+            }
+            'd' | 'i' | 'u' => {
+                let n = arg.to_num().trunc();
+                let mut digits = format!("{}", n.abs() as i64);
+                if let Some(p) = precision {
+                    digits = format!("{:0>width$}", digits, width = p);
+                }
+                (sign(n).to_string(), digits)
+            }
+            'o' | 'x' | 'X' => {
+                let n = arg.to_num().trunc() as i64 as u64;
+                let digits = match conversion {
+                    'o' => format!("{:o}", n),
+                    'x' => format!("{:x}", n),
+                    _ => format!("{:X}", n),
+                };
+                let prefix = match (flags.contains('#'), conversion) {
+                    (true, 'x') if n != 0 => "0x",
+                    (true, 'X') if n != 0 => "0X",
+                    (true, 'o') => "0",
+                    _ => "",
+                };
+                (prefix.to_string(), digits)
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let n = arg.to_num();
+                let text = format_float(
+                    conversion,
+                    n.abs(),
+                    precision.unwrap_or(6),
+                    flags.contains('#'),
+                );
+                (sign(n).to_string(), text)
+            }
+            other => {
+                output.push('%');
+                output.push_str(&flags);
+                output.push(other);
+                continue;
+            }
+        };
+
+        let length = prefix.chars().count() + body.chars().count();
+        let padding = width.saturating_sub(length);
+        if left {
+            output.push_str(&prefix);
+            output.push_str(&body);
+            output.extend(std::iter::repeat_n(' ', padding));
+        } else if zero && conversion != 's' && conversion != 'c' {
+            output.push_str(&prefix);
+            output.extend(std::iter::repeat_n('0', padding));
+            output.push_str(&body);
+        } else {
+            output.extend(std::iter::repeat_n(' ', padding));
+            output.push_str(&prefix);
+            output.push_str(&body);
+        }
+    }
+
+    output
+}
+
+/// An associative array that remembers insertion order, so `for (k in a)`
+/// walks keys in the order they were first seen.
+#[derive(Default)]
+struct Array {
+    keys: Vec<String>,
+    values: HashMap<String, Value>,
+}
+
+impl Array {
+    fn get(&mut self, key: &str) -> Value {
+        if !self.values.contains_key(key) {
+            self.keys.push(key.to_string());
+            self.values.insert(key.to_string(), Value::Uninit);
+        }
+        self.values[key].clone()
+    }
+
+    fn set(&mut self, key: &str, value: Value) {
+        if self.values.insert(key.to_string(), value).is_none() {
+            self.keys.push(key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if self.values.remove(key).is_some() {
+            self.keys.retain(|k| k != key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+    }
+}
+
+enum Flow {
+    Normal,
+    Next,
+    Exit,
+    Break,
+    Continue,
+}
+
+type Run<T> = Result<T, String>;
+
+/// The most loop iterations a program may run, in total, before it is
+/// stopped.
+const STEP_LIMIT: usize = 1_000_000;
+
+fn awk_error(err: String) -> String {
+    format!("awk: {}", err)
+}
+
+struct Interpreter {
+    vars: HashMap<String, Value>,
+    arrays: HashMap<String, Array>,
+    /// `$0` followed by `$1..$NF`.
+    fields: Vec<String>,
+    output: String,
+    regexes: HashMap<String, Regex>,
+    /// The main inputs not yet opened, and the records left in the current
+    /// one.
+    inputs: std::vec::IntoIter<Result<(String, String), String>>,
+    records: std::vec::IntoIter<String>,
+    /// Files opened by `getline < file`, with their unread lines.
+    files: HashMap<String, std::vec::IntoIter<String>>,
+    steps: usize,
+}
+
+impl Interpreter {
+    fn var(&self, name: &str) -> Value {
+        if name == "NF" {
+            return Value::Num((self.fields.len() - 1) as f64);
+        }
+        self.vars.get(name).cloned().unwrap_or(Value::Uninit)
+    }
+
+    fn var_str(&self, name: &str) -> String {
+        self.var(name).to_str()
+    }
+
+    /// Counts one loop iteration against `STEP_LIMIT`.
+    fn step(&mut self) -> Run<()> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return Err("awk: execution limit exceeded".to_string());
+        }
+        Ok(())
+    }
+
+    /// Takes the next record from the main input, opening the next file
+    /// when one runs out, and counts it in `NR` and `FNR`.
+    fn next_record(&mut self) -> Option<String> {
+        loop {
+            if let Some(record) = self.records.next() {
+                for name in ["NR", "FNR"] {
+                    let count = self.var(name).to_num() + 1.0;
+                    self.vars.insert(name.to_string(), Value::Num(count));
+                }
+                return Some(record);
+            }
+            match self.inputs.next()? {
+                Ok((name, content)) => {
+                    let filename = if name == "-" { String::new() } else { name };
+                    self.vars
+                        .insert("FILENAME".to_string(), Value::Str(filename));
+                    self.vars.insert("FNR".to_string(), Value::Num(0.0));
+                    self.records = lines(&content);
+                }
+                Err(err) => {
+                    self.output.push_str(&err);
+                    self.output.push('\n');
+                }
+            }
+        }
+    }
+
+    /// Runs `getline`: 1 when a line was read, 0 at the end of the input and
+    /// -1 when the file cannot be read.
+    fn getline(&mut self, target: Option<&Expr>, source: Option<&Expr>) -> Run<Value> {
+        let line = match source {
+            None => self.next_record(),
+            Some(source) => {
+                let name = self.eval(source)?.to_str();
+                if !self.files.contains_key(&name) {
+                    match text::inputs("awk", &[name.as_str()], None).pop() {
+                        Some(Ok((_, content))) => {
+                            self.files.insert(name.clone(), lines(&content));
+                        }
+                        _ => return Ok(Value::Num(-1.0)),
+                    }
+                }
+                self.files.get_mut(&name).and_then(Iterator::next)
+            }
+        };
+        let line = match line {
+            Some(line) => line,
+            None => return Ok(Value::Num(0.0)),
+        };
+        match target {
+            Some(target) => self.assign(target, Value::StrNum(line))?,
+            None => self.set_record(&line)?,
+        }
+        Ok(Value::Num(1.0))
+    }
+
+    fn regex(&mut self, pattern: &str) -> Run<&Regex> {
+        if !self.regexes.contains_key(pattern) {
+            let regex = Regex::new(pattern, Syntax::Extended, false).map_err(awk_error)?;
+            self.regexes.insert(pattern.to_string(), regex);
+        }
+        Ok(&self.regexes[pattern])
+    }
+
+    /// Splits `text` the way awk splits records: on blank runs for the
+    /// default `FS` of a single space, on a literal single character, or on
+    /// a regex.
+    fn split(&mut self, text: &str, separator: &str) -> Run<Vec<String>> {
+        if separator == " " {
+            return Ok(text.split_whitespace().map(str::to_string).collect());
+        }
+        if text.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut chars = separator.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c != '\\' {
+                return Ok(text.split(c).map(str::to_string).collect());
+            }
+        }
+
+        let regex = self.regex(separator)?;
+        let mut parts = Vec::new();
+        let mut last = 0;
+        for found in regex.find_iter(text).map_err(awk_error)? {
+            if found.end == found.start {
+                continue;
+            }
+            parts.push(text[last..found.start].to_string());
+            last = found.end;
+        }
+        parts.push(text[last..].to_string());
+        Ok(parts)
+    }
+
+    fn set_record(&mut self, record: &str) -> Run<()> {
+        let separator = self.var_str("FS");
+        let fields = self.split(record, &separator)?;
+        self.fields = std::iter::once(record.to_string()).chain(fields).collect();
+        Ok(())
+    }
+
+    fn rebuild_record(&mut self) {
+        let separator = self.var_str("OFS");
+        self.fields[0] = self.fields[1..].join(&separator);
+    }
+
+    fn field(&self, index: usize) -> Value {
+        self.fields
+            .get(index)
+            .map_or(Value::Uninit, |field| Value::StrNum(field.clone()))
+    }
+
+    fn set_field(&mut self, index: usize, value: String) -> Run<()> {
+        if index == 0 {
+            return self.set_record(&value);
+        }
+        if self.fields.len() <= index {
+            self.fields.resize(index + 1, String::new());
+        }
+        self.fields[index] = value;
+        self.rebuild_record();
+        Ok(())
+    }
+
+    fn set_var(&mut self, name: &str, value: Value) {
+        if name == "NF" {
+            let count = value.to_num().max(0.0) as usize;
+            self.fields.resize(count + 1, String::new());
+            self.rebuild_record();
+            return;
+        }
+        self.vars.insert(name.to_string(), value);
+    }
+
+    fn key(&mut self, keys: &[Expr]) -> Run<String> {
+        let mut parts = Vec::new();
+        for key in keys {
+            parts.push(self.eval(key)?.to_str());
+        }
+        Ok(parts.join(&self.var_str("SUBSEP")))
+    }
+
+    fn field_index(&mut self, expr: &Expr) -> Run<usize> {
+        let index = self.eval(expr)?.to_num();
+        if index < 0.0 {
+            return Err(format!(
+                "awk: trying to access out of range field {}",
+                index as i64
+            ));
+        }
+        Ok(index as usize)
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Run<()> {
+        match target {
+            Expr::Var(name) => self.set_var(name, value),
+            Expr::Index(name, keys) => {
+                let key = self.key(keys)?;
+                self.arrays
+                    .entry(name.clone())
+                    .or_default()
+                    .set(&key, value);
+            }
+            Expr::Field(index) => {
+                let index = self.field_index(index)?;
+                self.set_field(index, value.to_str())?;
+            }
+            _ => return Err("awk: assignment to non-variable".to_string()),
+        }
+        Ok(())
+    }
+
+    /// The regex source for an argument that may be a `/re/` literal or
+    /// any expression evaluating to a pattern string.
+    fn pattern_of(&mut self, expr: &Expr) -> Run<String> {
+        match expr {
+            Expr::Regex(pattern) => Ok(pattern.clone()),
+            other => Ok(self.eval(other)?.to_str()),
+        }
+    }
+
+    fn compare(&mut self, operator: &str, left: Value, right: Value) -> bool {
+        let ordering = if left.is_numeric() && right.is_numeric() {
+            left.to_num()
+                .partial_cmp(&right.to_num())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        } else {
+            left.to_str().cmp(&right.to_str())
+        };
+        match operator {
+            "<" => ordering.is_lt(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            ">=" => ordering.is_ge(),
+            "==" => ordering.is_eq(),
+            _ => ordering.is_ne(),
+        }
+    }
+
+    fn arithmetic(operator: &str, left: f64, right: f64) -> Run<f64> {
+        Ok(match operator {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => {
+                if right == 0.0 {
+                    return Err("awk: division by zero".to_string());
+                }
+                left / right
+            }
+            "%" => {
+                if right == 0.0 {
+                    return Err("awk: division by zero in %".to_string());
+                }
+                left % right
+            }
+            _ => left.powf(right),
+        })
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Run<Value> {
+        Ok(match expr {
+            Expr::Number(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Regex(pattern) => {
+                let record = self.fields[0].clone();
+                let matched = self.regex(pattern)?.is_match(&record).map_err(awk_error)?;
+                Value::Num(matched as i32 as f64)
+            }
+            Expr::Var(name) => self.var(name),
+            Expr::Index(name, keys) => {
+                let key = self.key(keys)?;
+                self.arrays.entry(name.clone()).or_default().get(&key)
+            }
+            Expr::Field(index) => {
+                let index = self.field_index(index)?;
+                self.field(index)
+            }
+            Expr::Group(items) => {
+                // Only reachable as `(a, b)` outside print or `in`.
+                let key = self.key(items)?;
+                Value::Str(key)
+            }
+            Expr::Assign(target, operator, value) => {
+                let value = self.eval(value)?;
+                let value = match operator {
+                    None => value,
+                    Some(operator) => {
+                        let current = self.eval(target)?.to_num();
+                        Value::Num(Self::arithmetic(operator, current, value.to_num())?)
+                    }
+                };
+                self.assign(target, value.clone())?;
+                value
+            }
+            Expr::IncDec {
+                target,
+                delta,
+                prefix,
+            } => {
+                let old = self.eval(target)?.to_num();
+                self.assign(target, Value::Num(old + delta))?;
+                Value::Num(if *prefix { old + delta } else { old })
+            }
+            Expr::Unary(operator, operand) => {
+                let value = self.eval(operand)?;
+                match *operator {
+                    "!" => Value::Num(!value.truthy() as i32 as f64),
+                    "-" => Value::Num(-value.to_num()),
+                    _ => Value::Num(value.to_num()),
+                }
+            }
+            Expr::Binary(operator, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                match *operator {
+                    "<" | "<=" | ">" | ">=" | "==" | "!=" => {
+                        Value::Num(self.compare(operator, left, right) as i32 as f64)
+                    }
+                    _ => Value::Num(Self::arithmetic(operator, left.to_num(), right.to_num())?),
+                }
+            }
+            Expr::Concat(left, right) => {
+                let mut text = self.eval(left)?.to_str();
+                text.push_str(&self.eval(right)?.to_str());
+                Value::Str(text)
+            }
+            Expr::Match(negated, text, pattern) => {
+                let text = self.eval(text)?.to_str();
+                let pattern = self.pattern_of(pattern)?;
+                let matched = self.regex(&pattern)?.is_match(&text).map_err(awk_error)?;
+                Value::Num((matched != *negated) as i32 as f64)
+            }
+            Expr::And(left, right) => {
+                Value::Num((self.eval(left)?.truthy() && self.eval(right)?.truthy()) as i32 as f64)
+            }
+            Expr::Or(left, right) => {
+                Value::Num((self.eval(left)?.truthy() || self.eval(right)?.truthy()) as i32 as f64)
+            }
+            Expr::Ternary(condition, then, otherwise) => {
+                if self.eval(condition)?.truthy() {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::In(keys, array) => {
+                let key = self.key(keys)?;
+                let present = self
+                    .arrays
+                    .get(array)
+                    .is_some_and(|array| array.values.contains_key(&key));
+                Value::Num(present as i32 as f64)
+            }
+            Expr::Call(name, args) => self.call(name, args)?,
+            Expr::Getline(target, source) => self.getline(target.as_deref(), source.as_deref())?,
+        })
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Run<Value> {
+        let arity = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(format!(
+                    "awk: function {} called with {} args",
+                    name,
+                    args.len()
+                ))
+            } else {
+                Ok(())
+            }
+        };
+
+        match name {
+            "length" => {
+                arity(0, 1)?;
+                if let Some(Expr::Var(array)) = args.first() {
+                    if let Some(array) = self.arrays.get(array) {
+                        return Ok(Value::Num(array.keys.len() as f64));
+                    }
+                }
+                let text = match args.first() {
+                    Some(arg) => self.eval(arg)?.to_str(),
+                    None => self.fields[0].clone(),
+                };
+                Ok(Value::Num(text.chars().count() as f64))
+            }
+            "substr" => {
+                arity(2, 3)?;
+                let chars: Vec<char> = self.eval(&args[0])?.to_str().chars().collect();
+                // awk positions are 1-based and rounded; the range is
+                // clipped to the string.
+                let start = self.eval(&args[1])?.to_num().round();
+                let end = match args.get(2) {
+                    Some(length) => start + self.eval(length)?.to_num().round(),
+                    None => f64::INFINITY,
+                };
+                let from = start.max(1.0) as usize;
+                let to = end.min(chars.len() as f64 + 1.0);
+                if to <= from as f64 {
+                    return Ok(Value::Str(String::new()));
+                }
+                Ok(Value::Str(
+                    chars[from - 1..to as usize - 1].iter().collect(),
+                ))
+            }
+            "index" => {
+                arity(2, 2)?;
+                let text = self.eval(&args[0])?.to_str();
+                let needle = self.eval(&args[1])?.to_str();
+                let position = text
+                    .find(&needle)
+                    .map_or(0, |byte| text[..byte].chars().count() + 1);
+                Ok(Value::Num(position as f64))
+            }
+            "split" => {
+                arity(2, 3)?;
+                let text = self.eval(&args[0])?.to_str();
+                let array = match &args[1] {
+                    Expr::Var(array) => array.clone(),
+                    _ => return Err("awk: split: second argument is not an array".to_string()),
+                };
+                let separator = match args.get(2) {
+                    Some(separator) => self.pattern_of(separator)?,
+                    None => self.var_str("FS"),
+                };
+                let parts = self.split(&text, &separator)?;
+                let target = self.arrays.entry(array).or_default();
+                target.clear();
+                for (i, part) in parts.iter().enumerate() {
+                    target.set(&(i + 1).to_string(), Value::StrNum(part.clone()));
+                }
+                Ok(Value::Num(parts.len() as f64))
+            }
+            "sub" | "gsub" => {
+                arity(2, 3)?;
+                let pattern = self.pattern_of(&args[0])?;
+                let replacement = self.eval(&args[1])?.to_str();
+                let record = Expr::Field(Box::new(Expr::Number(0.0)));
+                let target = args.get(2).unwrap_or(&record);
+                if !matches!(target, Expr::Var(_) | Expr::Index(..) | Expr::Field(_)) {
+                    return Err(format!("awk: {}: third argument is not a variable", name));
+                }
+                let text = self.eval(target)?.to_str();
+
+                let regex = self.regex(&pattern)?;
+                let mut matches = regex.find_iter(&text).map_err(awk_error)?;
+                if name == "sub" {
+                    matches.truncate(1);
+                }
+                if matches.is_empty() {
+                    return Ok(Value::Num(0.0));
+                }
+
+                let mut result = String::new();
+                let mut last = 0;
+                for found in &matches {
+                    result.push_str(&text[last..found.start]);
+                    let mut chars = replacement.chars().peekable();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' if chars.peek() == Some(&'&') => {
+                                chars.next();
+                                result.push('&');
+                            }
+                            '&' => result.push_str(&text[found.start..found.end]),
+                            c => result.push(c),
+                        }
+                    }
+                    last = found.end;
+                }
+                result.push_str(&text[last..]);
+                self.assign(target, Value::Str(result))?;
+                Ok(Value::Num(matches.len() as f64))
+            }
+            "match" => {
+                arity(2, 2)?;
+                let text = self.eval(&args[0])?.to_str();
+                let pattern = self.pattern_of(&args[1])?;
+                let found = self.regex(&pattern)?.find_at(&text, 0).map_err(awk_error)?;
+                let (start, length) = match found {
+                    Some(found) => (
+                        text[..found.start].chars().count() as f64 + 1.0,
+                        text[found.start..found.end].chars().count() as f64,
+                    ),
+                    None => (0.0, -1.0),
+                };
+                self.vars.insert("RSTART".to_string(), Value::Num(start));
+                self.vars.insert("RLENGTH".to_string(), Value::Num(length));
+                Ok(Value::Num(start))
+            }
+            "sprintf" => {
+                if args.is_empty() {
+                    return Err("awk: sprintf: no format".to_string());
+                }
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                Ok(Value::Str(sprintf(&values[0].to_str(), &values[1..])))
+            }
+            "tolower" | "toupper" => {
+                arity(1, 1)?;
+                let text = self.eval(&args[0])?.to_str();
+                Ok(Value::Str(if name == "tolower" {
+                    text.to_lowercase()
+                } else {
+                    text.to_uppercase()
+                }))
+            }
+            "atan2" => {
+                arity(2, 2)?;
+                let y = self.eval(&args[0])?.to_num();
+                let x = self.eval(&args[1])?.to_num();
+                Ok(Value::Num(y.atan2(x)))
+            }
+            _ => {
+                arity(1, 1)?;
+                let n = self.eval(&args[0])?.to_num();
+                Ok(Value::Num(match name {
+                    "int" => n.trunc(),
+                    "sqrt" => n.sqrt(),
+                    "exp" => n.exp(),
+                    "log" => n.ln(),
+                    "sin" => n.sin(),
+                    _ => n.cos(),
+                }))
+            }
+        }
+    }
+
+    fn exec_all(&mut self, statements: &[Stmt]) -> Run<Flow> {
+        for statement in statements {
+            match self.exec(statement)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, statement: &Stmt) -> Run<Flow> {
+        match statement {
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::Print(args) => {
+                let line = if args.is_empty() {
+                    self.fields[0].clone()
+                } else {
+                    let mut parts = Vec::new();
+                    for arg in args {
+                        parts.push(self.eval(arg)?.to_str());
+                    }
+                    parts.join(&self.var_str("OFS"))
+                };
+                self.output.push_str(&line);
+                self.output.push_str(&self.var_str("ORS"));
+            }
+            Stmt::Printf(args) => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                let text = sprintf(&values[0].to_str(), &values[1..]);
+                self.output.push_str(&text);
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.eval(condition)?.truthy() {
+                    return self.exec(then);
+                } else if let Some(otherwise) = otherwise {
+                    return self.exec(otherwise);
+                }
+            }
+            Stmt::While(condition, body) => {
+                while self.eval(condition)?.truthy() {
+                    self.step()?;
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Stmt::DoWhile(body, condition) => loop {
+                self.step()?;
+                match self.exec(body)? {
+                    Flow::Break => break,
+                    Flow::Normal | Flow::Continue => {}
+                    flow => return Ok(flow),
+                }
+                if !self.eval(condition)?.truthy() {
+                    break;
+                }
+            },
+            Stmt::For(init, condition, step, body) => {
+                if let Some(init) = init {
+                    self.eval(init)?;
+                }
+                loop {
+                    if let Some(condition) = condition {
+                        if !self.eval(condition)?.truthy() {
+                            break;
+                        }
+                    }
+                    self.step()?;
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow => return Ok(flow),
+                    }
+                    if let Some(step) = step {
+                        self.eval(step)?;
+                    }
+                }
+            }
+            Stmt::ForIn(var, array, body) => {
+                let keys = self
+                    .arrays
+                    .get(array)
+                    .map_or(Vec::new(), |array| array.keys.clone());
+                for key in keys {
+                    self.set_var(var, Value::StrNum(key));
+                    self.step()?;
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Stmt::Block(statements) => return self.exec_all(statements),
+            Stmt::Delete(array, keys) => match keys {
+                Some(keys) => {
+                    let key = self.key(keys)?;
+                    if let Some(array) = self.arrays.get_mut(array) {
+                        array.remove(&key);
+                    }
+                }
+                None => {
+                    if let Some(array) = self.arrays.get_mut(array) {
+                        array.clear();
+                    }
+                }
+            },
+            Stmt::Next => return Ok(Flow::Next),
+            Stmt::Exit(code) => {
+                if let Some(code) = code {
+                    self.eval(code)?;
+                }
+                return Ok(Flow::Exit);
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+}
+
+/// Splits an input into records, one per line.
+fn lines(content: &str) -> std::vec::IntoIter<String> {
+    content
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Runs the main rules over one record. Returns false once `exit` runs.
+fn run_record(interpreter: &mut Interpreter, rules: &[Rule], ranges: &mut [bool]) -> Run<bool> {
+    for (index, rule) in rules.iter().enumerate() {
+        let selected = match &rule.pattern {
+            Pattern::Begin | Pattern::End => continue,
+            Pattern::All => true,
+            Pattern::Expr(expr) => interpreter.eval(expr)?.truthy(),
+            Pattern::Range(start, end) => {
+                if !ranges[index] && interpreter.eval(start)?.truthy() {
+                    ranges[index] = true;
+                }
+                if ranges[index] && interpreter.eval(end)?.truthy() {
+                    ranges[index] = false;
+                    true
+                } else {
+                    ranges[index]
+                }
+            }
+        };
+        if !selected {
+            continue;
+        }
+
+        let flow = match &rule.action {
+            Some(action) => interpreter.exec_all(action)?,
+            None => interpreter.exec(&Stmt::Print(Vec::new()))?,
+        };
+        match flow {
+            Flow::Next => return Ok(true),
+            Flow::Exit => return Ok(false),
+            _ => {}
+        }
+    }
+    Ok(true)
+}
+
+/// Runs the rules for one special pattern. Returns false once `exit` runs.
+fn run_special(interpreter: &mut Interpreter, rules: &[Rule], begin: bool) -> Run<bool> {
+    for rule in rules {
+        let wanted = match rule.pattern {
+            Pattern::Begin => begin,
+            Pattern::End => !begin,
+            _ => false,
+        };
+        if !wanted {
+            continue;
+        }
+        if let Some(action) = &rule.action {
+            if let Flow::Exit = interpreter.exec_all(action)? {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Undoes the escapes in a `-v` or `-F` value, as awk does for string
+/// literals.
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('\\') => text.push('\\'),
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// `awk [-F fs] [-v var=value]... 'program' [file...]`: pattern-action
+/// rules, `BEGIN`/`END`, fields, variables, arrays, control flow and the
+/// usual string and maths builtins, and `getline` from the input or a file.
+/// Output redirection is left out.
+pub fn awk(args: &[&str], stdin: Option<&str>) -> String {
+    let mut separator: Option<String> = None;
+    let mut assignments = Vec::new();
+    let mut program: Option<&str> = None;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        if program.is_some() || arg == "-" || !arg.starts_with('-') {
+            if program.is_none() {
+                program = Some(arg);
+            } else {
+                operands.push(arg);
+            }
+            continue;
+        }
+        if arg == "--" {
+            if let Some((&first, tail)) = rest.split_first() {
+                program = Some(first);
+                operands.extend(tail.iter().copied());
+            }
+            break;
+        }
+
+        let flag = &arg[1..2];
+        let value = if arg.len() > 2 {
+            &arg[2..]
+        } else if let Some((&next, tail)) = rest.split_first() {
+            rest = tail;
+            next
+        } else {
+            return format!("awk: option requires an argument -- {}", flag);
+        };
+        match flag {
+            "F" => {
+                separator = Some(match value {
+                    "t" => "\t".to_string(),
+                    _ => unescape(value),
+                })
+            }
+            "v" => match value.split_once('=') {
+                Some((name, value)) => assignments.push((name.to_string(), unescape(value))),
+                None => return format!("awk: invalid -v argument: {}", value),
+            },
+            _ => return format!("awk: invalid option -- {}", flag),
+        }
+    }
+
+    let program = match program {
+        Some(program) => program,
+        None => return "usage: awk [-F fs][-v var=value] 'prog' [file ...]".to_string(),
+    };
+
+    let rules = match lex(program).and_then(|tokens| {
+        Parser {
+            tokens,
+            pos: 0,
+            in_print: false,
+        }
+        .program()
+    }) {
+        Ok(rules) => rules,
+        Err(err) => return err,
+    };
+
+    let mut interpreter = Interpreter {
+        vars: HashMap::new(),
+        arrays: HashMap::new(),
+        fields: vec![String::new()],
+        output: String::new(),
+        regexes: HashMap::new(),
+        inputs: text::inputs("awk", &operands, stdin).into_iter(),
+        records: Vec::new().into_iter(),
+        files: HashMap::new(),
+        steps: 0,
+    };
+    for (name, value) in [
+        ("FS", " "),
+        ("OFS", " "),
+        ("ORS", "\n"),
+        ("SUBSEP", "\x1c"),
+        ("FILENAME", ""),
+    ] {
+        interpreter
+            .vars
+            .insert(name.to_string(), Value::Str(value.to_string()));
+    }
+    if let Some(separator) = separator {
+        interpreter
+            .vars
+            .insert("FS".to_string(), Value::Str(separator));
+    }
+    for (name, value) in assignments {
+        interpreter.vars.insert(name, Value::StrNum(value));
+    }
+
+    let result = (|| -> Run<()> {
+        let begun = run_special(&mut interpreter, &rules, true)?;
+
+        let has_main = rules
+            .iter()
+            .any(|rule| !matches!(rule.pattern, Pattern::Begin));
+        if begun && has_main {
+            let mut ranges = vec![false; rules.len()];
+            while let Some(record) = interpreter.next_record() {
+                interpreter.set_record(&record)?;
+                if !run_record(&mut interpreter, &rules, &mut ranges)? {
+                    break;
+                }
+            }
+        }
+
+        // `exit` in BEGIN or a main rule still runs END, as in POSIX awk.
+        run_special(&mut interpreter, &rules, false)?;
+        Ok(())
+    })();
+
+    let mut output = std::mem::take(&mut interpreter.output);
+    if let Err(err) = result {
+        output.push_str(&err);
+    }
+    if output.ends_with('\n') {
+        output.pop();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(program: &str) -> Parsed<Vec<Rule>> {
+        Parser {
+            tokens: lex(program)?,
+            pos: 0,
+            in_print: false,
+        }
+        .program()
+    }
+
+    #[test]
+    fn parses_patterns_and_actions() {
+        let rules = parse("BEGIN { x = 1 }\n/a/, /b/\nNR > 1 { print $1 }\nEND {}").unwrap();
+        assert!(matches!(rules[0].pattern, Pattern::Begin));
+        assert!(matches!(rules[1].pattern, Pattern::Range(..)));
+        assert!(rules[1].action.is_none());
+        assert!(matches!(
+            rules[2].pattern,
+            Pattern::Expr(Expr::Binary(">", ..))
+        ));
+        assert!(matches!(rules[3].pattern, Pattern::End));
+
+        let rules = parse("{ getline line < \"f\" }").unwrap();
+        let action = rules[0].action.as_ref().unwrap();
+        assert!(matches!(
+            &action[0],
+            Stmt::Expr(Expr::Getline(Some(_), Some(_)))
+        ));
+
+        assert_eq!(
+            parse("{ print (1 }").err().unwrap(),
+            "awk: line 1: syntax error at or near }"
+        );
+    }
+
+    #[test]
+    fn splits_fields() {
+        assert_eq!(awk(&["{ print $2, NF }"], Some("  a   b  c ")), "b 3");
+        assert_eq!(awk(&["-F:", "{ print $3 }"], Some("a:b:c")), "c");
+        assert_eq!(awk(&["-F", "[0-9]+", "{ print $2 }"], Some("a12b3c")), "b");
+        assert_eq!(
+            awk(
+                &["BEGIN { OFS = \"-\" } { $2 = \"x\"; print }"],
+                Some("a b c")
+            ),
+            "a-x-c"
+        );
+        assert_eq!(awk(&["{ NF = 2; print }"], Some("a b c")), "a b");
+    }
+
+    #[test]
+    fn formats_with_printf() {
+        assert_eq!(
+            awk(
+                &["{ printf \"%-5s|%5.2f|%03d|%x|%c\\n\", $1, $2, $3, $3, $1 }"],
+                Some("ab 3.14159 42")
+            ),
+            "ab   | 3.14|042|2a|a"
+        );
+        assert_eq!(awk(&["BEGIN { printf \"%d%%\", 99.9 }"], None), "99%");
+    }
+
+    #[test]
+    fn counts_in_arrays() {
+        let program = "{ count[$1]++ } END { for (k in count) print k, count[k] }";
+        assert_eq!(awk(&[program], Some("a\nb\na")), "a 2\nb 1");
+        assert_eq!(
+            awk(
+                &["BEGIN { a[1, 2] = 3; print ((1, 2) in a), (2 in a); delete a; print length(a) }"],
+                None
+            ),
+            "1 0\n0"
+        );
+        assert_eq!(
+            awk(
+                &["BEGIN { n = split(\"x:y:z\", parts, \":\"); print n, parts[3] }"],
+                None
+            ),
+            "3 z"
+        );
+    }
+
+    #[test]
+    fn reads_lines_with_getline() {
+        assert_eq!(awk(&["{ getline; print }"], Some("1\n2\n3\n4")), "2\n4");
+        assert_eq!(
+            awk(
+                &["{ getline next_line; print $0 \"+\" next_line, NR }"],
+                Some("a\nb")
+            ),
+            "a+b 2"
+        );
+        assert_eq!(
+            awk(
+                &["BEGIN { while ((getline line) > 0) n++; print n }"],
+                Some("a\nb\nc")
+            ),
+            "3"
+        );
+
+        let passwd = text::inputs("awk", &["/etc/passwd"], None)
+            .pop()
+            .unwrap()
+            .unwrap()
+            .1;
+        assert_eq!(
+            awk(
+                &["BEGIN { getline line < \"/etc/passwd\"; print line }"],
+                None
+            ),
+            passwd.lines().next().unwrap()
+        );
+        assert_eq!(
+            awk(&["BEGIN { print getline line < \"/nonexistent\" }"], None),
+            "-1"
+        );
+    }
+
+    #[test]
+    fn stops_runaway_loops() {
+        for program in [
+            "BEGIN { while (1) {} }",
+            "BEGIN { do x++; while (1) }",
+            "BEGIN { for (;;) {} }",
+        ] {
+            assert_eq!(awk(&[program], None), "awk: execution limit exceeded");
+        }
+        assert_eq!(
+            awk(&["BEGIN { print \"start\"; while (1) {} }"], None),
+            "start\nawk: execution limit exceeded"
+        );
+        assert_eq!(
+            awk(&["BEGIN { for (i = 0; i < 1000; i++) n++; print n }"], None),
+            "1000"
+        );
+    }
+}
@@ -1,92 +1,320 @@
+use crate::commands::disk;
 use crate::commands::filesystem::{
-    get_node, get_node_mut, normalize_path, Node, CURRENT_PATH, CURRENT_USER, FILESYSTEM,
+    collate, get_node, get_node_mut, is_binary, normalize_path, Children, Filesystem, FsError,
+    Inode, InodeTable, Node, CURRENT_PATH, FILESYSTEM,
 };
-use std::collections::HashMap;
-
-pub fn ls(args: &[&str]) -> String {
-    let filesystem = FILESYSTEM.lock().unwrap();
-    let current_path = CURRENT_PATH.lock().unwrap();
+use crate::commands::markdown;
+use crate::commands::permissions::{self, Access};
+use crate::commands::text;
+use crate::commands::trash;
+use crate::commands::users::{self, Session};
+use crate::terminal::buffer;
+
+#[derive(Default)]
+struct LsOptions {
+    all: bool,
+    long: bool,
+    by_time: bool,
+    by_size: bool,
+    reverse: bool,
+    one_per_line: bool,
+    recursive: bool,
+    human: bool,
+    directory: bool,
+    classify: bool,
+}
 
-    let mut show_hidden = false;
-    let mut long_format = false;
-    let mut target_path = None;
+impl LsOptions {
+    fn parse(args: &[&str]) -> Result<(Self, Vec<String>), String> {
+        let mut options = LsOptions::default();
+        let mut operands = Vec::new();
+        let mut only_operands = false;
 
-    for arg in args {
-        if arg.starts_with('-') {
-            for c in arg.chars().skip(1) {
-                match c {
-                    'a' => show_hidden = true,
-                    'l' => long_format = true,
-                    _ => return format!("ls: invalid option -- '{}'", c),
+        for arg in args {
+            if only_operands || !arg.starts_with('-') || *arg == "-" {
+                operands.push(arg.to_string());
+                continue;
+            }
+            let flags: Vec<char> = match *arg {
+                "--" => {
+                    only_operands = true;
+                    continue;
+                }
+                "--all" => vec!['a'],
+                "--reverse" => vec!['r'],
+                "--recursive" => vec!['R'],
+                "--human-readable" => vec!['h'],
+                "--directory" => vec!['d'],
+                "--classify" => vec!['F'],
+                long if long.starts_with("--") => {
+                    return Err(format!("ls: unrecognized option '{}'", long))
+                }
+                short => short.chars().skip(1).collect(),
+            };
+            for flag in flags {
+                match flag {
+                    'a' => options.all = true,
+                    'l' => options.long = true,
+                    't' => options.by_time = true,
+                    'S' => options.by_size = true,
+                    'r' => options.reverse = true,
+                    '1' => options.one_per_line = true,
+                    'R' => options.recursive = true,
+                    'h' => options.human = true,
+                    'd' => options.directory = true,
+                    'F' => options.classify = true,
+                    _ => return Err(format!("ls: invalid option -- '{}'", flag)),
                 }
             }
+        }
+        Ok((options, operands))
+    }
+
+    /// Orders entries already in name order: `-S` by size and `-t` by
+    /// modification, both largest first with ties left in name order,
+    /// then `-r` flips the result.
+    fn sort(&self, fs: &Filesystem, entries: &mut Vec<(String, &Node)>) {
+        if self.by_size {
+            entries.sort_by_key(|(_, node)| std::cmp::Reverse(disk::apparent(fs, node)));
+        } else if self.by_time {
+            entries.sort_by_key(|(_, node)| std::cmp::Reverse(modified(fs, node)));
+        }
+        if self.reverse {
+            entries.reverse();
+        }
+    }
+
+    fn name(&self, fs: &Filesystem, name: &str, node: &Node) -> String {
+        if !self.classify {
+            return name.to_string();
+        }
+        let indicator = match node {
+            Node::Directory { .. } => "/",
+            Node::Symlink { .. } if !self.long => "@",
+            Node::File { .. } if node.get_permissions(&fs.inodes) & 0o111 != 0 => "*",
+            _ => "",
+        };
+        format!("{}{}", name, indicator)
+    }
+
+    /// Renders one group of entries, either one `-l` line each or in
+    /// columns.
+    fn format(&self, fs: &Filesystem, entries: &[(String, &Node)]) -> String {
+        if self.long {
+            return entries
+                .iter()
+                .map(|(name, node)| self.long_entry(fs, name, node))
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        let names: Vec<String> = entries
+            .iter()
+            .map(|(name, node)| self.name(fs, name, node))
+            .collect();
+        if self.one_per_line {
+            names.join("\n")
         } else {
-            target_path = Some(*arg);
+            columns(&names, buffer::terminal_width())
         }
     }
 
-    let path = if let Some(target) = target_path {
-        normalize_path(target, &current_path)
-    } else {
-        current_path.clone()
-    };
+    fn long_entry(&self, fs: &Filesystem, name: &str, node: &Node) -> String {
+        let size = disk::apparent(fs, node);
+        let size = if self.human {
+            disk::human(size)
+        } else {
+            size.to_string()
+        };
 
-    let node = match get_node(&filesystem, &path) {
-        Some(node) => node,
-        None => return "ls: cannot access: No such file or directory".into(),
-    };
+        let name = match node {
+            Node::Symlink { target, .. } => format!("{} -> {}", name, target),
+            _ => self.name(fs, name, node),
+        };
 
+        // Like `stat`, the logical clock tick counts seconds since boot.
+        let tick = modified(fs, node);
+        format!(
+            "{} {:>2} {} {} {:>8} Jan  1 12:{:02} {}",
+            node.mode_string(&fs.inodes),
+            node.link_count(&fs.inodes),
+            node.get_owner(&fs.inodes),
+            node.get_group(&fs.inodes),
+            size,
+            (tick / 60) % 60,
+            name
+        )
+    }
+}
+
+fn modified(fs: &Filesystem, node: &Node) -> u64 {
     match node {
-        Node::Directory { children, .. } => {
-            let mut entries: Vec<_> = children.iter().collect();
-            entries.sort_by_key(|(name, _)| name.as_str());
-
-            if long_format {
-                let mut output = String::new();
-                for (name, node) in &entries {
-                    if !show_hidden && name.starts_with('.') {
-                        continue;
-                    }
+        Node::File { inode } => fs.inodes.modified(*inode),
+        _ => 0,
+    }
+}
 
-                    let (file_type, permissions, size) = match node {
-                        Node::Directory { permissions, .. } => ('d', *permissions, 4096),
-                        Node::File {
-                            permissions,
-                            content,
-                            ..
-                        } => ('-', *permissions, content.len()),
-                        Node::Symlink { .. } => ('l', 0o777, 0),
-                    };
+/// Lays `names` out top-to-bottom in as many columns as fit in `width`,
+/// two spaces apart, the way `ls` does on a terminal.
+fn columns(names: &[String], width: usize) -> String {
+    let widths: Vec<usize> = names.iter().map(|name| name.chars().count()).collect();
+    let layout = (1..=names.len().max(1))
+        .rev()
+        .map(|count| {
+            let rows = names.len().div_ceil(count).max(1);
+            let column_widths: Vec<usize> = widths
+                .chunks(rows)
+                .map(|column| column.iter().copied().max().unwrap_or(0))
+                .collect();
+            (rows, column_widths)
+        })
+        .find(|(_, column_widths)| {
+            column_widths.iter().sum::<usize>() + 2 * column_widths.len().saturating_sub(1) <= width
+        });
+    let (rows, column_widths) = match layout {
+        Some(layout) => layout,
+        None => return names.join("\n"),
+    };
 
-                    output.push_str(&format!(
-                        "{}{:o} 1 objz objz {:>8} {} {}\n",
-                        file_type, permissions, size, "Jan  1 12:00", name
-                    ));
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            for (column, column_width) in column_widths.iter().enumerate() {
+                if let Some(name) = names.get(column * rows + row) {
+                    if !line.is_empty() {
+                        line.push_str("  ");
+                    }
+                    line.push_str(&format!("{:<width$}", name, width = column_width));
                 }
-                output
-            } else {
-                entries
-                    .iter()
-                    .filter(|(name, _)| show_hidden || !name.starts_with('.'))
-                    .map(|(name, node)| match node {
-                        Node::Directory { .. } => format!("{}/", name),
-                        Node::File { .. } => name.to_string(),
-                        Node::Symlink { .. } => format!("{}@", name),
-                    })
-                    .collect::<Vec<_>>()
-                    .join("  ")
             }
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lists the directory at `path` and, with `-R`, every directory below it.
+/// Each listing goes into `sections` with its heading when `heading` is set.
+fn list_directory(
+    fs: &Filesystem,
+    session: &Session,
+    options: &LsOptions,
+    display: &str,
+    node: &Node,
+    heading: bool,
+    sections: &mut Vec<String>,
+) {
+    let children = match node {
+        Node::Directory { children, .. } => children,
+        _ => return,
+    };
+    let title = if heading {
+        format!("{}:\n", display)
+    } else {
+        String::new()
+    };
+    if !permissions::check(fs, node, session, Access::Read) {
+        sections.push(format!(
+            "{}ls: cannot open directory '{}': {}",
+            title,
+            display,
+            FsError::PermissionDenied
+        ));
+        return;
+    }
+
+    let mut entries: Vec<(String, &Node)> = children
+        .iter()
+        .filter(|(name, _)| options.all || !name.starts_with('.'))
+        .map(|(name, child)| (name.clone(), child))
+        .collect();
+    options.sort(fs, &mut entries);
+    sections.push(format!("{}{}", title, options.format(fs, &entries)));
+
+    if options.recursive {
+        for (name, child) in &entries {
+            if let Node::Directory { .. } = child {
+                let path = format!("{}/{}", display.trim_end_matches('/'), name);
+                list_directory(fs, session, options, &path, child, true, sections);
+            }
+        }
+    }
+}
+
+/// `ls [-altSr1RhdF] [path...]`. Names are collated the way `ls` does in
+/// a UTF-8 locale; see `filesystem::collate`.
+pub fn ls(args: &[&str]) -> String {
+    let (options, operands) = match LsOptions::parse(args) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let operands = if operands.is_empty() {
+        vec![".".to_string()]
+    } else {
+        operands
+    };
+
+    let mut errors = Vec::new();
+    let mut files: Vec<(String, &Node)> = Vec::new();
+    let mut directories: Vec<(String, &Node)> = Vec::new();
+    for operand in &operands {
+        let path = normalize_path(operand, &current_path);
+        // `-l` and `-d` describe a link itself; everything else follows it.
+        let lookup = match permissions::traverse(&filesystem, &path, &session) {
+            Ok(link @ Node::Symlink { .. }) if options.long || options.directory => Ok(link),
+            Ok(_) => permissions::resolve(&filesystem, &path, &session).map(|(_, node)| node),
+            Err(err) => Err(err),
+        };
+        match lookup {
+            Ok(node @ Node::Directory { .. }) if !options.directory => {
+                directories.push((operand.clone(), node))
+            }
+            Ok(node) => files.push((operand.clone(), node)),
+            Err(err) => errors.push(format!("ls: cannot access '{}': {}", operand, err)),
         }
-        Node::File { .. } => target_path.unwrap_or(".").to_string(),
-        Node::Symlink { target, .. } => format!("-> {}", target),
     }
+
+    let mut sections = Vec::new();
+    if !files.is_empty() {
+        files.sort_by(|(a, _), (b, _)| collate(a, b));
+        options.sort(&filesystem, &mut files);
+        sections.push(options.format(&filesystem, &files));
+    }
+    directories.sort_by(|(a, _), (b, _)| collate(a, b));
+    if options.reverse {
+        directories.reverse();
+    }
+    let heading = options.recursive || operands.len() > 1;
+    for (display, node) in &directories {
+        list_directory(
+            &filesystem,
+            &session,
+            &options,
+            display,
+            node,
+            heading,
+            &mut sections,
+        );
+    }
+
+    let listing = sections
+        .iter()
+        .map(|section| section.trim_end())
+        .filter(|section| !section.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    errors.extend(Some(listing).filter(|listing| !listing.is_empty()));
+    errors.join("\n")
 }
 
 pub fn cd(args: &[&str]) -> String {
     if args.is_empty() {
         {
             let mut path = CURRENT_PATH.lock().unwrap();
-            *path = vec!["home".to_string(), "objz".to_string()];
+            *path = users::home_dir();
         }
         return String::new();
     }
@@ -94,21 +322,23 @@ pub fn cd(args: &[&str]) -> String {
     let new_path = {
         let filesystem = FILESYSTEM.lock().unwrap();
         let current_path = CURRENT_PATH.lock().unwrap();
+        let session = users::current_session();
 
         let new_path = normalize_path(args[0], &current_path);
 
-        match get_node(&filesystem, &new_path) {
-            Some(Node::Directory { .. }) => new_path,
-            Some(Node::Symlink { target, .. }) => {
-                let symlink_path = normalize_path(target, &current_path);
-                match get_node(&filesystem, &symlink_path) {
-                    Some(Node::Directory { .. }) => symlink_path,
-                    Some(_) => return format!("cd: {}: Not a directory", args[0]),
-                    None => return format!("cd: {}: No such file or directory", args[0]),
+        let (resolved, node) = match permissions::resolve(&filesystem, &new_path, &session) {
+            Ok(found) => found,
+            Err(err) => return format!("cd: {}: {}", args[0], err),
+        };
+
+        match node {
+            Node::Directory { .. } => {
+                if !permissions::check(&filesystem, node, &session, Access::Execute) {
+                    return format!("cd: {}: {}", args[0], FsError::PermissionDenied);
                 }
+                resolved
             }
-            Some(_) => return format!("cd: {}: Not a directory", args[0]),
-            None => return format!("cd: {}: No such file or directory", args[0]),
+            _ => return format!("cd: {}: {}", args[0], FsError::NotADirectory),
         }
     };
 
@@ -128,54 +358,124 @@ pub fn pwd(_: &[&str]) -> String {
     }
 }
 
+/// `cat -v` notation: `^X` for control characters, `M-` for bytes above
+/// 127. Newlines and tabs stay as they are.
+fn show_nonprinting(content: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in content {
+        let (meta, byte) = if byte >= 0x80 {
+            ("M-", byte - 0x80)
+        } else {
+            ("", byte)
+        };
+        out.push_str(meta);
+        match byte {
+            b'\n' | b'\t' if meta.is_empty() => out.push(byte as char),
+            0x7f => out.push_str("^?"),
+            0..=0x1f => {
+                out.push('^');
+                out.push((byte + 0x40) as char);
+            }
+            _ => out.push(byte as char),
+        }
+    }
+    out
+}
+
+/// `cat file...` writing into a pipe: the files' bytes, unchanged, with
+/// `-` or no operands meaning stdin.
+pub fn cat_raw(args: &[&str], stdin: Option<&[u8]>) -> Vec<u8> {
+    let mut output = Vec::new();
+    for input in text::binary_inputs("cat", args, stdin) {
+        match input {
+            Ok((_, content)) => output.extend(content),
+            Err(err) => output.extend(format!("{}\n", err).into_bytes()),
+        }
+    }
+    output
+}
+
+/// `cat [-v] file...`. Binary files are not dumped onto the terminal;
+/// `-v` shows them with control characters made visible instead.
 pub fn cat(args: &[&str]) -> String {
-    if args.is_empty() {
+    let mut visible = false;
+    let mut render = false;
+    let mut operands = Vec::new();
+    for &arg in args {
+        match arg {
+            "-v" | "--show-nonprinting" => visible = true,
+            "--render" => render = true,
+            option if option.starts_with('-') && option.len() > 1 => {
+                return format!(
+                    "cat: invalid option -- '{}'",
+                    option.trim_start_matches('-')
+                )
+            }
+            operand => operands.push(operand),
+        }
+    }
+    if operands.is_empty() {
         return "cat: missing file operand".into();
     }
 
     let filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
 
     let mut output = String::new();
 
-    for &filename in args {
+    for (index, &filename) in operands.iter().enumerate() {
         let file_path = normalize_path(filename, &current_path);
 
-        match get_node(&filesystem, &file_path) {
-            Some(Node::File { content, .. }) => {
-                output.push_str(content);
-                if args.len() > 1 && filename != args[args.len() - 1] {
-                    output.push('\n');
-                }
-            }
-            Some(Node::Directory { .. }) => {
-                output.push_str(&format!("cat: {}: Is a directory\n", filename));
+        let node = match permissions::resolve(&filesystem, &file_path, &session) {
+            Ok((_, node)) => node,
+            Err(err) => {
+                output.push_str(&format!("cat: {}: {}\n", filename, err));
+                continue;
             }
-            Some(Node::Symlink { target, .. }) => {
-                let target_path = normalize_path(target, &current_path);
-                match get_node(&filesystem, &target_path) {
-                    Some(Node::File { content, .. }) => {
-                        output.push_str(content);
-                        if args.len() > 1 && filename != args[args.len() - 1] {
-                            output.push('\n');
-                        }
-                    }
-                    Some(Node::Directory { .. }) => {
-                        output.push_str(&format!("cat: {}: Is a directory\n", filename));
-                    }
-                    Some(Node::Symlink { .. }) => {
-                        output.push_str(&format!(
-                            "cat: {}: Too many levels of symbolic links\n",
-                            filename
-                        ));
-                    }
-                    None => {
-                        output.push_str(&format!("cat: {}: No such file or directory\n", filename));
-                    }
+        };
+
+        match node {
+            Node::File { inode } => {
+                if !permissions::check(&filesystem, node, &session, Access::Read) {
+                    output.push_str(&format!(
+                        "cat: {}: {}\n",
+                        filename,
+                        FsError::PermissionDenied
+                    ));
+                    continue;
+                }
+                let content = filesystem.read_bytes(*inode);
+                // Kernel files under /proc separate fields with NULs but
+                // are meant to be read; devices like /dev/random are not.
+                let kernel_text = filesystem
+                    .inodes
+                    .generator(*inode)
+                    .is_some_and(|generator| !generator.device);
+                if visible {
+                    output.push_str(&show_nonprinting(&content));
+                } else if is_binary(&content) && !kernel_text {
+                    output.push_str(&format!(
+                        "cat: {}: binary file ({} bytes) not shown; use 'cat -v' or 'xxd {}'\n",
+                        filename,
+                        content.len(),
+                        filename
+                    ));
+                    continue;
+                } else if render {
+                    output.push_str(&markdown::render(
+                        &String::from_utf8_lossy(&content),
+                        buffer::terminal_width(),
+                    ));
+                } else {
+                    output.push_str(&String::from_utf8_lossy(&content));
+                }
+                if index + 1 < operands.len() {
+                    output.push('\n');
                 }
             }
-            None => {
-                output.push_str(&format!("cat: {}: No such file or directory\n", filename));
+            _ => {
+                output.push_str(&format!("cat: {}: {}\n", filename, FsError::IsADirectory));
             }
         }
     }
@@ -190,7 +490,7 @@ pub fn mkdir(args: &[&str]) -> String {
 
     let mut filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
-    let current_user = &*CURRENT_USER;
+    let session = users::current_session();
 
     for &dirname in args {
         let dir_path = normalize_path(dirname, &current_path);
@@ -202,15 +502,14 @@ pub fn mkdir(args: &[&str]) -> String {
         let parent_path = &dir_path[..dir_path.len() - 1];
         let dir_name = &dir_path[dir_path.len() - 1];
 
-        let parent = match get_node_mut(&mut filesystem, parent_path) {
+        let parent_path = match permissions::can_create(&filesystem, parent_path, &session) {
+            Ok(canonical) => canonical,
+            Err(err) => return format!("mkdir: cannot create directory '{}': {}", dirname, err),
+        };
+
+        let parent = match get_node_mut(&mut filesystem, &parent_path) {
             Some(Node::Directory { children, .. }) => children,
-            Some(_) => {
-                return format!(
-                    "mkdir: cannot create directory '{}': Not a directory",
-                    dirname
-                )
-            }
-            None => {
+            _ => {
                 return format!(
                     "mkdir: cannot create directory '{}': No such file or directory",
                     dirname
@@ -219,16 +518,20 @@ pub fn mkdir(args: &[&str]) -> String {
         };
 
         if parent.contains_key(dir_name) {
-            return format!("mkdir: cannot create directory '{}': File exists", dirname);
+            return format!(
+                "mkdir: cannot create directory '{}': {}",
+                dirname,
+                FsError::AlreadyExists
+            );
         }
 
         parent.insert(
             dir_name.clone(),
             Node::Directory {
                 permissions: 0o755,
-                owner: current_user.clone(),
-                protected: false,
-                children: HashMap::new(),
+                owner: session.user.clone(),
+                group: session.primary_group(),
+                children: Children::new(),
             },
         );
     }
@@ -243,7 +546,7 @@ pub fn touch(args: &[&str]) -> String {
 
     let mut filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
-    let current_user = &*CURRENT_USER;
+    let session = users::current_session();
 
     for &filename in args {
         let file_path = normalize_path(filename, &current_path);
@@ -255,10 +558,41 @@ pub fn touch(args: &[&str]) -> String {
         let parent_path = &file_path[..file_path.len() - 1];
         let file_name = &file_path[file_path.len() - 1];
 
-        let parent = match get_node_mut(&mut filesystem, parent_path) {
-            Some(Node::Directory { children, .. }) => children,
-            Some(_) => return format!("touch: cannot touch '{}': Not a directory", filename),
-            None => {
+        match permissions::resolve(&filesystem, &file_path, &session) {
+            Ok((canonical, node)) => {
+                if let Err(err) = filesystem.writable(&canonical) {
+                    return format!("touch: cannot touch '{}': {}", filename, err);
+                }
+                if !permissions::check(&filesystem, node, &session, Access::Write) {
+                    return format!(
+                        "touch: cannot touch '{}': {}",
+                        filename,
+                        FsError::PermissionDenied
+                    );
+                }
+                if let Node::File { inode } = *node {
+                    filesystem.inodes.touch(inode);
+                }
+                continue;
+            }
+            // A dangling symlink: there is nothing sensible to create.
+            Err(FsError::NotFound)
+                if permissions::traverse(&filesystem, &file_path, &session).is_ok() =>
+            {
+                return format!("touch: cannot touch '{}': {}", filename, FsError::NotFound);
+            }
+            Err(FsError::NotFound) => {}
+            Err(err) => return format!("touch: cannot touch '{}': {}", filename, err),
+        }
+
+        let parent_path = match permissions::can_create(&filesystem, parent_path, &session) {
+            Ok(canonical) => canonical,
+            Err(err) => return format!("touch: cannot touch '{}': {}", filename, err),
+        };
+
+        let (parent, inodes) = match filesystem.split_mut(&parent_path) {
+            Some((Node::Directory { children, .. }, inodes)) => (children, inodes),
+            _ => {
                 return format!(
                     "touch: cannot touch '{}': No such file or directory",
                     filename
@@ -266,42 +600,47 @@ pub fn touch(args: &[&str]) -> String {
             }
         };
 
-        if !parent.contains_key(file_name) {
-            parent.insert(
-                file_name.clone(),
-                Node::File {
-                    content: String::new(),
-                    permissions: 0o644,
-                    owner: current_user.clone(),
-                    protected: false,
-                },
-            );
-        }
+        parent.insert(
+            file_name.clone(),
+            inodes.create(Inode {
+                content: Vec::new(),
+                permissions: 0o644,
+                owner: session.user.clone(),
+                group: session.primary_group(),
+            }),
+        );
     }
 
     String::new()
 }
 
-pub fn rm(args: &[&str]) -> String {
+/// Also says whether root set about wiping the whole system, which the
+/// terminal answers with a kernel panic.
+pub fn rm(args: &[&str]) -> (String, bool) {
     if args.is_empty() {
-        return "rm: missing operand".into();
+        return ("rm: missing operand".into(), false);
     }
 
     let mut filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
-    let current_user = &*CURRENT_USER;
+    let session = users::current_session();
 
     let mut recursive = false;
     let mut force = false;
+    let mut to_trash = false;
     let mut files = Vec::new();
 
     for &arg in args {
-        if arg.starts_with('-') {
+        if arg == "--trash" {
+            to_trash = true;
+        } else if arg == "--no-preserve-root" {
+            // `/` is never protected here; the panic is the point.
+        } else if arg.starts_with('-') {
             for c in arg.chars().skip(1) {
                 match c {
                     'r' | 'R' => recursive = true,
                     'f' => force = true,
-                    _ => return format!("rm: invalid option -- '{}'", c),
+                    _ => return (format!("rm: invalid option -- '{}'", c), false),
                 }
             }
         } else {
@@ -313,82 +652,102 @@ pub fn rm(args: &[&str]) -> String {
         let file_path = normalize_path(filename, &current_path);
 
         if file_path.is_empty() {
+            // Root really can wipe the system, as far as the mounts let it.
+            // The panic sequence takes it from here and brings it back from
+            // the snapshot taken before.
+            if recursive && force && session.is_root() {
+                wipe(&mut filesystem, &[]);
+                return (String::new(), true);
+            }
             if !force {
-                return "rm: cannot remove '/': Permission denied".into();
+                return ("rm: cannot remove '/': Permission denied".into(), false);
             }
             continue;
         }
 
-        let parent_path = &file_path[..file_path.len() - 1];
-        let file_name = &file_path[file_path.len() - 1];
+        let node = match permissions::traverse(&filesystem, &file_path, &session) {
+            Ok(node) => node,
+            Err(FsError::NotFound) if force => continue,
+            Err(err) => return (format!("rm: cannot remove '{}': {}", filename, err), false),
+        };
 
-        let parent = match get_node_mut(&mut filesystem, parent_path) {
-            Some(Node::Directory { children, .. }) => children,
-            Some(_) => {
+        if let Node::Directory { .. } = node {
+            if !recursive {
                 if !force {
-                    return format!("rm: cannot remove '{}': Not a directory", filename);
-                }
-                continue;
-            }
-            None => {
-                if !force {
-                    return format!(
-                        "rm: cannot remove '{}': No such file or directory",
-                        filename
+                    return (
+                        format!("rm: cannot remove '{}': Is a directory", filename),
+                        false,
                     );
                 }
                 continue;
             }
-        };
+            if !permissions::can_remove_tree(&filesystem, node, &session) {
+                return (
+                    format!(
+                        "rm: cannot remove '{}': {}",
+                        filename,
+                        FsError::PermissionDenied
+                    ),
+                    false,
+                );
+            }
+        }
 
-        match parent.get(file_name) {
-            Some(node) => {
-                if node.is_protected() {
-                    return format!(
-                        "rm: cannot remove '{}': Operation not permitted (protected system file)",
-                        filename
-                    );
-                }
+        let canonical = match permissions::can_remove(&filesystem, &file_path, &session) {
+            Ok(canonical) => canonical,
+            Err(err) => return (format!("rm: cannot remove '{}': {}", filename, err), false),
+        };
 
-                if node.get_owner() != current_user && current_user != "root" {
-                    return format!(
-                        "rm: cannot remove '{}': Permission denied (not owner)",
-                        filename
-                    );
-                }
+        if to_trash {
+            if let Err(err) = trash::move_to_trash(&mut filesystem, &canonical, &session) {
+                return (
+                    format!("rm: cannot move '{}' to trash: {}", filename, err),
+                    false,
+                );
+            }
+            continue;
+        }
 
-                match node {
-                    Node::Directory { .. } => {
-                        if !recursive {
-                            if !force {
-                                return format!("rm: cannot remove '{}': Is a directory", filename);
-                            }
-                            continue;
-                        }
-                        parent.remove(file_name);
-                    }
-                    Node::File { .. } | Node::Symlink { .. } => {
-                        parent.remove(file_name);
-                    }
-                }
+        let (file_name, parent_path) = canonical.split_last().unwrap();
+        if let Some((Node::Directory { children, .. }, inodes)) = filesystem.split_mut(parent_path)
+        {
+            if let Some(removed) = children.remove(file_name) {
+                inodes.release(&removed);
             }
-            None => {
-                if !force {
-                    return format!(
-                        "rm: cannot remove '{}': No such file or directory",
-                        filename
-                    );
+        }
+    }
+
+    (String::new(), false)
+}
+
+/// Removes everything below the directory at `path` (canonical) that sits
+/// on a writable mount. Mount points stay but are emptied.
+fn wipe(fs: &mut Filesystem, path: &[String]) {
+    let names: Vec<String> = match get_node(fs, path) {
+        Some(Node::Directory { children, .. }) => children.keys().cloned().collect(),
+        _ => return,
+    };
+
+    for name in names {
+        let mut child = path.to_vec();
+        child.push(name.clone());
+
+        if fs.has_mount_under(&child) {
+            wipe(fs, &child);
+        } else if fs.writable(path).is_ok() {
+            if let Some((Node::Directory { children, .. }, inodes)) = fs.split_mut(path) {
+                if let Some(node) = children.remove(&name) {
+                    inodes.release(&node);
                 }
             }
         }
     }
-
-    String::new()
 }
 
 pub fn tree(args: &[&str]) -> String {
     let filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
 
     let start_path = if args.is_empty() {
         current_path.clone()
@@ -396,17 +755,22 @@ pub fn tree(args: &[&str]) -> String {
         normalize_path(args[0], &current_path)
     };
 
-    let start_node = match get_node(&filesystem, &start_path) {
-        Some(node) => node,
-        None => return "tree: No such file or directory".into(),
+    let start_node = match permissions::resolve(&filesystem, &start_path, &session) {
+        Ok((_, node)) => node,
+        Err(err) => return format!("tree: {}", err),
     };
 
-    fn build_tree(node: &Node, prefix: &str, _is_last: bool) -> String {
+    fn build_tree(fs: &Filesystem, node: &Node, prefix: &str, session: &Session) -> String {
         let mut output = String::new();
 
         if let Node::Directory { children, .. } = node {
-            let mut entries: Vec<_> = children.iter().collect();
-            entries.sort_by_key(|(name, _)| name.as_str());
+            if !permissions::check(fs, node, session, Access::Read)
+                || !permissions::check(fs, node, session, Access::Execute)
+            {
+                return format!("{}└── [error opening dir]\n", prefix);
+            }
+
+            let entries: Vec<_> = children.iter().collect();
 
             for (i, (name, child)) in entries.iter().enumerate() {
                 let is_last_child = i == entries.len() - 1;
@@ -427,7 +791,7 @@ pub fn tree(args: &[&str]) -> String {
                 let new_prefix =
                     format!("{}{}", prefix, if is_last_child { "    " } else { "│   " });
 
-                output.push_str(&build_tree(child, &new_prefix, is_last_child));
+                output.push_str(&build_tree(fs, child, &new_prefix, session));
             }
         }
 
@@ -440,26 +804,36 @@ pub fn tree(args: &[&str]) -> String {
         start_path.last().unwrap_or(&"/".to_string()).clone()
     };
 
-    format!("{}\n{}", tree_name, build_tree(start_node, "", true))
+    format!(
+        "{}\n{}",
+        tree_name,
+        build_tree(&filesystem, start_node, "", &session)
+    )
 }
 
 pub fn ln(args: &[&str]) -> String {
-    if args.len() < 2 {
-        return "ln: missing file operand".into();
+    let mut symbolic = false;
+    let mut operands = Vec::new();
+
+    for &arg in args {
+        match arg.strip_prefix('-') {
+            Some("s") => symbolic = true,
+            Some(option) if !option.is_empty() => {
+                return format!("ln: invalid option -- '{}'", option)
+            }
+            _ => operands.push(arg),
+        }
     }
 
+    let (target, link_name) = match operands[..] {
+        [target, link_name] => (target, link_name),
+        [_] | [] => return "ln: missing file operand".into(),
+        _ => return format!("ln: extra operand '{}'", operands[2]),
+    };
+
     let mut filesystem = FILESYSTEM.lock().unwrap();
     let current_path = CURRENT_PATH.lock().unwrap();
-    let current_user = &*CURRENT_USER;
-
-    let (target, link_name) = if args[0] == "-s" {
-        if args.len() < 3 {
-            return "ln: missing file operand".into();
-        }
-        (args[1], args[2])
-    } else {
-        return "ln: hard links not supported in this filesystem".into();
-    };
+    let session = users::current_session();
 
     let link_path = normalize_path(link_name, &current_path);
 
@@ -470,10 +844,41 @@ pub fn ln(args: &[&str]) -> String {
     let parent_path = &link_path[..link_path.len() - 1];
     let file_name = &link_path[link_path.len() - 1];
 
-    let parent = match get_node_mut(&mut filesystem, parent_path) {
-        Some(Node::Directory { children, .. }) => children,
-        Some(_) => return format!("ln: cannot create link '{}': Not a directory", link_name),
-        None => {
+    // A hard link names the target itself, so it has to exist and may not
+    // be a directory. Like Linux's protected_hardlinks, only the owner or
+    // someone who could read and write the file may link to it.
+    let hard_target = if symbolic {
+        None
+    } else {
+        let target_path = normalize_path(target, &current_path);
+        let node = match permissions::traverse(&filesystem, &target_path, &session) {
+            Ok(node) => node,
+            Err(err) => return format!("ln: failed to access '{}': {}", target, err),
+        };
+        if let Node::Directory { .. } = node {
+            return format!("ln: {}: hard link not allowed for directory", target);
+        }
+        if !session.is_root()
+            && node.get_owner(&filesystem.inodes) != session.user
+            && !(permissions::check(&filesystem, node, &session, Access::Read)
+                && permissions::check(&filesystem, node, &session, Access::Write))
+        {
+            return format!(
+                "ln: failed to create hard link '{}' => '{}': Operation not permitted",
+                link_name, target
+            );
+        }
+        Some(node.clone())
+    };
+
+    let parent_path = match permissions::can_create(&filesystem, parent_path, &session) {
+        Ok(canonical) => canonical,
+        Err(err) => return format!("ln: cannot create link '{}': {}", link_name, err),
+    };
+
+    let (parent, inodes) = match filesystem.split_mut(&parent_path) {
+        Some((Node::Directory { children, .. }, inodes)) => (children, inodes),
+        _ => {
             return format!(
                 "ln: cannot create link '{}': No such file or directory",
                 link_name
@@ -482,20 +887,274 @@ pub fn ln(args: &[&str]) -> String {
     };
 
     if parent.contains_key(file_name) {
-        return format!("ln: cannot create link '{}': File exists", link_name);
+        return format!(
+            "ln: cannot create link '{}': {}",
+            link_name,
+            FsError::AlreadyExists
+        );
     }
 
-    parent.insert(
-        file_name.clone(),
-        Node::Symlink {
+    let link = match hard_target {
+        Some(Node::File { inode }) => inodes.link(inode),
+        // Symlinks have no inode of their own here; linking one copies it.
+        Some(symlink) => symlink,
+        None => Node::Symlink {
             target: target.to_string(),
-            owner: current_user.clone(),
+            owner: session.user.clone(),
         },
-    );
+    };
+    parent.insert(file_name.clone(), link);
 
     String::new()
 }
 
+pub fn chmod(args: &[&str]) -> String {
+    let mut recursive = false;
+    let mut mode = None;
+    let mut files = Vec::new();
+
+    for &arg in args {
+        if arg == "-R" {
+            recursive = true;
+        } else if mode.is_none() {
+            mode = Some(arg);
+        } else {
+            files.push(arg);
+        }
+    }
+
+    let mode = match mode {
+        Some(mode) => mode,
+        None => return "chmod: missing operand".into(),
+    };
+    if files.is_empty() {
+        return format!("chmod: missing operand after '{}'", mode);
+    }
+    if permissions::parse_mode(mode, 0, false).is_none() {
+        return format!("chmod: invalid mode: '{}'", mode);
+    }
+
+    change_attributes("chmod", "permissions", &files, recursive, |node, inodes| {
+        let is_dir = matches!(node, Node::Directory { .. });
+        let current = node.get_permissions(inodes);
+        if let Some(new_mode) = permissions::parse_mode(mode, current, is_dir) {
+            node.set_permissions(inodes, new_mode);
+        }
+    })
+}
+
+pub fn chown(args: &[&str]) -> String {
+    let (recursive, spec, files) = match split_owner_args("chown", args) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    let (user, group) = match spec.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (spec, None),
+    };
+
+    {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        if !user.is_empty() && users::find_user(&filesystem, user).is_none() {
+            return format!("chown: invalid user: '{}'", spec);
+        }
+        if let Some(group) = group.filter(|group| !group.is_empty()) {
+            if users::find_group(&filesystem, group).is_none() {
+                return format!("chown: invalid group: '{}'", spec);
+            }
+        }
+    }
+
+    change_ownership("chown", &files, recursive, |node, inodes| {
+        if !user.is_empty() {
+            node.set_owner(inodes, user);
+        }
+        if let Some(group) = group.filter(|group| !group.is_empty()) {
+            node.set_group(inodes, group);
+        }
+    })
+}
+
+pub fn chgrp(args: &[&str]) -> String {
+    let (recursive, group, files) = match split_owner_args("chgrp", args) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        if users::find_group(&filesystem, group).is_none() {
+            return format!("chgrp: invalid group: '{}'", group);
+        }
+    }
+
+    change_ownership("chgrp", &files, recursive, |node, inodes| {
+        node.set_group(inodes, group)
+    })
+}
+
+fn split_owner_args<'a>(
+    command: &str,
+    args: &[&'a str],
+) -> Result<(bool, &'a str, Vec<&'a str>), String> {
+    let mut recursive = false;
+    let mut operands = Vec::new();
+
+    for &arg in args {
+        if arg == "-R" {
+            recursive = true;
+        } else if let Some(option) = arg.strip_prefix('-') {
+            return Err(format!("{}: invalid option -- '{}'", command, option));
+        } else {
+            operands.push(arg);
+        }
+    }
+
+    match operands.split_first() {
+        Some((spec, files)) if !files.is_empty() => Ok((recursive, spec, files.to_vec())),
+        Some((spec, _)) => Err(format!("{}: missing operand after '{}'", command, spec)),
+        None => Err(format!("{}: missing operand", command)),
+    }
+}
+
+/// Only root may give files away or move them between groups.
+fn change_ownership(
+    command: &str,
+    files: &[&str],
+    recursive: bool,
+    apply: impl FnMut(&mut Node, &mut InodeTable),
+) -> String {
+    if !users::current_session().is_root() {
+        return files
+            .iter()
+            .map(|file| {
+                format!(
+                    "{}: changing ownership of '{}': Operation not permitted",
+                    command, file
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    change_attributes(command, "ownership", files, recursive, apply)
+}
+
+/// Shared walk for chmod/chown/chgrp: follows symlinks on the command line and
+/// requires the caller to own each node it touches (root excepted).
+fn change_attributes(
+    command: &str,
+    what: &str,
+    files: &[&str],
+    recursive: bool,
+    mut apply: impl FnMut(&mut Node, &mut InodeTable),
+) -> String {
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let mut output = Vec::new();
+
+    for &file in files {
+        let path =
+            match permissions::resolve(&filesystem, &normalize_path(file, &current_path), &session)
+            {
+                Ok((canonical, _)) => canonical,
+                Err(err) => {
+                    output.push(format!("{}: cannot access '{}': {}", command, file, err));
+                    continue;
+                }
+            };
+
+        if let Err(err) = filesystem.writable(&path) {
+            output.push(format!(
+                "{}: changing {} of '{}': {}",
+                command, what, file, err
+            ));
+            continue;
+        }
+
+        let (node, inodes) = match filesystem.split_mut(&path) {
+            Some(split) => split,
+            None => continue,
+        };
+
+        let mut denied = false;
+        node.for_each_mut(recursive, &mut |node| {
+            if node.get_owner(inodes) == session.user || session.is_root() {
+                apply(node, inodes);
+            } else {
+                denied = true;
+            }
+        });
+
+        if denied {
+            output.push(format!(
+                "{}: changing {} of '{}': Operation not permitted",
+                command, what, file
+            ));
+        }
+    }
+
+    output.join("\n")
+}
+
+pub fn readlink(args: &[&str]) -> String {
+    let canonicalize = args.contains(&"-f");
+    let files: Vec<&str> = args.iter().copied().filter(|&arg| arg != "-f").collect();
+
+    if files.is_empty() {
+        return "readlink: missing operand".into();
+    }
+    if canonicalize {
+        return canonical_paths("readlink", &files);
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    files
+        .iter()
+        .filter_map(|&file| {
+            let path = normalize_path(file, &current_path);
+            match permissions::traverse(&filesystem, &path, &session) {
+                Ok(Node::Symlink { target, .. }) => Some(target.clone()),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn realpath(args: &[&str]) -> String {
+    if args.is_empty() {
+        return "realpath: missing operand".into();
+    }
+    canonical_paths("realpath", args)
+}
+
+/// Resolves each of `files` to its canonical path, as `realpath` and
+/// `readlink -f` do; errors name `program`.
+fn canonical_paths(program: &str, files: &[&str]) -> String {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    files
+        .iter()
+        .map(|&file| {
+            let path = normalize_path(file, &current_path);
+            match permissions::resolve(&filesystem, &path, &session) {
+                Ok((canonical, _)) => format!("/{}", canonical.join("/")),
+                Err(err) => format!("{}: {}: {}", program, file, err),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn uname(args: &[&str]) -> String {
     if args.is_empty() || args[0] == "-s" {
         "WASM".to_string()
@@ -506,3 +1165,62 @@ pub fn uname(args: &[&str]) -> String {
         "uname: invalid option".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::filesystem;
+    use crate::commands::processor::{CommandHandler, CommandResult};
+    use crate::commands::snapshot;
+
+    fn names(fs: &Filesystem, path: &[&str]) -> Vec<String> {
+        let path: Vec<String> = path.iter().map(|part| part.to_string()).collect();
+        match get_node(fs, &path) {
+            Some(Node::Directory { children, .. }) => children.keys().cloned().collect(),
+            _ => panic!("/{} is not a directory", path.join("/")),
+        }
+    }
+
+    #[test]
+    fn wiping_root_panics_and_recovers() {
+        let _lock = filesystem::exclusive_for_test();
+        let home = names(&FILESYSTEM.lock().unwrap(), &["home", "objz"]);
+        assert!(!home.is_empty());
+
+        let mut handler = CommandHandler::new();
+        let result =
+            users::run_as("root", || handler.handle("rm -rf --no-preserve-root /")).unwrap();
+        assert!(matches!(result.0, CommandResult::Panic));
+        // Only the writable mounts gave anything up.
+        assert!(names(&FILESYSTEM.lock().unwrap(), &["home", "objz"]).is_empty());
+        assert!(names(&FILESYSTEM.lock().unwrap(), &["etc"]).contains(&"passwd".to_string()));
+
+        // What the panic sequence does once it has played.
+        snapshot::revert().unwrap();
+        assert_eq!(names(&FILESYSTEM.lock().unwrap(), &["home", "objz"]), home);
+    }
+
+    #[test]
+    fn only_root_can_wipe_the_system() {
+        assert_eq!(
+            rm(&["-r", "/"]),
+            (
+                "rm: cannot remove '/': Permission denied".to_string(),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn readlink_names_itself_in_errors() {
+        assert_eq!(
+            readlink(&["-f", "/no/such/file"]),
+            "readlink: /no/such/file: No such file or directory"
+        );
+        assert_eq!(
+            realpath(&["/no/such/file"]),
+            "realpath: /no/such/file: No such file or directory"
+        );
+        assert_eq!(readlink(&["-f", "/etc/../etc/passwd"]), "/etc/passwd");
+    }
+}
@@ -0,0 +1,860 @@
+use crate::commands::filesystem::{
+    collate, is_binary, normalize_path, Filesystem, FsError, Node, CURRENT_PATH, FILESYSTEM,
+};
+use crate::commands::permissions::{self, Access};
+use crate::commands::text;
+use crate::commands::users::{self, Session};
+use crate::terminal::ansi;
+
+/// One step of an edit script: keep, remove or add a line. `a` and `b` are
+/// how many lines of each side come before it.
+#[derive(Clone, Copy)]
+struct Op {
+    kind: char,
+    a: usize,
+    b: usize,
+}
+
+/// The shortest edit script turning `a` into `b`, by longest common
+/// subsequence over whatever is left once the common prefix and suffix
+/// are set aside.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let middle_a = &a[prefix..a.len() - suffix];
+    let middle_b = &b[prefix..b.len() - suffix];
+
+    // lcs[i][j]: common subsequence length of middle_a[i..] and middle_b[j..].
+    let (n, m) = (middle_a.len(), middle_b.len());
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if middle_a[i] == middle_b[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    for k in 0..prefix {
+        ops.push(Op {
+            kind: ' ',
+            a: k,
+            b: k,
+        });
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let (a_at, b_at) = (prefix + i, prefix + j);
+        if i < n && j < m && middle_a[i] == middle_b[j] {
+            ops.push(Op {
+                kind: ' ',
+                a: a_at,
+                b: b_at,
+            });
+            i += 1;
+            j += 1;
+        } else if j >= m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+            ops.push(Op {
+                kind: '-',
+                a: a_at,
+                b: b_at,
+            });
+            i += 1;
+        } else {
+            ops.push(Op {
+                kind: '+',
+                a: a_at,
+                b: b_at,
+            });
+            j += 1;
+        }
+    }
+    for k in 0..suffix {
+        ops.push(Op {
+            kind: ' ',
+            a: a.len() - suffix + k,
+            b: b.len() - suffix + k,
+        });
+    }
+    ops
+}
+
+struct Style {
+    unified: Option<usize>,
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.color {
+            ansi::paint(text, code)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// `start,count` for a hunk header, dropping the count when it is one.
+fn unified_range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
+
+/// `start,end` for the normal format, or just `start` for one line.
+fn normal_range(start: usize, end: usize) -> String {
+    if start == end {
+        start.to_string()
+    } else {
+        format!("{},{}", start, end)
+    }
+}
+
+/// The changes between two texts, without file headers. Empty when they
+/// are the same. A missing final newline is not treated as a difference.
+fn diff_lines(old: &str, new: &str, style: &Style) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&a, &b);
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].kind != ' ').collect();
+    let mut output = Vec::new();
+
+    if let Some(context) = style.unified {
+        let mut k = 0;
+        while k < changes.len() {
+            // Merge changes whose context windows touch.
+            let mut last = k;
+            while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * context + 1 {
+                last += 1;
+            }
+            let start = changes[k].saturating_sub(context);
+            let end = (changes[last] + context + 1).min(ops.len());
+            let hunk = &ops[start..end];
+
+            let old_count = hunk.iter().filter(|op| op.kind != '+').count();
+            let new_count = hunk.iter().filter(|op| op.kind != '-').count();
+            let header = format!(
+                "@@ -{} +{} @@",
+                unified_range(hunk[0].a, old_count),
+                unified_range(hunk[0].b, new_count)
+            );
+            output.push(style.paint(&header, "36"));
+            for op in hunk {
+                output.push(match op.kind {
+                    '-' => style.paint(&format!("-{}", a[op.a]), "31"),
+                    '+' => style.paint(&format!("+{}", b[op.b]), "32"),
+                    _ => format!(" {}", a[op.a]),
+                });
+            }
+            k = last + 1;
+        }
+        return output;
+    }
+
+    let mut k = 0;
+    while k < changes.len() {
+        let mut last = k;
+        while last + 1 < changes.len() && changes[last + 1] == changes[last] + 1 {
+            last += 1;
+        }
+        let block = &ops[changes[k]..=changes[last]];
+        let removed: Vec<&Op> = block.iter().filter(|op| op.kind == '-').collect();
+        let added: Vec<&Op> = block.iter().filter(|op| op.kind == '+').collect();
+        let (a_before, b_before) = (block[0].a, block[0].b);
+
+        let header = match (removed.is_empty(), added.is_empty()) {
+            (false, true) => format!(
+                "{}d{}",
+                normal_range(a_before + 1, a_before + removed.len()),
+                b_before
+            ),
+            (true, false) => format!(
+                "{}a{}",
+                a_before,
+                normal_range(b_before + 1, b_before + added.len())
+            ),
+            _ => format!(
+                "{}c{}",
+                normal_range(a_before + 1, a_before + removed.len()),
+                normal_range(b_before + 1, b_before + added.len())
+            ),
+        };
+        output.push(style.paint(&header, "36"));
+        for op in &removed {
+            output.push(style.paint(&format!("< {}", a[op.a]), "31"));
+        }
+        if !removed.is_empty() && !added.is_empty() {
+            output.push("---".to_string());
+        }
+        for op in &added {
+            output.push(style.paint(&format!("> {}", b[op.b]), "32"));
+        }
+        k = last + 1;
+    }
+    output
+}
+
+/// What an operand turned out to be.
+enum Entry {
+    File(Vec<u8>),
+    Directory(Vec<String>),
+}
+
+fn load(fs: &Filesystem, path: &[String], session: &Session) -> Result<Entry, FsError> {
+    let (_, node) = permissions::resolve(fs, path, session)?;
+    match node {
+        Node::Directory { children, .. } => {
+            if !permissions::check(fs, node, session, Access::Read) {
+                return Err(FsError::PermissionDenied);
+            }
+            Ok(Entry::Directory(children.keys().cloned().collect()))
+        }
+        _ => text::read_bytes(fs, path, session).map(Entry::File),
+    }
+}
+
+struct Comparison<'a> {
+    fs: &'a Filesystem,
+    session: &'a Session,
+    style: Style,
+    recursive: bool,
+    brief: bool,
+    /// The options as typed, repeated in `diff -r` file headers.
+    flags: String,
+    stdin: Option<&'a [u8]>,
+    output: Vec<String>,
+}
+
+impl Comparison<'_> {
+    fn entry(&self, name: &str, path: &[String]) -> Result<Entry, String> {
+        if name == "-" {
+            return Ok(Entry::File(self.stdin.unwrap_or(b"").to_vec()));
+        }
+        load(self.fs, path, self.session).map_err(|err| format!("diff: {}: {}", name, err))
+    }
+
+    fn files(&mut self, names: (&str, &str), contents: (&[u8], &[u8]), nested: bool) {
+        // Binary files are only compared, as with GNU diff.
+        if is_binary(contents.0) || is_binary(contents.1) {
+            if contents.0 != contents.1 {
+                self.output
+                    .push(format!("Binary files {} and {} differ", names.0, names.1));
+            }
+            return;
+        }
+        let (old, new) = (
+            String::from_utf8_lossy(contents.0),
+            String::from_utf8_lossy(contents.1),
+        );
+        let lines = diff_lines(&old, &new, &self.style);
+        if lines.is_empty() {
+            return;
+        }
+        if self.brief {
+            self.output
+                .push(format!("Files {} and {} differ", names.0, names.1));
+            return;
+        }
+        if nested {
+            self.output.push(
+                self.style
+                    .paint(&format!("diff{} {} {}", self.flags, names.0, names.1), "1"),
+            );
+        }
+        if self.style.unified.is_some() {
+            self.output
+                .push(self.style.paint(&format!("--- {}", names.0), "1"));
+            self.output
+                .push(self.style.paint(&format!("+++ {}", names.1), "1"));
+        }
+        self.output.extend(lines);
+    }
+
+    fn compare(&mut self, names: (&str, &str), paths: (&[String], &[String]), nested: bool) {
+        let old = match self.entry(names.0, paths.0) {
+            Ok(entry) => entry,
+            Err(err) => return self.output.push(err),
+        };
+        let new = match self.entry(names.1, paths.1) {
+            Ok(entry) => entry,
+            Err(err) => return self.output.push(err),
+        };
+
+        match (old, new) {
+            (Entry::File(old), Entry::File(new)) => self.files(names, (&old, &new), nested),
+            (Entry::Directory(old), Entry::Directory(new)) => {
+                if nested && !self.recursive {
+                    self.output.push(format!(
+                        "Common subdirectories: {} and {}",
+                        names.0, names.1
+                    ));
+                    return;
+                }
+                let mut all: Vec<&String> = old.iter().chain(new.iter()).collect();
+                all.sort_by(|a, b| collate(a, b));
+                all.dedup();
+                for name in all {
+                    let child = |dir: &str| format!("{}/{}", dir.trim_end_matches('/'), name);
+                    match (old.contains(name), new.contains(name)) {
+                        (true, true) => {
+                            let child_paths = (
+                                [paths.0, std::slice::from_ref(name)].concat(),
+                                [paths.1, std::slice::from_ref(name)].concat(),
+                            );
+                            self.compare(
+                                (&child(names.0), &child(names.1)),
+                                (&child_paths.0, &child_paths.1),
+                                true,
+                            );
+                        }
+                        (true, false) => self.output.push(format!("Only in {}: {}", names.0, name)),
+                        _ => self.output.push(format!("Only in {}: {}", names.1, name)),
+                    }
+                }
+            }
+            // At the top level `diff file dir` means `diff file dir/file`.
+            (Entry::File(_), Entry::Directory(_)) if !nested && names.0 != "-" => {
+                let base = paths.0.last().cloned().unwrap_or_default();
+                let name = format!("{}/{}", names.1.trim_end_matches('/'), base);
+                let path = [paths.1, &[base]].concat();
+                self.compare((names.0, &name), (paths.0, &path), false);
+            }
+            (Entry::Directory(_), Entry::File(_)) if !nested && names.1 != "-" => {
+                let base = paths.1.last().cloned().unwrap_or_default();
+                let name = format!("{}/{}", names.0.trim_end_matches('/'), base);
+                let path = [paths.0, &[base]].concat();
+                self.compare((&name, names.1), (&path, paths.1), false);
+            }
+            (Entry::File(_), _) => self.output.push(format!(
+                "File {} is a regular file while file {} is a directory",
+                names.0, names.1
+            )),
+            _ => self.output.push(format!(
+                "File {} is a directory while file {} is a regular file",
+                names.0, names.1
+            )),
+        }
+    }
+}
+
+/// `diff [-u | -U N] [-r] [-q] [--color=never] old new`. Output is in the
+/// normal format unless `-u` asks for a unified diff, which `patch` reads.
+pub fn diff(args: &[&str], stdin: Option<&[u8]>) -> String {
+    let mut style = Style {
+        unified: None,
+        color: true,
+    };
+    let mut recursive = false;
+    let mut brief = false;
+    let mut flags = String::new();
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        if arg == "-" || !arg.starts_with('-') {
+            operands.push(arg);
+            continue;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            match long {
+                "unified" => style.unified = Some(3),
+                "recursive" => recursive = true,
+                "brief" => brief = true,
+                "color" | "colour" | "color=always" | "color=auto" => style.color = true,
+                "color=never" | "colour=never" => style.color = false,
+                _ => return format!("diff: unrecognized option '{}'", arg),
+            }
+            continue;
+        }
+
+        let chars: Vec<char> = arg[1..].chars().collect();
+        for (index, &flag) in chars.iter().enumerate() {
+            match flag {
+                'u' => style.unified = Some(style.unified.unwrap_or(3)),
+                'r' => recursive = true,
+                'q' => brief = true,
+                'U' => {
+                    let attached: String = chars[index + 1..].iter().collect();
+                    let value = if !attached.is_empty() {
+                        attached
+                    } else if let Some((&next, tail)) = rest.split_first() {
+                        rest = tail;
+                        next.to_string()
+                    } else {
+                        return "diff: option requires an argument -- 'U'".to_string();
+                    };
+                    match value.parse() {
+                        Ok(lines) => style.unified = Some(lines),
+                        Err(_) => return format!("diff: invalid context length '{}'", value),
+                    }
+                    break;
+                }
+                _ => return format!("diff: invalid option -- '{}'", flag),
+            }
+        }
+        flags.push(' ');
+        flags.push_str(arg);
+    }
+
+    match operands.len() {
+        0 => return "diff: missing operand after 'diff'".to_string(),
+        1 => return format!("diff: missing operand after '{}'", operands[0]),
+        2 => {}
+        _ => return format!("diff: extra operand '{}'", operands[2]),
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let old_path = normalize_path(operands[0], &current_path);
+    let new_path = normalize_path(operands[1], &current_path);
+
+    let mut comparison = Comparison {
+        fs: &filesystem,
+        session: &session,
+        style,
+        recursive,
+        brief,
+        flags,
+        stdin,
+        output: Vec::new(),
+    };
+    comparison.compare((operands[0], operands[1]), (&old_path, &new_path), false);
+    comparison.output.join("\n")
+}
+
+/// One `@@` hunk of a unified diff.
+struct Hunk {
+    old_start: usize,
+    lines: Vec<(char, String)>,
+}
+
+/// A file's worth of hunks and the names from its `---`/`+++` lines.
+struct FilePatch {
+    old_name: String,
+    new_name: String,
+    hunks: Vec<Hunk>,
+}
+
+/// The name in a `---`/`+++` line, without any trailing timestamp.
+fn header_name(line: &str) -> String {
+    let name = &line[4..];
+    name.split('\t').next().unwrap_or("").trim().to_string()
+}
+
+/// `start[,count]` from a hunk header.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_patch(input: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("--- ")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| next.starts_with("+++ "))
+        {
+            patches.push(FilePatch {
+                old_name: header_name(line),
+                new_name: header_name(lines[i + 1]),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("@@ -") {
+            let patch = patches
+                .last_mut()
+                .ok_or("patch: **** hunk without a file header")?;
+            let line_number = i + 1;
+            let malformed = || {
+                format!(
+                    "patch: **** malformed patch at line {}: {}",
+                    line_number, line
+                )
+            };
+            let (old_range, rest) = header.split_once(" +").ok_or_else(malformed)?;
+            let new_range = rest.split(" @@").next().unwrap_or("");
+            let (old_start, mut old_count) = parse_range(old_range).ok_or_else(malformed)?;
+            let (_, mut new_count) = parse_range(new_range).ok_or_else(malformed)?;
+
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+            while old_count > 0 || new_count > 0 {
+                let body = match lines.get(i) {
+                    Some(body) => *body,
+                    None => return Err("patch: **** unexpected end of file in patch".to_string()),
+                };
+                i += 1;
+                if body.starts_with('\\') {
+                    continue;
+                }
+                // Editors sometimes strip the space from empty context lines.
+                let (kind, text) = match body.chars().next() {
+                    Some(kind @ (' ' | '-' | '+')) => (kind, &body[1..]),
+                    None => (' ', ""),
+                    Some(_) => return Err(malformed()),
+                };
+                if kind != '+' {
+                    old_count = old_count.checked_sub(1).ok_or_else(malformed)?;
+                }
+                if kind != '-' {
+                    new_count = new_count.checked_sub(1).ok_or_else(malformed)?;
+                }
+                hunk.lines.push((kind, text.to_string()));
+            }
+            patch.hunks.push(hunk);
+            continue;
+        }
+        i += 1;
+    }
+
+    patches.retain(|patch| !patch.hunks.is_empty());
+    if patches.is_empty() {
+        return Err("patch: **** Only garbage was found in the patch input.".to_string());
+    }
+    Ok(patches)
+}
+
+/// Strips `count` leading components from a patch file name, as `-p` does.
+fn strip_components(name: &str, count: usize) -> Option<String> {
+    let parts: Vec<&str> = name.split('/').filter(|part| !part.is_empty()).collect();
+    if count >= parts.len() && count > 0 {
+        return None;
+    }
+    if count == 0 {
+        return Some(name.to_string());
+    }
+    Some(parts[count..].join("/"))
+}
+
+/// Applies hunks to `content`, looking for each one at its stated line
+/// first and then further and further away. Returns the new text and a
+/// report line per hunk that did not apply cleanly where stated.
+fn apply(content: &str, hunks: &[Hunk], reverse: bool) -> (String, Vec<String>, usize) {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut notes = Vec::new();
+    let mut failed = 0;
+    // How far earlier hunks have moved everything below them.
+    let mut shift: isize = 0;
+
+    for (number, hunk) in hunks.iter().enumerate() {
+        let (remove, add) = if reverse { ('+', '-') } else { ('-', '+') };
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|(kind, _)| *kind != add)
+            .map(|(_, text)| text.as_str())
+            .collect();
+        let new: Vec<String> = hunk
+            .lines
+            .iter()
+            .filter(|(kind, _)| *kind != remove)
+            .map(|(_, text)| text.clone())
+            .collect();
+
+        let stated = if old.is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = (stated as isize + shift).max(0) as usize;
+        let fits = |at: usize| {
+            at + old.len() <= lines.len()
+                && lines[at..at + old.len()]
+                    .iter()
+                    .zip(&old)
+                    .all(|(x, y)| x == y)
+        };
+
+        let found = (0..=lines.len()).find_map(|distance| {
+            let candidates = [expected.checked_sub(distance), Some(expected + distance)];
+            candidates
+                .iter()
+                .flatten()
+                .copied()
+                .find(|&at| at <= lines.len() && fits(at))
+        });
+
+        match found {
+            Some(at) => {
+                let offset = at as isize - stated as isize - shift;
+                if offset != 0 {
+                    let plural = if offset.abs() == 1 { "" } else { "s" };
+                    notes.push(format!(
+                        "Hunk #{} succeeded at {} (offset {} line{}).",
+                        number + 1,
+                        at + 1,
+                        offset,
+                        plural
+                    ));
+                }
+                lines.splice(at..at + old.len(), new.iter().cloned());
+                shift += offset + new.len() as isize - old.len() as isize;
+            }
+            None => {
+                notes.push(format!(
+                    "Hunk #{} FAILED at {}.",
+                    number + 1,
+                    hunk.old_start
+                ));
+                failed += 1;
+            }
+        }
+    }
+
+    let mut result = lines.join("\n");
+    if content.ends_with('\n') && !result.is_empty() {
+        result.push('\n');
+    }
+    (result, notes, failed)
+}
+
+/// `patch [-p N] [-R] [--dry-run] [-i patchfile] [file]`: applies a
+/// unified diff read from stdin (as in `diff -u a b | patch a`) or from
+/// `-i`. Without a file operand the target comes from the diff's headers.
+pub fn patch(args: &[&str], stdin: Option<&str>) -> String {
+    let mut strip: Option<usize> = None;
+    let mut reverse = false;
+    let mut dry_run = false;
+    let mut patch_file: Option<&str> = None;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        match arg {
+            "-R" | "--reverse" => reverse = true,
+            "--dry-run" => dry_run = true,
+            _ if arg.starts_with("-p") || arg.starts_with("-i") => {
+                let value = if arg.len() > 2 {
+                    &arg[2..]
+                } else if let Some((&next, tail)) = rest.split_first() {
+                    rest = tail;
+                    next
+                } else {
+                    return format!("patch: option requires an argument -- '{}'", &arg[1..2]);
+                };
+                if arg.starts_with("-i") {
+                    patch_file = Some(value);
+                } else {
+                    match value.parse() {
+                        Ok(count) => strip = Some(count),
+                        Err(_) => return format!("patch: strip count {} is not a number", value),
+                    }
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return format!("patch: unrecognized option '{}'", arg)
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if operands.len() > 1 {
+        return format!("patch: extra operand '{}'", operands[1]);
+    }
+
+    let input = match patch_file {
+        Some(file) => match text::inputs("patch", &[file], stdin).pop() {
+            Some(Ok((_, content))) => content,
+            Some(Err(err)) => return err,
+            None => String::new(),
+        },
+        None => stdin.unwrap_or("").to_string(),
+    };
+    let patches = match parse_patch(&input) {
+        Ok(patches) => patches,
+        Err(err) => return err,
+    };
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let mut output = Vec::new();
+
+    for file_patch in &patches {
+        let target = match operands.first() {
+            Some(target) => target.to_string(),
+            None => {
+                // Whichever of the new and old names exists, or else the
+                // new one, which is then created. /dev/null never counts.
+                let candidates: Vec<String> = [&file_patch.new_name, &file_patch.old_name]
+                    .iter()
+                    .filter(|name| name.as_str() != "/dev/null")
+                    .filter_map(|name| match strip {
+                        Some(count) => strip_components(name, count),
+                        None => name.rsplit('/').next().map(str::to_string),
+                    })
+                    .collect();
+                let existing = candidates.iter().find(|name| {
+                    let path = normalize_path(name, &current_path);
+                    permissions::resolve(&filesystem, &path, &session).is_ok()
+                });
+                match existing.or(candidates.first()) {
+                    Some(name) => name.clone(),
+                    None => {
+                        output.push("patch: **** can't find file to patch".to_string());
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let path = normalize_path(&target, &current_path);
+        let creating = file_patch.old_name == "/dev/null" && !reverse;
+        let content = match text::read_file(&filesystem, &path, &session) {
+            Ok(content) => content,
+            Err(FsError::NotFound) if creating => String::new(),
+            Err(err) => {
+                output.push(format!("patch: **** can't open {}: {}", target, err));
+                continue;
+            }
+        };
+
+        output.push(format!(
+            "{} file {}",
+            if dry_run { "checking" } else { "patching" },
+            target
+        ));
+        let (patched, notes, failed) = apply(&content, &file_patch.hunks, reverse);
+        output.extend(notes);
+        if failed > 0 {
+            let total = file_patch.hunks.len();
+            output.push(format!(
+                "{} out of {} hunk{} FAILED",
+                failed,
+                total,
+                if total == 1 { "" } else { "s" }
+            ));
+        }
+        if dry_run || failed == file_patch.hunks.len() {
+            continue;
+        }
+        if let Err(err) = text::write_file(&mut filesystem, &path, patched.into_bytes(), &session) {
+            output.push(format!("patch: **** can't write {}: {}", target, err));
+        }
+    }
+
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::filesystem;
+
+    const CLICK: &str = "/usr/share/sounds/click.wav";
+    const OLD: &str = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten";
+    const NEW: &str = "zero\none\ntwo\nTHREE\nfour\nfive\nsix\nseven\nnine\nten\neleven";
+
+    /// Writes a scratch file under /tmp, named for the test using it.
+    fn put(path: &str, content: &str) {
+        let mut filesystem = FILESYSTEM.lock().unwrap();
+        let session = users::current_session();
+        let path = normalize_path(path, &[]);
+        text::write_file(&mut filesystem, &path, content.into(), &session).unwrap();
+    }
+
+    fn read(path: &str) -> String {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        let session = users::current_session();
+        text::read_file(&filesystem, &normalize_path(path, &[]), &session).unwrap()
+    }
+
+    /// What GNU diff prints for `OLD` and `NEW`, timestamps aside.
+    #[test]
+    fn matches_gnu_diff() {
+        let _lock = filesystem::shared_for_test();
+        put("/tmp/golden-old", OLD);
+        put("/tmp/golden-new", NEW);
+        let run = |flags: &[&str]| {
+            let args = [
+                flags,
+                &["--color=never", "/tmp/golden-old", "/tmp/golden-new"],
+            ]
+            .concat();
+            diff(&args, None)
+        };
+
+        assert_eq!(
+            run(&[]),
+            "0a1\n> zero\n3c4\n< three\n---\n> THREE\n8d8\n< eight\n10a11\n> eleven"
+        );
+        assert_eq!(
+            run(&["-u"]),
+            "--- /tmp/golden-old\n+++ /tmp/golden-new\n@@ -1,10 +1,11 @@\n+zero\n one\n two\n\
+             -three\n+THREE\n four\n five\n six\n seven\n-eight\n nine\n ten\n+eleven"
+        );
+        assert_eq!(
+            run(&["-U1"]),
+            "--- /tmp/golden-old\n+++ /tmp/golden-new\n@@ -1,4 +1,5 @@\n+zero\n one\n two\n\
+             -three\n+THREE\n four\n@@ -7,4 +8,4 @@\n seven\n-eight\n nine\n ten\n+eleven"
+        );
+        assert_eq!(
+            run(&["-q"]),
+            "Files /tmp/golden-old and /tmp/golden-new differ"
+        );
+        assert_eq!(diff(&["/tmp/golden-old", "-"], Some(OLD.as_bytes())), "");
+    }
+
+    #[test]
+    fn patch_applies_and_reverts_diffs() {
+        let _lock = filesystem::shared_for_test();
+        put("/tmp/patch-old", OLD);
+        put("/tmp/patch-new", NEW);
+        let unified = diff(
+            &["-u", "--color=never", "/tmp/patch-old", "/tmp/patch-new"],
+            None,
+        );
+
+        assert_eq!(
+            patch(&["/tmp/patch-old"], Some(&unified)),
+            "patching file /tmp/patch-old"
+        );
+        assert_eq!(read("/tmp/patch-old"), NEW);
+        assert_eq!(
+            patch(&["-R", "/tmp/patch-old"], Some(&unified)),
+            "patching file /tmp/patch-old"
+        );
+        assert_eq!(read("/tmp/patch-old"), OLD);
+        assert!(patch(&[], Some("not a diff")).starts_with("patch: "));
+    }
+
+    #[test]
+    fn binary_files_are_only_compared() {
+        assert_eq!(
+            diff(&[CLICK, "/etc/hostname"], None),
+            format!("Binary files {} and /etc/hostname differ", CLICK)
+        );
+        assert_eq!(diff(&[CLICK, CLICK], None), "");
+        let wav = include_bytes!("../../static/audio/click.wav");
+        assert_eq!(diff(&["-", CLICK], Some(wav)), "");
+        assert_eq!(
+            diff(&["-", CLICK], Some(b"RIFF\n")),
+            format!("Binary files - and {} differ", CLICK)
+        );
+    }
+}
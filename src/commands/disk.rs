@@ -0,0 +1,781 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use crate::commands::archive;
+use crate::commands::filesystem::{
+    get_node, is_binary, normalize_path, Filesystem, FsError, InodeId, Node, CURRENT_PATH,
+    FILESYSTEM,
+};
+use crate::commands::mounts::Mount;
+use crate::commands::permissions::{self, Access};
+use crate::commands::users::{self, Session};
+
+/// Allocation unit: files take whole blocks, directories exactly one.
+const BLOCK_SIZE: u64 = 4096;
+
+/// The simulated size of the root filesystem.
+pub const CAPACITY: u64 = 64 * 1024 * 1024;
+
+/// Bytes a node occupies on disk (not counting children).
+fn allocated(fs: &Filesystem, node: &Node) -> u64 {
+    match node {
+        Node::Directory { .. } => BLOCK_SIZE,
+        Node::File { inode } => {
+            let len = fs.inodes.get(*inode).content.len() as u64;
+            len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+        }
+        // Short targets are stored inside the link itself.
+        Node::Symlink { .. } => 0,
+    }
+}
+
+/// Bytes a node's content actually holds, as `ls -l` shows it.
+pub fn apparent(fs: &Filesystem, node: &Node) -> u64 {
+    match node {
+        Node::Directory { .. } => BLOCK_SIZE,
+        Node::File { inode } => fs.inodes.get(*inode).content.len() as u64,
+        Node::Symlink { target, .. } => target.len() as u64,
+    }
+}
+
+/// Everything allocated on the filesystem, each inode counted once however
+/// many names it has.
+pub fn used(fs: &Filesystem) -> u64 {
+    fn walk(fs: &Filesystem, node: &Node, seen: &mut HashSet<InodeId>) -> u64 {
+        match node {
+            Node::Directory { children, .. } => {
+                allocated(fs, node)
+                    + children
+                        .values()
+                        .map(|child| walk(fs, child, seen))
+                        .sum::<u64>()
+            }
+            Node::File { inode } if !seen.insert(*inode) => 0,
+            _ => allocated(fs, node),
+        }
+    }
+    walk(fs, &fs.root, &mut HashSet::new())
+}
+
+/// `-h` sizes the way coreutils prints them: powers of 1024, rounded up,
+/// with one decimal below 10.
+pub fn human(bytes: u64) -> String {
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < 4 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let suffix = ["", "K", "M", "G", "T"][unit];
+    if value < 10.0 {
+        let tenths = (value * 10.0).ceil() / 10.0;
+        if tenths < 10.0 {
+            return format!("{:.1}{}", tenths, suffix);
+        }
+    }
+    format!("{}{}", value.ceil() as u64, suffix)
+}
+
+/// "used / capacity" of the root filesystem for `neofetch`.
+pub fn memory_summary() -> String {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let used = used(&filesystem);
+    format!(
+        "{}KiB / {}MiB",
+        used.div_ceil(1024),
+        CAPACITY / (1024 * 1024)
+    )
+}
+
+struct DuOptions {
+    all: bool,
+    summarize: bool,
+    human: bool,
+    bytes: bool,
+    max_depth: Option<usize>,
+}
+
+impl DuOptions {
+    fn format(&self, size: u64) -> String {
+        if self.human {
+            human(size)
+        } else if self.bytes {
+            size.to_string()
+        } else {
+            size.div_ceil(1024).to_string()
+        }
+    }
+}
+
+struct DiskUsage<'a> {
+    fs: &'a Filesystem,
+    session: &'a Session,
+    options: &'a DuOptions,
+    seen: HashSet<InodeId>,
+    output: Vec<String>,
+}
+
+impl DiskUsage<'_> {
+    /// Totals `node` (shown as `name`), printing lines for directories and,
+    /// with `-a`, files, down to the requested depth.
+    fn walk(&mut self, name: &str, node: &Node, depth: usize) -> u64 {
+        let size = |this: &Self, node: &Node| {
+            if this.options.bytes {
+                apparent(this.fs, node)
+            } else {
+                allocated(this.fs, node)
+            }
+        };
+        let shown =
+            !self.options.summarize && self.options.max_depth.is_none_or(|max| depth <= max);
+
+        let children = match node {
+            Node::Directory { children, .. } => children,
+            Node::File { inode } => {
+                if !self.seen.insert(*inode) {
+                    return 0;
+                }
+                let total = size(self, node);
+                if shown && self.options.all && depth > 0 {
+                    self.output
+                        .push(format!("{}\t{}", self.options.format(total), name));
+                }
+                return total;
+            }
+            Node::Symlink { .. } => {
+                let total = size(self, node);
+                if shown && self.options.all && depth > 0 {
+                    self.output
+                        .push(format!("{}\t{}", self.options.format(total), name));
+                }
+                return total;
+            }
+        };
+
+        let mut total = size(self, node);
+        if permissions::check(self.fs, node, self.session, Access::Read)
+            && permissions::check(self.fs, node, self.session, Access::Execute)
+        {
+            for (child, child_node) in children {
+                let child_name = format!("{}/{}", name.trim_end_matches('/'), child);
+                total += self.walk(&child_name, child_node, depth + 1);
+            }
+        } else {
+            self.output.push(format!(
+                "du: cannot read directory '{}': {}",
+                name,
+                FsError::PermissionDenied
+            ));
+        }
+
+        if shown {
+            self.output
+                .push(format!("{}\t{}", self.options.format(total), name));
+        }
+        total
+    }
+}
+
+/// `du [-a] [-s] [-h] [-b] [-c] [-d N] [path...]`: space used under each
+/// directory, in 1K blocks unless `-h` or `-b` say otherwise.
+pub fn du(args: &[&str]) -> String {
+    let mut options = DuOptions {
+        all: false,
+        summarize: false,
+        human: false,
+        bytes: false,
+        max_depth: None,
+    };
+    let mut grand_total = false;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        if let Some(depth) = arg.strip_prefix("--max-depth=") {
+            match depth.parse() {
+                Ok(depth) => options.max_depth = Some(depth),
+                Err(_) => return format!("du: invalid maximum depth '{}'", depth),
+            }
+            continue;
+        }
+        match arg {
+            "--summarize" => options.summarize = true,
+            "--human-readable" => options.human = true,
+            "--all" => options.all = true,
+            "--total" => grand_total = true,
+            "--bytes" => options.bytes = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for (index, flag) in arg.char_indices().skip(1) {
+                    match flag {
+                        'a' => options.all = true,
+                        's' => options.summarize = true,
+                        'h' => options.human = true,
+                        'b' => options.bytes = true,
+                        'c' => grand_total = true,
+                        'd' => {
+                            let attached = &arg[index + 1..];
+                            let value = if !attached.is_empty() {
+                                attached
+                            } else if let Some((&next, tail)) = rest.split_first() {
+                                rest = tail;
+                                next
+                            } else {
+                                return "du: option requires an argument -- 'd'".to_string();
+                            };
+                            match value.parse() {
+                                Ok(depth) => options.max_depth = Some(depth),
+                                Err(_) => return format!("du: invalid maximum depth '{}'", value),
+                            }
+                            break;
+                        }
+                        _ => return format!("du: invalid option -- '{}'", flag),
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if options.summarize && options.all {
+        return "du: cannot both summarize and show all entries".to_string();
+    }
+    if operands.is_empty() {
+        operands.push(".");
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let mut usage = DiskUsage {
+        fs: &filesystem,
+        session: &session,
+        options: &options,
+        seen: HashSet::new(),
+        output: Vec::new(),
+    };
+
+    let mut total = 0;
+    for operand in operands {
+        let path = normalize_path(operand, &current_path);
+        match permissions::traverse(&filesystem, &path, &session) {
+            Ok(node) => {
+                let size = usage.walk(operand, node, 0);
+                let listed = matches!(node, Node::Directory { .. }) && !options.summarize;
+                if !listed {
+                    usage
+                        .output
+                        .push(format!("{}\t{}", options.format(size), operand));
+                }
+                total += size;
+            }
+            Err(err) => usage
+                .output
+                .push(format!("du: cannot access '{}': {}", operand, err)),
+        }
+    }
+    if grand_total {
+        usage
+            .output
+            .push(format!("{}\ttotal", options.format(total)));
+    }
+    usage.output.join("\n")
+}
+
+/// Space used on `mount` alone, leaving out whatever is mounted below it.
+fn mount_used(fs: &Filesystem, mount: &Mount) -> u64 {
+    fn walk(
+        fs: &Filesystem,
+        node: &Node,
+        path: &mut Vec<String>,
+        seen: &mut HashSet<InodeId>,
+    ) -> u64 {
+        match node {
+            Node::Directory { children, .. } => {
+                let mut total = allocated(fs, node);
+                for (name, child) in children {
+                    path.push(name.clone());
+                    if !fs.mounts.iter().any(|mount| mount.target == *path) {
+                        total += walk(fs, child, path, seen);
+                    }
+                    path.pop();
+                }
+                total
+            }
+            Node::File { inode } if !seen.insert(*inode) => 0,
+            _ => allocated(fs, node),
+        }
+    }
+
+    match get_node(fs, &mount.target) {
+        Some(node) => walk(fs, node, &mut mount.target.clone(), &mut HashSet::new()),
+        None => 0,
+    }
+}
+
+/// `df [-h] [-a] [path...]`: capacity and usage of each mount, or of the
+/// mounts holding the given paths. Like coreutils, pseudo filesystems
+/// without a size only show up with `-a` or when asked for.
+pub fn df(args: &[&str]) -> String {
+    let mut human_sizes = false;
+    let mut all = false;
+    let mut operands = Vec::new();
+    for &arg in args {
+        match arg {
+            "-h" | "--human-readable" => human_sizes = true,
+            "-a" | "--all" => all = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return format!("df: invalid option -- '{}'", &arg[1..])
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let mut errors = Vec::new();
+    let mut mounts: Vec<&Mount> = Vec::new();
+    if operands.is_empty() {
+        mounts.extend(
+            filesystem
+                .mounts
+                .iter()
+                .filter(|mount| all || mount.size > 0),
+        );
+    }
+    for operand in &operands {
+        let path = normalize_path(operand, &current_path);
+        match permissions::resolve(&filesystem, &path, &session) {
+            Ok((canonical, _)) => {
+                let mount = filesystem.mount_of(&canonical);
+                if !mounts.iter().any(|seen| std::ptr::eq(*seen, mount)) {
+                    mounts.push(mount);
+                }
+            }
+            Err(err) => errors.push(format!("df: {}: {}", operand, err)),
+        }
+    }
+
+    let size = |bytes: u64| {
+        if human_sizes {
+            human(bytes)
+        } else {
+            bytes.div_ceil(1024).to_string()
+        }
+    };
+    let header = if human_sizes {
+        ["Filesystem", "Size", "Used", "Avail", "Use%", "Mounted on"]
+    } else {
+        [
+            "Filesystem",
+            "1K-blocks",
+            "Used",
+            "Available",
+            "Use%",
+            "Mounted on",
+        ]
+    };
+    let mut rows: Vec<[String; 6]> = vec![header.map(str::to_string)];
+    for mount in mounts {
+        let (used, percent) = if mount.size == 0 {
+            (0, "-".to_string())
+        } else {
+            let used = mount_used(&filesystem, mount);
+            (used, format!("{}%", (used * 100).div_ceil(mount.size)))
+        };
+        rows.push([
+            mount.source.clone(),
+            size(mount.size),
+            size(used),
+            size(mount.size.saturating_sub(used)),
+            percent,
+            mount.mount_point(),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..5)
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+    let mut output = errors;
+    if rows.len() > 1 {
+        output.extend(rows.iter().map(|row| {
+            format!(
+                "{:<w0$} {:>w1$} {:>w2$} {:>w3$} {:>w4$} {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                row[5],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3],
+                w4 = widths[4]
+            )
+        }));
+    }
+    output.join("\n")
+}
+
+/// Recognises the binary formats the system knows by their magic bytes.
+fn describe_binary(content: &[u8]) -> Option<String> {
+    let u16_at = |at: usize| {
+        Some(u16::from_le_bytes(
+            content.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let u32_at = |at: usize| {
+        Some(u32::from_le_bytes(
+            content.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+
+    if archive::is_gzip(content) {
+        let flags = *content.get(3)?;
+        let name = (flags & 0x08 != 0 && flags & 0x04 == 0)
+            .then(|| content.get(10..)?.split(|&b| b == 0).next())
+            .flatten()
+            .map(|name| format!(", was \"{}\"", String::from_utf8_lossy(name)))
+            .unwrap_or_default();
+        return Some(format!("gzip compressed data{}, from Unix", name));
+    }
+    if content.get(257..262) == Some(b"ustar") {
+        return Some("POSIX tar archive".to_string());
+    }
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let be = |at: usize| {
+            Some(u32::from_be_bytes(
+                content.get(at..at + 4)?.try_into().ok()?,
+            ))
+        };
+        return Some(format!("PNG image data, {} x {}", be(16)?, be(20)?));
+    }
+    if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WAVE") {
+        let mut description = "RIFF (little-endian) data, WAVE audio".to_string();
+        if content.get(12..16) == Some(b"fmt ") {
+            if u16_at(20)? == 1 {
+                description.push_str(", Microsoft PCM");
+            }
+            let channels = match u16_at(22)? {
+                1 => "mono".to_string(),
+                2 => "stereo".to_string(),
+                n => format!("{} channels", n),
+            };
+            description.push_str(&format!(
+                ", {} bit, {} {} Hz",
+                u16_at(34)?,
+                channels,
+                u32_at(24)?
+            ));
+        }
+        return Some(description);
+    }
+    None
+}
+
+/// What `file` reports for a regular file's content.
+fn describe_content(name: &str, content: &[u8]) -> String {
+    if content.is_empty() {
+        return "empty".to_string();
+    }
+    if let Some(description) = describe_binary(content) {
+        return description;
+    }
+    if is_binary(content) {
+        return "data".to_string();
+    }
+    let content = String::from_utf8_lossy(content);
+    let encoding = if content.is_ascii() {
+        "ASCII text"
+    } else {
+        "Unicode text, UTF-8 text"
+    };
+
+    if let Some(shebang) = content
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"))
+    {
+        let interpreter = shebang
+            .split_whitespace()
+            .flat_map(|word| word.rsplit('/').next())
+            .find(|word| *word != "env")
+            .unwrap_or("");
+        let kind = match interpreter {
+            "bash" => "Bourne-Again shell script".to_string(),
+            "sh" => "POSIX shell script".to_string(),
+            "zsh" => "Paul Falstad's zsh script".to_string(),
+            "python" | "python3" => "Python script".to_string(),
+            "node" => "Node.js script".to_string(),
+            other => format!("{} script", other),
+        };
+        return format!("{}, {} executable", kind, encoding);
+    }
+
+    let extension = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    let kind = match extension.to_lowercase().as_str() {
+        "md" | "markdown" => "Markdown document",
+        "rs" => "Rust source",
+        "sh" => "Bourne-Again shell script",
+        "py" => "Python script",
+        "js" => "JavaScript source",
+        "html" | "htm" => "HTML document",
+        "json" => "JSON data",
+        "toml" => "TOML document",
+        "css" => "CSS stylesheet",
+        _ if content.trim_start().starts_with("<!DOCTYPE html") => "HTML document",
+        _ => return encoding.to_string(),
+    };
+    format!("{}, {}", kind, encoding)
+}
+
+/// `file [-b] [-L] path...`: guesses a file's type from its extension and
+/// content.
+pub fn file(args: &[&str]) -> String {
+    let mut brief = false;
+    let mut follow = false;
+    let mut operands = Vec::new();
+    for &arg in args {
+        match arg {
+            "-b" | "--brief" => brief = true,
+            "-L" | "--dereference" => follow = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return format!("file: invalid option -- '{}'", &arg[1..])
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if operands.is_empty() {
+        return "Usage: file [-bL] file...".to_string();
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let width = operands.iter().map(|name| name.len()).max().unwrap_or(0) + 1;
+
+    operands
+        .iter()
+        .map(|&operand| {
+            let path = normalize_path(operand, &current_path);
+            let node = if follow {
+                permissions::resolve(&filesystem, &path, &session).map(|(_, node)| node)
+            } else {
+                permissions::traverse(&filesystem, &path, &session)
+            };
+            let description = match node {
+                Ok(Node::Directory { .. }) => "directory".to_string(),
+                Ok(Node::Symlink { target, .. }) => {
+                    match permissions::resolve(&filesystem, &path, &session) {
+                        Ok(_) => format!("symbolic link to {}", target),
+                        Err(_) => format!("broken symbolic link to {}", target),
+                    }
+                }
+                Ok(Node::File { inode })
+                    if filesystem
+                        .inodes
+                        .generator(*inode)
+                        .is_some_and(|g| g.device) =>
+                {
+                    "character special".to_string()
+                }
+                Ok(node) => match filesystem.file(node) {
+                    Some(inode)
+                        if permissions::check(&filesystem, node, &session, Access::Read) =>
+                    {
+                        describe_content(operand, &inode.content)
+                    }
+                    _ => format!("cannot open `{}' ({})", operand, FsError::PermissionDenied),
+                },
+                Err(err) => format!("cannot open `{}' ({})", operand, err),
+            };
+            if brief {
+                description
+            } else {
+                format!(
+                    "{:<width$}{}",
+                    format!("{}:", operand),
+                    description,
+                    width = width + 1
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Directories and symlinks live in the tree rather than the inode table,
+/// so they get a stable number derived from their path instead.
+fn inode_number(node: &Node, path: &[String]) -> u64 {
+    match node {
+        Node::File { inode } => *inode,
+        _ => {
+            let hash = path
+                .join("/")
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |hash, byte| {
+                    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+                });
+            100_000 + hash % 900_000
+        }
+    }
+}
+
+/// Modification time as a timestamp. Files record a logical clock tick
+/// rather than wall time, so ticks count seconds from the boot image date.
+fn timestamp(fs: &Filesystem, node: &Node) -> String {
+    let tick = match node {
+        Node::File { inode } => fs.inodes.modified(*inode),
+        _ => 0,
+    };
+    format!(
+        "2025-01-01 12:{:02}:{:02}.000000000 +0000",
+        (tick / 60) % 60,
+        tick % 60
+    )
+}
+
+/// `stat [-L] [-c format] path...`. Formats understand `%n %s %b %F %a %A
+/// %U %u %G %g %h %i %y %%`.
+pub fn stat(args: &[&str]) -> String {
+    let mut follow = false;
+    let mut format: Option<&str> = None;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        match arg {
+            "-L" | "--dereference" => follow = true,
+            "-c" => match rest.split_first() {
+                Some((&value, tail)) => {
+                    rest = tail;
+                    format = Some(value);
+                }
+                None => return "stat: option requires an argument -- 'c'".to_string(),
+            },
+            _ if arg.starts_with("--format=") => format = Some(&arg["--format=".len()..]),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return format!("stat: invalid option -- '{}'", &arg[1..])
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if operands.is_empty() {
+        return "stat: missing operand".to_string();
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let mut output = Vec::new();
+
+    for operand in operands {
+        let path = normalize_path(operand, &current_path);
+        let found = if follow {
+            permissions::resolve(&filesystem, &path, &session)
+        } else {
+            permissions::traverse(&filesystem, &path, &session).map(|node| (path.clone(), node))
+        };
+        let (path, node) = match found {
+            Ok(found) => found,
+            Err(err) => {
+                output.push(format!("stat: cannot statx '{}': {}", operand, err));
+                continue;
+            }
+        };
+
+        let inodes = &filesystem.inodes;
+        let owner = node.get_owner(inodes);
+        let group = node.get_group(inodes);
+        let uid = users::find_user(&filesystem, owner).map_or(0, |user| user.uid);
+        let gid = users::find_group(&filesystem, group).map_or(0, |group| group.gid);
+        let mode = node.get_permissions(inodes);
+        let size = apparent(&filesystem, node);
+        let blocks = allocated(&filesystem, node) / 512;
+        let kind = match node {
+            Node::Directory { .. } => "directory",
+            Node::Symlink { .. } => "symbolic link",
+            Node::File { inode }
+                if filesystem
+                    .inodes
+                    .generator(*inode)
+                    .is_some_and(|g| g.device) =>
+            {
+                "character special file"
+            }
+            Node::File { .. } if size == 0 => "regular empty file",
+            Node::File { .. } => "regular file",
+        };
+        let name = match node {
+            Node::Symlink { target, .. } => format!("{} -> {}", operand, target),
+            _ => operand.to_string(),
+        };
+        let time = timestamp(&filesystem, node);
+
+        if let Some(format) = format {
+            let mut line = String::new();
+            let mut chars = format.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    line.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('n') => line.push_str(operand),
+                    Some('N') => line.push_str(&name),
+                    Some('s') => line.push_str(&size.to_string()),
+                    Some('b') => line.push_str(&blocks.to_string()),
+                    Some('F') => line.push_str(kind),
+                    Some('a') => line.push_str(&format!("{:o}", mode)),
+                    Some('A') => line.push_str(&node.mode_string(inodes)),
+                    Some('U') => line.push_str(owner),
+                    Some('u') => line.push_str(&uid.to_string()),
+                    Some('G') => line.push_str(group),
+                    Some('g') => line.push_str(&gid.to_string()),
+                    Some('h') => line.push_str(&node.link_count(inodes).to_string()),
+                    Some('i') => line.push_str(&inode_number(node, &path).to_string()),
+                    Some('y') => line.push_str(&time),
+                    Some('%') => line.push('%'),
+                    Some(other) => {
+                        line.push('?');
+                        line.push(other);
+                    }
+                    None => line.push('%'),
+                }
+            }
+            output.push(line);
+            continue;
+        }
+
+        output.push(format!("  File: {}", name));
+        output.push(format!(
+            "  Size: {:<10}\tBlocks: {:<10} IO Block: {}   {}",
+            size, blocks, BLOCK_SIZE, kind
+        ));
+        output.push(format!(
+            "Device: 0,1\tInode: {:<11} Links: {}",
+            inode_number(node, &path),
+            node.link_count(inodes)
+        ));
+        output.push(format!(
+            "Access: ({:04o}/{})  Uid: ({:>5}/{:>8})   Gid: ({:>5}/{:>8})",
+            mode,
+            node.mode_string(inodes),
+            uid,
+            owner,
+            gid,
+            group
+        ));
+        output.push(format!("Access: {}", time));
+        output.push(format!("Modify: {}", time));
+        output.push(format!("Change: {}", time));
+        output.push(" Birth: -".to_string());
+    }
+
+    output.join("\n")
+}
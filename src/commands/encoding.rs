@@ -0,0 +1,657 @@
+//! Byte-level views of files: `base64`, `xxd`, `hexdump`, and the
+//! `sha256sum`/`md5sum` checksums. Everything here reads files as raw
+//! bytes, so it works on archives and other binary content too.
+
+use crate::commands::text::{self, binary_inputs};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes base64, skipping whitespace. `None` on anything else that is
+/// not part of the alphabet or on misplaced padding.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let padding = digits.iter().rev().take_while(|&&b| b == b'=').count();
+    if padding > 2 || (!digits.len().is_multiple_of(4) && padding > 0) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(digits.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut count = 0;
+    for &digit in &digits[..digits.len() - padding] {
+        let value = BASE64_ALPHABET.iter().position(|&b| b == digit)? as u32;
+        group = group << 6 | value;
+        count += 1;
+        if count == 4 {
+            out.extend_from_slice(&[(group >> 16) as u8, (group >> 8) as u8, group as u8]);
+            group = 0;
+            count = 0;
+        }
+    }
+    match count {
+        0 => {}
+        2 => out.push((group >> 4) as u8),
+        3 => out.extend_from_slice(&[(group >> 10) as u8, (group >> 2) as u8]),
+        _ => return None,
+    }
+    Some(out)
+}
+
+/// The flags seen, option values by flag, and the operands.
+type Options<'a> = (Vec<char>, Vec<(char, String)>, Vec<&'a str>);
+
+/// Splits `-w 76`, `-w76` style value options from the rest.
+fn parse_options<'a>(
+    command: &str,
+    args: &[&'a str],
+    flags: &str,
+    valued: &str,
+) -> Result<Options<'a>, String> {
+    let mut seen = Vec::new();
+    let mut values = Vec::new();
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let option = match arg.strip_prefix('-') {
+            Some(option) if !option.is_empty() => option,
+            _ => {
+                operands.push(arg);
+                continue;
+            }
+        };
+        for (index, flag) in option.char_indices() {
+            if valued.contains(flag) {
+                let attached = &option[index + flag.len_utf8()..];
+                let value = if !attached.is_empty() {
+                    attached.to_string()
+                } else if let Some((&next, tail)) = rest.split_first() {
+                    rest = tail;
+                    next.to_string()
+                } else {
+                    return Err(format!(
+                        "{}: option requires an argument -- '{}'",
+                        command, flag
+                    ));
+                };
+                values.push((flag, value));
+                break;
+            } else if flags.contains(flag) {
+                seen.push(flag);
+            } else {
+                return Err(format!("{}: invalid option -- '{}'", command, flag));
+            }
+        }
+    }
+    Ok((seen, values, operands))
+}
+
+/// Parses a count, accepting `0x` hex as xxd does.
+fn parse_number(command: &str, value: &str) -> Result<usize, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| format!("{}: invalid number '{}'", command, value))
+}
+
+/// `base64 [-d] [-i] [-w COLS] [FILE]`: encodes to lines of 76 characters
+/// (`-w 0` for one line), or decodes with `-d`. The output is bytes, since
+/// decoding gives back whatever was encoded.
+pub fn base64(args: &[&str], stdin: Option<&[u8]>) -> Vec<u8> {
+    encode_or_decode(args, stdin).unwrap_or_else(|err| format!("{}\n", err).into_bytes())
+}
+
+fn encode_or_decode(args: &[&str], stdin: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let (flags, values, operands) = parse_options("base64", args, "di", "w")?;
+    let mut wrap = 76;
+    for (_, value) in values {
+        match value.parse() {
+            Ok(columns) => wrap = columns,
+            Err(_) => return Err(format!("base64: invalid wrap size: '{}'", value)),
+        }
+    }
+    if operands.len() > 1 {
+        return Err(format!("base64: extra operand '{}'", operands[1]));
+    }
+
+    let (_, content) = binary_inputs("base64", &operands, stdin).remove(0)?;
+
+    if flags.contains(&'d') {
+        let text = String::from_utf8_lossy(&content);
+        // -i ignores anything outside the alphabet instead of failing.
+        let text: String = if flags.contains(&'i') {
+            text.chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+                .collect()
+        } else {
+            text.into_owned()
+        };
+        return decode_base64(&text).ok_or_else(|| "base64: invalid input".to_string());
+    }
+
+    let encoded = encode_base64(&content);
+    let mut output = Vec::new();
+    if wrap == 0 {
+        output.extend(encoded.into_bytes());
+        output.push(b'\n');
+        return Ok(output);
+    }
+    for line in encoded.as_bytes().chunks(wrap) {
+        output.extend_from_slice(line);
+        output.push(b'\n');
+    }
+    Ok(output)
+}
+
+fn printable(byte: u8) -> char {
+    if (0x20..0x7f).contains(&byte) {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// `xxd [-p] [-u] [-c COLS] [-g BYTES] [-s SEEK] [-l LEN] [FILE]`: a hex
+/// dump with offsets and the printable characters alongside.
+pub fn xxd(args: &[&str], stdin: Option<&[u8]>) -> String {
+    let (flags, values, operands) = match parse_options("xxd", args, "pu", "cgsl") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let plain = flags.contains(&'p');
+    let mut columns = if plain { 30 } else { 16 };
+    let mut group = 2;
+    let mut seek = 0;
+    let mut length = None;
+    for (flag, value) in values {
+        let number = match parse_number("xxd", &value) {
+            Ok(number) => number,
+            Err(err) => return err,
+        };
+        match flag {
+            'c' => columns = number.max(1),
+            'g' => group = number,
+            's' => seek = number,
+            _ => length = Some(number),
+        }
+    }
+    if operands.len() > 1 {
+        return format!("xxd: extra operand '{}'", operands[1]);
+    }
+
+    let content = match binary_inputs("xxd", &operands, stdin).remove(0) {
+        Ok((_, content)) => content,
+        Err(err) => return err,
+    };
+    let start = seek.min(content.len());
+    let end = length.map_or(content.len(), |length| (start + length).min(content.len()));
+    let bytes = &content[start..end];
+
+    let hex = |byte: &u8| {
+        if flags.contains(&'u') {
+            format!("{:02X}", byte)
+        } else {
+            format!("{:02x}", byte)
+        }
+    };
+
+    if plain {
+        return bytes
+            .chunks(columns)
+            .map(|line| line.iter().map(hex).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    // Each group is followed by a space, then one more before the text.
+    let groups = if group == 0 {
+        1
+    } else {
+        columns.div_ceil(group)
+    };
+    let hex_width = columns * 2 + groups;
+    bytes
+        .chunks(columns)
+        .enumerate()
+        .map(|(index, line)| {
+            let mut dump = String::new();
+            for (i, byte) in line.iter().enumerate() {
+                dump.push_str(&hex(byte));
+                if group != 0 && (i + 1) % group == 0 {
+                    dump.push(' ');
+                }
+            }
+            format!(
+                "{:08x}: {:<width$} {}",
+                start + index * columns,
+                dump,
+                line.iter().map(|&b| printable(b)).collect::<String>(),
+                width = hex_width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `hexdump [-C] [-v] [FILE...]`: 16-bit words by default, or bytes with
+/// their text under `-C`. Runs of identical lines collapse to `*` unless
+/// `-v` is given.
+pub fn hexdump(args: &[&str], stdin: Option<&[u8]>) -> String {
+    let (flags, _, operands) = match parse_options("hexdump", args, "Cv", "") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let canonical = flags.contains(&'C');
+
+    let mut output = Vec::new();
+    let mut content = Vec::new();
+    for input in binary_inputs("hexdump", &operands, stdin) {
+        match input {
+            Ok((_, bytes)) => content.extend(bytes),
+            Err(err) => output.push(err),
+        }
+    }
+
+    let mut previous: Option<&[u8]> = None;
+    let mut skipping = false;
+    for (index, line) in content.chunks(16).enumerate() {
+        let offset = index * 16;
+        if !flags.contains(&'v') && line.len() == 16 && previous == Some(line) {
+            if !skipping {
+                output.push("*".to_string());
+                skipping = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        skipping = false;
+
+        if canonical {
+            let mut dump = String::new();
+            for (i, byte) in line.iter().enumerate() {
+                dump.push_str(&format!("{:02x} ", byte));
+                if i == 7 {
+                    dump.push(' ');
+                }
+            }
+            output.push(format!(
+                "{:08x}  {:<49} |{}|",
+                offset,
+                dump,
+                line.iter().map(|&b| printable(b)).collect::<String>()
+            ));
+        } else {
+            let words: Vec<String> = line
+                .chunks(2)
+                .map(|pair| {
+                    format!(
+                        "{:04x}",
+                        pair[0] as u16 | (*pair.get(1).unwrap_or(&0) as u16) << 8
+                    )
+                })
+                .collect();
+            output.push(format!("{:07x} {}", offset, words.join(" ")));
+        }
+    }
+    if !content.is_empty() || operands.is_empty() {
+        output.push(if canonical {
+            format!("{:08x}", content.len())
+        } else {
+            format!("{:07x}", content.len())
+        });
+    }
+    output.join("\n")
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, word) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let k: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+
+    for block in message.chunks(64) {
+        let m: Vec<u32> = block
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(k[i])
+                .wrapping_add(m[g])
+                .rotate_left(SHIFTS[i / 16 * 4 + i % 4]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 16];
+    for (chunk, word) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn hex_digest(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Shared by `sha256sum` and `md5sum`: prints `digest  name` per input,
+/// or with `-c` checks the digests listed in the given files.
+fn checksum(
+    command: &str,
+    args: &[&str],
+    stdin: Option<&[u8]>,
+    hash: fn(&[u8]) -> String,
+) -> String {
+    let (flags, _, operands) = match parse_options(command, args, "c", "") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    if !flags.contains(&'c') {
+        return binary_inputs(command, &operands, stdin)
+            .into_iter()
+            .map(|input| match input {
+                Ok((name, content)) => format!("{}  {}", hash(&content), name),
+                Err(err) => err,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    let mut output = Vec::new();
+    let mut failed = 0;
+    let mut unreadable = 0;
+    let stdin = stdin.map(text::decode);
+    for input in text::inputs(command, &operands, stdin.as_deref()) {
+        let (_, listing) = match input {
+            Ok(input) => input,
+            Err(err) => {
+                output.push(err);
+                continue;
+            }
+        };
+        for line in listing.lines().filter(|line| !line.trim().is_empty()) {
+            let (expected, name) = match line.split_once("  ").or_else(|| line.split_once(" *")) {
+                Some(entry) => entry,
+                None => {
+                    output.push(format!("{}: improperly formatted checksum line", command));
+                    continue;
+                }
+            };
+            match binary_inputs(command, &[name], None).remove(0) {
+                Ok((_, content)) if hash(&content) == expected.to_ascii_lowercase() => {
+                    output.push(format!("{}: OK", name))
+                }
+                Ok(_) => {
+                    failed += 1;
+                    output.push(format!("{}: FAILED", name));
+                }
+                Err(err) => {
+                    unreadable += 1;
+                    output.push(err);
+                    output.push(format!("{}: FAILED open or read", name));
+                }
+            }
+        }
+    }
+    if unreadable > 0 {
+        output.push(format!(
+            "{}: WARNING: {} listed file{} could not be read",
+            command,
+            unreadable,
+            if unreadable == 1 { "" } else { "s" }
+        ));
+    }
+    if failed > 0 {
+        output.push(format!(
+            "{}: WARNING: {} computed checksum{} did NOT match",
+            command,
+            failed,
+            if failed == 1 { "" } else { "s" }
+        ));
+    }
+    output.join("\n")
+}
+
+/// `sha256sum [-c] [FILE...]`
+pub fn sha256sum(args: &[&str], stdin: Option<&[u8]>) -> String {
+    checksum("sha256sum", args, stdin, |data| hex_digest(&sha256(data)))
+}
+
+/// `md5sum [-c] [FILE...]`
+pub fn md5sum(args: &[&str], stdin: Option<&[u8]>) -> String {
+    checksum("md5sum", args, stdin, |data| hex_digest(&md5(data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: Vec<u8>) -> String {
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn base64_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors.iter().copied() {
+            assert_eq!(encode_base64(plain.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded).unwrap(), plain.as_bytes());
+        }
+    }
+
+    #[test]
+    fn base64_round_trips_every_byte() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(1_000).collect();
+        assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+        assert_eq!(decode_base64("Zm9v\nYmFy\n").unwrap(), b"foobar");
+        assert_eq!(decode_base64("Zm9v!"), None);
+        assert_eq!(decode_base64("Zg==="), None);
+        assert_eq!(decode_base64("Z"), None);
+    }
+
+    #[test]
+    fn base64_command_wraps_and_decodes() {
+        let input = [b'x'; 60];
+        let wrapped = text(base64(&[], Some(&input)));
+        let lines: Vec<&str> = wrapped.lines().collect();
+        assert_eq!(
+            lines.iter().map(|line| line.len()).collect::<Vec<_>>(),
+            [76, 4]
+        );
+        assert_eq!(text(base64(&["-w", "0"], Some(b"hi"))), "aGk=\n");
+        assert_eq!(base64(&["-d"], Some(b"AAH/\n")), [0, 1, 255]);
+        assert_eq!(text(base64(&["-d"], Some(b"*"))), "base64: invalid input\n");
+        assert_eq!(base64(&["-di"], Some(b"aG*k=")), b"hi");
+    }
+
+    #[test]
+    fn digests_match_known_vectors() {
+        let vectors: [(&[u8], &str, &str); 3] = [
+            (
+                b"",
+                "d41d8cd98f00b204e9800998ecf8427e",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abc",
+                "900150983cd24fb0d6963f7d28e17f72",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                b"The quick brown fox jumps over the lazy dog",
+                "9e107d9d372bb6826bd81d3542a419d6",
+                "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592",
+            ),
+        ];
+        for (data, md5_hex, sha256_hex) in vectors.iter().copied() {
+            assert_eq!(hex_digest(&md5(data)), md5_hex);
+            assert_eq!(hex_digest(&sha256(data)), sha256_hex);
+        }
+
+        // Long enough to cross many blocks and the length padding.
+        let million = vec![b'a'; 1_000_000];
+        assert_eq!(
+            hex_digest(&md5(&million)),
+            "7707d6ae4e027c70eea2a935c2296f21"
+        );
+        assert_eq!(
+            hex_digest(&sha256(&million)),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn checksum_commands_name_stdin() {
+        assert_eq!(
+            md5sum(&[], Some(b"abc")),
+            "900150983cd24fb0d6963f7d28e17f72  -"
+        );
+        assert_eq!(sha256sum(&["-x"], None), "sha256sum: invalid option -- 'x'");
+    }
+
+    #[test]
+    fn hex_dumps() {
+        let data = b"Hello, binary\x00\x01\xff world";
+        assert_eq!(
+            xxd(&[], Some(data)),
+            "00000000: 4865 6c6c 6f2c 2062 696e 6172 7900 01ff  Hello, binary...\n\
+             00000010: 2077 6f72 6c64                            world"
+        );
+        assert_eq!(xxd(&["-p", "-l", "4"], Some(data)), "48656c6c");
+        assert_eq!(
+            xxd(&["-s", "0x7", "-l", "3", "-u"], Some(data)),
+            "00000007: 6269 6E                                  bin"
+        );
+        assert_eq!(
+            hexdump(&["-C"], Some(&[0; 40])),
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+             *\n\
+             00000020  00 00 00 00 00 00 00 00                           |........|\n\
+             00000028"
+        );
+        assert_eq!(hexdump(&[], Some(b"abc")), "0000000 6261 0063\n0000003");
+    }
+}
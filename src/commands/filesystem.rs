@@ -1,20 +1,376 @@
+use crate::commands::mounts::{self, Mount};
+use crate::commands::procfs;
+use crate::commands::users;
 use lazy_static::lazy_static;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::{Arc, Mutex};
 
+pub type InodeId = u64;
+
+/// What a regular file actually is: its bytes and metadata. Directory
+/// entries only point at an inode, so several names can share one.
 #[derive(Clone, Debug)]
+pub struct Inode {
+    pub content: Vec<u8>,
+    pub permissions: u16,
+    pub owner: String,
+    pub group: String,
+}
+
+impl Inode {
+    /// The content as text, with anything that is not UTF-8 replaced.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.content)
+    }
+}
+
+/// Whether `content` is better not shown as text: it is not UTF-8, or it
+/// holds control characters other than the whitespace and escapes that
+/// text files use.
+pub fn is_binary(content: &[u8]) -> bool {
+    std::str::from_utf8(content).is_err()
+        || content
+            .iter()
+            .any(|&b| (b < 0x20 && !matches!(b, b'\n' | b'\t' | b'\r' | 0x1b)) || b == 0x7f)
+}
+
+/// Produces a generated file's bytes from the current filesystem.
+pub type ReadFn = dyn Fn(&Filesystem) -> Vec<u8> + Send + Sync;
+
+/// Content computed on every read instead of stored, for the kernel files
+/// under /proc and the devices under /dev.
+#[derive(Clone)]
+pub struct Generator {
+    /// Character devices show up as `c` in `ls -l`.
+    pub device: bool,
+    pub read: Arc<ReadFn>,
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Generator")
+            .field("device", &self.device)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Every file inode plus the number of directory entries naming it and
+/// when it was last written. Times come from a counter that ticks on every
+/// write, which is all `find -newer` needs to order files.
+#[derive(Clone, Debug, Default)]
+pub struct InodeTable {
+    /// Shared with any snapshot of the table until one side writes.
+    inodes: HashMap<InodeId, Arc<Inode>>,
+    links: HashMap<InodeId, usize>,
+    modified: HashMap<InodeId, u64>,
+    generators: HashMap<InodeId, Generator>,
+    next_id: InodeId,
+    clock: u64,
+}
+
+impl InodeTable {
+    /// Stores `inode` and returns the first directory entry for it.
+    pub fn create(&mut self, inode: Inode) -> Node {
+        let node = self.preload(inode);
+        if let Node::File { inode } = node {
+            self.touch(inode);
+        }
+        node
+    }
+
+    /// Like `create`, for files baked into the system image: they all
+    /// predate anything written at runtime.
+    pub fn preload(&mut self, inode: Inode) -> Node {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.inodes.insert(id, Arc::new(inode));
+        self.links.insert(id, 1);
+        Node::File { inode: id }
+    }
+
+    /// Like `preload`, for a file whose content `generator` computes on
+    /// each read. The stored content stays empty, so sizes read as 0.
+    pub fn generate(&mut self, inode: Inode, generator: Generator) -> Node {
+        let node = self.preload(inode);
+        if let Node::File { inode } = node {
+            self.generators.insert(inode, generator);
+        }
+        node
+    }
+
+    pub fn generator(&self, id: InodeId) -> Option<&Generator> {
+        self.generators.get(&id)
+    }
+
+    /// Replaces the stored content and marks the inode as written. Writes
+    /// to generated files are discarded, which is all `/dev/null` needs.
+    pub fn write(&mut self, id: InodeId, content: impl Into<Vec<u8>>) {
+        if !self.generators.contains_key(&id) {
+            self.get_mut(id).content = content.into();
+        }
+        self.touch(id);
+    }
+
+    /// Marks the inode as written just now.
+    pub fn touch(&mut self, id: InodeId) {
+        self.clock += 1;
+        self.modified.insert(id, self.clock);
+    }
+
+    pub fn modified(&self, id: InodeId) -> u64 {
+        self.modified.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Another name for an existing inode, as `ln` without `-s` makes.
+    pub fn link(&mut self, id: InodeId) -> Node {
+        *self.links.entry(id).or_insert(0) += 1;
+        Node::File { inode: id }
+    }
+
+    /// Drops the links held by `node` and everything below it; an inode
+    /// goes away once its last name does.
+    pub fn release(&mut self, node: &Node) {
+        match node {
+            Node::File { inode } => {
+                let remaining = self.links.get_mut(inode).map(|count| {
+                    *count = count.saturating_sub(1);
+                    *count
+                });
+                if remaining == Some(0) {
+                    self.links.remove(inode);
+                    self.modified.remove(inode);
+                    self.generators.remove(inode);
+                    self.inodes.remove(inode);
+                }
+            }
+            Node::Directory { children, .. } => {
+                for child in children.values() {
+                    self.release(child);
+                }
+            }
+            Node::Symlink { .. } => {}
+        }
+    }
+
+    pub fn get(&self, id: InodeId) -> &Inode {
+        &self.inodes[&id]
+    }
+
+    /// Copies the inode first if a snapshot still shares it.
+    pub fn get_mut(&mut self, id: InodeId) -> &mut Inode {
+        Arc::make_mut(self.inodes.get_mut(&id).expect("dangling inode"))
+    }
+
+    pub fn link_count(&self, id: InodeId) -> usize {
+        self.links.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Whether inode `id` was left alone since `other` was cloned from
+    /// this table: a write copies it away from the one `other` shares.
+    pub fn unchanged_inode(&self, other: &InodeTable, id: InodeId) -> bool {
+        match (self.inodes.get(&id), other.inodes.get(&id)) {
+            (Some(inode), Some(old)) => Arc::ptr_eq(inode, old),
+            _ => false,
+        }
+    }
+
+    /// Whether nothing was written, linked or removed since `other` was
+    /// cloned from this table. Untouched inodes are still shared, so this
+    /// only compares pointers.
+    pub fn unchanged_since(&self, other: &InodeTable) -> bool {
+        self.links == other.links
+            && self.modified == other.modified
+            && self.inodes.len() == other.inodes.len()
+            && self.inodes.iter().all(|(id, inode)| {
+                other
+                    .inodes
+                    .get(id)
+                    .is_some_and(|old| Arc::ptr_eq(inode, old))
+            })
+    }
+}
+
+/// How `ls` orders names: leading dots are ignored, letters compare
+/// case-insensitively and runs of digits by their value, so `.bashrc`
+/// sorts among the `b`s and `file2` before `file10`. Names that still tie
+/// fall back to lowercase first, then plain byte order, so distinct names
+/// never compare equal.
+pub fn collate(a: &str, b: &str) -> Ordering {
+    fn natural(a: &str, b: &str) -> Ordering {
+        let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+        loop {
+            match (a.peek().copied(), b.peek().copied()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                    let run = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                        let mut digits = String::new();
+                        while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+                            digits.push(c);
+                            chars.next();
+                        }
+                        digits.trim_start_matches('0').to_string()
+                    };
+                    let (x, y) = (run(&mut a), run(&mut b));
+                    let order = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+                (Some(x), Some(y)) => {
+                    let order = x.to_lowercase().cmp(y.to_lowercase());
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                    a.next();
+                    b.next();
+                }
+            }
+        }
+    }
+
+    natural(a.trim_start_matches('.'), b.trim_start_matches('.'))
+        .then_with(|| {
+            let case = |name: &str| name.chars().map(char::is_uppercase).collect::<Vec<_>>();
+            case(a).cmp(&case(b))
+        })
+        .then_with(|| a.cmp(b))
+}
+
+/// A directory's entries, kept in `collate` order so every listing and
+/// walk sees the same sequence without sorting.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Children {
+    entries: Vec<(String, Node)>,
+}
+
+impl Children {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position(&self, name: &str) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by(|(entry, _)| collate(entry, name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.position(name).ok().map(|index| &self.entries[index].1)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Node> {
+        match self.position(name) {
+            Ok(index) => Some(&mut self.entries[index].1),
+            Err(_) => None,
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.position(name).is_ok()
+    }
+
+    /// Adds or replaces `name`, returning the node it replaced.
+    pub fn insert(&mut self, name: String, node: Node) -> Option<Node> {
+        match self.position(&name) {
+            Ok(index) => Some(std::mem::replace(&mut self.entries[index].1, node)),
+            Err(index) => {
+                self.entries.insert(index, (name, node));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Node> {
+        match self.position(name) {
+            Ok(index) => Some(self.entries.remove(index).1),
+            Err(_) => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Node)> {
+        self.entries.iter().map(|(name, node)| (name, node))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut Node)> {
+        self.entries.iter_mut().map(|(name, node)| (&*name, node))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Node> {
+        self.entries.iter().map(|(_, node)| node)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.entries.iter_mut().map(|(_, node)| node)
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = (String, Node)> + '_ {
+        self.entries.drain(..)
+    }
+}
+
+impl FromIterator<(String, Node)> for Children {
+    fn from_iter<I: IntoIterator<Item = (String, Node)>>(iter: I) -> Self {
+        let mut children = Children::new();
+        for (name, node) in iter {
+            children.insert(name, node);
+        }
+        children
+    }
+}
+
+impl<'a> IntoIterator for &'a Children {
+    type Item = (&'a String, &'a Node);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, Node)>,
+        fn(&'a (String, Node)) -> (&'a String, &'a Node),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(name, node)| (name, node))
+    }
+}
+
+impl std::ops::Index<&str> for Children {
+    type Output = Node;
+
+    fn index(&self, name: &str) -> &Node {
+        self.get(name).expect("no such entry")
+    }
+}
+
+impl<const N: usize> From<[(String, Node); N]> for Children {
+    fn from(entries: [(String, Node); N]) -> Self {
+        IntoIterator::into_iter(entries).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Node {
     File {
-        content: String,
-        permissions: u16,
-        owner: String,
-        protected: bool,
+        inode: InodeId,
     },
     Directory {
-        children: HashMap<String, Node>,
+        children: Children,
         permissions: u16,
         owner: String,
-        protected: bool,
+        group: String,
     },
     Symlink {
         target: String,
@@ -31,116 +387,320 @@ impl Node {
         matches!(self, Node::File { .. })
     }
 
-    pub fn is_protected(&self) -> bool {
+    pub fn get_owner<'a>(&'a self, inodes: &'a InodeTable) -> &'a str {
         match self {
-            Node::File { protected, .. } => *protected,
-            Node::Directory { protected, .. } => *protected,
-            Node::Symlink { .. } => false,
+            Node::File { inode } => &inodes.get(*inode).owner,
+            Node::Directory { owner, .. } => owner,
+            Node::Symlink { owner, .. } => owner,
         }
     }
 
-    pub fn get_owner(&self) -> &str {
+    /// Symlinks carry no group of their own and report their owner's.
+    pub fn get_group<'a>(&'a self, inodes: &'a InodeTable) -> &'a str {
         match self {
-            Node::File { owner, .. } => owner,
-            Node::Directory { owner, .. } => owner,
+            Node::File { inode } => &inodes.get(*inode).group,
+            Node::Directory { group, .. } => group,
             Node::Symlink { owner, .. } => owner,
         }
     }
+
+    /// Mode bits including setuid/setgid/sticky. Symlinks are always 0o777.
+    pub fn get_permissions(&self, inodes: &InodeTable) -> u16 {
+        match self {
+            Node::File { inode } => inodes.get(*inode).permissions,
+            Node::Directory { permissions, .. } => *permissions,
+            Node::Symlink { .. } => 0o777,
+        }
+    }
+
+    /// Names pointing at this node: hard links for files, `.`, `..` and
+    /// each subdirectory's `..` for directories.
+    pub fn link_count(&self, inodes: &InodeTable) -> usize {
+        match self {
+            Node::File { inode } => inodes.link_count(*inode),
+            Node::Directory { children, .. } => {
+                2 + children
+                    .values()
+                    .filter(|child| matches!(child, Node::Directory { .. }))
+                    .count()
+            }
+            Node::Symlink { .. } => 1,
+        }
+    }
+
+    pub fn set_permissions(&mut self, inodes: &mut InodeTable, mode: u16) {
+        match self {
+            Node::File { inode } => inodes.get_mut(*inode).permissions = mode & 0o7777,
+            Node::Directory { permissions, .. } => *permissions = mode & 0o7777,
+            Node::Symlink { .. } => {}
+        }
+    }
+
+    pub fn set_owner(&mut self, inodes: &mut InodeTable, user: &str) {
+        match self {
+            Node::File { inode } => inodes.get_mut(*inode).owner = user.to_string(),
+            Node::Directory { owner, .. } | Node::Symlink { owner, .. } => {
+                *owner = user.to_string()
+            }
+        }
+    }
+
+    pub fn set_group(&mut self, inodes: &mut InodeTable, name: &str) {
+        match self {
+            Node::File { inode } => inodes.get_mut(*inode).group = name.to_string(),
+            Node::Directory { group, .. } => *group = name.to_string(),
+            Node::Symlink { .. } => {}
+        }
+    }
+
+    /// Applies `f` to this node and, when `recursive`, to everything below it.
+    pub fn for_each_mut(&mut self, recursive: bool, f: &mut dyn FnMut(&mut Node)) {
+        f(self);
+        if recursive {
+            if let Node::Directory { children, .. } = self {
+                for child in children.values_mut() {
+                    child.for_each_mut(true, f);
+                }
+            }
+        }
+    }
+
+    /// `ls -l` style mode string, e.g. `drwxrwxrwt`.
+    pub fn mode_string(&self, inodes: &InodeTable) -> String {
+        let file_type = match self {
+            Node::Directory { .. } => 'd',
+            Node::File { inode } if inodes.generator(*inode).is_some_and(|g| g.device) => 'c',
+            Node::File { .. } => '-',
+            Node::Symlink { .. } => 'l',
+        };
+        let mode = self.get_permissions(inodes);
+
+        let mut out = String::with_capacity(10);
+        out.push(file_type);
+        for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+            let bits = (mode >> shift) & 0o7;
+            out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            out.push(match (bits & 0o1 != 0, mode & special != 0) {
+                (true, true) => set_char,
+                (false, true) => set_char.to_ascii_uppercase(),
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        out
+    }
+}
+
+/// Errors shared by every filesystem command, printed in coreutils wording.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    PermissionDenied,
+    AlreadyExists,
+    TooManyLinks,
+    ReadOnly,
+    Busy,
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            FsError::NotFound => "No such file or directory",
+            FsError::NotADirectory => "Not a directory",
+            FsError::IsADirectory => "Is a directory",
+            FsError::PermissionDenied => "Permission denied",
+            FsError::AlreadyExists => "File exists",
+            FsError::TooManyLinks => "Too many levels of symbolic links",
+            FsError::ReadOnly => "Read-only file system",
+            FsError::Busy => "Device or resource busy",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// The directory tree together with the inodes its file entries point at
+/// and the mounts it is split into.
+#[derive(Clone, Debug)]
+pub struct Filesystem {
+    pub root: Node,
+    pub inodes: InodeTable,
+    pub mounts: Vec<Mount>,
+}
+
+impl Filesystem {
+    /// The inode behind a regular file entry.
+    pub fn file(&self, node: &Node) -> Option<&Inode> {
+        match node {
+            Node::File { inode } => Some(self.inodes.get(*inode)),
+            _ => None,
+        }
+    }
+
+    /// A file's content as a reader sees it, generated afresh if need be.
+    pub fn read(&self, id: InodeId) -> String {
+        match self.inodes.generator(id) {
+            Some(generator) => String::from_utf8_lossy(&(generator.read)(self)).into_owned(),
+            None => self.inodes.get(id).text().into_owned(),
+        }
+    }
+
+    /// Like `read`, but the raw bytes, for tools that handle binary files.
+    pub fn read_bytes(&self, id: InodeId) -> Vec<u8> {
+        match self.inodes.generator(id) {
+            Some(generator) => (generator.read)(self),
+            None => self.inodes.get(id).content.clone(),
+        }
+    }
+
+    /// The mount `path` (canonical) lives on.
+    pub fn mount_of(&self, path: &[String]) -> &Mount {
+        self.mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.target))
+            .max_by_key(|mount| mount.target.len())
+            .expect("/ is always mounted")
+    }
+
+    /// Fails with "Read-only file system" when `path` (canonical) is on a
+    /// read-only mount.
+    pub fn writable(&self, path: &[String]) -> Result<(), FsError> {
+        if self.mount_of(path).read_only {
+            Err(FsError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether a mount sits at or below `path`, which then cannot go away.
+    pub fn has_mount_under(&self, path: &[String]) -> bool {
+        self.mounts
+            .iter()
+            .any(|mount| mount.target.starts_with(path))
+    }
+
+    /// Like `get_node_mut`, but also hands out the inode table so entries
+    /// and the files behind them can be changed together.
+    pub fn split_mut(&mut self, path: &[String]) -> Option<(&mut Node, &mut InodeTable)> {
+        let canonical = resolve_path(self, path, false, &|_| true).ok()?.0;
+
+        let mut current = &mut self.root;
+        for part in &canonical {
+            if let Node::Directory { children, .. } = current {
+                current = children.get_mut(part)?;
+            } else {
+                return None;
+            }
+        }
+        Some((current, &mut self.inodes))
+    }
 }
 
 lazy_static! {
-    pub static ref FILESYSTEM: Mutex<Node> = Mutex::new({
+    pub static ref FILESYSTEM: Mutex<Filesystem> = Mutex::new({
         use Node::*;
-        Directory {
+        let mut inodes = InodeTable::default();
+        let root = Directory {
             permissions: 0o755,
             owner: "root".to_string(),
-            protected: true,
-            children: HashMap::from([
+            group: "root".to_string(),
+            children: Children::from([
                 (
                     "home".into(),
                     Directory {
                         permissions: 0o755,
                         owner: "root".to_string(),
-                        protected: true,
-                        children: HashMap::from([(
+                        group: "root".to_string(),
+                        children: Children::from([(
                             "objz".into(),
                             Directory {
                                 permissions: 0o755,
                                 owner: "objz".to_string(),
-                                protected: true,
-                                children: HashMap::from([
+                                group: "objz".to_string(),
+                                children: Children::from([
                                     (
                                         "projects".into(),
                                         Directory {
                                             permissions: 0o755,
                                             owner: "objz".to_string(),
-                                            protected: true,
-                                            children: HashMap::from([
+                                            group: "objz".to_string(),
+                                            children: Children::from([
                                                 (
                                                     "CommandBridge.md".into(),
-                                                    File {
+                                                    inodes.preload(Inode {
                                                         content: "A scripting-based plugin enabling advanced command forwarding between Velocity and Paper Minecraft servers. Supports flexible automation and custom workflows for server administrators.\n\nProject link: [https://github.com/objz/CommandBridge]\n\nStatus: Active development".into(),
                                                         permissions: 0o644,
                                                         owner: "objz".to_string(),
-                                                        protected: true,
-                                                    }
+                                                        group: "objz".to_string(),
+                                                    })
                                                 ),
                                                 (
                                                     "mcl.md".into(),
-                                                    File {
+                                                    inodes.preload(Inode {
                                                         content: "A fast, Rust-powered command-line Minecraft launcher focused on performance and simplicity. Designed for direct launching, version management, and mod integration.\n\nProject link: [https://github.com/objz/mcl]\n\nStatus: In development (not finished yet)".into(),
                                                         permissions: 0o644,
                                                         owner: "objz".to_string(),
-                                                        protected: true,
-                                                    }
+                                                        group: "objz".to_string(),
+                                                    })
                                                 ),
                                                 (
                                                     "PowerImport.md".into(),
-                                                    File {
+                                                    inodes.preload(Inode {
                                                         content: "An Excel VSTO add-in for importing and synchronizing Power BI queries directly into spreadsheets. Built for seamless integration and efficient data workflows in enterprise environments.\n\nProject link: [https://github.com/objz/PowerImport]\n\nStatus: Completed".into(),
                                                         permissions: 0o644,
                                                         owner: "objz".to_string(),
-                                                        protected: true,
-                                                    }
+                                                        group: "objz".to_string(),
+                                                    })
                                                 ),
                                             ]),
                                         }
                                     ),
                                     (
                                         "about.txt".into(),
-                                        File {
+                                        inodes.preload(Inode {
                                             content: "Hi, I'm objz and I'm 17 years old.\nMy main skills are:\n\n- Rust (primary)\n- Java (primary)\n- C (occasionally)\n- Web Development (only if absolutely necessary)".into(),
                                             permissions: 0o644,
                                             owner: "objz".to_string(),
-                                            protected: true,
-                                        }
+                                            group: "objz".to_string(),
+                                        })
                                     ),
                                     (
                                         "contact.txt".into(),
-                                        File {
+                                        inodes.preload(Inode {
                                             content: "GitHub: @objz\nEmail: me@objz.dev\nLocation: Bavaria, Germany\nResponse time: Eventually™".into(),
                                             permissions: 0o644,
                                             owner: "objz".to_string(),
-                                            protected: true,
-                                        }
+                                            group: "objz".to_string(),
+                                        })
+                                    ),
+                                    (
+                                        ".notes".into(),
+                                        inodes.preload(Inode {
+                                            content: "TODO:\n- finish mcl\n- change my password, \"password\" is probably not that secure".into(),
+                                            permissions: 0o600,
+                                            owner: "objz".to_string(),
+                                            group: "objz".to_string(),
+                                        })
                                     ),
                                     (
                                         ".bashrc".into(),
-                                        File {
+                                        inodes.preload(Inode {
                                             content: "# ~/.bashrc\nexport PS1='\\u@\\h:\\w\\$ '\nalias ll='ls -la'".into(),
                                             permissions: 0o644,
                                             owner: "objz".to_string(),
-                                            protected: false,
-                                        }
+                                            group: "objz".to_string(),
+                                        })
                                     ),
                                     (
                                         "credits.txt".into(),
-                                        File {
+                                        inodes.preload(Inode {
                                             content: "This site was developed by objz.\n\nBuilt with:\n- Rust and WebAssembly (Wasm)\n- Three.js for 3D rendering\n\n3D model provided by Sketchfab: [https://shorturl.at/OXITb]\nLooping background music from Freesound: [https://shorturl.at/YYufx]\n\nNo warranty, express or implied.".into(),
                                             permissions: 0o644,
                                             owner: "objz".to_string(),
-                                            protected: true,
-                                        }
+                                            group: "objz".to_string(),
+                                        })
                                     ),
                                 ]),
                             }
@@ -152,44 +712,80 @@ lazy_static! {
                     Directory {
                         permissions: 0o755,
                         owner: "root".to_string(),
-                        protected: true,
-                        children: HashMap::from([
+                        group: "root".to_string(),
+                        children: Children::from([
                             (
                                 "hostname".into(),
-                                File {
+                                inodes.preload(Inode {
                                     content: "wasm-host".into(),
                                     permissions: 0o644,
                                     owner: "root".to_string(),
-                                    protected: true,
-                                }
+                                    group: "root".to_string(),
+                                })
                             ),
                             (
                                 "passwd".into(),
-                                File {
+                                inodes.preload(Inode {
                                     content: "root:x:0:0:root:/root:/bin/bash\nobjz:x:1000:1000:objz:/home/objz:/bin/bash\nnobody:x:65534:65534:nobody:/:/usr/bin/nologin".into(),
                                     permissions: 0o644,
                                     owner: "root".to_string(),
-                                    protected: true,
-                                }
+                                    group: "root".to_string(),
+                                })
+                            ),
+                            (
+                                "shadow".into(),
+                                inodes.preload(Inode {
+                                    content: "root:!:19875:0:99999:7:::\nobjz:$fnv1a$4b1a493507b3a318:19875:0:99999:7:::\nnobody:*:19875:0:99999:7:::".into(),
+                                    permissions: 0o600,
+                                    owner: "root".to_string(),
+                                    group: "root".to_string(),
+                                })
+                            ),
+                            (
+                                "sudoers".into(),
+                                inodes.preload(Inode {
+                                    content: "# /etc/sudoers\n#\n# User privilege specification\nroot ALL=(ALL:ALL) ALL\n\n# Members of group sudo may run any command\n%sudo ALL=(ALL:ALL) ALL".into(),
+                                    permissions: 0o440,
+                                    owner: "root".to_string(),
+                                    group: "root".to_string(),
+                                })
+                            ),
+                            (
+                                "group".into(),
+                                inodes.preload(Inode {
+                                    content: "root:x:0:\nsudo:x:27:objz\nobjz:x:1000:\nnobody:x:65534:".into(),
+                                    permissions: 0o644,
+                                    owner: "root".to_string(),
+                                    group: "root".to_string(),
+                                })
                             ),
                         ]),
                     }
                 ),
+                (
+                    "root".into(),
+                    Directory {
+                        permissions: 0o700,
+                        owner: "root".to_string(),
+                        group: "root".to_string(),
+                        children: Children::new(),
+                    }
+                ),
                 (
                     "tmp".into(),
                     Directory {
                         permissions: 0o1777,
                         owner: "root".to_string(),
-                        protected: false,
-                        children: HashMap::from([
+                        group: "root".to_string(),
+                        children: Children::from([
                             (
                                 "rust.txt".into(),
-                                File {
+                                inodes.preload(Inode {
                                     content: "Did you know?\nRust was originally developed by Mozilla.\nThe first stable release was in 2015.".into(),
                                     permissions: 0o644,
                                     owner: "objz".to_string(),
-                                    protected: false,
-                                }
+                                    group: "objz".to_string(),
+                                })
                             ),
                         ]),
                     }
@@ -199,15 +795,45 @@ lazy_static! {
                     Directory {
                         permissions: 0o755,
                         owner: "root".to_string(),
-                        protected: true,
-                        children: HashMap::from([
+                        group: "root".to_string(),
+                        children: Children::from([
                             (
                                 "bin".into(),
                                 Directory {
                                     permissions: 0o755,
                                     owner: "root".to_string(),
-                                    protected: true,
-                                    children: HashMap::new(),
+                                    group: "root".to_string(),
+                                    children: Children::new(),
+                                }
+                            ),
+                            (
+                                "share".into(),
+                                Directory {
+                                    permissions: 0o755,
+                                    owner: "root".to_string(),
+                                    group: "root".to_string(),
+                                    children: Children::from([
+                                        (
+                                            "sounds".into(),
+                                            Directory {
+                                                permissions: 0o755,
+                                                owner: "root".to_string(),
+                                                group: "root".to_string(),
+                                                children: Children::from([
+                                                    (
+                                                        // The terminal's own key click, as shipped with the site.
+                                                        "click.wav".into(),
+                                                        inodes.preload(Inode {
+                                                            content: include_bytes!("../../static/audio/click.wav").to_vec(),
+                                                            permissions: 0o644,
+                                                            owner: "root".to_string(),
+                                                            group: "root".to_string(),
+                                                        })
+                                                    ),
+                                                ]),
+                                            }
+                                        ),
+                                    ]),
                                 }
                             ),
                         ]),
@@ -218,23 +844,23 @@ lazy_static! {
                     Directory {
                         permissions: 0o755,
                         owner: "root".to_string(),
-                        protected: true,
-                        children: HashMap::from([
+                        group: "root".to_string(),
+                        children: Children::from([
                             (
                                 "log".into(),
                                 Directory {
                                     permissions: 0o755,
                                     owner: "root".to_string(),
-                                    protected: true,
-                                    children: HashMap::from([
+                                    group: "root".to_string(),
+                                    children: Children::from([
                                         (
                                             "boot.log".into(),
-                                            File {
+                                            inodes.preload(Inode {
                                                 content: "Loading Linux kernel version 6.8.9-wasm-1...\nLoading initial ramdisk (initramfs)...\nStarting systemd-udevd v254.5-1...\nProbing hardware...\nDetected storage device: /dev/nvme0n1\nDetected storage device: /dev/sda\nActivating swap on /dev/sda2...\nMounting root filesystem...\nChecking file system on /dev/sda1...\nMounting /boot...\nMounting /home...\nMounting /var...\nStarting systemd-journald.service...\nStarting systemd-tmpfiles-setup-dev.service...\nStarting systemd-sysctl.service...\nStarting Load Kernel Modules...\nLoading kernel modules: i915 ext4 fuse...\nStarting Network Manager...\nStarting Login Service (systemd-logind)...\nStarting Authorization Manager (polkitd)...\nStarting User Manager for UID 1000...\nStarting Interface...".into(),
                                                 permissions: 0o644,
                                                 owner: "root".to_string(),
-                                                protected: true,
-                                            }
+                                                group: "root".to_string(),
+                                            })
                                         ),
                                     ]),
                                 }
@@ -242,72 +868,156 @@ lazy_static! {
                         ]),
                     }
                 ),
+                ("proc".into(), procfs::proc_dir(&mut inodes)),
+                ("dev".into(), procfs::dev_dir(&mut inodes)),
             ]),
-        }
+        };
+        let mounts = mounts::boot(&root, &mut inodes);
+        let filesystem = Filesystem {
+            root,
+            inodes,
+            mounts,
+        };
+        users::prepare_boot_session(&filesystem);
+        filesystem
     });
     pub static ref CURRENT_PATH: Mutex<Vec<String>> =
         Mutex::new(vec!["home".to_string(), "objz".to_string()]);
-    pub static ref CURRENT_USER: String = "objz".to_string();
+}
+
+/// Every test in the process shares `FILESYSTEM`. Tests that rely on the
+/// writable mounts hold this shared; one that wipes them holds it alone.
+#[cfg(test)]
+static TEST_FILESYSTEM: std::sync::RwLock<()> = std::sync::RwLock::new(());
+
+#[cfg(test)]
+pub fn shared_for_test() -> std::sync::RwLockReadGuard<'static, ()> {
+    TEST_FILESYSTEM
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(test)]
+pub fn exclusive_for_test() -> std::sync::RwLockWriteGuard<'static, ()> {
+    TEST_FILESYSTEM
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
 }
 
 pub fn normalize_path(path: &str, current: &[String]) -> Vec<String> {
-    if path.starts_with('/') {
-        let mut result = Vec::new();
-        for part in path.split('/').filter(|s| !s.is_empty()) {
-            match part {
-                "." => continue,
-                ".." => {
-                    result.pop();
-                }
-                _ => result.push(part.to_string()),
-            }
-        }
-        result
+    if path == "~" || path.starts_with("~/") {
+        return normalize_path(
+            path[1..].trim_start_matches('/'),
+            &crate::commands::users::home_dir(),
+        );
+    }
+
+    let base = if path.starts_with('/') {
+        &[][..]
     } else {
-        let mut result = current.to_vec();
-        for part in path.split('/').filter(|s| !s.is_empty()) {
-            match part {
-                "." => continue,
-                ".." => {
-                    result.pop();
+        current
+    };
+    lexical_path(path, base)
+}
+
+/// Linux's MAXSYMLINKS: how many links one lookup may expand before ELOOP.
+pub const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Walks `path` from `root`, expanding symlinks in every intermediate
+/// component and, when `follow_last` is set, in the final one too.
+/// Relative link targets resolve against the directory holding the link.
+/// `can_search` is asked about every directory passed through.
+///
+/// Returns the canonical (link-free) path together with the node.
+pub fn resolve_path<'a>(
+    fs: &'a Filesystem,
+    path: &[String],
+    follow_last: bool,
+    can_search: &dyn Fn(&Node) -> bool,
+) -> Result<(Vec<String>, &'a Node), FsError> {
+    let root = &fs.root;
+    let mut pending: VecDeque<String> = path.iter().cloned().collect();
+    let mut resolved: Vec<String> = Vec::new();
+    let mut current = root;
+    let mut expansions = 0;
+
+    while let Some(part) = pending.pop_front() {
+        let children = match current {
+            Node::Directory { children, .. } => children,
+            _ => return Err(FsError::NotADirectory),
+        };
+        if !can_search(current) {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let child = children.get(&part).ok_or(FsError::NotFound)?;
+
+        if let Node::Symlink { target, .. } = child {
+            if follow_last || !pending.is_empty() {
+                expansions += 1;
+                if expansions > MAX_SYMLINK_DEPTH {
+                    return Err(FsError::TooManyLinks);
                 }
-                _ => result.push(part.to_string()),
+
+                let base = if target.starts_with('/') {
+                    &[][..]
+                } else {
+                    &resolved[..]
+                };
+                let mut expanded: VecDeque<String> = lexical_path(target, base).into();
+                expanded.extend(pending);
+                pending = expanded;
+                resolved.clear();
+                current = root;
+                continue;
             }
         }
-        result
+
+        resolved.push(part);
+        current = child;
     }
+
+    Ok((resolved, current))
 }
 
-pub fn get_node<'a>(root: &'a Node, path: &[String]) -> Option<&'a Node> {
-    let mut current = root;
-    for part in path {
-        if let Node::Directory { children, .. } = current {
-            current = children.get(part)?;
-        } else {
-            return None;
+/// Applies `.` and `..` components of `path` on top of `base`.
+fn lexical_path(path: &str, base: &[String]) -> Vec<String> {
+    let mut result = base.to_vec();
+    for part in path.split('/').filter(|s| !s.is_empty()) {
+        match part {
+            "." => continue,
+            ".." => {
+                result.pop();
+            }
+            _ => result.push(part.to_string()),
         }
     }
-    Some(current)
+    result
 }
 
-pub fn get_node_mut<'a>(root: &'a mut Node, path: &[String]) -> Option<&'a mut Node> {
-    let mut current = root;
-    for part in path {
-        if let Node::Directory { children, .. } = current {
-            current = children.get_mut(part)?;
-        } else {
-            return None;
-        }
-    }
-    Some(current)
+/// Looks up `path` without permission checks. Intermediate symlinks are
+/// followed; a symlink in the final position is returned as-is.
+pub fn get_node<'a>(fs: &'a Filesystem, path: &[String]) -> Option<&'a Node> {
+    resolve_path(fs, path, false, &|_| true)
+        .ok()
+        .map(|(_, node)| node)
+}
+
+pub fn get_node_mut<'a>(fs: &'a mut Filesystem, path: &[String]) -> Option<&'a mut Node> {
+    fs.split_mut(path).map(|(node, _)| node)
 }
 
 pub fn autocomplete_entries(path: &[String], dirs_only: bool) -> Vec<String> {
+    use crate::commands::permissions::{self, Access};
+
     let filesystem = FILESYSTEM.lock().unwrap();
+    let session = crate::commands::users::current_session();
 
-    match get_node(&filesystem, path) {
-        Some(Node::Directory { children, .. }) => {
-            let mut entries: Vec<String> = children
+    match permissions::resolve(&filesystem, path, &session) {
+        Ok((_, node @ Node::Directory { children, .. }))
+            if permissions::check(&filesystem, node, &session, Access::Read) =>
+        {
+            let entries: Vec<String> = children
                 .iter()
                 .filter_map(|(name, node)| {
                     if dirs_only {
@@ -325,7 +1035,6 @@ pub fn autocomplete_entries(path: &[String], dirs_only: bool) -> Vec<String> {
                 })
                 .collect();
 
-            entries.sort();
             entries
         }
         _ => Vec::new(),
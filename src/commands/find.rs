@@ -0,0 +1,524 @@
+use crate::commands::filesystem::{
+    normalize_path, Filesystem, FsError, Node, CURRENT_PATH, FILESYSTEM,
+};
+use crate::commands::permissions::{self, Access};
+use crate::commands::users::{self, Session};
+
+/// One predicate of a `find` expression. Consecutive tests are and-ed.
+enum Test {
+    Name { pattern: String, fold_case: bool },
+    Type(char),
+    Size { cmp: Cmp, amount: u64, unit: u64 },
+    Newer(u64),
+    Perm { mode: u16, how: PermMatch },
+    User(String),
+    Not(Box<Test>),
+}
+
+#[derive(Clone, Copy)]
+enum Cmp {
+    Less,
+    Equal,
+    Greater,
+}
+
+#[derive(Clone, Copy)]
+enum PermMatch {
+    Exact,
+    All,
+    Any,
+}
+
+enum Action {
+    Print,
+    Delete,
+    Exec { command: Vec<String>, batch: bool },
+}
+
+struct Query {
+    starts: Vec<String>,
+    tests: Vec<Test>,
+    actions: Vec<Action>,
+    min_depth: usize,
+    max_depth: usize,
+}
+
+/// Everything the actions need to know about one match once the
+/// filesystem lock has been released.
+struct Found {
+    path: String,
+    is_dir: bool,
+}
+
+/// Matches and walk errors, in the order the walk produced them.
+type Walked = Vec<Result<Found, String>>;
+
+/// `find [path...] [expression]`. Tests run while the tree is locked;
+/// actions run afterwards, with `-exec` handed to `exec` so it can go
+/// through the regular command dispatcher.
+pub fn find(args: &[&str], exec: &mut dyn FnMut(&[String]) -> String) -> String {
+    let query = match parse(args) {
+        Ok(query) => query,
+        Err(err) => return format!("find: {}", err),
+    };
+
+    let mut output = Vec::new();
+    let mut found = Vec::new();
+    {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        let current_path = CURRENT_PATH.lock().unwrap();
+        let session = users::current_session();
+
+        // -delete implies -depth so directories are emptied before they go.
+        let depth_first = query
+            .actions
+            .iter()
+            .any(|action| matches!(action, Action::Delete));
+
+        for start in &query.starts {
+            let path = normalize_path(start, &current_path);
+            match permissions::traverse(&filesystem, &path, &session) {
+                Ok(node) => {
+                    let mut walker = Walker {
+                        fs: &filesystem,
+                        session: &session,
+                        query: &query,
+                        depth_first,
+                        found: &mut found,
+                    };
+                    walker.visit(start.trim_end_matches('/').to_string(), node, 0);
+                }
+                Err(err) => found.push(Err(format!("find: '{}': {}", start, err))),
+            }
+        }
+    }
+
+    let mut batches: Vec<Vec<String>> = vec![Vec::new(); query.actions.len()];
+    for entry in &found {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                output.push(err.clone());
+                continue;
+            }
+        };
+        for (index, action) in query.actions.iter().enumerate() {
+            match action {
+                Action::Print => output.push(entry.path.clone()),
+                Action::Delete => {
+                    if let Err(err) = delete(&entry.path, entry.is_dir) {
+                        output.push(format!("find: cannot delete '{}': {}", entry.path, err));
+                    }
+                }
+                Action::Exec { batch: true, .. } => batches[index].push(entry.path.clone()),
+                Action::Exec { command, .. } => {
+                    let words: Vec<String> = command
+                        .iter()
+                        .map(|arg| arg.replace("{}", &entry.path))
+                        .collect();
+                    push_output(&mut output, exec(&words));
+                }
+            }
+        }
+    }
+
+    for (action, paths) in query.actions.iter().zip(batches) {
+        if let Action::Exec {
+            command,
+            batch: true,
+        } = action
+        {
+            if !paths.is_empty() {
+                let words: Vec<String> = command
+                    .iter()
+                    .flat_map(|arg| {
+                        if arg == "{}" {
+                            paths.clone()
+                        } else {
+                            vec![arg.clone()]
+                        }
+                    })
+                    .collect();
+                push_output(&mut output, exec(&words));
+            }
+        }
+    }
+
+    output.join("\n")
+}
+
+fn push_output(output: &mut Vec<String>, text: String) {
+    if !text.is_empty() {
+        output.push(text);
+    }
+}
+
+struct Walker<'a> {
+    fs: &'a Filesystem,
+    session: &'a Session,
+    query: &'a Query,
+    depth_first: bool,
+    found: &'a mut Walked,
+}
+
+impl Walker<'_> {
+    fn visit(&mut self, path: String, node: &Node, depth: usize) {
+        let path = if path.is_empty() {
+            "/".to_string()
+        } else {
+            path
+        };
+        let name = path.rsplit('/').next().filter(|name| !name.is_empty());
+        let name = name.unwrap_or(&path).to_string();
+
+        let matched = depth >= self.query.min_depth
+            && self
+                .query
+                .tests
+                .iter()
+                .all(|test| self.test(test, &name, node));
+        let is_dir = matches!(node, Node::Directory { .. });
+
+        if matched && !self.depth_first {
+            self.found.push(Ok(Found {
+                path: path.clone(),
+                is_dir,
+            }));
+        }
+
+        if let Node::Directory { children, .. } = node {
+            if depth < self.query.max_depth {
+                if permissions::check(self.fs, node, self.session, Access::Read)
+                    && permissions::check(self.fs, node, self.session, Access::Execute)
+                {
+                    let prefix = path.trim_end_matches('/');
+                    for (child_name, child) in children {
+                        self.visit(format!("{}/{}", prefix, child_name), child, depth + 1);
+                    }
+                } else {
+                    let err = format!("find: '{}': {}", path, FsError::PermissionDenied);
+                    self.found.push(Err(err));
+                }
+            }
+        }
+
+        if matched && self.depth_first {
+            self.found.push(Ok(Found { path, is_dir }));
+        }
+    }
+
+    fn test(&self, test: &Test, name: &str, node: &Node) -> bool {
+        let inodes = &self.fs.inodes;
+        match test {
+            Test::Name { pattern, fold_case } => {
+                if *fold_case {
+                    glob_match(&pattern.to_lowercase(), &name.to_lowercase())
+                } else {
+                    glob_match(pattern, name)
+                }
+            }
+            Test::Type(kind) => {
+                let actual = match node {
+                    Node::File { .. } => 'f',
+                    Node::Directory { .. } => 'd',
+                    Node::Symlink { .. } => 'l',
+                };
+                actual == *kind
+            }
+            Test::Size { cmp, amount, unit } => {
+                let units = size_of(self.fs, node).div_ceil(*unit);
+                match cmp {
+                    Cmp::Less => units < *amount,
+                    Cmp::Equal => units == *amount,
+                    Cmp::Greater => units > *amount,
+                }
+            }
+            Test::Newer(reference) => modified_of(self.fs, node) > *reference,
+            Test::Perm { mode, how } => {
+                let actual = node.get_permissions(inodes) & 0o7777;
+                match how {
+                    PermMatch::Exact => actual == *mode,
+                    PermMatch::All => actual & mode == *mode,
+                    PermMatch::Any => *mode == 0 || actual & mode != 0,
+                }
+            }
+            Test::User(user) => node.get_owner(inodes) == user,
+            Test::Not(inner) => !self.test(inner, name, node),
+        }
+    }
+}
+
+fn size_of(fs: &Filesystem, node: &Node) -> u64 {
+    match node {
+        Node::File { inode } => fs.inodes.get(*inode).content.len() as u64,
+        Node::Directory { .. } => 4096,
+        Node::Symlink { target, .. } => target.len() as u64,
+    }
+}
+
+/// Directories and links keep the time the system image was built.
+fn modified_of(fs: &Filesystem, node: &Node) -> u64 {
+    match node {
+        Node::File { inode } => fs.inodes.modified(*inode),
+        _ => 0,
+    }
+}
+
+/// `-delete` removes files and empty directories, with the same checks
+/// `rm` applies.
+fn delete(path: &str, is_dir: bool) -> Result<(), String> {
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let path = normalize_path(path, &current_path);
+
+    let node = permissions::traverse(&filesystem, &path, &session).map_err(|e| e.to_string())?;
+    if is_dir {
+        if let Node::Directory { children, .. } = node {
+            if !children.is_empty() {
+                return Err("Directory not empty".into());
+            }
+        }
+    }
+
+    let canonical =
+        permissions::can_remove(&filesystem, &path, &session).map_err(|e| e.to_string())?;
+    let (name, parent_path) = canonical.split_last().ok_or("Permission denied")?;
+    if let Some((Node::Directory { children, .. }, inodes)) = filesystem.split_mut(parent_path) {
+        if let Some(removed) = children.remove(name) {
+            inodes.release(&removed);
+        }
+    }
+    Ok(())
+}
+
+fn parse(args: &[&str]) -> Result<Query, String> {
+    let mut query = Query {
+        starts: Vec::new(),
+        tests: Vec::new(),
+        actions: Vec::new(),
+        min_depth: 0,
+        max_depth: usize::MAX,
+    };
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        if arg.starts_with('-') || arg == "!" {
+            break;
+        }
+        query.starts.push(arg.to_string());
+        rest = tail;
+    }
+    if query.starts.is_empty() {
+        query.starts.push(".".to_string());
+    }
+
+    let mut negate = false;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let mut value = |name: &str| -> Result<&str, String> {
+            let (&value, tail) = rest
+                .split_first()
+                .ok_or_else(|| format!("missing argument to '{}'", name))?;
+            rest = tail;
+            Ok(value)
+        };
+
+        let test = match arg {
+            "!" | "-not" => {
+                negate = !negate;
+                continue;
+            }
+            "-name" | "-iname" => Test::Name {
+                pattern: value(arg)?.to_string(),
+                fold_case: arg == "-iname",
+            },
+            "-type" => match value(arg)? {
+                kind @ ("f" | "d" | "l") => Test::Type(kind.chars().next().unwrap()),
+                kind => return Err(format!("Unknown argument to -type: {}", kind)),
+            },
+            "-size" => parse_size(value(arg)?)?,
+            "-newer" => {
+                let reference = value(arg)?;
+                Test::Newer(reference_time(reference)?)
+            }
+            "-perm" => parse_perm(value(arg)?)?,
+            "-user" => {
+                let user = value(arg)?;
+                let filesystem = FILESYSTEM.lock().unwrap();
+                if users::find_user(&filesystem, user).is_none() {
+                    return Err(format!("'{}' is not the name of a known user", user));
+                }
+                Test::User(user.to_string())
+            }
+            "-maxdepth" | "-mindepth" => {
+                let raw = value(arg)?;
+                let depth = raw.parse().map_err(|_| {
+                    format!(
+                        "Expected a positive decimal integer argument to {}, but got '{}'",
+                        arg, raw
+                    )
+                })?;
+                if arg == "-maxdepth" {
+                    query.max_depth = depth;
+                } else {
+                    query.min_depth = depth;
+                }
+                continue;
+            }
+            "-print" => {
+                query.actions.push(Action::Print);
+                continue;
+            }
+            "-delete" => {
+                query.actions.push(Action::Delete);
+                continue;
+            }
+            "-exec" => {
+                let end = rest
+                    .iter()
+                    .position(|&arg| matches!(arg, ";" | "\\;" | "+"))
+                    .ok_or("missing argument to '-exec'")?;
+                let batch = rest[end] == "+";
+                if end == 0 || (batch && rest[end - 1] != "{}") {
+                    return Err("missing argument to '-exec'".into());
+                }
+                query.actions.push(Action::Exec {
+                    command: rest[..end].iter().map(|arg| arg.to_string()).collect(),
+                    batch,
+                });
+                rest = &rest[end + 1..];
+                continue;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown predicate '{}'", arg)),
+            _ => return Err(format!("paths must precede expression: '{}'", arg)),
+        };
+
+        query.tests.push(if negate {
+            Test::Not(Box::new(test))
+        } else {
+            test
+        });
+        negate = false;
+    }
+
+    if query.actions.is_empty() {
+        query.actions.push(Action::Print);
+    }
+    Ok(query)
+}
+
+/// `-size [+-]N[cwbkMG]`, counted in 512-byte blocks by default and
+/// rounded up to whole units like GNU find.
+fn parse_size(spec: &str) -> Result<Test, String> {
+    let invalid = || format!("invalid -size argument: '{}'", spec);
+
+    let (cmp, rest) = match spec.as_bytes().first() {
+        Some(b'+') => (Cmp::Greater, &spec[1..]),
+        Some(b'-') => (Cmp::Less, &spec[1..]),
+        _ => (Cmp::Equal, spec),
+    };
+    let (digits, unit) = match rest.char_indices().last() {
+        Some((index, c)) if c.is_ascii_alphabetic() => (&rest[..index], c),
+        _ => (rest, 'b'),
+    };
+    let unit = match unit {
+        'c' => 1,
+        'w' => 2,
+        'b' => 512,
+        'k' => 1024,
+        'M' => 1024 * 1024,
+        'G' => 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+    let amount = digits.parse().map_err(|_| invalid())?;
+
+    Ok(Test::Size { cmp, amount, unit })
+}
+
+/// `-perm MODE` (exactly), `-perm -MODE` (all of) or `-perm /MODE` (any of).
+fn parse_perm(spec: &str) -> Result<Test, String> {
+    let (how, mode) = match spec.as_bytes().first() {
+        Some(b'-') => (PermMatch::All, &spec[1..]),
+        Some(b'/') => (PermMatch::Any, &spec[1..]),
+        _ => (PermMatch::Exact, spec),
+    };
+    let mode = permissions::parse_mode(mode, 0, false)
+        .ok_or_else(|| format!("invalid mode '{}'", spec))?;
+
+    Ok(Test::Perm { mode, how })
+}
+
+fn reference_time(reference: &str) -> Result<u64, String> {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let path = normalize_path(reference, &current_path);
+    match permissions::resolve(&filesystem, &path, &session) {
+        Ok((_, node)) => Ok(modified_of(&filesystem, node)),
+        Err(err) => Err(format!("'{}': {}", reference, err)),
+    }
+}
+
+/// Shell-style wildcard match supporting `*`, `?` and `[...]` classes
+/// (with `!` or `^` negation and `a-z` ranges).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    fn class_end(pattern: &[char], start: usize) -> Option<usize> {
+        let mut i = start + 1;
+        if matches!(pattern.get(i), Some('!' | '^')) {
+            i += 1;
+        }
+        if pattern.get(i) == Some(&']') {
+            i += 1;
+        }
+        while i < pattern.len() {
+            if pattern[i] == ']' {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    fn class_matches(class: &[char], c: char) -> bool {
+        let (negated, class) = match class.first() {
+            Some('!' | '^') => (true, &class[1..]),
+            _ => (false, class),
+        };
+        let mut matched = false;
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                matched |= class[i] <= c && c <= class[i + 2];
+                i += 3;
+            } else {
+                matched |= class[i] == c;
+                i += 1;
+            }
+        }
+        matched != negated
+    }
+
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+            Some('*') => (0..=text.len()).any(|skip| matches(&pattern[1..], &text[skip..])),
+            Some('?') => !text.is_empty() && matches(&pattern[1..], &text[1..]),
+            Some('[') => match class_end(pattern, 0) {
+                Some(end) => {
+                    !text.is_empty()
+                        && class_matches(&pattern[1..end], text[0])
+                        && matches(&pattern[end + 1..], &text[1..])
+                }
+                None => text.first() == Some(&'[') && matches(&pattern[1..], &text[1..]),
+            },
+            Some(&c) => text.first() == Some(&c) && matches(&pattern[1..], &text[1..]),
+        }
+    }
+
+    matches(&pattern, &text)
+}
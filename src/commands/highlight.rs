@@ -0,0 +1,862 @@
+//! `bat`: files shown with syntax highlighting, line numbers and a grid.
+//! The highlighter is a small hand-written scanner per language, good
+//! enough for keywords, strings, comments, numbers and the like.
+
+use crate::commands::filesystem::{is_binary, normalize_path, CURRENT_PATH, FILESYSTEM};
+use crate::commands::text;
+use crate::commands::users;
+use crate::terminal::{ansi, buffer};
+
+const GRID: &str = "38;2;88;88;88";
+const NUMBER_COLUMN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    Java,
+    C,
+    JavaScript,
+    Toml,
+    Shell,
+    Json,
+}
+
+impl Language {
+    pub const ALL: [Language; 7] = [
+        Language::Rust,
+        Language::Java,
+        Language::C,
+        Language::JavaScript,
+        Language::Toml,
+        Language::Shell,
+        Language::Json,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Java => "Java",
+            Language::C => "C",
+            Language::JavaScript => "JavaScript",
+            Language::Toml => "TOML",
+            Language::Shell => "Shell",
+            Language::Json => "JSON",
+        }
+    }
+
+    /// Names accepted by `-l`, extensions included.
+    pub fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["rust", "rs"],
+            Language::Java => &["java"],
+            Language::C => &["c", "h"],
+            Language::JavaScript => &["javascript", "js", "mjs", "cjs", "jsx"],
+            Language::Toml => &["toml"],
+            Language::Shell => &["sh", "bash", "zsh", "shell"],
+            Language::Json => &["json"],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Language> {
+        let name = name.to_lowercase();
+        Language::ALL
+            .iter()
+            .copied()
+            .find(|language| language.aliases().contains(&name.as_str()))
+    }
+
+    /// The language of the file `name`, from its extension, a few well
+    /// known names, or a `#!` line.
+    pub fn detect(name: &str, content: &str) -> Option<Language> {
+        let base = name.rsplit('/').next().unwrap_or(name);
+        if let Some(language) = base
+            .rsplit_once('.')
+            .filter(|(stem, _)| !stem.is_empty())
+            .and_then(|(_, extension)| Language::from_name(extension))
+        {
+            return Some(language);
+        }
+        match base {
+            "Cargo.lock" => return Some(Language::Toml),
+            ".bashrc" | ".bash_profile" | ".profile" | ".zshrc" => return Some(Language::Shell),
+            _ => {}
+        }
+        let shebang = content.lines().next()?.strip_prefix("#!")?;
+        let interpreter = shebang.split_whitespace().last()?;
+        let interpreter = interpreter.rsplit('/').next().unwrap_or(interpreter);
+        match interpreter {
+            "sh" | "bash" | "zsh" | "dash" => Some(Language::Shell),
+            "node" => Some(Language::JavaScript),
+            _ => None,
+        }
+    }
+
+    fn syntax(self) -> Syntax {
+        match self {
+            Language::Rust => Syntax {
+                keywords: &[
+                    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                    "enum", "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+                    "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static",
+                    "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+                ],
+                types: &[
+                    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16",
+                    "i32", "i64", "i128", "isize", "f32", "f64",
+                ],
+                constants: &["true", "false", "None", "Some", "Ok", "Err"],
+                line_comment: &["//"],
+                block_comment: Some(("/*", "*/")),
+            },
+            Language::Java => Syntax {
+                keywords: &[
+                    "abstract",
+                    "assert",
+                    "break",
+                    "case",
+                    "catch",
+                    "class",
+                    "continue",
+                    "default",
+                    "do",
+                    "else",
+                    "enum",
+                    "extends",
+                    "final",
+                    "finally",
+                    "for",
+                    "if",
+                    "implements",
+                    "import",
+                    "instanceof",
+                    "interface",
+                    "native",
+                    "new",
+                    "package",
+                    "private",
+                    "protected",
+                    "public",
+                    "record",
+                    "return",
+                    "static",
+                    "super",
+                    "switch",
+                    "synchronized",
+                    "this",
+                    "throw",
+                    "throws",
+                    "try",
+                    "var",
+                    "void",
+                    "volatile",
+                    "while",
+                    "yield",
+                ],
+                types: &[
+                    "boolean", "byte", "char", "short", "int", "long", "float", "double",
+                ],
+                constants: &["true", "false", "null"],
+                line_comment: &["//"],
+                block_comment: Some(("/*", "*/")),
+            },
+            Language::C => Syntax {
+                keywords: &[
+                    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum",
+                    "extern", "for", "goto", "if", "inline", "register", "restrict", "return",
+                    "sizeof", "static", "struct", "switch", "typedef", "union", "volatile",
+                    "while",
+                ],
+                types: &[
+                    "char", "short", "int", "long", "float", "double", "void", "signed",
+                    "unsigned", "bool", "size_t", "ssize_t", "FILE", "int8_t", "int16_t",
+                    "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t", "uint64_t",
+                ],
+                constants: &["NULL", "true", "false", "EOF", "stdin", "stdout", "stderr"],
+                line_comment: &["//"],
+                block_comment: Some(("/*", "*/")),
+            },
+            Language::JavaScript => Syntax {
+                keywords: &[
+                    "async",
+                    "await",
+                    "break",
+                    "case",
+                    "catch",
+                    "class",
+                    "const",
+                    "continue",
+                    "debugger",
+                    "default",
+                    "delete",
+                    "do",
+                    "else",
+                    "export",
+                    "extends",
+                    "finally",
+                    "for",
+                    "from",
+                    "function",
+                    "if",
+                    "import",
+                    "in",
+                    "instanceof",
+                    "let",
+                    "new",
+                    "of",
+                    "return",
+                    "static",
+                    "super",
+                    "switch",
+                    "this",
+                    "throw",
+                    "try",
+                    "typeof",
+                    "var",
+                    "void",
+                    "while",
+                    "with",
+                    "yield",
+                ],
+                types: &[],
+                constants: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+                line_comment: &["//"],
+                block_comment: Some(("/*", "*/")),
+            },
+            Language::Toml => Syntax {
+                keywords: &[],
+                types: &[],
+                constants: &["true", "false", "inf", "nan"],
+                line_comment: &["#"],
+                block_comment: None,
+            },
+            Language::Shell => Syntax {
+                keywords: &[
+                    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done",
+                    "case", "esac", "in", "function", "return", "local", "export", "readonly",
+                    "exit", "break", "continue", "select",
+                ],
+                types: &[
+                    "echo", "cd", "printf", "read", "source", "test", "set", "unset", "shift",
+                    "eval", "exec", "trap",
+                ],
+                constants: &["true", "false"],
+                line_comment: &["#"],
+                block_comment: None,
+            },
+            Language::Json => Syntax {
+                keywords: &[],
+                types: &[],
+                constants: &["true", "false", "null"],
+                line_comment: &[],
+                block_comment: None,
+            },
+        }
+    }
+}
+
+/// The words and comment markers a language is scanned with.
+struct Syntax {
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    constants: &'static [&'static str],
+    line_comment: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+}
+
+/// What a run of source text is, and so how it is coloured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    Plain,
+    Keyword,
+    Type,
+    Constant,
+    Number,
+    String,
+    Comment,
+    Function,
+    Macro,
+    Attribute,
+    Variable,
+    Key,
+    Section,
+}
+
+impl Class {
+    /// Colours after the Monokai theme `bat` uses by default.
+    fn code(self) -> &'static str {
+        match self {
+            Class::Plain => "",
+            Class::Keyword => "38;2;249;38;114",
+            Class::Type | Class::Key => "38;2;102;217;239",
+            Class::Constant | Class::Number => "38;2;174;129;255",
+            Class::String => "38;2;230;219;116",
+            Class::Comment => "38;2;117;113;94",
+            Class::Function | Class::Macro | Class::Section => "38;2;166;226;46",
+            Class::Attribute | Class::Variable => "38;2;253;151;31",
+        }
+    }
+}
+
+/// An open string literal: what closes it, whether backslash escapes
+/// the next character, and whether it may run onto the next line.
+#[derive(Debug, Clone)]
+struct Quote {
+    close: String,
+    escapes: bool,
+    multiline: bool,
+}
+
+impl Quote {
+    fn new(close: &str, escapes: bool, multiline: bool) -> Self {
+        Self {
+            close: close.to_string(),
+            escapes,
+            multiline,
+        }
+    }
+}
+
+/// What carries over from one line to the next.
+#[derive(Default)]
+struct State {
+    comment: bool,
+    string: Option<Quote>,
+}
+
+/// A line being coloured, as runs of one class each.
+#[derive(Default)]
+struct Runs(Vec<(Class, String)>);
+
+impl Runs {
+    fn push(&mut self, class: Class, chars: &[char]) {
+        if chars.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some((last, text)) if *last == class => text.extend(chars),
+            _ => self.0.push((class, chars.iter().collect())),
+        }
+    }
+
+    fn to_ansi(&self) -> String {
+        self.0
+            .iter()
+            .map(|(class, text)| match class {
+                Class::Plain => text.clone(),
+                class => ansi::paint(text, class.code()),
+            })
+            .collect()
+    }
+}
+
+fn starts_with(chars: &[char], at: usize, pattern: &str) -> bool {
+    (at..)
+        .zip(pattern.chars())
+        .all(|(i, c)| chars.get(i) == Some(&c))
+}
+
+fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    (from..chars.len()).find(|&i| starts_with(chars, i, pattern))
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Highlighter {
+    language: Language,
+    syntax: Syntax,
+    state: State,
+}
+
+impl Highlighter {
+    fn new(language: Language) -> Self {
+        Self {
+            language,
+            syntax: language.syntax(),
+            state: State::default(),
+        }
+    }
+
+    /// The string literal opening at `chars[i]`, and how long its opening is.
+    fn quote_at(&self, chars: &[char], i: usize) -> Option<(Quote, usize)> {
+        let c = chars[i];
+        let after_word = i > 0 && is_word(chars[i - 1]);
+        match self.language {
+            Language::Rust if (c == 'r' || starts_with(chars, i, "br")) && !after_word => {
+                let prefix = if c == 'r' { 1 } else { 2 };
+                let hashes = chars[i + prefix..]
+                    .iter()
+                    .take_while(|&&c| c == '#')
+                    .count();
+                if chars.get(i + prefix + hashes) != Some(&'"') {
+                    return None;
+                }
+                let close = format!("\"{}", "#".repeat(hashes));
+                Some((Quote::new(&close, false, true), prefix + hashes + 1))
+            }
+            Language::Rust if c == 'b' && chars.get(i + 1) == Some(&'"') && !after_word => {
+                Some((Quote::new("\"", true, true), 2))
+            }
+            Language::Rust if c == '"' => Some((Quote::new("\"", true, true), 1)),
+            // `'a'` and `'\n'` are characters; `'a` alone is a lifetime.
+            Language::Rust if c == '\'' => {
+                let char_literal =
+                    chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'');
+                char_literal.then(|| (Quote::new("'", true, false), 1))
+            }
+            Language::Java if starts_with(chars, i, "\"\"\"") => {
+                Some((Quote::new("\"\"\"", true, true), 3))
+            }
+            Language::Toml if starts_with(chars, i, "\"\"\"") => {
+                Some((Quote::new("\"\"\"", true, true), 3))
+            }
+            Language::Toml if starts_with(chars, i, "'''") => {
+                Some((Quote::new("'''", false, true), 3))
+            }
+            Language::Toml if c == '\'' => Some((Quote::new("'", false, false), 1)),
+            Language::JavaScript if c == '`' => Some((Quote::new("`", true, true), 1)),
+            Language::Shell if c == '"' => Some((Quote::new("\"", true, true), 1)),
+            Language::Shell if c == '\'' => Some((Quote::new("'", false, true), 1)),
+            Language::Json if c == '"' => Some((Quote::new("\"", true, false), 1)),
+            Language::Json | Language::Shell => None,
+            _ if c == '"' || c == '\'' => Some((Quote::new(&c.to_string(), true, false), 1)),
+            _ => None,
+        }
+    }
+
+    /// Scans the open string from `body` to its close or the end of the
+    /// line, colouring it from `start`. Returns where scanning stopped.
+    fn string(&mut self, chars: &[char], start: usize, body: usize, runs: &mut Runs) -> usize {
+        let quote = match self.state.string.clone() {
+            Some(quote) => quote,
+            None => return body,
+        };
+        let mut i = body;
+        let mut closed = false;
+        while i < chars.len() {
+            if quote.escapes && chars[i] == '\\' {
+                i += 2;
+                continue;
+            }
+            if starts_with(chars, i, &quote.close) {
+                i += quote.close.chars().count();
+                closed = true;
+                break;
+            }
+            i += 1;
+        }
+        let i = i.min(chars.len());
+        if closed || !quote.multiline {
+            self.state.string = None;
+        }
+        // A JSON string followed by `:` is an object key.
+        let key = closed
+            && self.language == Language::Json
+            && chars[i..].iter().find(|c| !c.is_whitespace()) == Some(&':');
+        runs.push(
+            if key { Class::Key } else { Class::String },
+            &chars[start..i],
+        );
+        i
+    }
+
+    /// Colours one line, carrying open comments and strings to the next.
+    fn line(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut runs = Runs::default();
+        let mut i = 0;
+
+        if self.state.string.is_none() && !self.state.comment {
+            i = self.line_start(&chars, &mut runs);
+        }
+
+        while i < chars.len() {
+            if self.state.comment {
+                let (_, close) = self.syntax.block_comment.unwrap_or(("", ""));
+                let end = find(&chars, i, close).map_or(chars.len(), |end| {
+                    self.state.comment = false;
+                    end + close.len()
+                });
+                runs.push(Class::Comment, &chars[i..end]);
+                i = end;
+                continue;
+            }
+            if self.state.string.is_some() {
+                i = self.string(&chars, i, i, &mut runs);
+                continue;
+            }
+
+            let c = chars[i];
+            let word_start = i == 0 || !is_word(chars[i - 1]);
+            let shell_word_start = i == 0 || chars[i - 1].is_whitespace();
+            let next = |at: usize| chars[at..].iter().find(|c| !c.is_whitespace()).copied();
+
+            if self
+                .syntax
+                .line_comment
+                .iter()
+                .any(|marker| starts_with(&chars, i, marker))
+                && (self.language != Language::Shell || shell_word_start)
+            {
+                runs.push(Class::Comment, &chars[i..]);
+                break;
+            }
+            if let Some((open, _)) = self
+                .syntax
+                .block_comment
+                .filter(|(open, _)| starts_with(&chars, i, open))
+            {
+                self.state.comment = true;
+                runs.push(Class::Comment, &chars[i..i + open.len()]);
+                i += open.len();
+                continue;
+            }
+            if let Some((quote, open)) = self.quote_at(&chars, i) {
+                self.state.string = Some(quote);
+                i = self.string(&chars, i, i + open, &mut runs);
+                continue;
+            }
+
+            match c {
+                '#' if self.language == Language::Rust
+                    && (starts_with(&chars, i, "#[") || starts_with(&chars, i, "#![")) =>
+                {
+                    let mut depth = 0;
+                    let mut end = chars.len();
+                    for (k, &c) in chars.iter().enumerate().skip(i) {
+                        match c {
+                            '[' => depth += 1,
+                            ']' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end = k + 1;
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    runs.push(Class::Attribute, &chars[i..end]);
+                    i = end;
+                }
+                '\'' if self.language == Language::Rust => {
+                    let end = i + 1 + chars[i + 1..].iter().take_while(|&&c| is_word(c)).count();
+                    runs.push(Class::Type, &chars[i..end]);
+                    i = end;
+                }
+                '@' if matches!(self.language, Language::Java | Language::JavaScript) => {
+                    let end = i + 1 + chars[i + 1..].iter().take_while(|&&c| is_word(c)).count();
+                    runs.push(Class::Attribute, &chars[i..end]);
+                    i = end;
+                }
+                '$' if self.language == Language::Shell => {
+                    let end = match chars.get(i + 1) {
+                        Some('{') => find(&chars, i, "}").map_or(chars.len(), |end| end + 1),
+                        Some(c) if c.is_ascii_digit() || "@*#?$!-".contains(*c) => i + 2,
+                        Some(_) => {
+                            i + 1 + chars[i + 1..].iter().take_while(|&&c| is_word(c)).count()
+                        }
+                        None => i + 1,
+                    };
+                    runs.push(Class::Variable, &chars[i..end]);
+                    i = end;
+                }
+                c if c.is_ascii_digit() && word_start => {
+                    let mut end = i;
+                    while end < chars.len()
+                        && (is_word(chars[end])
+                            || (chars[end] == '.'
+                                && chars.get(end + 1).is_some_and(|c| c.is_ascii_digit())))
+                    {
+                        end += 1;
+                    }
+                    runs.push(Class::Number, &chars[i..end]);
+                    i = end;
+                }
+                c if (c.is_alphabetic() || c == '_') && word_start => {
+                    let end = i + chars[i..].iter().take_while(|&&c| is_word(c)).count();
+                    let word: String = chars[i..end].iter().collect();
+                    let (class, end) = self.word(&word, &chars, end, next(end));
+                    runs.push(class, &chars[i..end]);
+                    i = end;
+                }
+                _ => {
+                    runs.push(Class::Plain, &chars[i..i + 1]);
+                    i += 1;
+                }
+            }
+        }
+        runs.to_ansi()
+    }
+
+    /// What a word is, given what follows it, and where it ends (a Rust
+    /// macro takes its `!`).
+    fn word(&self, word: &str, chars: &[char], end: usize, next: Option<char>) -> (Class, usize) {
+        let syntax = &self.syntax;
+        let capitalized = word.starts_with(|c: char| c.is_uppercase());
+        let shouting = word.len() > 1
+            && word
+                .chars()
+                .all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_');
+        let class = if syntax.keywords.contains(&word) {
+            Class::Keyword
+        } else if syntax.constants.contains(&word) {
+            Class::Constant
+        } else if syntax.types.contains(&word) {
+            Class::Type
+        } else if self.language == Language::Rust && chars.get(end) == Some(&'!') {
+            return (Class::Macro, end + 1);
+        } else if self.language == Language::Shell && chars.get(end) == Some(&'=') {
+            Class::Variable
+        } else if matches!(self.language, Language::Toml | Language::Json) {
+            Class::Plain
+        } else if next == Some('(') {
+            Class::Function
+        } else if shouting {
+            Class::Constant
+        } else if capitalized && self.language != Language::Shell {
+            Class::Type
+        } else {
+            Class::Plain
+        };
+        (class, end)
+    }
+
+    /// Things only seen at the start of a line: TOML tables and keys and
+    /// C preprocessor directives. Returns where the rest of the line
+    /// should be scanned from.
+    fn line_start(&mut self, chars: &[char], runs: &mut Runs) -> usize {
+        let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+        runs.push(Class::Plain, &chars[..indent]);
+        match (self.language, chars.get(indent)) {
+            (Language::Toml, Some('[')) => {
+                let end = chars
+                    .iter()
+                    .rposition(|&c| c == ']')
+                    .map_or(chars.len(), |end| end + 1);
+                runs.push(Class::Section, &chars[indent..end]);
+                end
+            }
+            (Language::Toml, Some(c)) if *c != '#' => {
+                let equals = match chars.iter().position(|&c| c == '=') {
+                    Some(equals) => equals,
+                    None => return indent,
+                };
+                let key_end = indent
+                    + chars[indent..equals]
+                        .iter()
+                        .rposition(|c| !c.is_whitespace())
+                        .map_or(0, |end| end + 1);
+                runs.push(Class::Key, &chars[indent..key_end]);
+                key_end
+            }
+            (Language::C, Some('#')) => {
+                let end = indent
+                    + 1
+                    + chars[indent + 1..]
+                        .iter()
+                        .take_while(|&&c| c.is_alphabetic() || c == ' ')
+                        .count();
+                runs.push(Class::Attribute, &chars[indent..end]);
+                if chars.get(end) == Some(&'<') {
+                    let close = find(chars, end, ">").map_or(chars.len(), |close| close + 1);
+                    runs.push(Class::String, &chars[end..close]);
+                    return close;
+                }
+                end
+            }
+            _ => indent,
+        }
+    }
+}
+
+/// `source` with each line coloured as `language`.
+pub fn highlight(source: &str, language: Language) -> Vec<String> {
+    let mut highlighter = Highlighter::new(language);
+    source.lines().map(|line| highlighter.line(line)).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    /// Header, grid and line numbers, as `bat` draws by default.
+    Full,
+    /// Line numbers only (`-n`).
+    Numbers,
+    /// Just the highlighted text (`-p`).
+    Plain,
+}
+
+/// A `-r` range such as `10:20`, `10:`, `:20` or `10`; lines count from 1.
+fn line_range(text: &str) -> Option<(usize, usize)> {
+    let number = |part: &str, default: usize| {
+        if part.is_empty() {
+            Some(default)
+        } else {
+            part.parse().ok()
+        }
+    };
+    match text.split_once(':') {
+        Some((from, to)) => Some((number(from, 1)?, number(to, usize::MAX)?)),
+        None => {
+            let line = text.parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+/// One input laid out the way `bat` prints it.
+fn layout(
+    name: &str,
+    lines: &[String],
+    style: Style,
+    range: (usize, usize),
+    width: usize,
+) -> Vec<String> {
+    let numbered = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|&(number, _)| number >= range.0 && number <= range.1);
+
+    if style == Style::Plain {
+        return numbered.map(|(_, line)| line.clone()).collect();
+    }
+
+    let digits = lines.len().to_string().len().max(NUMBER_COLUMN);
+    let (column, bar) = match style {
+        Style::Full => (digits + 3, ansi::paint("│ ", GRID)),
+        _ => (digits + 1, String::new()),
+    };
+    let text_width = width.saturating_sub(column + 2).max(10);
+    let rule = |joint: &str| {
+        ansi::paint(
+            &format!(
+                "{}{}{}",
+                "─".repeat(column),
+                joint,
+                "─".repeat(width.saturating_sub(column + 1))
+            ),
+            GRID,
+        )
+    };
+
+    let mut out = Vec::new();
+    if style == Style::Full {
+        out.push(rule("┬"));
+        let label = if name == "STDIN" {
+            name.to_string()
+        } else {
+            format!("File: {}", ansi::paint(name, "1"))
+        };
+        let empty = if lines.is_empty() { "   <EMPTY>" } else { "" };
+        out.push(format!("{}{}{}{}", " ".repeat(column), bar, label, empty));
+        out.push(rule("┼"));
+    }
+    for (number, line) in numbered {
+        for (row, part) in buffer::wrap(line, text_width).into_iter().enumerate() {
+            let gutter = if row == 0 {
+                format!("{:>width$}", number, width = digits)
+            } else {
+                String::new()
+            };
+            out.push(format!(
+                "{}{}{}",
+                ansi::paint(&format!("{:<width$}", gutter, width = column), GRID),
+                bar,
+                part
+            ));
+        }
+    }
+    if style == Style::Full {
+        out.push(rule("┴"));
+    }
+    out
+}
+
+/// `bat [-p] [-n] [-l LANG] [-r N:M] [FILE]...`: prints files with syntax
+/// highlighting; `-L` lists the languages it knows.
+pub fn bat(args: &[&str], stdin: Option<&str>) -> String {
+    let mut style = Style::Full;
+    let mut language = None;
+    let mut range = (1, usize::MAX);
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, value)) if arg.starts_with("--") => (option, Some(value)),
+            _ => (arg, None),
+        };
+        match option {
+            "-p" | "--plain" => style = Style::Plain,
+            "-n" | "--number" => style = Style::Numbers,
+            "-L" | "--list-languages" => {
+                return Language::ALL
+                    .iter()
+                    .map(|language| {
+                        format!("{}: {}", language.name(), language.aliases().join(", "))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "-l" | "--language" | "-r" | "--line-range" => {
+                let value = match inline.or_else(|| args.next().copied()) {
+                    Some(value) => value,
+                    None => return format!("bat: option '{}' requires an argument", option),
+                };
+                if matches!(option, "-l" | "--language") {
+                    match Language::from_name(value) {
+                        Some(found) => language = Some(found),
+                        None => return format!("bat: unknown language '{}'", value),
+                    }
+                } else {
+                    match line_range(value) {
+                        Some(found) => range = found,
+                        None => return format!("bat: invalid line range '{}'", value),
+                    }
+                }
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return format!(
+                    "bat: invalid option -- '{}'",
+                    option.trim_start_matches('-')
+                )
+            }
+            file => files.push(file),
+        }
+    }
+
+    let width = buffer::terminal_width();
+    let show = |name: &str, content: &str| {
+        let lines = match language.or_else(|| Language::detect(name, content)) {
+            Some(language) => highlight(content, language),
+            None => content.lines().map(str::to_string).collect(),
+        };
+        layout(name, &lines, style, range, width).join("\n")
+    };
+
+    if files.is_empty() {
+        return match stdin {
+            Some(input) => show("STDIN", input),
+            None => "bat: missing file operand".to_string(),
+        };
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let mut output = Vec::new();
+    for &operand in &files {
+        let path = normalize_path(operand, &current_path);
+        match text::read_bytes(&filesystem, &path, &session) {
+            Ok(content) if is_binary(&content) => {
+                output.push(format!("bat: {}: binary file not shown", operand))
+            }
+            Ok(content) => output.push(show(operand, &String::from_utf8_lossy(&content))),
+            Err(err) => output.push(format!("bat: {}: {}", operand, err)),
+        }
+    }
+    output.join("\n")
+}
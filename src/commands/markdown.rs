@@ -0,0 +1,733 @@
+//! Markdown for the terminal, in the style of `glow` and `mdcat`: `view`
+//! and `cat --render` style headings, emphasis, lists, code and quotes,
+//! and turn links into clickable runs.
+
+use std::collections::HashMap;
+
+use crate::commands::filesystem::{is_binary, normalize_path, CURRENT_PATH, FILESYSTEM};
+use crate::commands::text;
+use crate::commands::users;
+use crate::terminal::{ansi, buffer};
+
+/// Narrower than this and nested lists have no room left for text.
+const MIN_WIDTH: usize = 20;
+
+/// Heading colours by level; level 1 is drawn as a reversed banner.
+const HEADING: [&str; 6] = ["95", "96", "92", "93", "94", "37"];
+const CODE: &str = "38;2;255;160;100";
+const LINK: &str = "4;36";
+const DIM: &str = "90";
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+/// How a run of inline text is drawn.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    reverse: bool,
+    strike: bool,
+    color: Option<&'static str>,
+    link: Option<String>,
+}
+
+impl Style {
+    fn sgr(&self) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1");
+        }
+        if self.italic {
+            codes.push("3");
+        }
+        if self.reverse {
+            codes.push("7");
+        }
+        if self.strike {
+            codes.push("9");
+        }
+        match (self.color, &self.link) {
+            (Some(color), _) => codes.push(color),
+            (None, Some(_)) => codes.push(LINK),
+            (None, None) if self.strike => codes.push(DIM),
+            (None, None) => {}
+        }
+        codes.join(";")
+    }
+}
+
+struct Run {
+    text: String,
+    style: Style,
+}
+
+/// Writes runs out as one line of escapes and text. Styles are set
+/// afresh at each change so nested spans cannot cancel each other.
+fn to_ansi(runs: &[Run]) -> String {
+    let mut out = String::new();
+    let mut current = Style::default();
+    for run in runs {
+        if run.style.link != current.link {
+            if current.link.is_some() {
+                out.push_str(ansi::LINK_END);
+            }
+            if let Some(url) = &run.style.link {
+                out.push_str(&ansi::link(url));
+            }
+        }
+        let sgr = run.style.sgr();
+        if sgr != current.sgr() {
+            out.push_str(ansi::RESET);
+            if !sgr.is_empty() {
+                out.push_str(&format!("\x1b[{}m", sgr));
+            }
+        }
+        out.push_str(&run.text);
+        current = run.style.clone();
+    }
+    if current.link.is_some() {
+        out.push_str(ansi::LINK_END);
+    }
+    if !current.sgr().is_empty() {
+        out.push_str(ansi::RESET);
+    }
+    out
+}
+
+enum Marker {
+    Bullet,
+    Ordered(usize),
+}
+
+/// A list item's marker and how many bytes it takes, space included.
+fn list_marker(line: &str) -> Option<(Marker, usize)> {
+    let mut chars = line.chars();
+    match chars.next()? {
+        '-' | '*' | '+' if matches!(chars.next(), Some(' ') | None) => {
+            Some((Marker::Bullet, 2.min(line.len())))
+        }
+        '0'..='9' => {
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let rest = &line[digits..];
+            if digits > 9
+                || !(rest.starts_with(". ") || rest.starts_with(") ") || rest == "." || rest == ")")
+            {
+                return None;
+            }
+            let number = line[..digits].parse().ok()?;
+            Some((Marker::Ordered(number), (digits + 2).min(line.len())))
+        }
+        _ => None,
+    }
+}
+
+/// `#` to `######` and the heading's text, without any closing `#`s.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    let text = rest.trim();
+    let closed = text.trim_end_matches('#');
+    if closed.is_empty() || closed.ends_with(' ') {
+        Some((level, closed.trim_end()))
+    } else {
+        Some((level, text))
+    }
+}
+
+/// `---`, `***` or `___`, spaces allowed between.
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|&c| c == marks[0])
+}
+
+/// The opening of a fenced code block: three or more backticks or tildes.
+fn fence(line: &str) -> Option<&str> {
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    if len >= 3 {
+        Some(&line[..len])
+    } else {
+        None
+    }
+}
+
+/// Whether `line` starts a block that ends a paragraph above it.
+fn starts_block(line: &str) -> bool {
+    heading(line).is_some()
+        || fence(line).is_some()
+        || line.starts_with('>')
+        || is_rule(line)
+        || list_marker(line).is_some()
+}
+
+/// A link reference definition: `[label]: url "title"`.
+fn reference(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    let rest = line.strip_prefix('[')?;
+    let (label, rest) = rest.split_once("]:")?;
+    let url = rest.split_whitespace().next()?;
+    let url = url.trim_start_matches('<').trim_end_matches('>');
+    Some((label.to_lowercase(), url.to_string()))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The length of the run of `c` starting at `chars[at]`.
+fn run_length(chars: &[char], at: usize, c: char) -> usize {
+    chars[at..].iter().take_while(|&&d| d == c).count()
+}
+
+struct Markdown {
+    references: HashMap<String, String>,
+}
+
+impl Markdown {
+    /// The index just past `]` for the `[` at `chars[at]`, skipping
+    /// nested brackets and escapes.
+    fn closing_bracket(chars: &[char], at: usize, open: char, close: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut i = at;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => i += 1,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i + 1);
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// A link starting with the `[` at `chars[at]`: its text, its URL
+    /// and where it ends. Inline `(url)`, full `[ref]` and shortcut
+    /// references are understood.
+    fn link_at(&self, chars: &[char], at: usize) -> Option<(String, String, usize)> {
+        let end = Self::closing_bracket(chars, at, '[', ']')?;
+        let label: String = chars[at + 1..end - 1].iter().collect();
+        match chars.get(end) {
+            Some('(') => {
+                let close = Self::closing_bracket(chars, end, '(', ')')?;
+                let inside: String = chars[end + 1..close - 1].iter().collect();
+                let url = inside.split_whitespace().next().unwrap_or("");
+                let url = url.trim_start_matches('<').trim_end_matches('>');
+                Some((label, url.to_string(), close))
+            }
+            Some('[') => {
+                let close = Self::closing_bracket(chars, end, '[', ']')?;
+                let name: String = chars[end + 1..close - 1].iter().collect();
+                let name = if name.is_empty() { &label } else { &name };
+                let url = self.references.get(&name.to_lowercase())?;
+                Some((label.clone(), url.clone(), close))
+            }
+            _ => {
+                let url = self.references.get(&label.to_lowercase())?;
+                Some((label.clone(), url.clone(), end))
+            }
+        }
+    }
+
+    /// Renders inline markdown in `text` on top of `style`.
+    fn inline(&self, text: &str, style: &Style, runs: &mut Vec<Run>) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut plain = String::new();
+        let flush = |plain: &mut String, runs: &mut Vec<Run>| {
+            if !plain.is_empty() {
+                runs.push(Run {
+                    text: std::mem::take(plain),
+                    style: style.clone(),
+                });
+            }
+        };
+        let linked = |url: &str| Style {
+            link: Some(url.to_string()),
+            ..style.clone()
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\\' if chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    plain.push(chars[i + 1]);
+                    i += 2;
+                    continue;
+                }
+                '`' => {
+                    let n = run_length(&chars, i, '`');
+                    let close = (i + n..chars.len()).find(|&k| {
+                        chars[k] == '`' && chars[k - 1] != '`' && run_length(&chars, k, '`') == n
+                    });
+                    if let Some(close) = close {
+                        flush(&mut plain, runs);
+                        let code: String = chars[i + n..close].iter().collect();
+                        let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                            Some(inner) if !inner.trim().is_empty() => inner.to_string(),
+                            _ => code,
+                        };
+                        runs.push(Run {
+                            text: code,
+                            style: Style {
+                                color: Some(CODE),
+                                ..style.clone()
+                            },
+                        });
+                        i = close + n;
+                    } else {
+                        plain.extend(&chars[i..i + n]);
+                        i += n;
+                    }
+                    continue;
+                }
+                '!' if chars.get(i + 1) == Some(&'[') => {
+                    if let Some((alt, url, end)) = self.link_at(&chars, i + 1) {
+                        flush(&mut plain, runs);
+                        runs.push(Run {
+                            text: format!("[image: {}]", alt),
+                            style: linked(&url),
+                        });
+                        i = end;
+                        continue;
+                    }
+                }
+                '[' => {
+                    if let Some((label, url, end)) = self.link_at(&chars, i) {
+                        flush(&mut plain, runs);
+                        self.inline(&label, &linked(&url), runs);
+                        i = end;
+                        continue;
+                    }
+                }
+                '<' => {
+                    let close = chars[i..].iter().position(|&c| c == '>' || c == ' ');
+                    if let Some(close) = close.filter(|&k| chars[i + k] == '>') {
+                        let url: String = chars[i + 1..i + close].iter().collect();
+                        if url.starts_with("http://")
+                            || url.starts_with("https://")
+                            || url.starts_with("mailto:")
+                        {
+                            flush(&mut plain, runs);
+                            runs.push(Run {
+                                style: linked(&url),
+                                text: url,
+                            });
+                            i += close + 1;
+                            continue;
+                        }
+                    }
+                }
+                'h' if i == 0 || !chars[i - 1].is_alphanumeric() => {
+                    let rest: String = chars[i..].iter().take(8).collect();
+                    if rest.starts_with("http://") || rest.starts_with("https://") {
+                        let mut end = i;
+                        while end < chars.len()
+                            && !chars[end].is_whitespace()
+                            && !matches!(chars[end], '<' | ']')
+                        {
+                            end += 1;
+                        }
+                        while end > i
+                            && matches!(chars[end - 1], '.' | ',' | ':' | ';' | '!' | '?' | ')')
+                        {
+                            end -= 1;
+                        }
+                        let url: String = chars[i..end].iter().collect();
+                        flush(&mut plain, runs);
+                        runs.push(Run {
+                            style: linked(&url),
+                            text: url,
+                        });
+                        i = end;
+                        continue;
+                    }
+                }
+                '*' | '_' | '~' => {
+                    let n = run_length(&chars, i, c);
+                    let word_char = |k: usize| chars.get(k).is_some_and(|c| c.is_alphanumeric());
+                    let opens = chars.get(i + n).is_some_and(|c| !c.is_whitespace())
+                        && (c != '_' || i == 0 || !word_char(i - 1))
+                        && (c != '~' || n == 2);
+                    let close = (i + n + 1..chars.len()).find(|&k| {
+                        chars[k] == c
+                            && chars[k - 1] != c
+                            && !chars[k - 1].is_whitespace()
+                            && run_length(&chars, k, c) == n
+                            && (c != '_' || !word_char(k + n))
+                    });
+                    if let Some(close) = close.filter(|_| opens) {
+                        flush(&mut plain, runs);
+                        let inner: String = chars[i + n..close].iter().collect();
+                        let mut emphasis = style.clone();
+                        match (c, n) {
+                            ('~', _) => emphasis.strike = true,
+                            (_, 1) => emphasis.italic = true,
+                            (_, 2) => emphasis.bold = true,
+                            _ => {
+                                emphasis.bold = true;
+                                emphasis.italic = true;
+                            }
+                        }
+                        self.inline(&inner, &emphasis, runs);
+                        i = close + n;
+                    } else {
+                        plain.extend(&chars[i..i + n]);
+                        i += n;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            plain.push(c);
+            i += 1;
+        }
+        flush(&mut plain, runs);
+    }
+
+    /// Inline markdown in `text`, styled and wrapped to `width`.
+    fn styled(&self, text: &str, style: &Style, width: usize) -> Vec<String> {
+        let mut runs = Vec::new();
+        self.inline(text, style, &mut runs);
+        buffer::wrap(&to_ansi(&runs), width)
+    }
+
+    fn heading(&self, level: usize, text: &str, width: usize) -> Vec<String> {
+        let style = Style {
+            bold: true,
+            reverse: level == 1,
+            color: Some(HEADING[level - 1]),
+            ..Style::default()
+        };
+        let mut runs = vec![Run {
+            text: if level == 1 {
+                " ".to_string()
+            } else {
+                format!("{} ", "#".repeat(level))
+            },
+            style: style.clone(),
+        }];
+        self.inline(text, &style, &mut runs);
+        if level == 1 {
+            runs.push(Run {
+                text: " ".to_string(),
+                style,
+            });
+        }
+        buffer::wrap(&to_ansi(&runs), width)
+    }
+
+    /// A paragraph's lines joined up, broken only where the source has a
+    /// hard break (two trailing spaces or a backslash).
+    fn paragraph(&self, lines: &[&str], width: usize) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut text = String::new();
+        for (n, line) in lines.iter().enumerate() {
+            let hard = line.ends_with("  ") || line.ends_with('\\');
+            let line = line.trim().trim_end_matches('\\');
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(line);
+            if hard && n + 1 < lines.len() {
+                out.extend(self.styled(&text, &Style::default(), width));
+                text.clear();
+            }
+        }
+        out.extend(self.styled(&text, &Style::default(), width));
+        out
+    }
+
+    fn code_block(lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    String::new()
+                } else {
+                    format!("    {}", ansi::paint(line, CODE))
+                }
+            })
+            .collect()
+    }
+
+    /// The list starting at `lines[start]`, and the index after it.
+    fn list(
+        &self,
+        lines: &[&str],
+        start: usize,
+        width: usize,
+        depth: usize,
+    ) -> (Vec<String>, usize) {
+        let base = indent_of(lines[start]);
+        let mut out = Vec::new();
+        let mut i = start;
+        let mut next_number = None;
+
+        while let Some((marker, marker_len)) = list_marker(lines[i].trim_start()) {
+            let content_indent = base + marker_len;
+            let mut item = vec![&lines[i][content_indent.min(lines[i].len())..]];
+            i += 1;
+            while i < lines.len() {
+                let line = lines[i];
+                let trimmed = line.trim_start();
+                if trimmed.is_empty() {
+                    let next = (i..lines.len()).find(|&k| !lines[k].trim().is_empty());
+                    match next {
+                        Some(k) if indent_of(lines[k]) >= content_indent => {
+                            item.push("");
+                            i += 1;
+                            continue;
+                        }
+                        _ => break,
+                    }
+                }
+                if indent_of(line) >= content_indent {
+                    item.push(line.get(content_indent..).unwrap_or(trimmed));
+                } else if starts_block(trimmed) {
+                    break;
+                } else {
+                    item.push(trimmed);
+                }
+                i += 1;
+            }
+
+            let bullet = match marker {
+                Marker::Ordered(number) => {
+                    let number = next_number.unwrap_or(number);
+                    next_number = Some(number + 1);
+                    format!("{}.", number)
+                }
+                Marker::Bullet => BULLETS[depth % BULLETS.len()].to_string(),
+            };
+            let (bullet, first) = match item[0].get(..4) {
+                Some("[ ] ") => ("☐".to_string(), &item[0][4..]),
+                Some("[x] " | "[X] ") => ("☑".to_string(), &item[0][4..]),
+                _ => (bullet, item[0]),
+            };
+            item[0] = first;
+
+            let pad = bullet.chars().count() + 1;
+            let body = self.blocks(
+                &item,
+                width.saturating_sub(pad).max(MIN_WIDTH / 2),
+                depth + 1,
+            );
+            for (n, line) in body.into_iter().enumerate() {
+                if n == 0 {
+                    out.push(format!("{} {}", ansi::paint(&bullet, "96"), line));
+                } else if line.is_empty() {
+                    out.push(line);
+                } else {
+                    out.push(format!("{}{}", " ".repeat(pad), line));
+                }
+            }
+            if item.iter().all(|line| line.trim().is_empty()) {
+                out.push(ansi::paint(&bullet, "96"));
+            }
+
+            // A blank line between items keeps the list going, but a
+            // change from bullets to numbers starts a new one.
+            let ordered = next_number.is_some();
+            match (i..lines.len()).find(|&k| !lines[k].trim().is_empty()) {
+                Some(k) if indent_of(lines[k]) == base => {
+                    match list_marker(lines[k].trim_start()) {
+                        Some((Marker::Ordered(_), _)) if ordered => i = k,
+                        Some((Marker::Bullet, _)) if !ordered => i = k,
+                        _ => break,
+                    }
+                }
+                _ => break,
+            }
+        }
+        (out, i)
+    }
+
+    /// Renders block-level markdown. `depth` counts enclosing lists.
+    fn blocks(&self, lines: &[&str], width: usize, depth: usize) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() {
+                i += 1;
+                continue;
+            }
+            // Blocks at the top level are always set apart; inside a
+            // list item only where the source leaves a blank line.
+            if !out.is_empty() && (depth == 0 || lines[i - 1].trim().is_empty()) {
+                out.push(String::new());
+            }
+
+            if let Some(marker) = fence(trimmed).filter(|_| indent_of(line) < 4) {
+                i += 1;
+                let start = i;
+                while i < lines.len() && !lines[i].trim_start().starts_with(marker) {
+                    i += 1;
+                }
+                out.extend(Self::code_block(&lines[start..i]));
+                i += 1;
+                continue;
+            }
+            if indent_of(line) >= 4 {
+                let start = i;
+                while i < lines.len() && (lines[i].trim().is_empty() || indent_of(lines[i]) >= 4) {
+                    i += 1;
+                }
+                let mut end = i;
+                while end > start && lines[end - 1].trim().is_empty() {
+                    end -= 1;
+                }
+                let code: Vec<&str> = lines[start..end]
+                    .iter()
+                    .map(|line| line.get(4..).unwrap_or(""))
+                    .collect();
+                out.extend(Self::code_block(&code));
+                continue;
+            }
+            if let Some((level, text)) = heading(trimmed) {
+                out.extend(self.heading(level, text, width));
+                i += 1;
+                continue;
+            }
+            if is_rule(trimmed) {
+                out.push(ansi::paint(&"─".repeat(width), DIM));
+                i += 1;
+                continue;
+            }
+            if trimmed.starts_with('>') {
+                let mut quoted = Vec::new();
+                while i < lines.len() {
+                    let inner = match lines[i].trim_start().strip_prefix('>') {
+                        Some(inner) => inner,
+                        None => break,
+                    };
+                    quoted.push(inner.strip_prefix(' ').unwrap_or(inner));
+                    i += 1;
+                }
+                let bar = ansi::paint("│ ", DIM);
+                for line in self.blocks(&quoted, width.saturating_sub(2).max(MIN_WIDTH / 2), depth)
+                {
+                    out.push(format!("{}{}", bar, line));
+                }
+                continue;
+            }
+            if list_marker(trimmed).is_some() {
+                let (list, next) = self.list(lines, i, width, depth);
+                out.extend(list);
+                i = next;
+                continue;
+            }
+
+            let start = i;
+            let mut setext = None;
+            while i < lines.len() {
+                let trimmed = lines[i].trim_start();
+                if trimmed.is_empty() || (i > start && starts_block(trimmed)) {
+                    break;
+                }
+                i += 1;
+                if let Some(next) = lines.get(i).map(|line| line.trim()) {
+                    if !next.is_empty() && next.chars().all(|c| c == '=') {
+                        setext = Some(1);
+                    } else if !next.is_empty() && next.chars().all(|c| c == '-') {
+                        setext = Some(2);
+                    }
+                    if setext.is_some() {
+                        break;
+                    }
+                }
+            }
+            match setext {
+                Some(level) => {
+                    let text: Vec<&str> = lines[start..i].iter().map(|line| line.trim()).collect();
+                    out.extend(self.heading(level, &text.join(" "), width));
+                    i += 1;
+                }
+                None => out.extend(self.paragraph(&lines[start..i], width)),
+            }
+        }
+        out
+    }
+}
+
+/// Renders markdown `source` for a terminal `width` columns wide.
+pub fn render(source: &str, width: usize) -> String {
+    let source = source.replace('\t', "    ");
+    let mut references = HashMap::new();
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in source.lines() {
+        if fence(line.trim_start()).is_some() {
+            in_fence = !in_fence;
+        }
+        match reference(line).filter(|_| !in_fence) {
+            Some((label, url)) => {
+                references.insert(label, url);
+            }
+            None => lines.push(line),
+        }
+    }
+    Markdown { references }
+        .blocks(&lines, width.max(MIN_WIDTH), 0)
+        .join("\n")
+}
+
+/// Whether `name` looks like a markdown file.
+pub fn is_markdown(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.ends_with(".md") || lower.ends_with(".markdown")
+}
+
+/// `view [FILE]...`: shows each file the way `glow` would. Markdown is
+/// rendered; other text is shown as it is.
+pub fn view(args: &[&str], stdin: Option<&str>) -> String {
+    let width = buffer::terminal_width();
+    if let Some(option) = args
+        .iter()
+        .find(|arg| arg.starts_with('-') && arg.len() > 1)
+    {
+        return format!(
+            "view: invalid option -- '{}'",
+            option.trim_start_matches('-')
+        );
+    }
+    if args.is_empty() {
+        return match stdin {
+            Some(input) => render(input, width),
+            None => "view: missing file operand".to_string(),
+        };
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let mut output = Vec::new();
+    for &operand in args {
+        let path = normalize_path(operand, &current_path);
+        match text::read_bytes(&filesystem, &path, &session) {
+            Ok(content) if is_binary(&content) => {
+                output.push(format!("view: {}: binary file not shown", operand))
+            }
+            Ok(content) => {
+                let content = String::from_utf8_lossy(&content);
+                if is_markdown(operand) {
+                    output.push(render(&content, width));
+                } else {
+                    output.push(content.trim_end().to_string());
+                }
+            }
+            Err(err) => output.push(format!("view: {}: {}", operand, err)),
+        }
+    }
+    output.join("\n\n")
+}
@@ -1,3 +1,5 @@
+use crate::terminal::ansi;
+
 pub fn help(_args: &[&str]) -> String {
     r#"Available commands:
 
@@ -8,15 +10,64 @@ System Info:
   date        - Current date and time
 
 File System:
-  ls, ll      - List directory contents
+  ls, ll      - List directory contents (-l, -a, -t, -S, -r, -R, -F, ...)
   cd          - Change directory
   pwd         - Print working directory
-  cat         - Display file contents
+  cat         - Display file contents (--render for markdown)
+  view        - Show files, rendering markdown (headings, lists, code, links)
+  bat         - Show files with syntax highlighting and line numbers (-p, -n, -l LANG, -r N:M, -L)
   tree        - Display directory tree
   mkdir       - Create directory
   touch       - Create empty file
-  rm          - Remove files/directories
-  ln          - Create symbolic links
+  rm          - Remove files/directories (--trash to keep them)
+  ln          - Create hard or symbolic (-s) links
+  chmod       - Change file permissions
+  chown       - Change file owner (root)
+  chgrp       - Change file group (root)
+  readlink    - Print a symlink's target
+  realpath    - Print the resolved absolute path
+  find        - Search for files (-name, -type, -exec ...)
+  du          - Show disk usage (-s, -h, -a, -c, -d N)
+  df          - Show filesystem capacity (-h)
+  file        - Guess a file's type
+  stat        - Show file metadata (-L, -c FORMAT)
+  mount       - List mounts, or mount a tmpfs/overlay (root)
+  umount      - Detach a mount (root)
+  undo        - Revert the last command that changed files (-l lists)
+  trash       - List, restore or empty trashed files (rm --trash)
+  tar         - Create, list or extract archives (-c, -t, -x, -z, -f)
+  gzip        - Compress files (-d, -k, -c, -l; gunzip, zcat)
+  upload      - Copy a file from your computer into this directory
+  download    - Save a file (or a directory as .tar) to your computer
+
+Users:
+  su          - Switch user (default root)
+  logout      - Leave a su session
+  whoami      - Print current user
+  id          - Print user and group ids
+  groups      - Print group memberships
+  users       - List logged-in users
+
+Text Processing (files or piped input, e.g. `cat f | grep x`):
+  grep        - Search for a pattern (-E, -i, -v, -n, -c, -r)
+  head, tail  - First or last lines (-n N, tail -n +N)
+  wc          - Count lines, words and bytes (-l, -w, -c)
+  sort        - Sort lines (-r, -n, -u, -k N, -t SEP)
+  uniq        - Collapse repeated lines (-c, -d, -u)
+  cut         - Select fields or characters (-d, -f, -c)
+  tr          - Translate or delete characters (-d, -s)
+  rev         - Reverse each line
+  nl          - Number lines
+  sed         - Stream editor (s///, addresses, -n, -i in place)
+  awk         - Pattern scanning (fields, BEGIN/END, printf)
+  diff        - Compare files or directories (-u, -r, -q)
+  patch       - Apply a unified diff (-p N, -R, --dry-run)
+  base64      - Encode or decode base64 (-d, -w COLS)
+  xxd         - Hex dump (-p, -c COLS, -s SEEK, -l LEN); also hexdump -C
+  sha256sum   - SHA-256 checksums (-c to verify); also md5sum
+  less        - Page through text (space/b, j/k, g/G, /search, n/N, -N, q)
+  nano        - Edit a file (^O write out, ^X exit, ^F search, ^K/^U cut/paste)
+  vi          - Modal editor (i insert, v visual, d/c/y + motion, u undo, :wq, :s///); also vim
 
 Utilities:
   clear       - Clear screen
@@ -24,9 +75,9 @@ Utilities:
   echo        - Display text
   cowsay      - ASCII cow with message
   sl          - Steam locomotive
-  lolcat      - Rainbow text
+  lolcat      - Rainbow text (arguments or piped input)
   calc        - Calculator
-  sudo        - Sudo access
+  sudo        - Run a command as root
 
 
 Type `ls`, then `cd projects` and `ls` again.  
@@ -34,26 +85,6 @@ Run a project with `./project-name`.""#
         .to_string()
 }
 
-pub fn sudo(args: &[&str]) -> String {
-    if args.is_empty() {
-        return "sudo: command required".into();
-    }
-
-    let valid_commands = vec![
-        "clear", "history", "echo", "date", "uptime", "neofetch", "ls", "cd", "cat", "pwd", "tree",
-        "mkdir", "touch", "rm", "uname", "ln", "ll", "help", "sudo", "cowsay", "sl", "lolcat",
-        "calc",
-    ];
-
-    let command = args[0];
-
-    if valid_commands.contains(&command) {
-        "sudo: access denied.".into()
-    } else {
-        format!("zsh: command not found: {}", command)
-    }
-}
-
 pub fn cowsay(args: &[&str]) -> String {
     let message = if args.is_empty() {
         "Hello from WASM!"
@@ -76,34 +107,44 @@ pub fn cowsay(args: &[&str]) -> String {
     )
 }
 
-pub fn sl(_args: &[&str]) -> String {
-    r#"                 (@@) (  ) (@)  ( )  @@    ()    @     O     @     O      @
-            (   )
-        (@@@@)
-     (    )
-
-   (@@@)
-====        ________                ___________
-_D _|  |_______/        \__I_I_____===__|_________|
- |(_)---  |   H\________/ |   |        =|___ ___|      _________________
- /     |  |   H  |  |     |   |         ||_| |_||     _|                \_____A
-|      |  |   H  |__--------------------| [___] |   =|                        |
-| ________|___H__/__|_____/[][]~\_______|       |   -|                        |
-|/ |   |-----------I_____I [][] []  D   |=======|____|________________________|_
-__/ =| o |=-~O=====O=====O=====O\ ____Y___________|__|__________________________|_
- |/-=|___|=    ||    ||    ||    |_____/~\___/          |_D__D__D_|  |_D__D__D_|
-  \_/      \__/  \__/  \__/  \__/      \_/               \_/   \_/    \_/   \_/
-
-You have new mail."#
-        .to_string()
+/// `lolcat [TEXT]`: the text, or what is piped in, in a rainbow that
+/// shifts along each line and a little further on each line down.
+pub fn lolcat(args: &[&str], stdin: Option<&str>) -> String {
+    let text = match stdin {
+        Some(input) if args.is_empty() => input.to_string(),
+        _ if args.is_empty() => return "Usage: lolcat <text>".to_string(),
+        _ => args.join(" "),
+    };
+    text.lines()
+        .enumerate()
+        .map(|(row, line)| {
+            line.chars()
+                .enumerate()
+                .map(|(column, c)| {
+                    if c.is_whitespace() {
+                        c.to_string()
+                    } else {
+                        ansi::paint(&c.to_string(), &rainbow(column + row * 3))
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-pub fn lolcat(args: &[&str]) -> String {
-    if args.is_empty() {
-        "Usage: lolcat <text>".to_string()
-    } else {
-        format!("🌈 {} 🌈", args.join(" "))
-    }
+/// The SGR colour of the `step`th character of a rainbow: three sine
+/// waves a third of a turn apart, as `lolcat` does.
+fn rainbow(step: usize) -> String {
+    let angle = step as f64 * 0.15;
+    let channel = |phase: f64| ((angle + phase).sin() * 127.0 + 128.0) as u8;
+    let third = 2.0 * std::f64::consts::PI / 3.0;
+    format!(
+        "38;2;{};{};{}",
+        channel(0.0),
+        channel(third),
+        channel(2.0 * third)
+    )
 }
 
 pub fn calc(args: &[&str]) -> String {
@@ -1,7 +1,26 @@
+pub mod archive;
+pub mod awk;
+#[allow(clippy::module_inception)]
 pub mod commands;
+pub mod diff;
+pub mod disk;
+pub mod encoding;
 pub mod filesystem;
+pub mod find;
+pub mod highlight;
+pub mod markdown;
 pub mod misc;
+pub mod mounts;
+pub mod permissions;
 pub mod processor;
+pub mod procfs;
+pub mod regex;
+pub mod sed;
+pub mod snapshot;
 pub mod system;
+pub mod text;
+pub mod transfer;
+pub mod trash;
+pub mod users;
 
 pub use processor::CommandHandler;
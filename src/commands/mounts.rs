@@ -0,0 +1,866 @@
+//! The mount table: which backend each part of the tree lives on, and
+//! with it which parts may be written. The built-in image is read-only,
+//! `/tmp` is a tmpfs that starts empty whenever it is mounted, and home
+//! directories are overlays whose changes are kept in the browser's
+//! `localStorage` across visits.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::commands::disk;
+use crate::commands::encoding;
+use crate::commands::filesystem::{
+    get_node, normalize_path, Children, Filesystem, Inode, InodeId, InodeTable, Node, CURRENT_PATH,
+    FILESYSTEM,
+};
+use crate::commands::permissions;
+use crate::commands::users;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// The system image compiled into the binary.
+    Image,
+    Tmpfs,
+    /// Writable, persisted on top of the image's copy of the directory.
+    Overlay,
+    Proc,
+    Devtmpfs,
+}
+
+impl Backend {
+    pub fn fstype(self) -> &'static str {
+        match self {
+            Backend::Image => "wasmfs",
+            Backend::Tmpfs => "tmpfs",
+            Backend::Overlay => "overlay",
+            Backend::Proc => "proc",
+            Backend::Devtmpfs => "devtmpfs",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mount {
+    pub source: String,
+    pub target: Vec<String>,
+    pub backend: Backend,
+    pub read_only: bool,
+    /// Capacity in bytes, as `df` reports it.
+    pub size: u64,
+    /// The directory this mount hides, put back by `umount`. For an
+    /// overlay that is the lower layer: the directory as first mounted.
+    pub covered: Node,
+}
+
+impl Mount {
+    pub fn mount_point(&self) -> String {
+        format!("/{}", self.target.join("/"))
+    }
+
+    fn options(&self) -> String {
+        let mut options = vec![if self.read_only { "ro" } else { "rw" }.to_string()];
+        if self.backend == Backend::Tmpfs {
+            options.push(format!("size={}k", self.size / 1024));
+        }
+        options.join(",")
+    }
+}
+
+const TMPFS_SIZE: u64 = 32 * 1024 * 1024;
+const DEVTMPFS_SIZE: u64 = 4 * 1024 * 1024;
+/// Roughly what browsers allow a site in `localStorage`.
+const OVERLAY_SIZE: u64 = 5 * 1024 * 1024;
+
+const STORAGE_PREFIX: &str = "overlay:";
+
+/// The filesystem as it was when the overlays were last saved, which
+/// `sync` compares against to find the ones that changed.
+static SYNCED: Mutex<Option<Filesystem>> = Mutex::new(None);
+const FORMAT_HEADER: &str = "overlay-v1";
+
+/// A directory with `node`'s metadata and nothing in it.
+fn empty_like(node: &Node, inodes: &InodeTable) -> Node {
+    Node::Directory {
+        children: Children::new(),
+        permissions: node.get_permissions(inodes),
+        owner: node.get_owner(inodes).to_string(),
+        group: node.get_group(inodes).to_string(),
+    }
+}
+
+/// Deep copy of `node` with fresh inodes, so the copy and the original can
+/// change independently. Hard links within the tree stay linked.
+fn copy_tree(node: &Node, inodes: &mut InodeTable) -> Node {
+    fn copy(node: &Node, inodes: &mut InodeTable, copies: &mut HashMap<InodeId, InodeId>) -> Node {
+        match node {
+            Node::File { inode } => match copies.get(inode) {
+                Some(&copy) => inodes.link(copy),
+                None => {
+                    let copy = inodes.preload(inodes.get(*inode).clone());
+                    if let Node::File { inode: id } = copy {
+                        copies.insert(*inode, id);
+                    }
+                    copy
+                }
+            },
+            Node::Directory {
+                children,
+                permissions,
+                owner,
+                group,
+            } => Node::Directory {
+                children: children
+                    .iter()
+                    .map(|(name, child)| (name.clone(), copy(child, inodes, copies)))
+                    .collect(),
+                permissions: *permissions,
+                owner: owner.clone(),
+                group: group.clone(),
+            },
+            Node::Symlink { .. } => node.clone(),
+        }
+    }
+    copy(node, inodes, &mut HashMap::new())
+}
+
+/// The mounts in place at boot, over the freshly built `root`.
+pub fn boot(root: &Node, inodes: &mut InodeTable) -> Vec<Mount> {
+    let at = |path: &[&str]| -> Option<&Node> {
+        let mut node = root;
+        for part in path {
+            match node {
+                Node::Directory { children, .. } => node = children.get(part)?,
+                _ => return None,
+            }
+        }
+        Some(node)
+    };
+
+    let mut mounts = vec![Mount {
+        source: "rootfs".to_string(),
+        target: Vec::new(),
+        backend: Backend::Image,
+        read_only: true,
+        size: disk::CAPACITY,
+        covered: empty_like(root, inodes),
+    }];
+    for (name, backend, read_only, size) in [
+        ("proc", Backend::Proc, true, 0),
+        ("dev", Backend::Devtmpfs, false, DEVTMPFS_SIZE),
+        ("tmp", Backend::Tmpfs, false, TMPFS_SIZE),
+    ] {
+        if let Some(node) = at(&[name]) {
+            mounts.push(Mount {
+                source: backend.fstype().to_string(),
+                target: vec![name.to_string()],
+                backend,
+                read_only,
+                size,
+                covered: empty_like(node, inodes),
+            });
+        }
+    }
+
+    let mut homes: Vec<Vec<String>> = match at(&["home"]) {
+        Some(Node::Directory { children, .. }) => children
+            .keys()
+            .map(|user| vec!["home".to_string(), user.clone()])
+            .collect(),
+        _ => Vec::new(),
+    };
+    homes.sort();
+    homes.push(vec!["root".to_string()]);
+    for target in homes {
+        let parts: Vec<&str> = target.iter().map(String::as_str).collect();
+        if let Some(node) = at(&parts) {
+            mounts.push(Mount {
+                source: "overlay".to_string(),
+                target,
+                backend: Backend::Overlay,
+                read_only: false,
+                size: OVERLAY_SIZE,
+                covered: copy_tree(node, inodes),
+            });
+        }
+    }
+    mounts
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Flattens an overlay into lines of tab-separated, escaped fields, parents
+/// before children:
+///
+/// ```text
+/// d  mode owner group path
+/// f  key mode owner group path content
+/// b  key mode owner group path base64   (a file that is not UTF-8)
+/// h  key path                           (another name for file `key`)
+/// l  owner path target
+/// ```
+fn serialize(fs: &Filesystem, node: &Node) -> String {
+    fn walk(
+        fs: &Filesystem,
+        node: &Node,
+        path: &str,
+        keys: &mut HashMap<InodeId, usize>,
+        lines: &mut Vec<String>,
+    ) {
+        let inodes = &fs.inodes;
+        let fields: Vec<String> = match node {
+            Node::Directory { .. } => vec![
+                "d".to_string(),
+                format!("{:o}", node.get_permissions(inodes)),
+                node.get_owner(inodes).to_string(),
+                node.get_group(inodes).to_string(),
+                path.to_string(),
+            ],
+            Node::File { inode } => match keys.get(inode) {
+                Some(key) => vec!["h".to_string(), key.to_string(), path.to_string()],
+                None => {
+                    let key = keys.len();
+                    keys.insert(*inode, key);
+                    let content = &inodes.get(*inode).content;
+                    let (kind, content) = match std::str::from_utf8(content) {
+                        Ok(text) => ("f", text.to_string()),
+                        Err(_) => ("b", encoding::encode_base64(content)),
+                    };
+                    vec![
+                        kind.to_string(),
+                        key.to_string(),
+                        format!("{:o}", node.get_permissions(inodes)),
+                        node.get_owner(inodes).to_string(),
+                        node.get_group(inodes).to_string(),
+                        path.to_string(),
+                        content,
+                    ]
+                }
+            },
+            Node::Symlink { target, owner } => vec![
+                "l".to_string(),
+                owner.clone(),
+                path.to_string(),
+                target.clone(),
+            ],
+        };
+        lines.push(
+            fields
+                .iter()
+                .map(|field| escape(field))
+                .collect::<Vec<_>>()
+                .join("\t"),
+        );
+
+        if let Node::Directory { children, .. } = node {
+            for (name, child) in children {
+                let child_path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", path, name)
+                };
+                walk(fs, child, &child_path, keys, lines);
+            }
+        }
+    }
+
+    let mut lines = vec![FORMAT_HEADER.to_string()];
+    walk(fs, node, "", &mut HashMap::new(), &mut lines);
+    lines.join("\n")
+}
+
+/// Rebuilds a tree written by `serialize`, or `None` if `text` is not one.
+fn deserialize(text: &str, inodes: &mut InodeTable) -> Option<Node> {
+    fn parse_line(
+        line: &str,
+        root: &mut Option<Node>,
+        keys: &mut HashMap<String, InodeId>,
+        inodes: &mut InodeTable,
+    ) -> Option<()> {
+        let fields: Vec<String> = line.split('\t').map(unescape).collect();
+        let (path, node) = match fields.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["d", mode, owner, group, path] => (
+                path,
+                Node::Directory {
+                    children: Children::new(),
+                    permissions: u16::from_str_radix(mode, 8).ok()?,
+                    owner: owner.to_string(),
+                    group: group.to_string(),
+                },
+            ),
+            [kind @ ("f" | "b"), key, mode, owner, group, path, content] => {
+                let content = match kind {
+                    "b" => encoding::decode_base64(content)?,
+                    _ => content.as_bytes().to_vec(),
+                };
+                let node = inodes.preload(Inode {
+                    content,
+                    permissions: u16::from_str_radix(mode, 8).ok()?,
+                    owner: owner.to_string(),
+                    group: group.to_string(),
+                });
+                if let Node::File { inode } = node {
+                    keys.insert(key.to_string(), inode);
+                }
+                (path, node)
+            }
+            ["h", key, path] => (path, inodes.link(*keys.get(key)?)),
+            ["l", owner, path, target] => (
+                path,
+                Node::Symlink {
+                    target: target.to_string(),
+                    owner: owner.to_string(),
+                },
+            ),
+            _ => return None,
+        };
+
+        if path.is_empty() {
+            return match (root.is_none(), &node) {
+                (true, Node::Directory { .. }) => {
+                    *root = Some(node);
+                    Some(())
+                }
+                _ => {
+                    inodes.release(&node);
+                    None
+                }
+            };
+        }
+
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let mut current = root.as_mut()?;
+        for part in parent.split('/').filter(|part| !part.is_empty()) {
+            match current {
+                Node::Directory { children, .. } => current = children.get_mut(part)?,
+                _ => return None,
+            }
+        }
+        match current {
+            Node::Directory { children, .. } if !children.contains_key(name) => {
+                children.insert(name.to_string(), node);
+                Some(())
+            }
+            _ => {
+                inodes.release(&node);
+                None
+            }
+        }
+    }
+
+    let mut lines = text.lines();
+    if lines.next() != Some(FORMAT_HEADER) {
+        return None;
+    }
+    let mut root = None;
+    let mut keys = HashMap::new();
+    for line in lines {
+        if parse_line(line, &mut root, &mut keys, inodes).is_none() {
+            if let Some(partial) = &root {
+                inodes.release(partial);
+            }
+            return None;
+        }
+    }
+    root
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// Puts `node` at the mount point `target`, returning what was there.
+fn replace(fs: &mut Filesystem, target: &[String], node: Node) -> Option<Node> {
+    let (slot, _) = fs.split_mut(target)?;
+    Some(std::mem::replace(slot, node))
+}
+
+/// Loads every overlay's saved contents over the built-in ones. Runs once
+/// at startup.
+pub fn restore() {
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return,
+    };
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let overlays: Vec<(Vec<String>, String)> = filesystem
+        .mounts
+        .iter()
+        .filter(|mount| mount.backend == Backend::Overlay)
+        .map(|mount| (mount.target.clone(), mount.mount_point()))
+        .collect();
+
+    for (target, mount_point) in overlays {
+        let key = format!("{}{}", STORAGE_PREFIX, mount_point);
+        let saved = match storage.get_item(&key) {
+            Ok(Some(saved)) => saved,
+            _ => continue,
+        };
+        if let Some(node) = deserialize(&saved, &mut filesystem.inodes) {
+            if let Some(old) = replace(&mut filesystem, &target, node) {
+                filesystem.inodes.release(&old);
+            }
+        }
+    }
+    *SYNCED.lock().unwrap() = Some(filesystem.clone());
+}
+
+/// Whether the overlay at `target` differs from how it was in `synced`:
+/// its tree of entries, or the content of any file in it.
+fn overlay_changed(fs: &Filesystem, synced: &Filesystem, target: &[String]) -> bool {
+    fn same_files(fs: &Filesystem, synced: &Filesystem, node: &Node) -> bool {
+        match node {
+            Node::File { inode } => fs.inodes.unchanged_inode(&synced.inodes, *inode),
+            Node::Directory { children, .. } => {
+                children.values().all(|child| same_files(fs, synced, child))
+            }
+            Node::Symlink { .. } => true,
+        }
+    }
+
+    match (get_node(fs, target), get_node(synced, target)) {
+        (Some(node), Some(old)) => node != old || !same_files(fs, synced, node),
+        _ => true,
+    }
+}
+
+/// Writes every overlay that changed since the last save back to storage.
+/// Runs after each command; fails with a message for the terminal when
+/// storage refuses the write, typically because its quota is used up.
+pub fn sync() -> Result<(), String> {
+    let storage = match storage() {
+        Some(storage) => storage,
+        None => return Ok(()),
+    };
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let mut synced = SYNCED.lock().unwrap();
+    let mut failed = Vec::new();
+    for mount in &filesystem.mounts {
+        if mount.backend != Backend::Overlay {
+            continue;
+        }
+        if let Some(synced) = synced.as_ref() {
+            if !overlay_changed(&filesystem, synced, &mount.target) {
+                continue;
+            }
+        }
+        let node = match get_node(&filesystem, &mount.target) {
+            Some(node) => node,
+            None => continue,
+        };
+        let key = format!("{}{}", STORAGE_PREFIX, mount.mount_point());
+        if storage
+            .set_item(&key, &serialize(&filesystem, node))
+            .is_err()
+        {
+            failed.push(format!(
+                "sync: {}: changes not saved: browser storage is full",
+                mount.mount_point()
+            ));
+        }
+    }
+
+    // A failed overlay stays changed, so the next sync tries it again.
+    if !failed.is_empty() {
+        return Err(failed.join("\n"));
+    }
+    *synced = Some(filesystem.clone());
+    Ok(())
+}
+
+/// `size=` values: bytes, or with a `k`, `m` or `g` suffix.
+fn parse_size(value: &str) -> Option<u64> {
+    let (digits, unit) = match value.char_indices().last()? {
+        (index, c) if c.is_ascii_alphabetic() => (&value[..index], c.to_ascii_lowercase()),
+        _ => (value, 'b'),
+    };
+    let scale = match unit {
+        'b' => 1,
+        'k' => 1024,
+        'm' => 1024 * 1024,
+        'g' => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok().map(|n| n * scale)
+}
+
+fn list(fs: &Filesystem, fstype: Option<&str>) -> String {
+    fs.mounts
+        .iter()
+        .filter(|mount| fstype.is_none_or(|fstype| mount.backend.fstype() == fstype))
+        .map(|mount| {
+            format!(
+                "{} on {} type {} ({})",
+                mount.source,
+                mount.mount_point(),
+                mount.backend.fstype(),
+                mount.options()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `mount`, `mount -t TYPE`, `mount -t tmpfs|overlay [-o OPTS] SOURCE DIR`
+/// and `mount -o remount,ro|rw DIR`.
+pub fn mount(args: &[&str]) -> String {
+    let mut fstype = None;
+    let mut options: Vec<&str> = Vec::new();
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        match arg {
+            "-t" | "-o" => {
+                let value = match rest.split_first() {
+                    Some((&value, tail)) => {
+                        rest = tail;
+                        value
+                    }
+                    None => {
+                        return format!("mount: option requires an argument -- '{}'", &arg[1..])
+                    }
+                };
+                if arg == "-t" {
+                    fstype = Some(value);
+                } else {
+                    options.extend(value.split(',').filter(|option| !option.is_empty()));
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return format!("mount: invalid option -- '{}'", &arg[1..])
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    if operands.is_empty() && options.is_empty() {
+        return list(&filesystem, fstype);
+    }
+    if !users::current_session().is_root() {
+        return "mount: only root can do that".to_string();
+    }
+
+    let mut read_only = None;
+    let mut remount = false;
+    let mut size = None;
+    for option in &options {
+        match *option {
+            "ro" => read_only = Some(true),
+            "rw" => read_only = Some(false),
+            "remount" => remount = true,
+            _ => match option.strip_prefix("size=").map(parse_size) {
+                Some(Some(bytes)) => size = Some(bytes),
+                _ => return format!("mount: unsupported option '{}'", option),
+            },
+        }
+    }
+
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let (source, dir) = match (remount, &operands[..]) {
+        (true, [dir]) | (true, [_, dir]) => (None, *dir),
+        (false, [source, dir]) => (Some(*source), *dir),
+        (false, [dir]) => return format!("mount: {}: can't find in /etc/fstab.", dir),
+        _ => return "mount: bad usage".to_string(),
+    };
+    let target =
+        match permissions::resolve(&filesystem, &normalize_path(dir, &current_path), &session) {
+            Ok((canonical, Node::Directory { .. })) => canonical,
+            Ok(_) => return format!("mount: {}: mount point is not a directory.", dir),
+            Err(_) => return format!("mount: {}: mount point does not exist.", dir),
+        };
+
+    if remount {
+        return match filesystem
+            .mounts
+            .iter_mut()
+            .find(|mount| mount.target == target)
+        {
+            Some(mount) => {
+                if let Some(read_only) = read_only {
+                    mount.read_only = read_only;
+                }
+                if let Some(size) = size {
+                    mount.size = size;
+                }
+                String::new()
+            }
+            None => format!("mount: {}: mount point not mounted or bad option.", dir),
+        };
+    }
+
+    let backend = match fstype {
+        Some("tmpfs") => Backend::Tmpfs,
+        Some("overlay") => Backend::Overlay,
+        Some(other) => return format!("mount: {}: unknown filesystem type '{}'.", dir, other),
+        None => return format!("mount: {}: no filesystem type specified.", dir),
+    };
+
+    let visible = match get_node(&filesystem, &target) {
+        Some(node) => node.clone(),
+        None => return format!("mount: {}: mount point does not exist.", dir),
+    };
+    let covered = match backend {
+        // A tmpfs hides the directory and starts out empty and world-writable.
+        Backend::Tmpfs => {
+            let fresh = Node::Directory {
+                children: Children::new(),
+                permissions: 0o1777,
+                owner: "root".to_string(),
+                group: "root".to_string(),
+            };
+            match replace(&mut filesystem, &target, fresh) {
+                Some(covered) => covered,
+                None => return format!("mount: {}: mount point does not exist.", dir),
+            }
+        }
+        // An overlay keeps showing the directory; its lower layer is a copy.
+        _ => copy_tree(&visible, &mut filesystem.inodes),
+    };
+
+    filesystem.mounts.push(Mount {
+        source: source.unwrap_or(backend.fstype()).to_string(),
+        target,
+        backend,
+        read_only: read_only.unwrap_or(false),
+        size: size.unwrap_or(match backend {
+            Backend::Tmpfs => TMPFS_SIZE,
+            _ => OVERLAY_SIZE,
+        }),
+        covered,
+    });
+    String::new()
+}
+
+/// `umount DIR`: detaches the mount at `DIR`, dropping its contents and
+/// showing what it covered again.
+pub fn umount(args: &[&str]) -> String {
+    let dirs: Vec<&str> = args
+        .iter()
+        .copied()
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    if dirs.is_empty() {
+        return "umount: bad usage".to_string();
+    }
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let mut output = Vec::new();
+
+    for dir in dirs {
+        if !session.is_root() {
+            output.push(format!("umount: {}: must be superuser to unmount.", dir));
+            continue;
+        }
+        let path = normalize_path(dir, &current_path);
+        let target = match permissions::resolve(&filesystem, &path, &session) {
+            Ok((canonical, _)) => canonical,
+            Err(err) => {
+                output.push(format!("umount: {}: {}", dir, err));
+                continue;
+            }
+        };
+        let index = match filesystem
+            .mounts
+            .iter()
+            .position(|mount| mount.target == target)
+        {
+            Some(index) => index,
+            None => {
+                output.push(format!("umount: {}: not mounted.", dir));
+                continue;
+            }
+        };
+
+        let nested = filesystem
+            .mounts
+            .iter()
+            .any(|mount| mount.target.len() > target.len() && mount.target.starts_with(&target));
+        if target.is_empty() || nested || current_path.starts_with(&target) {
+            output.push(format!("umount: {}: target is busy.", dir));
+            continue;
+        }
+
+        let mount = filesystem.mounts.remove(index);
+        if let Some(detached) = replace(&mut filesystem, &target, mount.covered) {
+            filesystem.inodes.release(&detached);
+        }
+    }
+
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(children: Vec<(&str, Node)>) -> Node {
+        Node::Directory {
+            children: children
+                .into_iter()
+                .map(|(name, node)| (name.to_string(), node))
+                .collect(),
+            permissions: 0o750,
+            owner: "objz".to_string(),
+            group: "objz".to_string(),
+        }
+    }
+
+    fn file(inodes: &mut InodeTable, content: &[u8], permissions: u16) -> Node {
+        inodes.create(Inode {
+            content: content.to_vec(),
+            permissions,
+            owner: "objz".to_string(),
+            group: "sudo".to_string(),
+        })
+    }
+
+    /// An overlay with every kind of line and fields that need escaping.
+    fn sample() -> Filesystem {
+        let mut inodes = InodeTable::default();
+        let notes = file(&mut inodes, b"tabs\there\nback\\slash\n", 0o600);
+        let shared = match notes {
+            Node::File { inode } => inodes.link(inode),
+            _ => unreachable!(),
+        };
+        let binary = file(&mut inodes, &[0, 159, 146, 150, 255], 0o755);
+        let empty = file(&mut inodes, b"", 0o644);
+        let root = directory(vec![
+            (
+                "docs\tand\nmore",
+                directory(vec![("notes.txt", notes), ("empty", empty)]),
+            ),
+            ("hard link", shared),
+            ("bin.dat", binary),
+            (
+                "link",
+                Node::Symlink {
+                    target: "../elsewhere\\x".to_string(),
+                    owner: "root".to_string(),
+                },
+            ),
+        ]);
+        Filesystem {
+            root,
+            inodes,
+            mounts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn overlays_round_trip() {
+        let mut fs = sample();
+        let text = serialize(&fs, &fs.root);
+        assert!(text.starts_with("overlay-v1\nd\t750\tobjz\tobjz\t\n"));
+        assert!(text.contains("\nb\t"));
+        assert!(text.contains("docs\\tand\\nmore/notes.txt\ttabs\\there\\nback\\\\slash\\n"));
+
+        let restored = deserialize(&text, &mut fs.inodes).unwrap();
+        assert_eq!(serialize(&fs, &restored), text);
+
+        // The hard link still names the same inode as the original.
+        let (notes, shared) = match &restored {
+            Node::Directory { children, .. } => {
+                let docs = match children.get("docs\tand\nmore") {
+                    Some(Node::Directory { children, .. }) => children,
+                    _ => panic!("docs is missing"),
+                };
+                (docs.get("notes.txt"), children.get("hard link"))
+            }
+            _ => panic!("the root is not a directory"),
+        };
+        match (notes, shared) {
+            (Some(Node::File { inode: a }), Some(Node::File { inode: b })) => {
+                assert_eq!(a, b);
+                assert_eq!(fs.inodes.link_count(*a), 2);
+            }
+            _ => panic!("the hard link was not restored"),
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_overlays() {
+        let mut fs = sample();
+        let text = serialize(&fs, &fs.root);
+        let damaged = [
+            String::new(),
+            text.replacen("overlay-v1", "overlay-v0", 1),
+            format!("{}\nh\t99\tdangling", text),
+            format!("{}\nx\tunknown", text),
+            format!("{}\nd\t755\tobjz\tobjz\tbin.dat", text),
+            format!("{}\nd\t755\tobjz\tobjz\tmissing/child", text),
+            text.replacen("AJ+Slv8=", "AJ+Slv8*", 1),
+        ];
+        for input in damaged.iter() {
+            assert!(deserialize(input, &mut fs.inodes).is_none(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn only_changed_overlays_are_saved() {
+        let _lock = crate::commands::filesystem::shared_for_test();
+        let synced = FILESYSTEM.lock().unwrap().clone();
+        let home = normalize_path("/home/objz", &[]);
+        let root = normalize_path("/root", &[]);
+        assert!(!overlay_changed(&synced.clone(), &synced, &home));
+
+        // Rewriting a file leaves the tree alone but not the inode.
+        let mut fs = synced.clone();
+        let inode = match get_node(&fs, &home) {
+            Some(Node::Directory { children, .. }) => children
+                .values()
+                .find_map(|child| match child {
+                    Node::File { inode } => Some(*inode),
+                    _ => None,
+                })
+                .unwrap(),
+            _ => unreachable!(),
+        };
+        fs.inodes.write(inode, b"changed".to_vec());
+        assert!(overlay_changed(&fs, &synced, &home));
+        assert!(!overlay_changed(&fs, &synced, &root));
+
+        let mut fs = synced.clone();
+        let mut path = home.clone();
+        path.push("new".to_string());
+        let session = users::Session::for_test("objz", &["objz"]);
+        crate::commands::text::write_file(&mut fs, &path, b"new".to_vec(), &session).unwrap();
+        assert!(overlay_changed(&fs, &synced, &home));
+        assert!(!overlay_changed(&fs, &synced, &root));
+    }
+
+    #[test]
+    fn escaping_round_trips() {
+        for field in ["", "plain", "a\tb", "line\nbreak", "back\\slash\\", "\\t"].iter() {
+            assert_eq!(unescape(&escape(field)), *field);
+            assert!(!escape(field).contains(['\t', '\n']));
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::commands::filesystem::{get_node, resolve_path, Filesystem, FsError, Node};
+use crate::commands::users::Session;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl Access {
+    fn bit(self) -> u16 {
+        match self {
+            Access::Read => 0o4,
+            Access::Write => 0o2,
+            Access::Execute => 0o1,
+        }
+    }
+}
+
+const STICKY: u16 = 0o1000;
+
+/// Classic owner/group/other check. Root bypasses read and write, and
+/// may execute anything with at least one execute bit (directories always).
+pub fn check(fs: &Filesystem, node: &Node, session: &Session, access: Access) -> bool {
+    let mode = node.get_permissions(&fs.inodes);
+
+    if session.is_root() {
+        return access != Access::Execute
+            || matches!(node, Node::Directory { .. })
+            || mode & 0o111 != 0;
+    }
+
+    let class = if node.get_owner(&fs.inodes) == session.user {
+        mode >> 6
+    } else if session.in_group(node.get_group(&fs.inodes)) {
+        mode >> 3
+    } else {
+        mode
+    };
+
+    class & access.bit() != 0
+}
+
+/// Looks up `path` like `lstat`: intermediate symlinks are followed, a
+/// final one is returned as-is. Every directory passed through needs
+/// search (execute) permission.
+pub fn traverse<'a>(
+    fs: &'a Filesystem,
+    path: &[String],
+    session: &Session,
+) -> Result<&'a Node, FsError> {
+    resolve_path(fs, path, false, &|dir| {
+        check(fs, dir, session, Access::Execute)
+    })
+    .map(|(_, node)| node)
+}
+
+/// Looks up `path` like `stat`, following symlinks all the way, and
+/// returns the canonical path alongside the node.
+pub fn resolve<'a>(
+    fs: &'a Filesystem,
+    path: &[String],
+    session: &Session,
+) -> Result<(Vec<String>, &'a Node), FsError> {
+    resolve_path(fs, path, true, &|dir| {
+        check(fs, dir, session, Access::Execute)
+    })
+}
+
+/// Checks that a new entry may be created inside `parent_path` and
+/// returns the directory's canonical path. A read-only mount refuses
+/// everyone, root included.
+pub fn can_create(
+    fs: &Filesystem,
+    parent_path: &[String],
+    session: &Session,
+) -> Result<Vec<String>, FsError> {
+    match resolve(fs, parent_path, session)? {
+        (canonical, parent @ Node::Directory { .. }) => {
+            fs.writable(&canonical)?;
+            if check(fs, parent, session, Access::Write)
+                && check(fs, parent, session, Access::Execute)
+            {
+                Ok(canonical)
+            } else {
+                Err(FsError::PermissionDenied)
+            }
+        }
+        _ => Err(FsError::NotADirectory),
+    }
+}
+
+/// Checks that the entry at `path` may be unlinked from its parent,
+/// honouring the sticky bit (as on `/tmp`) and refusing mount points and
+/// anything containing one. Returns the entry's canonical
+/// path; a symlink itself is what gets removed, never its target.
+pub fn can_remove(
+    fs: &Filesystem,
+    path: &[String],
+    session: &Session,
+) -> Result<Vec<String>, FsError> {
+    let (name, parent_path) = match path.split_last() {
+        Some(split) => split,
+        None => return Err(FsError::PermissionDenied),
+    };
+
+    let mut canonical = can_create(fs, parent_path, session)?;
+
+    let parent = get_node(fs, &canonical).ok_or(FsError::NotFound)?;
+    let child = match parent {
+        Node::Directory { children, .. } => children.get(name).ok_or(FsError::NotFound)?,
+        _ => return Err(FsError::NotADirectory),
+    };
+
+    if parent.get_permissions(&fs.inodes) & STICKY != 0
+        && !session.is_root()
+        && child.get_owner(&fs.inodes) != session.user
+        && parent.get_owner(&fs.inodes) != session.user
+    {
+        return Err(FsError::PermissionDenied);
+    }
+
+    canonical.push(name.clone());
+    if fs.has_mount_under(&canonical) {
+        return Err(FsError::Busy);
+    }
+    Ok(canonical)
+}
+
+/// Recursive removal needs to empty every directory below `node` first,
+/// which takes read, write and search permission on each of them.
+pub fn can_remove_tree(fs: &Filesystem, node: &Node, session: &Session) -> bool {
+    match node {
+        Node::Directory { children, .. } => {
+            let sticky = node.get_permissions(&fs.inodes) & STICKY != 0
+                && !session.is_root()
+                && node.get_owner(&fs.inodes) != session.user;
+
+            children.is_empty()
+                || (check(fs, node, session, Access::Read)
+                    && check(fs, node, session, Access::Write)
+                    && check(fs, node, session, Access::Execute)
+                    && children.values().all(|child| {
+                        !(sticky && child.get_owner(&fs.inodes) != session.user)
+                            && can_remove_tree(fs, child, session)
+                    }))
+        }
+        _ => true,
+    }
+}
+
+/// Applies a `chmod` mode to `current`: either octal (`755`, `1777`) or a
+/// comma-separated list of symbolic clauses (`u+x`, `go-w`, `a=rX`).
+pub fn parse_mode(spec: &str, current: u16, is_dir: bool) -> Option<u16> {
+    if !spec.is_empty() && spec.len() <= 4 && spec.chars().all(|c| ('0'..='7').contains(&c)) {
+        return u16::from_str_radix(spec, 8).ok();
+    }
+
+    let mut mode = current;
+    for clause in spec.split(',') {
+        let op_start = clause.find(['+', '-', '='])?;
+        let (who, mut rest) = clause.split_at(op_start);
+
+        let mut who_mask = 0;
+        for c in who.chars() {
+            who_mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return None,
+            };
+        }
+        if who_mask == 0 {
+            who_mask = 0o7777;
+        }
+
+        while let Some(op) = rest.chars().next() {
+            if !matches!(op, '+' | '-' | '=') {
+                return None;
+            }
+            rest = &rest[1..];
+            let perm_end = rest.find(['+', '-', '=']).unwrap_or(rest.len());
+            let (perms, next) = rest.split_at(perm_end);
+            rest = next;
+
+            let mut bits = 0;
+            for c in perms.chars() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if is_dir || mode & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    _ => return None,
+                };
+            }
+            bits &= who_mask;
+
+            mode = match op {
+                '+' => mode | bits,
+                '-' => mode & !bits,
+                _ => (mode & !who_mask) | bits,
+            };
+        }
+    }
+
+    Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::filesystem::{normalize_path, Inode, InodeTable};
+    use crate::commands::mounts::{Backend, Mount};
+
+    fn directory(permissions: u16, owner: &str, children: Vec<(&str, Node)>) -> Node {
+        Node::Directory {
+            children: children
+                .into_iter()
+                .map(|(name, node)| (name.to_string(), node))
+                .collect(),
+            permissions,
+            owner: owner.to_string(),
+            group: owner.to_string(),
+        }
+    }
+
+    fn file(inodes: &mut InodeTable, permissions: u16, owner: &str, group: &str) -> Node {
+        inodes.create(Inode {
+            content: Vec::new(),
+            permissions,
+            owner: owner.to_string(),
+            group: group.to_string(),
+        })
+    }
+
+    /// A writable tree with a private home, a sticky `/tmp`, a directory
+    /// only root may search, and a read-only mount.
+    fn sample() -> Filesystem {
+        let mut inodes = InodeTable::default();
+        let notes = file(&mut inodes, 0o640, "ada", "staff");
+        let script = file(&mut inodes, 0o744, "ada", "ada");
+        let plain = file(&mut inodes, 0o644, "root", "root");
+        let ada_tmp = file(&mut inodes, 0o666, "ada", "ada");
+        let bob_tmp = file(&mut inodes, 0o666, "bob", "bob");
+        let deep = file(&mut inodes, 0o644, "root", "root");
+        let image = file(&mut inodes, 0o644, "root", "root");
+        let root = directory(
+            0o755,
+            "root",
+            vec![
+                (
+                    "home",
+                    directory(
+                        0o755,
+                        "root",
+                        vec![(
+                            "ada",
+                            directory(0o755, "ada", vec![("notes", notes), ("script", script)]),
+                        )],
+                    ),
+                ),
+                (
+                    "tmp",
+                    directory(0o1777, "root", vec![("ada", ada_tmp), ("bob", bob_tmp)]),
+                ),
+                (
+                    "secret",
+                    directory(
+                        0o711,
+                        "root",
+                        vec![("inner", directory(0o700, "root", vec![("file", deep)]))],
+                    ),
+                ),
+                ("plain", plain),
+                ("ro", directory(0o777, "root", vec![("image", image)])),
+            ],
+        );
+        let mount = |target: &str, read_only| Mount {
+            source: "tmpfs".to_string(),
+            target: path(target),
+            backend: Backend::Tmpfs,
+            read_only,
+            size: 0,
+            covered: directory(0o755, "root", Vec::new()),
+        };
+        Filesystem {
+            root,
+            inodes,
+            mounts: vec![mount("/", false), mount("/ro", true)],
+        }
+    }
+
+    fn path(path: &str) -> Vec<String> {
+        normalize_path(path, &[])
+    }
+
+    fn ada() -> Session {
+        Session::for_test("ada", &["ada"])
+    }
+
+    fn bob() -> Session {
+        Session::for_test("bob", &["bob", "staff"])
+    }
+
+    fn carol() -> Session {
+        Session::for_test("carol", &["carol"])
+    }
+
+    fn root() -> Session {
+        Session::for_test("root", &["root"])
+    }
+
+    fn allowed(fs: &Filesystem, at: &str, session: &Session, access: Access) -> bool {
+        let node = traverse(fs, &path(at), &root()).unwrap();
+        check(fs, node, session, access)
+    }
+
+    #[test]
+    fn picks_the_owner_group_or_other_bits() {
+        let fs = sample();
+        assert!(allowed(&fs, "/home/ada/notes", &ada(), Access::Write));
+        assert!(allowed(&fs, "/home/ada/notes", &bob(), Access::Read));
+        assert!(!allowed(&fs, "/home/ada/notes", &bob(), Access::Write));
+        assert!(!allowed(&fs, "/home/ada/notes", &carol(), Access::Read));
+
+        assert!(allowed(&fs, "/home/ada/script", &ada(), Access::Execute));
+        assert!(!allowed(&fs, "/home/ada/script", &bob(), Access::Execute));
+    }
+
+    #[test]
+    fn root_bypasses_all_but_execute() {
+        let fs = sample();
+        assert!(allowed(&fs, "/home/ada/notes", &root(), Access::Read));
+        assert!(allowed(&fs, "/home/ada/notes", &root(), Access::Write));
+        assert!(allowed(&fs, "/home/ada/script", &root(), Access::Execute));
+        assert!(!allowed(&fs, "/plain", &root(), Access::Execute));
+        assert!(allowed(&fs, "/secret/inner", &root(), Access::Execute));
+
+        assert!(can_create(&fs, &path("/home/ada"), &root()).is_ok());
+        assert_eq!(
+            can_create(&fs, &path("/ro"), &root()),
+            Err(FsError::ReadOnly)
+        );
+    }
+
+    #[test]
+    fn every_directory_on_the_way_needs_search_permission() {
+        let fs = sample();
+        // `/secret` may be searched by anyone, `/secret/inner` only by root.
+        assert!(traverse(&fs, &path("/secret/inner"), &carol()).is_ok());
+        assert_eq!(
+            traverse(&fs, &path("/secret/inner/file"), &carol()).err(),
+            Some(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            resolve(&fs, &path("/secret/inner/file"), &carol()).err(),
+            Some(FsError::PermissionDenied)
+        );
+        assert!(traverse(&fs, &path("/secret/inner/file"), &root()).is_ok());
+        assert_eq!(
+            can_create(&fs, &path("/secret/inner"), &carol()),
+            Err(FsError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn sticky_tmp_only_lets_owners_remove() {
+        let fs = sample();
+        assert_eq!(
+            can_remove(&fs, &path("/tmp/ada"), &ada()),
+            Ok(path("/tmp/ada"))
+        );
+        assert_eq!(
+            can_remove(&fs, &path("/tmp/bob"), &ada()),
+            Err(FsError::PermissionDenied)
+        );
+        assert!(can_remove(&fs, &path("/tmp/bob"), &root()).is_ok());
+
+        let tmp = traverse(&fs, &path("/tmp"), &root()).unwrap();
+        assert!(!can_remove_tree(&fs, tmp, &ada()));
+        assert!(can_remove_tree(&fs, tmp, &root()));
+        assert_eq!(can_remove(&fs, &path("/ro"), &root()), Err(FsError::Busy));
+    }
+
+    #[test]
+    fn parses_octal_and_symbolic_modes() {
+        assert_eq!(parse_mode("755", 0o600, false), Some(0o755));
+        assert_eq!(parse_mode("1777", 0o755, true), Some(0o1777));
+        assert_eq!(parse_mode("8", 0o600, false), None);
+        assert_eq!(parse_mode("u+x", 0o644, false), Some(0o744));
+        assert_eq!(parse_mode("go-w,o-r", 0o666, false), Some(0o640));
+        assert_eq!(parse_mode("a=rX", 0o700, false), Some(0o555));
+        assert_eq!(parse_mode("a=rX", 0o600, false), Some(0o444));
+        assert_eq!(parse_mode("a=rX", 0o600, true), Some(0o555));
+        assert_eq!(parse_mode("+t", 0o777, true), Some(0o1777));
+        assert_eq!(parse_mode("u+s", 0o755, false), Some(0o4755));
+        assert_eq!(parse_mode("u=rw,g=r,o=", 0o777, false), Some(0o640));
+        assert_eq!(parse_mode("q+x", 0o644, false), None);
+        assert_eq!(parse_mode("u+z", 0o644, false), None);
+    }
+}
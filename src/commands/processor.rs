@@ -2,21 +2,60 @@
 
 use std::{future::Future, pin::Pin};
 
-use crate::{ascii, commands::system, terminal::renderer::TerminalRenderer};
+use crate::{
+    ascii,
+    commands::system,
+    programs::{less, nano, vi, Program},
+    terminal::{ansi, buffer, renderer::TerminalRenderer},
+};
 
-use super::{commands, misc};
+use super::{
+    archive, awk, commands, diff, disk, encoding, find, highlight, markdown, misc, mounts, sed,
+    snapshot, text, transfer, trash, users,
+};
+
+/// Boxed Fn so we can capture owned data in an async block.
+pub type AnimationFn =
+    Box<dyn Fn(TerminalRenderer) -> Pin<Box<dyn Future<Output = ()> + 'static>> + 'static>;
 
 /// A command’s result can either be immediate text,
-/// or an animated async routine (no Send bound on the future).
+/// or an animated async routine (no Send bound on the future),
+/// or a request to read a password before continuing,
+/// or a full-screen program that takes over the terminal,
+/// or the kernel panic that follows wiping the root filesystem.
+/// `Bytes` is raw output, such as a binary file, for the next command in
+/// a pipeline; it is shown as text if it reaches the terminal.
 pub enum CommandResult {
     Output(String),
+    Bytes(Vec<u8>),
+    Animated(AnimationFn),
+    Prompt(PendingPrompt),
+    Program(Box<dyn Program>),
+    Panic,
+}
+
+/// A masked prompt the input handler shows; whatever the user types is
+/// passed back through `CommandHandler::resume`.
+pub struct PendingPrompt {
+    pub prompt: String,
+    pub notice: Option<String>,
+    pub action: PendingAction,
+}
 
-    /// Boxed Fn so we can capture owned data in an async block.
-    Animated(
-        Box<dyn Fn(TerminalRenderer) -> Pin<Box<dyn Future<Output = ()> + 'static>> + 'static>,
-    ),
+pub enum PendingAction {
+    Sudo {
+        target: String,
+        command: String,
+        attempts: u8,
+    },
+    Su {
+        user: users::User,
+        login: bool,
+    },
 }
 
+const SUDO_MAX_ATTEMPTS: u8 = 3;
+
 #[derive(Clone)]
 pub struct CommandHandler {
     history: Vec<String>,
@@ -43,18 +82,151 @@ impl CommandHandler {
         }
 
         self.history.push(trimmed.to_string());
+        let before = snapshot::take();
+        let mut result = self.execute(trimmed);
+        // Undoing is not itself undoable; a second `undo` goes further back.
+        if trimmed.split_whitespace().next() != Some("undo") {
+            snapshot::record(before, trimmed);
+        }
+        sync_unless_panicked(&mut result.0);
+        result
+    }
+
+    /// Continue a command that was waiting on a password.
+    pub fn resume(&mut self, pending: PendingPrompt, password: &str) -> (CommandResult, bool) {
+        let command = match &pending.action {
+            PendingAction::Sudo { command, .. } => format!("sudo {}", command),
+            PendingAction::Su { user, .. } => format!("su {}", user.name),
+        };
+        let before = snapshot::take();
+        let mut result = self.continue_prompt(pending, password);
+        snapshot::record(before, &command);
+        sync_unless_panicked(&mut result.0);
+        result
+    }
+
+    fn continue_prompt(&mut self, pending: PendingPrompt, password: &str) -> (CommandResult, bool) {
+        match pending.action {
+            PendingAction::Sudo {
+                target,
+                command,
+                attempts,
+            } => {
+                let user = users::current_user();
+                if users::verify_password(&user, password) {
+                    users::touch_sudo(&user);
+                    self.execute_as(&target, &command)
+                } else if attempts + 1 >= SUDO_MAX_ATTEMPTS {
+                    (
+                        CommandResult::Output(format!(
+                            "sudo: {} incorrect password attempts",
+                            SUDO_MAX_ATTEMPTS
+                        )),
+                        false,
+                    )
+                } else {
+                    (
+                        CommandResult::Prompt(PendingPrompt {
+                            prompt: pending.prompt,
+                            notice: Some("Sorry, try again.".into()),
+                            action: PendingAction::Sudo {
+                                target,
+                                command,
+                                attempts: attempts + 1,
+                            },
+                        }),
+                        false,
+                    )
+                }
+            }
+            PendingAction::Su { user, login } => {
+                if users::verify_password(&user.name, password) {
+                    (
+                        CommandResult::Output(users::switch_user(&user, login)),
+                        true,
+                    )
+                } else {
+                    (
+                        CommandResult::Output("su: Authentication failure".into()),
+                        false,
+                    )
+                }
+            }
+        }
+    }
+
+    fn execute_as(&mut self, target: &str, command: &str) -> (CommandResult, bool) {
+        match users::run_as(target, || self.execute(command)) {
+            Ok(result) => result,
+            Err(err) => (CommandResult::Output(format!("sudo: {}", err)), false),
+        }
+    }
+
+    /// Runs a line, which may be a pipeline: each stage's output becomes
+    /// the next one's stdin, as bytes.
+    fn execute(&mut self, trimmed: &str) -> (CommandResult, bool) {
+        let stages = match split_line(trimmed) {
+            Ok(stages) => stages,
+            Err(err) => return (CommandResult::Output(format!("zsh: {}", err)), false),
+        };
+
+        let mut stdin: Option<Vec<u8>> = None;
+        let mut directory_changed = false;
+        for (index, stage) in stages.iter().enumerate() {
+            let rest = &stages[index + 1..];
+            let (result, changed) = self.dispatch(stage, stdin.as_deref(), !rest.is_empty());
+            directory_changed |= changed;
+
+            match result {
+                // Colours are for the terminal, as with tools that check
+                // whether they write to a tty. The text ends in the newline
+                // a terminal would have printed after it.
+                CommandResult::Output(text) if !rest.is_empty() => {
+                    let mut bytes = ansi::strip(&text).into_bytes();
+                    if !bytes.is_empty() {
+                        bytes.push(b'\n');
+                    }
+                    stdin = Some(bytes);
+                }
+                CommandResult::Bytes(bytes) if !rest.is_empty() => stdin = Some(bytes),
+                // A sudo waiting for its password takes the rest of the
+                // pipeline with it, so it runs once the password is in.
+                CommandResult::Prompt(mut pending) => {
+                    if let PendingAction::Sudo { command, .. } = &mut pending.action {
+                        for stage in rest {
+                            command.push_str(" | ");
+                            command.push_str(&join_args(stage));
+                        }
+                    }
+                    return (CommandResult::Prompt(pending), directory_changed);
+                }
+                result => return (result, directory_changed),
+            }
+        }
 
-        // Split into &str parts...
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        let cmd = parts[0];
+        (CommandResult::Output(String::new()), directory_changed)
+    }
+
+    /// Runs one stage. `input` is what the previous stage wrote, and
+    /// `piped` says whether this one writes into another.
+    fn dispatch(
+        &mut self,
+        parts: &[String],
+        input: Option<&[u8]>,
+        piped: bool,
+    ) -> (CommandResult, bool) {
+        let cmd = parts[0].as_str();
+        // Most tools read their stdin as text.
+        let stdin_text = input.map(text::decode);
+        let stdin = stdin_text.as_deref();
 
-        // ...then own them as Strings so we can later move them into async.
-        let args_owned: Vec<String> = parts.iter().skip(1).map(|s| s.to_string()).collect();
+        // Own the arguments so we can later move them into async.
+        let args_owned: Vec<String> = parts[1..].to_vec();
 
         // A helper slice for all the immediate (non-animated) commands:
         let args: Vec<&str> = args_owned.iter().map(String::as_str).collect();
 
-        let directory_changed = cmd == "cd";
+        let directory_changed = matches!(cmd, "cd" | "su" | "logout" | "exit");
 
         let result = match cmd {
             // --- System built-ins
@@ -65,24 +237,126 @@ impl CommandHandler {
             "uptime" => CommandResult::Output(system::uptime(&args)),
             "neofetch" => CommandResult::Output(system::neofetch(&args)),
 
+            // --- Users and sessions
+            "su" => match users::parse_su(&args) {
+                Ok((user, login)) if users::current_session().is_root() => {
+                    CommandResult::Output(users::switch_user(&user, login))
+                }
+                Ok((user, login)) => CommandResult::Prompt(PendingPrompt {
+                    prompt: "Password: ".into(),
+                    notice: None,
+                    action: PendingAction::Su { user, login },
+                }),
+                Err(err) => CommandResult::Output(err),
+            },
+            "sudo" => return self.sudo(&args),
+            "logout" | "exit" => CommandResult::Output(users::logout(&args)),
+            "whoami" => CommandResult::Output(users::whoami(&args)),
+            "id" => CommandResult::Output(users::id(&args)),
+            "groups" => CommandResult::Output(users::groups(&args)),
+            "users" => CommandResult::Output(users::users(&args)),
+
             // --- File-system commands
             "ls" => CommandResult::Output(commands::ls(&args)),
             "cd" => CommandResult::Output(commands::cd(&args)),
-            "cat" => CommandResult::Output(commands::cat(&args)),
+            "cat" => match stdin {
+                Some(text) if args == ["--render"] => {
+                    CommandResult::Output(markdown::render(text, buffer::terminal_width()))
+                }
+                Some(_) if args.is_empty() => CommandResult::Bytes(commands::cat_raw(&args, input)),
+                _ if piped
+                    && !args.is_empty()
+                    && args.iter().all(|arg| !arg.starts_with('-') || *arg == "-") =>
+                {
+                    CommandResult::Bytes(commands::cat_raw(&args, input))
+                }
+                _ => CommandResult::Output(commands::cat(&args)),
+            },
+            "view" => CommandResult::Output(markdown::view(&args, stdin)),
+            "bat" | "batcat" => CommandResult::Output(highlight::bat(&args, stdin)),
             "pwd" => CommandResult::Output(commands::pwd(&args)),
             "tree" => CommandResult::Output(commands::tree(&args)),
             "mkdir" => CommandResult::Output(commands::mkdir(&args)),
             "touch" => CommandResult::Output(commands::touch(&args)),
-            "rm" => CommandResult::Output(commands::rm(&args)),
+            "rm" => match commands::rm(&args) {
+                (_, true) => CommandResult::Panic,
+                (output, false) => CommandResult::Output(output),
+            },
             "uname" => CommandResult::Output(commands::uname(&args)),
             "ln" => CommandResult::Output(commands::ln(&args)),
             "ll" => CommandResult::Output(commands::ls(&["-la"])),
+            "chmod" => CommandResult::Output(commands::chmod(&args)),
+            "chown" => CommandResult::Output(commands::chown(&args)),
+            "chgrp" => CommandResult::Output(commands::chgrp(&args)),
+            "readlink" => CommandResult::Output(commands::readlink(&args)),
+            "realpath" => CommandResult::Output(commands::realpath(&args)),
+            "find" => CommandResult::Output(find::find(&args, &mut |line| self.run_nested(line))),
+            "du" => CommandResult::Output(disk::du(&args)),
+            "df" => CommandResult::Output(disk::df(&args)),
+            "file" => CommandResult::Output(disk::file(&args)),
+            "stat" => CommandResult::Output(disk::stat(&args)),
+            "mount" => CommandResult::Output(mounts::mount(&args)),
+            "umount" => CommandResult::Output(mounts::umount(&args)),
+            "undo" => CommandResult::Output(snapshot::undo(&args)),
+            "trash" => CommandResult::Output(trash::trash(&args)),
+            "tar" => CommandResult::Output(archive::tar(&args)),
+            "gzip" => CommandResult::Bytes(archive::gzip(&args, input, !piped)),
+            "gunzip" => CommandResult::Bytes(archive::gunzip(&args, input, !piped)),
+            "zcat" => CommandResult::Bytes(archive::zcat(&args, input, !piped)),
+            "download" => CommandResult::Output(transfer::download(&args)),
+            // The picker resolves whenever the visitor is done with it, so
+            // the result is printed from the future.
+            "upload" => {
+                let args_clone = args_owned.clone();
+                CommandResult::Animated(Box::new(move |_renderer: TerminalRenderer| {
+                    let args_for_future = args_clone.clone();
+                    Box::pin(async move {
+                        let output = transfer::upload(&args_for_future).await;
+                        buffer::add_output_lines(&output, None);
+                        if let Err(err) = mounts::sync() {
+                            buffer::add_output_lines(&err, None);
+                        }
+                    })
+                }))
+            }
+
+            // --- Text processing (file arguments or stdin)
+            "grep" => CommandResult::Output(text::grep(&args, stdin)),
+            "head" => CommandResult::Bytes(text::head(&args, input)),
+            "tail" => CommandResult::Bytes(text::tail(&args, input)),
+            "wc" => CommandResult::Output(text::wc(&args, input)),
+            "sort" => CommandResult::Output(text::sort(&args, stdin)),
+            "uniq" => CommandResult::Output(text::uniq(&args, stdin)),
+            "cut" => CommandResult::Output(text::cut(&args, stdin)),
+            "tr" => CommandResult::Output(text::tr(&args, stdin)),
+            "rev" => CommandResult::Output(text::rev(&args, stdin)),
+            "nl" => CommandResult::Output(text::nl(&args, stdin)),
+            "sed" => CommandResult::Output(sed::sed(&args, stdin)),
+            "awk" => CommandResult::Output(awk::awk(&args, stdin)),
+            "diff" => CommandResult::Output(diff::diff(&args, input)),
+            "patch" => CommandResult::Output(diff::patch(&args, stdin)),
+            "base64" => CommandResult::Bytes(encoding::base64(&args, input)),
+            "xxd" => CommandResult::Output(encoding::xxd(&args, input)),
+            "hexdump" => CommandResult::Output(encoding::hexdump(&args, input)),
+            "sha256sum" => CommandResult::Output(encoding::sha256sum(&args, input)),
+            "md5sum" => CommandResult::Output(encoding::md5sum(&args, input)),
+            "less" | "more" => match less::open(cmd, &args, stdin) {
+                Ok(pager) => CommandResult::Program(Box::new(pager)),
+                Err(err) => CommandResult::Output(err),
+            },
+            "nano" => match nano::open(&args) {
+                Ok(editor) => CommandResult::Program(Box::new(editor)),
+                Err(err) => CommandResult::Output(err),
+            },
+            "vi" | "vim" => match vi::open(cmd, &args) {
+                Ok(editor) => CommandResult::Program(Box::new(editor)),
+                Err(err) => CommandResult::Output(err),
+            },
 
             // --- Miscellany
             "help" => CommandResult::Output(misc::help(&args)),
-            "sudo" => CommandResult::Output(misc::sudo(&args)),
             "cowsay" => CommandResult::Output(misc::cowsay(&args)),
-            "lolcat" => CommandResult::Output(misc::lolcat(&args)),
+            "lolcat" => CommandResult::Output(misc::lolcat(&args, stdin)),
             "calc" => CommandResult::Output(misc::calc(&args)),
 
             // in src/commands/processor.rs, inside your match arm for "sl":
@@ -108,6 +382,116 @@ impl CommandHandler {
         (result, directory_changed)
     }
 
+    /// `sudo [-u user] [-i|-s] [-k] [-l] command`, authorised by
+    /// `/etc/sudoers` and cached per user for a few minutes.
+    fn sudo(&mut self, args: &[&str]) -> (CommandResult, bool) {
+        let session = users::current_session();
+        let mut target = "root".to_string();
+        let mut shell = None;
+        let mut rest = args;
+
+        while let Some((&flag, tail)) = rest.split_first() {
+            match flag {
+                "-u" => match tail.split_first() {
+                    Some((user, tail)) => {
+                        target = user.to_string();
+                        rest = tail;
+                        continue;
+                    }
+                    None => {
+                        let msg = "sudo: option requires an argument -- 'u'".to_string();
+                        return (CommandResult::Output(msg), false);
+                    }
+                },
+                "-i" => shell = Some("su -"),
+                "-s" => shell = Some("su"),
+                "-k" => {
+                    users::reset_sudo(&session.user);
+                    if tail.is_empty() {
+                        return (CommandResult::Output(String::new()), false);
+                    }
+                }
+                "-l" => {
+                    let msg = match users::sudo_rule(&session) {
+                        Some(rule) => format!(
+                            "User {} may run the following commands on portfolio:\n    {}",
+                            session.user,
+                            rule.describe()
+                        ),
+                        None => format!(
+                            "User {} is not allowed to run sudo on portfolio.",
+                            session.user
+                        ),
+                    };
+                    return (CommandResult::Output(msg), false);
+                }
+                _ if flag.starts_with('-') => {
+                    let msg = format!("sudo: invalid option -- '{}'", &flag[1..]);
+                    return (CommandResult::Output(msg), false);
+                }
+                _ => break,
+            }
+            rest = tail;
+        }
+
+        let command = match shell {
+            Some(shell) => format!("{} {}", shell, target),
+            None if rest.is_empty() => {
+                return (
+                    CommandResult::Output("usage: sudo [-u user] [-iskl] command".into()),
+                    false,
+                )
+            }
+            None => join_args(rest),
+        };
+        let target = if shell.is_some() {
+            "root".to_string()
+        } else {
+            target
+        };
+
+        if session.is_root() {
+            return self.execute_as(&target, &command);
+        }
+
+        match users::sudo_rule(&session) {
+            None => (
+                CommandResult::Output(format!(
+                    "{} is not in the sudoers file.  This incident will be reported.",
+                    session.user
+                )),
+                false,
+            ),
+            Some(rule) if !rule.password => self.execute_as(&target, &command),
+            Some(_) if users::sudo_cached(&session.user) => self.execute_as(&target, &command),
+            Some(_) => (
+                CommandResult::Prompt(PendingPrompt {
+                    prompt: format!("[sudo] password for {}: ", session.user),
+                    notice: None,
+                    action: PendingAction::Sudo {
+                        target,
+                        command,
+                        attempts: 0,
+                    },
+                }),
+                false,
+            ),
+        }
+    }
+
+    /// Runs a command on behalf of another one (`find -exec`), keeping
+    /// only its text output.
+    fn run_nested(&mut self, args: &[String]) -> String {
+        if args.is_empty() {
+            return String::new();
+        }
+        match self.dispatch(args, None, false).0 {
+            CommandResult::Output(text) => text,
+            CommandResult::Bytes(bytes) => text::decode(&bytes),
+            _ => String::new(),
+        }
+    }
+
     fn print_history(&self, _args: &[&str]) -> String {
         if self.history.is_empty() {
             "No commands in history yet.".to_string()
@@ -121,3 +505,230 @@ impl CommandHandler {
         }
     }
 }
+
+/// Splits a command line into pipeline stages of words. Single quotes
+/// keep everything literally, double quotes and backslashes the way a
+/// shell would; an unquoted `|` starts the next stage.
+fn split_line(line: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut stages = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unmatched '".into()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unmatched \"".into()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unmatched \"".into()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            '|' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if words.is_empty() {
+                    return Err("parse error near `|'".into());
+                }
+                stages.push(std::mem::take(&mut words));
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err("parse error near `|'".into());
+    }
+    stages.push(words);
+    Ok(stages)
+}
+
+/// Joins words back into a line `split_line` reads the same way.
+fn join_args<S: AsRef<str>>(args: &[S]) -> String {
+    args.iter()
+        .map(|arg| {
+            let arg = arg.as_ref();
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "-_./=:,+@%^~{}*?[]".contains(c));
+            if plain {
+                arg.to_string()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Saves the overlays after a command. After a panic they are saved once
+/// the panic sequence has put the snapshot back, never in their wiped state.
+/// A failure to save is reported after the command's own output.
+fn sync_unless_panicked(result: &mut CommandResult) {
+    if matches!(result, CommandResult::Panic) {
+        return;
+    }
+    let err = match mounts::sync() {
+        Ok(()) => return,
+        Err(err) => err,
+    };
+    match result {
+        CommandResult::Output(text) => {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&err);
+        }
+        _ => buffer::add_output_lines(&err, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::filesystem;
+    use crate::commands::{archive, encoding};
+
+    const CLICK: &[u8] = include_bytes!("../../static/audio/click.wav");
+
+    fn run(line: &str) -> Vec<u8> {
+        match CommandHandler::new().execute(line).0 {
+            CommandResult::Output(text) => text.into_bytes(),
+            CommandResult::Bytes(bytes) => bytes,
+            _ => panic!("{} did not print", line),
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn binary_data_passes_through_pipes() {
+        let _lock = filesystem::shared_for_test();
+        let head = run("head -c 32 /usr/share/sounds/click.wav | xxd -p -c 32");
+        assert_eq!(String::from_utf8(head).unwrap(), hex(&CLICK[..32]));
+
+        let random = run("head -c 8 /dev/random | xxd -p");
+        assert_eq!(random.len(), 16);
+
+        let sum = run("base64 /usr/share/sounds/click.wav | base64 -d | sha256sum");
+        let expected = format!("{}  -", hex(&encoding::sha256(CLICK)));
+        assert_eq!(String::from_utf8(sum).unwrap(), expected);
+
+        let copied = run("cat /usr/share/sounds/click.wav | cat");
+        assert_eq!(copied, CLICK);
+    }
+
+    #[test]
+    fn piped_text_ends_in_a_newline() {
+        assert_eq!(
+            run("echo abc | md5sum"),
+            b"0bee89b07a248e27c83fc3d5951213c1  -"
+        );
+        assert_eq!(
+            run("echo abc | sha256sum"),
+            b"edeaaff3f1774ad2888673770c6d64097e391bc362d7d6fb34982ddf0efd18cb  -".to_vec()
+        );
+        assert_eq!(run("echo abc | base64"), b"YWJjCg==\n");
+        assert_eq!(run("echo abc | base64 | base64 -d"), b"abc\n");
+        assert_eq!(run("echo one two | tail -c 4"), b"two\n");
+        assert_eq!(run("echo abc | cat | cat"), b"abc\n");
+    }
+
+    #[test]
+    fn text_keeps_working_between_stages() {
+        let passwd = String::from_utf8(run("cat /etc/passwd")).unwrap();
+        let first = run("cat /etc/passwd | head -n 1 | tr a-z A-Z");
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            passwd.lines().next().unwrap().to_uppercase()
+        );
+        let last = run("cat /etc/passwd | tail -n 2 | head -n 1");
+        let lines: Vec<&str> = passwd.lines().collect();
+        assert_eq!(
+            String::from_utf8(last).unwrap().trim_end(),
+            lines[lines.len() - 2]
+        );
+    }
+    #[test]
+    fn sudo_lists_the_matching_sudoers_rule() {
+        let _lock = filesystem::shared_for_test();
+        assert_eq!(
+            String::from_utf8(run("sudo -l")).unwrap(),
+            "User objz may run the following commands on portfolio:\n    (ALL : ALL) ALL"
+        );
+    }
+    #[test]
+    fn gzip_writes_compressed_data_into_pipes() {
+        assert_eq!(run("echo abc | gzip | gunzip"), b"abc\n");
+        assert_eq!(run("echo abc | gzip -c | zcat"), b"abc\n");
+        assert_eq!(run("gzip -c /etc/passwd | gunzip"), run("cat /etc/passwd"));
+        assert_eq!(run("gzip -c /etc/hostname | head -c 2 | xxd -p"), b"1f8b");
+
+        let refused = String::from_utf8(run("gzip -c /etc/hostname")).unwrap();
+        assert!(refused.starts_with("gzip: compressed data not written to a terminal"));
+        assert!(archive::is_gzip(&run("gzip -cf /etc/hostname")));
+    }
+    #[test]
+    fn wc_counts_what_was_piped() {
+        assert_eq!(run("echo abc | wc -c"), b"4");
+        assert_eq!(run("echo abc | wc"), b"1 1 4");
+        let passwd = String::from_utf8(run("cat /etc/passwd")).unwrap();
+        assert_eq!(
+            String::from_utf8(run("cat /etc/passwd | wc -l")).unwrap(),
+            passwd.lines().count().to_string()
+        );
+        assert_eq!(
+            run("cat /etc/passwd | wc -l"),
+            run("wc -l /etc/passwd")
+                .split(|&b| b == b' ')
+                .next()
+                .unwrap()
+        );
+        assert_eq!(run("head -c 3 /etc/hostname | wc -c"), b"3");
+    }
+}
@@ -0,0 +1,201 @@
+//! The pseudo filesystems mounted at /proc and /dev. Their files hold no
+//! content of their own; a `Generator` produces it whenever they are read.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::commands::disk;
+use crate::commands::filesystem::{Children, Filesystem, Generator, Inode, InodeTable, Node};
+use crate::commands::system;
+
+/// The processes `/proc` lists: pid, owner and command line.
+pub const PROCESSES: &[(u32, &str, &[&str])] = &[
+    (1, "root", &["/sbin/init", "splash"]),
+    (2, "root", &[]),
+    (187, "root", &["/usr/lib/systemd/systemd-journald"]),
+    (402, "root", &["/usr/bin/NetworkManager", "--no-daemon"]),
+    (731, "objz", &["objz-term"]),
+    (732, "objz", &["-objz-shell"]),
+];
+
+/// The shell's own pid, which `/proc/self` points at.
+const SHELL_PID: u32 = 732;
+
+const CPU_CORES: usize = 2;
+
+/// How much `/dev/zero` and `/dev/random` hand out per read.
+const DEVICE_READ_SIZE: usize = 4096;
+
+fn directory(owner: &str, permissions: u16, children: Children) -> Node {
+    Node::Directory {
+        children,
+        permissions,
+        owner: owner.to_string(),
+        group: owner.to_string(),
+    }
+}
+
+fn generated<T: Into<Vec<u8>>>(
+    inodes: &mut InodeTable,
+    owner: &str,
+    permissions: u16,
+    device: bool,
+    read: impl Fn(&Filesystem) -> T + Send + Sync + 'static,
+) -> Node {
+    inodes.generate(
+        Inode {
+            content: Vec::new(),
+            permissions,
+            owner: owner.to_string(),
+            group: owner.to_string(),
+        },
+        Generator {
+            device,
+            read: Arc::new(move |fs| read(fs).into()),
+        },
+    )
+}
+
+fn uptime(_: &Filesystem) -> String {
+    let seconds = system::uptime_seconds();
+    let idle = seconds * CPU_CORES as f64 * 0.97;
+    format!("{:.2} {:.2}\n", seconds, idle)
+}
+
+fn cpuinfo(_: &Filesystem) -> String {
+    (0..CPU_CORES)
+        .map(|core| {
+            format!(
+                "processor\t: {core}\n\
+                 vendor_id\t: WasmVirtual\n\
+                 model name\t: WebAssembly Virtual CPU @ 2.40GHz\n\
+                 cpu MHz\t\t: 2400.000\n\
+                 cache size\t: 512 KB\n\
+                 core id\t\t: {core}\n\
+                 cpu cores\t: {cores}\n\
+                 flags\t\t: wasm32 simd128 bulk-memory mutable-globals\n",
+                core = core,
+                cores = CPU_CORES
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The filesystem lives in memory, so its usage doubles as memory usage.
+fn meminfo(fs: &Filesystem) -> String {
+    let total = disk::CAPACITY / 1024;
+    let used = disk::used(fs).div_ceil(1024);
+    let free = total.saturating_sub(used);
+    [
+        ("MemTotal", total),
+        ("MemFree", free),
+        ("MemAvailable", free),
+        ("Buffers", 0),
+        ("Cached", used),
+        ("SwapTotal", 0),
+        ("SwapFree", 0),
+    ]
+    .iter()
+    .map(|(name, kb)| format!("{:<16}{:>8} kB\n", format!("{}:", name), kb))
+    .collect()
+}
+
+fn version(_: &Filesystem) -> String {
+    "Linux version 6.8.9-wasm-1 (objz@portfolio) (rustc) #1 SMP PREEMPT_DYNAMIC\n".to_string()
+}
+
+/// A small xorshift generator; good enough for `/dev/random` to look
+/// random, and seeded from the boot time so each visit differs.
+fn random_bytes(len: usize) -> Vec<u8> {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+
+    let mut state = STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        state = system::boot_time().map_or(0x9e3779b97f4a7c15, |time| time.to_bits()) | 1;
+    }
+    let bytes = (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 56) as u8
+        })
+        .collect();
+    STATE.store(state, Ordering::Relaxed);
+    bytes
+}
+
+/// `/proc`: kernel information and one directory per simulated process.
+pub fn proc_dir(inodes: &mut InodeTable) -> Node {
+    let mut children = Children::from([
+        (
+            "uptime".to_string(),
+            generated(inodes, "root", 0o444, false, uptime),
+        ),
+        (
+            "cpuinfo".to_string(),
+            generated(inodes, "root", 0o444, false, cpuinfo),
+        ),
+        (
+            "meminfo".to_string(),
+            generated(inodes, "root", 0o444, false, meminfo),
+        ),
+        (
+            "version".to_string(),
+            generated(inodes, "root", 0o444, false, version),
+        ),
+        (
+            "self".to_string(),
+            Node::Symlink {
+                target: SHELL_PID.to_string(),
+                owner: "root".to_string(),
+            },
+        ),
+    ]);
+
+    for &(pid, owner, argv) in PROCESSES {
+        // The kernel separates arguments with NUL bytes and ends with one.
+        let cmdline: String = argv.iter().map(|arg| format!("{}\0", arg)).collect();
+        let entries = Children::from([(
+            "cmdline".to_string(),
+            generated(inodes, owner, 0o444, false, move |_| cmdline.clone()),
+        )]);
+        children.insert(pid.to_string(), directory(owner, 0o555, entries));
+    }
+
+    directory("root", 0o555, children)
+}
+
+/// `/dev`: the character devices every shell expects.
+pub fn dev_dir(inodes: &mut InodeTable) -> Node {
+    let children = Children::from([
+        (
+            "null".to_string(),
+            generated(inodes, "root", 0o666, true, |_| String::new()),
+        ),
+        (
+            "zero".to_string(),
+            generated(inodes, "root", 0o666, true, |_| vec![0; DEVICE_READ_SIZE]),
+        ),
+        (
+            "random".to_string(),
+            generated(inodes, "root", 0o666, true, |_| {
+                random_bytes(DEVICE_READ_SIZE)
+            }),
+        ),
+        (
+            "urandom".to_string(),
+            generated(inodes, "root", 0o666, true, |_| {
+                random_bytes(DEVICE_READ_SIZE)
+            }),
+        ),
+        // The terminal itself has no pending input to read.
+        (
+            "tty".to_string(),
+            generated(inodes, "root", 0o666, true, |_| String::new()),
+        ),
+    ]);
+
+    directory("root", 0o755, children)
+}
@@ -0,0 +1,1004 @@
+//! A small regex engine for the text tools (`grep`, `sed`, `awk`). It
+//! speaks POSIX basic and extended syntax plus the usual GNU extensions:
+//! `\w \W \s \S \b \B \< \>`, back-references and, in basic syntax,
+//! `\+ \? \|`.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    Basic,
+    Extended,
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Named(fn(char) -> bool),
+}
+
+#[derive(Clone, Debug)]
+enum Ast {
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    WordBoundary(bool),
+    WordStart,
+    WordEnd,
+    Group(Box<Ast>, usize),
+    Backref(usize),
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+    Repeat {
+        node: Box<Ast>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// The most a match may spend backtracking, in steps, before it gives up.
+/// Only patterns with back-references backtrack; the rest run in time
+/// proportional to the text.
+const STEP_LIMIT: usize = 5_000_000;
+
+/// The most instructions a compiled pattern may have, and how deeply
+/// groups and quantifiers may nest.
+const PROGRAM_LIMIT: usize = 100_000;
+const DEPTH_LIMIT: usize = 250;
+
+const TOO_BIG: &str = "Regular expression too big";
+
+/// A compiled pattern: a program for a small virtual machine, run as a
+/// Pike VM (every thread in lockstep, so no recursion and no blow-up), or
+/// by an iterative backtracker with a step limit when the pattern has
+/// back-references, which threads in lockstep can't honour.
+#[derive(Clone, Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    /// Two per group (group 0 is the whole match), then one per unbounded
+    /// loop to note where its current iteration began.
+    slots: usize,
+    groups: usize,
+    backrefs: bool,
+    ignore_case: bool,
+}
+
+/// Where a match landed, as byte offsets. `groups[n - 1]` is the span of
+/// `\n`, if that group took part in the match.
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary(bool),
+    WordStart,
+    WordEnd,
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Assert(Assertion),
+    Backref(usize),
+    /// Records the position in a slot.
+    Save(usize),
+    /// Tries the first target, then the second.
+    Split(usize, usize),
+    Jump(usize),
+    /// Fails if the position is still the one in the slot: a loop
+    /// iteration that matched nothing can't go round again.
+    Progress(usize),
+    Match,
+}
+
+type Slots = Vec<Option<usize>>;
+
+struct Compiler {
+    program: Vec<Inst>,
+    /// The first slot after the groups' ones.
+    loop_slots: usize,
+    loops: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= PROGRAM_LIMIT {
+            return Err(TOO_BIG.into());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, ast: &Ast) -> Result<(), String> {
+        match ast {
+            Ast::Char(c) => {
+                self.emit(Inst::Char(*c))?;
+            }
+            Ast::Any => {
+                self.emit(Inst::Any)?;
+            }
+            Ast::Class { items, negated } => {
+                self.emit(Inst::Class {
+                    items: items.clone(),
+                    negated: *negated,
+                })?;
+            }
+            Ast::Start => {
+                self.emit(Inst::Assert(Assertion::Start))?;
+            }
+            Ast::End => {
+                self.emit(Inst::Assert(Assertion::End))?;
+            }
+            Ast::WordBoundary(expected) => {
+                self.emit(Inst::Assert(Assertion::WordBoundary(*expected)))?;
+            }
+            Ast::WordStart => {
+                self.emit(Inst::Assert(Assertion::WordStart))?;
+            }
+            Ast::WordEnd => {
+                self.emit(Inst::Assert(Assertion::WordEnd))?;
+            }
+            Ast::Backref(index) => {
+                self.emit(Inst::Backref(*index))?;
+            }
+            Ast::Group(inner, index) => {
+                self.emit(Inst::Save(2 * index))?;
+                self.compile(inner)?;
+                self.emit(Inst::Save(2 * index + 1))?;
+            }
+            Ast::Concat(nodes) => {
+                for node in nodes {
+                    self.compile(node)?;
+                }
+            }
+            Ast::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (n, branch) in branches.iter().enumerate() {
+                    if n + 1 == branches.len() {
+                        self.compile(branch)?;
+                        break;
+                    }
+                    let split = self.emit(Inst::Split(0, 0))?;
+                    self.compile(branch)?;
+                    jumps.push(self.emit(Inst::Jump(0))?);
+                    self.program[split] = Inst::Split(split + 1, self.program.len());
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jump(end);
+                }
+            }
+            Ast::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    None => {
+                        let slot = self.loop_slots + self.loops;
+                        self.loops += 1;
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.emit(Inst::Save(slot))?;
+                        self.compile(node)?;
+                        self.emit(Inst::Progress(slot))?;
+                        self.emit(Inst::Jump(split))?;
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Threads waiting at one position, in priority order, at most one per
+/// instruction.
+struct Threads {
+    list: Vec<(usize, Slots)>,
+    seen: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Self {
+            list: Vec::new(),
+            seen: vec![0; size],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+}
+
+/// The text being searched, decoded once.
+struct Text {
+    chars: Vec<char>,
+    /// Byte offset of each character, and of the end.
+    offsets: Vec<usize>,
+}
+
+impl Text {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            offsets: text
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain(std::iter::once(text.len()))
+                .collect(),
+        }
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str, syntax: Syntax, ignore_case: bool) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            syntax,
+            groups: 0,
+        };
+        let ast = parser.alternation(0)?;
+        if parser.pos < parser.chars.len() {
+            return Err(match syntax {
+                Syntax::Basic => "Unmatched \\)".into(),
+                Syntax::Extended => "Unmatched ) or \\)".into(),
+            });
+        }
+        Regex::compile(&ast, parser.groups, ignore_case)
+    }
+
+    /// A pattern matching `text` literally, as `grep -F` wants.
+    pub fn literal(text: &str, ignore_case: bool) -> Result<Regex, String> {
+        let ast = Ast::Concat(text.chars().map(Ast::Char).collect());
+        Regex::compile(&ast, 0, ignore_case)
+    }
+
+    fn compile(ast: &Ast, groups: usize, ignore_case: bool) -> Result<Regex, String> {
+        let mut compiler = Compiler {
+            program: Vec::new(),
+            loop_slots: 2 * (groups + 1),
+            loops: 0,
+        };
+        compiler.compile(ast)?;
+        compiler.emit(Inst::Match)?;
+        let backrefs = compiler
+            .program
+            .iter()
+            .any(|inst| matches!(inst, Inst::Backref(_)));
+        Ok(Regex {
+            slots: compiler.loop_slots + compiler.loops,
+            groups,
+            program: compiler.program,
+            backrefs,
+            ignore_case,
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> Result<bool, String> {
+        Ok(self.search(&Text::new(text), 0)?.is_some())
+    }
+
+    /// The leftmost match starting at or after byte offset `from`.
+    pub fn find_at(&self, text: &str, from: usize) -> Result<Option<Match>, String> {
+        let text = Text::new(text);
+        match text.offsets.iter().position(|&offset| offset >= from) {
+            Some(first) => self.search(&text, first),
+            None => Ok(None),
+        }
+    }
+
+    /// Every non-overlapping match, left to right. As in GNU tools, an
+    /// empty match right where the previous match ended does not count.
+    pub fn find_iter(&self, text: &str) -> Result<Vec<Match>, String> {
+        let decoded = Text::new(text);
+        let mut matches: Vec<Match> = Vec::new();
+        let mut from = 0;
+        while from <= decoded.chars.len() {
+            let found = match self.search(&decoded, from)? {
+                Some(found) => found,
+                None => break,
+            };
+            let end = decoded
+                .offsets
+                .partition_point(|&offset| offset < found.end);
+            // Step over one character after an empty match.
+            from = if found.end > found.start {
+                end
+            } else {
+                end + 1
+            };
+            let adjacent = matches.last().is_some_and(|last| last.end == found.start);
+            if found.end > found.start || !adjacent {
+                matches.push(found);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// The leftmost match starting at character `first` or later.
+    fn search(&self, text: &Text, first: usize) -> Result<Option<Match>, String> {
+        let slots = if self.backrefs {
+            let mut steps = 0;
+            let mut found = None;
+            for start in first..=text.chars.len() {
+                found = self.backtrack(&text.chars, start, &mut steps)?;
+                if found.is_some() {
+                    break;
+                }
+            }
+            found
+        } else {
+            self.pike(&text.chars, first)
+        };
+
+        Ok(slots.map(|slots| {
+            let span = |group: usize| match (slots[2 * group], slots[2 * group + 1]) {
+                (Some(start), Some(end)) => Some((text.offsets[start], text.offsets[end])),
+                _ => None,
+            };
+            let (start, end) = span(0).unwrap_or((0, 0));
+            Match {
+                start,
+                end,
+                groups: (1..=self.groups).map(span).collect(),
+            }
+        }))
+    }
+
+    /// Runs every thread in lockstep over the text, leftmost match first
+    /// and, among those, the one a backtracker would have found first.
+    fn pike(&self, chars: &[char], first: usize) -> Option<Slots> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+
+        let mut pos = first;
+        loop {
+            if matched.is_none() {
+                let mut slots = vec![None; self.slots];
+                slots[0] = Some(pos);
+                self.follow(&mut current, 0, slots, chars, pos);
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+
+            for (pc, mut slots) in current.list.drain(..) {
+                let step = match &self.program[pc] {
+                    Inst::Match => {
+                        slots[1] = Some(pos);
+                        matched = Some(slots);
+                        // Lower-priority threads are cut off.
+                        break;
+                    }
+                    inst => pos < chars.len() && self.consumes(inst, chars[pos]),
+                };
+                if step {
+                    self.follow(&mut next, pc + 1, slots, chars, pos + 1);
+                }
+            }
+
+            if pos >= chars.len() {
+                break;
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+            pos += 1;
+        }
+        matched
+    }
+
+    /// Adds the thread at `pc` to `threads`, following jumps, splits,
+    /// saves and assertions until it reaches an instruction that reads a
+    /// character (or `Match`).
+    fn follow(&self, threads: &mut Threads, pc: usize, slots: Slots, chars: &[char], pos: usize) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if threads.seen[pc] == threads.generation {
+                continue;
+            }
+            threads.seen[pc] = threads.generation;
+            match &self.program[pc] {
+                Inst::Jump(target) => stack.push((*target, slots)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Progress(slot) => {
+                    if slots[*slot] != Some(pos) {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::Assert(assertion) => {
+                    if holds(*assertion, chars, pos) {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => threads.list.push((pc, slots)),
+            }
+        }
+    }
+
+    /// Tries a match starting at `start` depth first, with an explicit
+    /// stack, counting steps against `STEP_LIMIT`.
+    fn backtrack(
+        &self,
+        chars: &[char],
+        start: usize,
+        steps: &mut usize,
+    ) -> Result<Option<Slots>, String> {
+        enum Job {
+            Explore(usize, usize),
+            Restore(usize, Option<usize>),
+        }
+
+        let mut slots: Slots = vec![None; self.slots];
+        slots[0] = Some(start);
+        let mut stack = vec![Job::Explore(0, start)];
+
+        while let Some(job) = stack.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+                Job::Explore(pc, pos) => (pc, pos),
+            };
+            loop {
+                *steps += 1;
+                if *steps > STEP_LIMIT {
+                    return Err("backtracking limit exceeded".into());
+                }
+                match &self.program[pc] {
+                    Inst::Match => {
+                        slots[1] = Some(pos);
+                        return Ok(Some(slots));
+                    }
+                    Inst::Jump(target) => pc = *target,
+                    Inst::Split(first, second) => {
+                        stack.push(Job::Explore(*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Save(slot) => {
+                        stack.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Progress(slot) if slots[*slot] == Some(pos) => break,
+                    Inst::Progress(_) => pc += 1,
+                    Inst::Assert(assertion) if holds(*assertion, chars, pos) => pc += 1,
+                    Inst::Assert(_) => break,
+                    Inst::Backref(index) => {
+                        let (from, to) = match (slots[2 * index], slots[2 * index + 1]) {
+                            (Some(from), Some(to)) => (from, to),
+                            _ => break,
+                        };
+                        let len = to - from;
+                        if pos + len > chars.len()
+                            || !(0..len).all(|i| self.same(chars[pos + i], chars[from + i]))
+                        {
+                            break;
+                        }
+                        pos += len;
+                        pc += 1;
+                    }
+                    inst if pos < chars.len() && self.consumes(inst, chars[pos]) => {
+                        pos += 1;
+                        pc += 1;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether a character-reading instruction accepts `c`.
+    fn consumes(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Char(expected) => self.same(c, *expected),
+            Inst::Any => true,
+            Inst::Class { items, negated } => self.in_class(items, c) != *negated,
+            _ => false,
+        }
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn in_class(&self, items: &[ClassItem], c: char) -> bool {
+        let check = |c: char| {
+            items.iter().any(|item| match item {
+                ClassItem::Char(x) => *x == c,
+                ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+                ClassItem::Named(test) => test(c),
+            })
+        };
+        check(c)
+            || (self.ignore_case && (c.to_lowercase().any(check) || c.to_uppercase().any(check)))
+    }
+}
+
+fn holds(assertion: Assertion, chars: &[char], pos: usize) -> bool {
+    let before = pos > 0 && is_word(chars[pos - 1]);
+    let after = pos < chars.len() && is_word(chars[pos]);
+    match assertion {
+        Assertion::Start => pos == 0,
+        Assertion::End => pos == chars.len(),
+        Assertion::WordBoundary(expected) => (before != after) == expected,
+        Assertion::WordStart => !before && after,
+        Assertion::WordEnd => before && !after,
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    syntax: Syntax,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn extended(&self) -> bool {
+        self.syntax == Syntax::Extended
+    }
+
+    /// Whether the upcoming input is `|` (ERE) or `\|` (BRE).
+    fn at_alternation(&self) -> bool {
+        if self.extended() {
+            self.peek() == Some('|')
+        } else {
+            self.peek() == Some('\\') && self.peek_at(1) == Some('|')
+        }
+    }
+
+    fn at_group_end(&self) -> bool {
+        if self.extended() {
+            self.peek() == Some(')')
+        } else {
+            self.peek() == Some('\\') && self.peek_at(1) == Some(')')
+        }
+    }
+
+    fn alternation(&mut self, depth: usize) -> Result<Ast, String> {
+        let mut branches = vec![self.concatenation(depth)?];
+        while self.at_alternation() {
+            self.pos += if self.extended() { 1 } else { 2 };
+            branches.push(self.concatenation(depth)?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Ast::Alternate(branches)
+        })
+    }
+
+    fn concatenation(&mut self, depth: usize) -> Result<Ast, String> {
+        let mut nodes: Vec<Ast> = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if self.at_alternation() || (depth > 0 && self.at_group_end()) {
+                break;
+            }
+            if self.extended() && c == ')' && depth == 0 {
+                // A lone `)` in ERE is an ordinary character.
+                self.pos += 1;
+                nodes.push(Ast::Char(')'));
+                continue;
+            }
+
+            let at_start = nodes.is_empty();
+            let atom = match c {
+                '^' if self.extended() || at_start => {
+                    self.pos += 1;
+                    Ast::Start
+                }
+                '$' if self.extended() || self.at_expression_end(1) => {
+                    self.pos += 1;
+                    Ast::End
+                }
+                // A leading `*` has nothing to repeat and is literal.
+                '*' if at_start => {
+                    self.pos += 1;
+                    Ast::Char('*')
+                }
+                '.' => {
+                    self.pos += 1;
+                    Ast::Any
+                }
+                '[' => self.bracket()?,
+                '(' if self.extended() => {
+                    self.pos += 1;
+                    self.group(depth)?
+                }
+                '\\' => self.escape(depth)?,
+                _ => {
+                    self.pos += 1;
+                    Ast::Char(c)
+                }
+            };
+
+            let atom = self.quantifiers(atom, depth)?;
+            nodes.push(atom);
+        }
+
+        Ok(if nodes.len() == 1 {
+            nodes.pop().unwrap()
+        } else {
+            Ast::Concat(nodes)
+        })
+    }
+
+    /// BRE only treats `$` as an anchor at the end of the expression or
+    /// right before `\)` or `\|`.
+    fn at_expression_end(&self, skip: usize) -> bool {
+        match self.peek_at(skip) {
+            None => true,
+            Some('\\') => matches!(self.peek_at(skip + 1), Some(')') | Some('|')),
+            _ => false,
+        }
+    }
+
+    fn group(&mut self, depth: usize) -> Result<Ast, String> {
+        if depth >= DEPTH_LIMIT {
+            return Err(TOO_BIG.into());
+        }
+        self.groups += 1;
+        let index = self.groups;
+        let inner = self.alternation(depth + 1)?;
+        if !self.at_group_end() {
+            return Err("Unmatched ( or \\(".into());
+        }
+        self.pos += if self.extended() { 1 } else { 2 };
+        Ok(Ast::Group(Box::new(inner), index))
+    }
+
+    fn escape(&mut self, depth: usize) -> Result<Ast, String> {
+        self.pos += 1;
+        let c = self.peek().ok_or("Trailing backslash")?;
+        self.pos += 1;
+
+        Ok(match c {
+            '(' if !self.extended() => self.group(depth)?,
+            '1'..='9' => {
+                let index = c.to_digit(10).unwrap() as usize;
+                if index > self.groups {
+                    return Err("Invalid back reference".into());
+                }
+                Ast::Backref(index)
+            }
+            'w' => Ast::Class {
+                items: vec![ClassItem::Named(is_word)],
+                negated: false,
+            },
+            'W' => Ast::Class {
+                items: vec![ClassItem::Named(is_word)],
+                negated: true,
+            },
+            's' => Ast::Class {
+                items: vec![ClassItem::Named(char::is_whitespace)],
+                negated: false,
+            },
+            'S' => Ast::Class {
+                items: vec![ClassItem::Named(char::is_whitespace)],
+                negated: true,
+            },
+            'b' => Ast::WordBoundary(true),
+            'B' => Ast::WordBoundary(false),
+            '<' => Ast::WordStart,
+            '>' => Ast::WordEnd,
+            't' => Ast::Char('\t'),
+            'n' => Ast::Char('\n'),
+            _ => Ast::Char(c),
+        })
+    }
+
+    /// Wraps `atom` in whatever quantifiers follow it. Each one nests the
+    /// pattern a level deeper, so they count towards the depth limit.
+    fn quantifiers(&mut self, mut atom: Ast, mut depth: usize) -> Result<Ast, String> {
+        loop {
+            let (min, max) = match (self.peek(), self.peek_at(1)) {
+                (Some('*'), _) => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                (Some('+'), _) if self.extended() => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                (Some('?'), _) if self.extended() => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                (Some('{'), _) if self.extended() => {
+                    self.pos += 1;
+                    self.interval()?
+                }
+                (Some('\\'), Some('+')) if !self.extended() => {
+                    self.pos += 2;
+                    (1, None)
+                }
+                (Some('\\'), Some('?')) if !self.extended() => {
+                    self.pos += 2;
+                    (0, Some(1))
+                }
+                (Some('\\'), Some('{')) if !self.extended() => {
+                    self.pos += 2;
+                    self.interval()?
+                }
+                _ => return Ok(atom),
+            };
+            depth += 1;
+            if depth >= DEPTH_LIMIT {
+                return Err(TOO_BIG.into());
+            }
+            atom = Ast::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+            };
+        }
+    }
+
+    /// The inside of `{m}`, `{m,}` or `{m,n}` (`\{...\}` in BRE).
+    fn interval(&mut self) -> Result<(usize, Option<usize>), String> {
+        let number = |parser: &mut Parser| {
+            let start = parser.pos;
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+            let digits: String = parser.chars[start..parser.pos].iter().collect();
+            digits.parse::<usize>().ok()
+        };
+
+        let min = number(self).unwrap_or(0);
+        let max = if self.peek() == Some(',') {
+            self.pos += 1;
+            number(self)
+        } else {
+            Some(min)
+        };
+
+        if !self.extended() {
+            if self.peek() != Some('\\') {
+                return Err("Unmatched \\{".into());
+            }
+            self.pos += 1;
+        }
+        if self.peek() != Some('}') {
+            return Err("Unmatched \\{".into());
+        }
+        self.pos += 1;
+
+        if max.is_some_and(|max| max < min) {
+            return Err("Invalid content of \\{\\}".into());
+        }
+        Ok((min, max))
+    }
+
+    fn bracket(&mut self) -> Result<Ast, String> {
+        self.pos += 1;
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or("Unmatched [, [^, [:, [., or [=")?;
+            if c == ']' && !first {
+                self.pos += 1;
+                break;
+            }
+            first = false;
+
+            if c == '[' && self.peek_at(1) == Some(':') {
+                let rest: String = self.chars[self.pos + 2..].iter().collect();
+                let end = rest.find(":]").ok_or("Unmatched [, [^, [:, [., or [=")?;
+                let name = &rest[..end];
+                items.push(ClassItem::Named(named_class(name)?));
+                self.pos += 2 + name.chars().count() + 2;
+                continue;
+            }
+
+            self.pos += 1;
+            if self.peek() == Some('-') && self.peek_at(1).is_some_and(|next| next != ']') {
+                let hi = self.peek_at(1).unwrap();
+                self.pos += 2;
+                if hi < c {
+                    return Err("Invalid range end".into());
+                }
+                items.push(ClassItem::Range(c, hi));
+            } else {
+                items.push(ClassItem::Char(c));
+            }
+        }
+
+        Ok(Ast::Class { items, negated })
+    }
+}
+
+fn named_class(name: &str) -> Result<fn(char) -> bool, String> {
+    Ok(match name {
+        "alpha" => char::is_alphabetic,
+        "digit" => |c: char| c.is_ascii_digit(),
+        "alnum" => char::is_alphanumeric,
+        "upper" => char::is_uppercase,
+        "lower" => char::is_lowercase,
+        "space" => char::is_whitespace,
+        "blank" => |c: char| c == ' ' || c == '\t',
+        "punct" => |c: char| c.is_ascii_punctuation(),
+        "xdigit" => |c: char| c.is_ascii_hexdigit(),
+        "cntrl" => char::is_control,
+        "print" => |c: char| !c.is_control(),
+        "graph" => |c: char| !c.is_control() && !c.is_whitespace(),
+        _ => return Err("Invalid character class name".into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn basic(pattern: &str) -> Regex {
+        Regex::new(pattern, Syntax::Basic, false).unwrap()
+    }
+
+    fn extended(pattern: &str) -> Regex {
+        Regex::new(pattern, Syntax::Extended, false).unwrap()
+    }
+
+    fn span(re: &Regex, text: &str) -> Option<(usize, usize)> {
+        re.find_at(text, 0)
+            .unwrap()
+            .map(|found| (found.start, found.end))
+    }
+
+    #[test]
+    fn matches_leftmost_longest_alternatives_in_order() {
+        assert_eq!(span(&extended("b+"), "abbbc"), Some((1, 4)));
+        assert_eq!(span(&extended("a|ab"), "ab"), Some((0, 1)));
+        assert_eq!(span(&basic("x.*y"), "axbycy"), Some((1, 6)));
+        assert_eq!(span(&extended("c{2,3}"), "cccc"), Some((0, 3)));
+        assert_eq!(span(&basic("^$"), "x"), None);
+    }
+
+    #[test]
+    fn basic_and_extended_syntax_differ() {
+        assert!(basic("a+").is_match("a+").unwrap());
+        assert!(!basic("a+").is_match("aa").unwrap());
+        assert!(basic("a\\+").is_match("aa").unwrap());
+        assert!(extended("(ab)+").is_match("abab").unwrap());
+        assert!(basic("\\(ab\\)\\{2\\}").is_match("abab").unwrap());
+    }
+
+    #[test]
+    fn captures_groups() {
+        let re = extended("([a-z]+)=([0-9]*)");
+        let found = re.find_at("key=42;", 0).unwrap().unwrap();
+        assert_eq!(found.groups, vec![Some((0, 3)), Some((4, 6))]);
+    }
+
+    #[test]
+    fn classes_and_anchors() {
+        assert!(extended("^[[:digit:]]+$").is_match("2024").unwrap());
+        assert!(!extended("^[^0-9]+$").is_match("a1").unwrap());
+        assert_eq!(span(&basic("\\<is\\>"), "this is"), Some((5, 7)));
+        assert_eq!(span(&extended("\\w+"), "  héllo!"), Some((2, 8)));
+        let re = Regex::new("HELLO", Syntax::Basic, true).unwrap();
+        assert!(re.is_match("say hello").unwrap());
+    }
+
+    #[test]
+    fn back_references() {
+        assert!(basic("\\(ab*\\)\\1").is_match("abbabb").unwrap());
+        assert!(!basic("^\\(ab*\\)\\1$").is_match("abbab").unwrap());
+        assert!(extended("(a|b)\\1").is_match("xbb").unwrap());
+    }
+
+    #[test]
+    fn find_iter_steps_over_empty_matches() {
+        let starts: Vec<usize> = extended("x*")
+            .find_iter("aéb")
+            .unwrap()
+            .iter()
+            .map(|found| found.start)
+            .collect();
+        assert_eq!(starts, vec![0, 1, 3, 4]);
+
+        let spans: Vec<(usize, usize)> = extended("x*")
+            .find_iter("axxb")
+            .unwrap()
+            .iter()
+            .map(|found| (found.start, found.end))
+            .collect();
+        assert_eq!(spans, vec![(0, 0), (1, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn empty_loops_terminate() {
+        assert_eq!(span(&extended("(a*)*b"), "aab"), Some((0, 3)));
+        assert_eq!(span(&extended("(a|)*c"), "aac"), Some((0, 3)));
+    }
+
+    #[test]
+    fn rejects_bad_patterns() {
+        assert!(Regex::new("a\\{2", Syntax::Basic, false).is_err());
+        assert!(Regex::new("(a", Syntax::Extended, false).is_err());
+        assert!(Regex::new("[[:nope:]]", Syntax::Basic, false).is_err());
+        let deep = format!("{}a{}", "(".repeat(1000), ")".repeat(1000));
+        assert!(Regex::new(&deep, Syntax::Extended, false).is_err());
+    }
+
+    #[test]
+    fn long_lines_do_not_overflow() {
+        let line = format!("x{}y", "a".repeat(20_000));
+        assert_eq!(span(&basic("x.*y"), &line), Some((0, 20_002)));
+        let line = "a".repeat(20_000);
+        assert_eq!(basic("x.*y").find_iter(&line).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn ambiguous_alternation_runs_in_linear_time() {
+        let started = Instant::now();
+        let re = extended("(a|a)*b");
+        assert!(!re.is_match(&"a".repeat(28)).unwrap());
+        assert!(!re.is_match(&"a".repeat(5_000)).unwrap());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn runaway_back_references_fail_cleanly() {
+        let re = extended("^(a*)*\\1b$");
+        assert!(re.is_match(&"a".repeat(5_000)).is_err());
+    }
+}
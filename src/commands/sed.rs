@@ -0,0 +1,1067 @@
+use crate::commands::filesystem::{normalize_path, FsError, Node, CURRENT_PATH, FILESYSTEM};
+use crate::commands::permissions::{self, Access};
+use crate::commands::regex::{Match, Regex, Syntax};
+use crate::commands::text;
+use crate::commands::users;
+
+/// A line selector. `Relative` (`addr,+N`) is only valid as a range end.
+enum Address {
+    Line(usize),
+    Last,
+    Pattern(Regex),
+    Step(usize, usize),
+    Relative(usize),
+}
+
+/// One piece of an `s` replacement.
+enum Piece {
+    Literal(String),
+    Group(usize),
+    Case(char),
+}
+
+enum Kind {
+    Block(usize),
+    BlockEnd,
+    Substitute {
+        regex: Regex,
+        replacement: Vec<Piece>,
+        global: bool,
+        occurrence: usize,
+        print: bool,
+    },
+    Transliterate(Vec<char>, Vec<char>),
+    Print,
+    PrintFirst,
+    Delete,
+    DeleteFirst,
+    LineNumber,
+    Append(String),
+    Insert(String),
+    Change(String),
+    Next,
+    NextAppend,
+    Hold,
+    HoldAppend,
+    Get,
+    GetAppend,
+    Exchange,
+    Zap,
+    Label(String),
+    Branch(String, Option<bool>),
+    Quit(bool),
+}
+
+struct Command {
+    start: Option<Address>,
+    end: Option<Address>,
+    negate: bool,
+    kind: Kind,
+    /// Branch destination, resolved once the whole script is parsed.
+    target: usize,
+}
+
+/// Parses a sed script into a flat list of commands; `{`/`}` and branches
+/// become jumps within it.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    syntax: Syntax,
+    last_regex: Option<Regex>,
+    /// Where each `-e` expression starts in `chars`, for error messages.
+    starts: Vec<usize>,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        let expression = self
+            .starts
+            .iter()
+            .filter(|&&start| start <= self.pos)
+            .count();
+        let start = self.starts[expression.max(1) - 1];
+        format!(
+            "sed: -e expression #{}, char {}: {}",
+            expression.max(1),
+            self.pos - start,
+            message
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    /// Reads up to an unescaped `delimiter`. Escaped delimiters lose their
+    /// backslash; `\n` becomes a newline; other escapes are kept for the
+    /// regex or replacement parser. A bare newline, which also ends each
+    /// `-e` expression, leaves the command unterminated.
+    fn delimited(&mut self, delimiter: char, what: &str) -> Result<String, String> {
+        let mut text = String::new();
+        loop {
+            if self.peek() == Some('\n') {
+                return Err(self.error(&format!("unterminated {}", what)));
+            }
+            match self.bump() {
+                None => return Err(self.error(&format!("unterminated {}", what))),
+                Some(c) if c == delimiter => return Ok(text),
+                Some('\\') => match self.bump() {
+                    Some(c) if c == delimiter => text.push(c),
+                    Some('n') => text.push('\n'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => return Err(self.error(&format!("unterminated {}", what))),
+                },
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn regex(&mut self, pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+        if pattern.is_empty() {
+            return self
+                .last_regex
+                .clone()
+                .ok_or_else(|| self.error("no previous regular expression"));
+        }
+        let regex =
+            Regex::new(pattern, self.syntax, ignore_case).map_err(|err| self.error(&err))?;
+        self.last_regex = Some(regex.clone());
+        Ok(regex)
+    }
+
+    fn address(&mut self, is_end: bool) -> Result<Option<Address>, String> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let line = self.number().unwrap_or(0);
+                if self.peek() == Some('~') {
+                    self.pos += 1;
+                    let step = self.number().unwrap_or(0);
+                    return Ok(Some(Address::Step(line, step)));
+                }
+                if line == 0 && !is_end {
+                    return Err(self.error("invalid usage of line address 0"));
+                }
+                Ok(Some(Address::Line(line)))
+            }
+            Some('+') if is_end => {
+                self.pos += 1;
+                match self.number() {
+                    Some(n) => Ok(Some(Address::Relative(n))),
+                    None => Err(self.error("expected address after ,+")),
+                }
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Some(Address::Last))
+            }
+            Some(open @ ('/' | '\\')) => {
+                self.pos += 1;
+                let delimiter = if open == '\\' {
+                    self.bump()
+                        .ok_or_else(|| self.error("unexpected end of script"))?
+                } else {
+                    '/'
+                };
+                let pattern = self.delimited(delimiter, "address regex")?;
+                let mut ignore_case = false;
+                while matches!(self.peek(), Some('I')) {
+                    self.pos += 1;
+                    ignore_case = true;
+                }
+                Ok(Some(Address::Pattern(self.regex(&pattern, ignore_case)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Text for `a`, `i` and `c`: the GNU one-liner form (`a text`) and
+    /// the classic `a\` followed by the text on the next line.
+    fn text_argument(&mut self) -> String {
+        self.skip_spaces();
+        if self.peek() == Some('\\') {
+            self.pos += 1;
+            if self.peek() == Some('\n') {
+                self.pos += 1;
+            }
+        }
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.pos += 1;
+            match c {
+                '\\' => match self.bump() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some(c) => text.push(c),
+                    None => break,
+                },
+                c => text.push(c),
+            }
+        }
+        text
+    }
+
+    fn label(&mut self) -> String {
+        self.skip_spaces();
+        let mut label = String::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, ';' | '\n' | '}') {
+                break;
+            }
+            label.push(c);
+            self.pos += 1;
+        }
+        label.trim_end().to_string()
+    }
+
+    fn substitute(&mut self) -> Result<Kind, String> {
+        let delimiter = match self.bump() {
+            Some(c) if c != '\n' && c != '\\' => c,
+            _ => return Err(self.error("unterminated `s' command")),
+        };
+        let pattern = self.delimited(delimiter, "`s' command")?;
+        let replacement = self.delimited(delimiter, "`s' command")?;
+
+        let mut global = false;
+        let mut print = false;
+        let mut ignore_case = false;
+        let mut occurrence = None;
+        loop {
+            match self.peek() {
+                Some(flag @ 'g') if global => return Err(self.repeated(flag)),
+                Some(flag @ 'p') if print => return Err(self.repeated(flag)),
+                Some('g') => global = true,
+                Some('p') => print = true,
+                Some('i' | 'I') => ignore_case = true,
+                Some(c) if c.is_ascii_digit() => {
+                    if occurrence.is_some() {
+                        return Err(self.error("multiple number options to `s' command"));
+                    }
+                    match self.number() {
+                        Some(0) | None => {
+                            return Err(self.error("number option to `s' command may not be zero"))
+                        }
+                        n => occurrence = n,
+                    }
+                    continue;
+                }
+                Some(';' | '\n' | '}' | ' ' | '\t' | '#') | None => break,
+                Some(_) => return Err(self.error("unknown option to `s'")),
+            }
+            self.pos += 1;
+        }
+
+        Ok(Kind::Substitute {
+            regex: self.regex(&pattern, ignore_case)?,
+            replacement: self::replacement(&replacement),
+            global,
+            occurrence: occurrence.unwrap_or(1),
+            print,
+        })
+    }
+
+    /// The error for an `s` flag given twice, pointing past it.
+    fn repeated(&mut self, flag: char) -> String {
+        self.pos += 1;
+        self.error(&format!("multiple `{}' options to `s' command", flag))
+    }
+
+    fn transliterate(&mut self) -> Result<Kind, String> {
+        let delimiter = match self.bump() {
+            Some(c) if c != '\n' && c != '\\' => c,
+            _ => return Err(self.error("unterminated `y' command")),
+        };
+        let unescape = |text: String| -> Vec<char> {
+            let mut chars = Vec::new();
+            let mut iter = text.chars();
+            while let Some(c) = iter.next() {
+                match c {
+                    '\\' => match iter.next() {
+                        Some('t') => chars.push('\t'),
+                        Some(c) => chars.push(c),
+                        None => chars.push('\\'),
+                    },
+                    c => chars.push(c),
+                }
+            }
+            chars
+        };
+        let from = unescape(self.delimited(delimiter, "`y' command")?);
+        let to = unescape(self.delimited(delimiter, "`y' command")?);
+        if from.len() != to.len() {
+            return Err(self.error("strings for `y' command are different lengths"));
+        }
+        Ok(Kind::Transliterate(from, to))
+    }
+
+    fn parse(&mut self) -> Result<Vec<Command>, String> {
+        let mut commands: Vec<Command> = Vec::new();
+        let mut blocks = Vec::new();
+
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\n' | ';')) {
+                self.pos += 1;
+            }
+            if self.peek() == Some('#') {
+                while !matches!(self.bump(), Some('\n') | None) {}
+                continue;
+            }
+            if self.peek().is_none() {
+                break;
+            }
+
+            let start = self.address(false)?;
+            let mut end = None;
+            if start.is_some() && self.peek() == Some(',') {
+                self.pos += 1;
+                self.skip_spaces();
+                end = self.address(true)?;
+                if end.is_none() {
+                    return Err(self.error("unexpected `,'"));
+                }
+            }
+            self.skip_spaces();
+            let mut negate = false;
+            while self.peek() == Some('!') {
+                self.pos += 1;
+                negate = true;
+                self.skip_spaces();
+            }
+
+            let command = match self.bump() {
+                Some(c) => c,
+                None => return Err(self.error("missing command")),
+            };
+            let addressed = start.is_some();
+            let kind = match command {
+                '{' => {
+                    blocks.push(commands.len());
+                    Kind::Block(0)
+                }
+                '}' => {
+                    if addressed || negate {
+                        return Err(self.error("} doesn't want any addresses"));
+                    }
+                    let open = blocks.pop().ok_or_else(|| self.error("unexpected `}'"))?;
+                    commands[open].kind = Kind::Block(commands.len());
+                    Kind::BlockEnd
+                }
+                's' => self.substitute()?,
+                'y' => self.transliterate()?,
+                'p' => Kind::Print,
+                'P' => Kind::PrintFirst,
+                'd' => Kind::Delete,
+                'D' => Kind::DeleteFirst,
+                '=' => Kind::LineNumber,
+                'a' => Kind::Append(self.text_argument()),
+                'i' => Kind::Insert(self.text_argument()),
+                'c' => Kind::Change(self.text_argument()),
+                'n' => Kind::Next,
+                'N' => Kind::NextAppend,
+                'h' => Kind::Hold,
+                'H' => Kind::HoldAppend,
+                'g' => Kind::Get,
+                'G' => Kind::GetAppend,
+                'x' => Kind::Exchange,
+                'z' => Kind::Zap,
+                'q' | 'Q' => {
+                    if end.is_some() {
+                        return Err(self.error("command only uses one address"));
+                    }
+                    self.skip_spaces();
+                    self.number();
+                    Kind::Quit(command == 'q')
+                }
+                ':' => {
+                    if addressed {
+                        return Err(self.error(": doesn't want any addresses"));
+                    }
+                    let label = self.label();
+                    if label.is_empty() {
+                        return Err(self.error("\":\" lacks a label"));
+                    }
+                    Kind::Label(label)
+                }
+                'b' => Kind::Branch(self.label(), None),
+                't' => Kind::Branch(self.label(), Some(true)),
+                'T' => Kind::Branch(self.label(), Some(false)),
+                c => return Err(self.error(&format!("unknown command: `{}'", c))),
+            };
+
+            commands.push(Command {
+                start,
+                end,
+                negate,
+                kind,
+                target: 0,
+            });
+
+            self.skip_spaces();
+            match self.peek() {
+                None | Some(';' | '\n' | '}' | '#') => {}
+                Some(_) if matches!(commands.last().unwrap().kind, Kind::Block(_)) => {}
+                Some(_) => return Err(self.error("extra characters after command")),
+            }
+        }
+
+        if !blocks.is_empty() {
+            return Err(self.error("unmatched `{'"));
+        }
+
+        for index in 0..commands.len() {
+            let target = match &commands[index].kind {
+                Kind::Branch(label, _) if label.is_empty() => commands.len(),
+                Kind::Branch(label, _) => commands
+                    .iter()
+                    .position(|command| matches!(&command.kind, Kind::Label(name) if name == label))
+                    .ok_or_else(|| format!("sed: can't find label for jump to `{}'", label))?,
+                _ => continue,
+            };
+            commands[index].target = target;
+        }
+        Ok(commands)
+    }
+}
+
+/// The state of one run of a script over a stream of lines.
+struct Machine<'a> {
+    commands: &'a [Command],
+    quiet: bool,
+    lines: Vec<&'a str>,
+    next: usize,
+    line_number: usize,
+    pattern: String,
+    hold: String,
+    /// Range end (line number, or `usize::MAX` for pattern ends) of each
+    /// command whose range is currently open.
+    ranges: Vec<Option<usize>>,
+    substituted: bool,
+    appended: Vec<String>,
+    output: Vec<String>,
+}
+
+impl<'a> Machine<'a> {
+    fn is_last(&self) -> bool {
+        self.next >= self.lines.len()
+    }
+
+    fn matches(&self, address: &Address) -> Result<bool, String> {
+        Ok(match address {
+            Address::Line(n) => self.line_number == *n,
+            Address::Last => self.is_last(),
+            Address::Pattern(regex) => regex.is_match(&self.pattern)?,
+            Address::Step(first, 0) => self.line_number == *first,
+            Address::Step(first, step) => {
+                self.line_number >= *first && (self.line_number - first).is_multiple_of(*step)
+            }
+            Address::Relative(_) => false,
+        })
+    }
+
+    fn selected(&mut self, index: usize) -> Result<bool, String> {
+        let command = &self.commands[index];
+        let hit = match (&command.start, &command.end) {
+            (None, _) => true,
+            (Some(start), None) => self.matches(start)?,
+            (Some(start), Some(end)) => match self.ranges[index] {
+                Some(limit) => {
+                    let closes = match end {
+                        Address::Line(_) | Address::Relative(_) => self.line_number >= limit,
+                        end => self.matches(end)?,
+                    };
+                    if closes {
+                        self.ranges[index] = None;
+                    }
+                    true
+                }
+                None if self.matches(start)? => {
+                    let limit = match end {
+                        Address::Line(n) => *n,
+                        Address::Relative(n) => self.line_number + n,
+                        _ => usize::MAX,
+                    };
+                    let closes = match end {
+                        Address::Line(_) | Address::Relative(_) => limit <= self.line_number,
+                        Address::Last => self.is_last(),
+                        _ => false,
+                    };
+                    if !closes {
+                        self.ranges[index] = Some(limit);
+                    }
+                    true
+                }
+                None => false,
+            },
+        };
+        Ok(hit != command.negate)
+    }
+
+    fn read_line(&mut self) -> Option<&'a str> {
+        let line = self.lines.get(self.next).copied()?;
+        self.next += 1;
+        self.line_number += 1;
+        Some(line)
+    }
+
+    fn flush_appended(&mut self) {
+        self.output.append(&mut self.appended);
+    }
+
+    /// Runs the script over every line, or until a `q` or a pattern that
+    /// fails to run.
+    fn run(&mut self) -> Result<(), String> {
+        let mut restart = false;
+        loop {
+            if !restart {
+                match self.read_line() {
+                    Some(line) => self.pattern = line.to_string(),
+                    None => return Ok(()),
+                }
+            }
+            restart = false;
+            self.substituted = false;
+
+            match self.cycle()? {
+                Cycle::Continue { print } => {
+                    if print && !self.quiet {
+                        self.output.push(self.pattern.clone());
+                    }
+                    self.flush_appended();
+                }
+                Cycle::Restart => {
+                    self.flush_appended();
+                    restart = true;
+                }
+                Cycle::Quit { print } => {
+                    if print && !self.quiet {
+                        self.output.push(self.pattern.clone());
+                    }
+                    self.flush_appended();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn cycle(&mut self) -> Result<Cycle, String> {
+        let mut pc = 0;
+        while pc < self.commands.len() {
+            let index = pc;
+            pc += 1;
+            let command = &self.commands[index];
+            if matches!(command.kind, Kind::BlockEnd | Kind::Label(_)) {
+                continue;
+            }
+            if !self.selected(index)? {
+                if let Kind::Block(end) = command.kind {
+                    pc = end + 1;
+                }
+                continue;
+            }
+
+            match &command.kind {
+                Kind::Block(_) | Kind::BlockEnd | Kind::Label(_) => {}
+                Kind::Substitute {
+                    regex,
+                    replacement,
+                    global,
+                    occurrence,
+                    print,
+                } => {
+                    if let Some(result) =
+                        substitute(&self.pattern, regex, replacement, *global, *occurrence)?
+                    {
+                        self.pattern = result;
+                        self.substituted = true;
+                        if *print {
+                            self.output.push(self.pattern.clone());
+                        }
+                    }
+                }
+                Kind::Transliterate(from, to) => {
+                    self.pattern = self
+                        .pattern
+                        .chars()
+                        .map(|c| from.iter().position(|&f| f == c).map_or(c, |i| to[i]))
+                        .collect();
+                }
+                Kind::Print => self.output.push(self.pattern.clone()),
+                Kind::PrintFirst => {
+                    let first = self.pattern.split('\n').next().unwrap_or("");
+                    self.output.push(first.to_string());
+                }
+                Kind::Delete => return Ok(Cycle::Continue { print: false }),
+                Kind::DeleteFirst => match self.pattern.find('\n') {
+                    Some(newline) => {
+                        self.pattern.drain(..=newline);
+                        return Ok(Cycle::Restart);
+                    }
+                    None => return Ok(Cycle::Continue { print: false }),
+                },
+                Kind::LineNumber => self.output.push(self.line_number.to_string()),
+                Kind::Append(text) => self.appended.push(text.clone()),
+                Kind::Insert(text) => self.output.push(text.clone()),
+                Kind::Change(text) => {
+                    // In an open range the text replaces the whole range,
+                    // so it is only printed at its end.
+                    if command.negate || self.ranges[index].is_none() {
+                        self.output.push(text.clone());
+                    }
+                    return Ok(Cycle::Continue { print: false });
+                }
+                Kind::Next => {
+                    if self.is_last() {
+                        return Ok(Cycle::Quit { print: true });
+                    }
+                    if !self.quiet {
+                        self.output.push(self.pattern.clone());
+                    }
+                    self.flush_appended();
+                    self.pattern = self.read_line().unwrap_or_default().to_string();
+                }
+                Kind::NextAppend => match self.read_line() {
+                    Some(line) => {
+                        self.flush_appended();
+                        self.pattern.push('\n');
+                        self.pattern.push_str(line);
+                    }
+                    None => return Ok(Cycle::Quit { print: true }),
+                },
+                Kind::Hold => self.hold = self.pattern.clone(),
+                Kind::HoldAppend => {
+                    self.hold.push('\n');
+                    self.hold.push_str(&self.pattern);
+                }
+                Kind::Get => self.pattern = self.hold.clone(),
+                Kind::GetAppend => {
+                    self.pattern.push('\n');
+                    self.pattern.push_str(&self.hold);
+                }
+                Kind::Exchange => std::mem::swap(&mut self.pattern, &mut self.hold),
+                Kind::Zap => self.pattern.clear(),
+                Kind::Branch(_, condition) => {
+                    let taken = match condition {
+                        None => true,
+                        Some(when) => self.substituted == *when,
+                    };
+                    if condition.is_some() {
+                        self.substituted = false;
+                    }
+                    if taken {
+                        pc = command.target;
+                    }
+                }
+                Kind::Quit(print) => return Ok(Cycle::Quit { print: *print }),
+            }
+        }
+        Ok(Cycle::Continue { print: true })
+    }
+}
+
+/// How a cycle over one pattern space ended.
+enum Cycle {
+    Continue { print: bool },
+    Restart,
+    Quit { print: bool },
+}
+
+/// Parses the replacement half of an `s` command.
+fn replacement(text: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        let piece = match c {
+            '&' => Piece::Group(0),
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => Piece::Group(d as usize - '0' as usize),
+                Some(c @ ('U' | 'L' | 'E' | 'u' | 'l')) => Piece::Case(c),
+                Some('t') => {
+                    literal.push('\t');
+                    continue;
+                }
+                Some(c) => {
+                    literal.push(c);
+                    continue;
+                }
+                None => {
+                    literal.push('\\');
+                    continue;
+                }
+            },
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+        }
+        pieces.push(piece);
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    pieces
+}
+
+/// One `s/regex/replacement/[g]` over a single line, for editors that
+/// borrow sed's replacement syntax (`&`, `\1`, `\U`...). Returns None if
+/// nothing matched.
+pub fn substitute_line(
+    text: &str,
+    regex: &Regex,
+    replacement: &str,
+    global: bool,
+) -> Result<Option<String>, String> {
+    substitute(text, regex, &self::replacement(replacement), global, 1)
+}
+
+/// Applies an `s` command, or returns None if nothing was replaced.
+fn substitute(
+    text: &str,
+    regex: &Regex,
+    replacement: &[Piece],
+    global: bool,
+    occurrence: usize,
+) -> Result<Option<String>, String> {
+    let mut result = String::new();
+    let mut last = 0;
+    let mut replaced = false;
+
+    for (n, found) in regex.find_iter(text)?.into_iter().enumerate() {
+        let n = n + 1;
+        if n < occurrence || (n > occurrence && !global) {
+            continue;
+        }
+        result.push_str(&text[last..found.start]);
+        expand(&mut result, text, &found, replacement);
+        last = found.end;
+        replaced = true;
+    }
+
+    if !replaced {
+        return Ok(None);
+    }
+    result.push_str(&text[last..]);
+    Ok(Some(result))
+}
+
+/// Appends one expanded replacement, applying the GNU `\U \L \E \u \l`
+/// case conversions.
+fn expand(result: &mut String, text: &str, found: &Match, pieces: &[Piece]) {
+    let mut mode: Option<char> = None;
+    let mut once: Option<char> = None;
+
+    for piece in pieces {
+        let chunk = match piece {
+            Piece::Literal(literal) => literal.as_str(),
+            Piece::Group(0) => &text[found.start..found.end],
+            Piece::Group(n) => match found.groups.get(n - 1).copied().flatten() {
+                Some((start, end)) => &text[start..end],
+                None => "",
+            },
+            Piece::Case(c @ ('u' | 'l')) => {
+                once = Some(*c);
+                continue;
+            }
+            Piece::Case('E') => {
+                mode = None;
+                continue;
+            }
+            Piece::Case(c) => {
+                mode = Some(*c);
+                continue;
+            }
+        };
+
+        for c in chunk.chars() {
+            let case = once.take().or(mode);
+            match case {
+                Some('u' | 'U') => result.extend(c.to_uppercase()),
+                Some('l' | 'L') => result.extend(c.to_lowercase()),
+                _ => result.push(c),
+            }
+        }
+    }
+}
+
+/// `sed [-n] [-E] [-i] [-e script]... [script] [file...]`. Without `-i`
+/// all inputs are one stream; with it each file is rewritten in place.
+pub fn sed(args: &[&str], stdin: Option<&str>) -> String {
+    let mut quiet = false;
+    let mut syntax = Syntax::Basic;
+    let mut in_place = false;
+    let mut scripts: Vec<String> = Vec::new();
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        match arg {
+            "--quiet" | "--silent" => quiet = true,
+            "--regexp-extended" => syntax = Syntax::Extended,
+            "--in-place" => in_place = true,
+            "--" => {
+                operands.extend(rest.iter().copied());
+                break;
+            }
+            _ if arg.starts_with("--expression=") => {
+                scripts.push(arg["--expression=".len()..].to_string())
+            }
+            _ if arg.starts_with("--") => return format!("sed: unrecognized option '{}'", arg),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for (index, flag) in arg.char_indices().skip(1) {
+                    match flag {
+                        'n' => quiet = true,
+                        'E' | 'r' => syntax = Syntax::Extended,
+                        'i' => {
+                            if index + 1 < arg.len() {
+                                return "sed: backup suffixes for -i are not supported".to_string();
+                            }
+                            in_place = true;
+                        }
+                        'e' => {
+                            let attached = &arg[index + 1..];
+                            if !attached.is_empty() {
+                                scripts.push(attached.to_string());
+                            } else if let Some((&next, tail)) = rest.split_first() {
+                                rest = tail;
+                                scripts.push(next.to_string());
+                            } else {
+                                return "sed: option requires an argument -- 'e'".to_string();
+                            }
+                            break;
+                        }
+                        _ => return format!("sed: invalid option -- '{}'", flag),
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+
+    if scripts.is_empty() {
+        if operands.is_empty() {
+            return "Usage: sed [OPTION]... {script-only-if-no-other-script} [input-file]..."
+                .to_string();
+        }
+        scripts.push(operands.remove(0).to_string());
+    }
+
+    let mut starts = Vec::new();
+    let mut source = String::new();
+    for script in &scripts {
+        starts.push(source.chars().count());
+        source.push_str(script);
+        source.push('\n');
+    }
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+        syntax,
+        last_regex: None,
+        starts,
+    };
+    let commands = match parser.parse() {
+        Ok(commands) => commands,
+        Err(err) => return err,
+    };
+
+    let run = |content: &str| -> Vec<String> {
+        let mut machine = Machine {
+            commands: &commands,
+            quiet,
+            lines: content.lines().collect(),
+            next: 0,
+            line_number: 0,
+            pattern: String::new(),
+            hold: String::new(),
+            ranges: vec![None; commands.len()],
+            substituted: false,
+            appended: Vec::new(),
+            output: Vec::new(),
+        };
+        if let Err(err) = machine.run() {
+            machine.output.push(format!("sed: {}", err));
+        }
+        machine.output
+    };
+
+    if in_place {
+        if operands.is_empty() {
+            return "sed: no input files".to_string();
+        }
+        return operands
+            .iter()
+            .filter_map(|operand| edit_in_place(operand, &run).err())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    let mut output = Vec::new();
+    let mut stream = String::new();
+    for input in text::inputs("sed", &operands, stdin) {
+        match input {
+            Ok((_, content)) => {
+                if !stream.is_empty() && !stream.ends_with('\n') {
+                    stream.push('\n');
+                }
+                stream.push_str(&content);
+            }
+            Err(err) => output.push(err),
+        }
+    }
+    output.extend(run(&stream));
+    output.join("\n")
+}
+
+/// `sed -i`: runs the script over one file and writes the result back,
+/// keeping a trailing newline if the file had one.
+fn edit_in_place(operand: &str, run: &dyn Fn(&str) -> Vec<String>) -> Result<(), String> {
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    let path = normalize_path(operand, &current_path);
+
+    let fail = |err: FsError| format!("sed: couldn't edit {}: {}", operand, err);
+    let content = text::read_file(&filesystem, &path, &session)
+        .map_err(|err| format!("sed: can't read {}: {}", operand, err))?;
+    let inode = match permissions::resolve(&filesystem, &path, &session) {
+        Ok((canonical, node @ Node::File { inode })) => {
+            filesystem.writable(&canonical).map_err(fail)?;
+            if !permissions::check(&filesystem, node, &session, Access::Write) {
+                return Err(fail(FsError::PermissionDenied));
+            }
+            *inode
+        }
+        Ok(_) => return Err(fail(FsError::IsADirectory)),
+        Err(err) => return Err(fail(err)),
+    };
+
+    let mut edited = run(&content).join("\n");
+    if content.ends_with('\n') && !edited.is_empty() {
+        edited.push('\n');
+    }
+    filesystem.inodes.write(inode, edited);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "one two\nthree four\nfive six\nseven";
+
+    /// Scripts and what GNU sed prints for them on `INPUT`.
+    #[test]
+    fn matches_gnu_sed() {
+        let cases: &[(&[&str], &str)] = &[
+            (&["s/o/0/"], "0ne two\nthree f0ur\nfive six\nseven"),
+            (&["s/o/0/g"], "0ne tw0\nthree f0ur\nfive six\nseven"),
+            (&["s/o/0/2"], "one tw0\nthree four\nfive six\nseven"),
+            (
+                &["-E", "s/(\\w+) (\\w+)/\\2 \\1/"],
+                "two one\nfour three\nsix five\nseven",
+            ),
+            (
+                &["s/[aeiou]/\\U&/g"],
+                "OnE twO\nthrEE fOUr\nfIvE sIx\nsEvEn",
+            ),
+            (&["-n", "2,3p"], "three four\nfive six"),
+            (&["2d"], "one two\nfive six\nseven"),
+            (&["$!d"], "seven"),
+            (&["-n", "/e/="], "1\n2\n3\n4"),
+            (&["1~2d"], "three four\nseven"),
+            (&["/three/,+1d"], "one two\nseven"),
+            (&["y/eo/EO/"], "OnE twO\nthrEE fOur\nfivE six\nsEvEn"),
+            (
+                &["1i\\\nheader"],
+                "header\none two\nthree four\nfive six\nseven",
+            ),
+            (
+                &["$a\\\nfooter"],
+                "one two\nthree four\nfive six\nseven\nfooter",
+            ),
+            (&["2c\\\nchanged"], "one two\nchanged\nfive six\nseven"),
+            (&["-n", "$p"], "seven"),
+            (&["N;P;D"], "one two\nthree four\nfive six\nseven"),
+            (&["G"], "one two\n\nthree four\n\nfive six\n\nseven\n"),
+            (&["-n", "h;n;G;p"], "three four\none two\nseven\nfive six"),
+            (&["1!G;h;$!d"], "seven\nfive six\nthree four\none two"),
+            (
+                &["/^f/{s/five/5/;s/six/6/}"],
+                "one two\nthree four\n5 6\nseven",
+            ),
+            (
+                &["s/\\(t\\)\\(w\\|h\\)/[\\2\\1]/g"],
+                "one [wt]o\n[ht]ree four\nfive six\nseven",
+            ),
+            (
+                &["s/x*/-/g"],
+                "-o-n-e- -t-w-o-\n-t-h-r-e-e- -f-o-u-r-\n-f-i-v-e- -s-i-\n-s-e-v-e-n-",
+            ),
+            (
+                &["-e", "s/one/1/", "-e", "s/two/2/"],
+                "1 2\nthree four\nfive six\nseven",
+            ),
+            (&["=;s/ .*//"], "1\none\n2\nthree\n3\nfive\n4\nseven"),
+            (&["-n", "s/e/E/gp"], "onE two\nthrEE four\nfivE six\nsEvEn"),
+            (&["s/O/0/Ig"], "0ne tw0\nthree f0ur\nfive six\nseven"),
+            (&["-n", "/two/,/five/{/five/!p}"], "one two\nthree four"),
+            (
+                &["$!N;s/\\n/ + /"],
+                "one two + three four\nfive six + seven",
+            ),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(sed(args, Some(INPUT)), *expected, "sed {:?}", args);
+        }
+    }
+
+    #[test]
+    fn reports_script_errors() {
+        assert_eq!(
+            sed(&["s/a/b"], Some(INPUT)),
+            "sed: -e expression #1, char 5: unterminated `s' command"
+        );
+        assert_eq!(
+            sed(&["k"], Some(INPUT)),
+            "sed: -e expression #1, char 1: unknown command: `k'"
+        );
+        assert_eq!(
+            sed(&["s/a/b/gg2"], Some(INPUT)),
+            "sed: -e expression #1, char 8: multiple `g' options to `s' command"
+        );
+    }
+}
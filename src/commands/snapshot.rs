@@ -0,0 +1,124 @@
+//! Snapshots of the filesystem taken around every command, so that `undo`
+//! can revert the last one that changed anything. Taking one is cheap:
+//! the tree is cloned, but file contents stay shared with the live
+//! filesystem until one side writes them.
+
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+use crate::commands::filesystem::{get_node, Filesystem, Node, CURRENT_PATH, FILESYSTEM};
+use crate::commands::users;
+
+/// How many commands `undo` can walk back.
+const MAX_SNAPSHOTS: usize = 20;
+
+struct Snapshot {
+    command: String,
+    user: String,
+    filesystem: Filesystem,
+}
+
+lazy_static! {
+    static ref HISTORY: Mutex<Vec<Snapshot>> = Mutex::new(Vec::new());
+}
+
+/// The filesystem as it is right now.
+pub fn take() -> Filesystem {
+    FILESYSTEM.lock().unwrap().clone()
+}
+
+/// Keeps `before` for `undo` if running `command` changed the filesystem.
+pub fn record(before: Filesystem, command: &str) {
+    let changed = {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        filesystem.root != before.root
+            || filesystem.mounts != before.mounts
+            || !filesystem.inodes.unchanged_since(&before.inodes)
+    };
+    if !changed {
+        return;
+    }
+
+    let mut history = HISTORY.lock().unwrap();
+    if history.len() == MAX_SNAPSHOTS {
+        history.remove(0);
+    }
+    history.push(Snapshot {
+        command: command.to_string(),
+        user: users::current_user(),
+        filesystem: before,
+    });
+}
+
+/// Puts the filesystem back as it was before the most recent recorded
+/// command and returns that command. If the working directory went away
+/// with it, the shell moves up to the nearest directory that still exists.
+pub fn revert() -> Option<String> {
+    let snapshot = HISTORY.lock().unwrap().pop()?;
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let mut current_path = CURRENT_PATH.lock().unwrap();
+    *filesystem = snapshot.filesystem;
+    while !matches!(
+        get_node(&filesystem, &current_path),
+        Some(Node::Directory { .. })
+    ) {
+        current_path.pop();
+    }
+    Some(snapshot.command)
+}
+
+/// `undo [-l]`: reverts the last command that changed the filesystem, or
+/// lists the ones that can be reverted, most recent first.
+pub fn undo(args: &[&str]) -> String {
+    match args {
+        [] => {}
+        ["-l"] | ["--list"] => {
+            let history = HISTORY.lock().unwrap();
+            if history.is_empty() {
+                return "undo: nothing to undo".to_string();
+            }
+            return history
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(index, snapshot)| {
+                    format!(
+                        "{:>3}  {:<8} {}",
+                        index + 1,
+                        snapshot.user,
+                        snapshot.command
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        [option, ..] if option.starts_with('-') => {
+            return format!(
+                "undo: invalid option -- '{}'",
+                option.trim_start_matches('-')
+            )
+        }
+        [extra, ..] => return format!("undo: extra operand '{}'", extra),
+    }
+
+    let session = users::current_session();
+    {
+        let history = HISTORY.lock().unwrap();
+        match history.last() {
+            None => return "undo: nothing to undo".to_string(),
+            Some(last) if last.user != session.user && !session.is_root() => {
+                return format!(
+                    "undo: '{}' was run by {}; only they or root can undo it",
+                    last.command, last.user
+                );
+            }
+            Some(_) => {}
+        }
+    }
+
+    match revert() {
+        Some(command) => format!("Undid: {}", command),
+        None => "undo: nothing to undo".to_string(),
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ascii::AsciiArt;
+use crate::commands::{disk, users};
 use std::sync::OnceLock;
 use wasm_bindgen::prelude::*;
 
@@ -26,7 +27,14 @@ pub fn echo(args: &[&str]) -> String {
     } else if args[0] == "$USER" {
         AsciiArt::get_user()
     } else {
-        args.join(" ")
+        args.iter()
+            .map(|&arg| match arg {
+                "$HOME" => format!("/{}", users::home_dir().join("/")),
+                "$LOGNAME" => users::current_user(),
+                _ => arg.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 }
 
@@ -36,11 +44,19 @@ pub fn date(_args: &[&str]) -> String {
     date.to_iso_string().into()
 }
 
-pub fn uptime(_args: &[&str]) -> String {
-    let start = *START_TIME.get().unwrap_or(&now());
-    let elapsed = now() - start;
+/// Milliseconds since the epoch at which the page loaded, once `init` ran.
+pub fn boot_time() -> Option<f64> {
+    START_TIME.get().copied()
+}
 
-    let total_secs = (elapsed / 1000.0) as u64;
+/// Seconds since `init`, as `/proc/uptime` reports them.
+pub fn uptime_seconds() -> f64 {
+    let start = boot_time().unwrap_or_else(now);
+    (now() - start) / 1000.0
+}
+
+pub fn uptime(_args: &[&str]) -> String {
+    let total_secs = uptime_seconds() as u64;
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
     let seconds = total_secs % 60;
@@ -68,11 +84,13 @@ pub fn neofetch(_args: &[&str]) -> String {
         .~?JJJ?!:        Theme: Dark
        :?JJJ?!:          Icons: ASCII Art Pack
       .?JJ7^.            Terminal: objz-term
-      :JJ~               Memory: 521MiB / ∞ GiB
+      :JJ~               Memory: {}
        7!                
                        
 "#,
-        uptime_str, resolution_str
+        uptime_str,
+        resolution_str,
+        disk::memory_summary()
     )
 }
 
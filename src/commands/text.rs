@@ -0,0 +1,1390 @@
+use crate::commands::filesystem::{
+    is_binary, normalize_path, Filesystem, FsError, Inode, Node, CURRENT_PATH, FILESYSTEM,
+};
+use crate::commands::permissions::{self, Access};
+use crate::commands::regex::{Regex, Syntax};
+use crate::commands::users::{self, Session};
+use crate::terminal::ansi;
+
+/// Reads a regular file for one of the text tools, following symlinks
+/// and checking read permission.
+pub fn read_file(fs: &Filesystem, path: &[String], session: &Session) -> Result<String, FsError> {
+    let (_, node) = permissions::resolve(fs, path, session)?;
+    match node {
+        Node::File { inode } if permissions::check(fs, node, session, Access::Read) => {
+            Ok(fs.read(*inode))
+        }
+        Node::File { .. } => Err(FsError::PermissionDenied),
+        _ => Err(FsError::IsADirectory),
+    }
+}
+
+/// Like `read_file`, but the raw bytes, for tools that handle binary data.
+pub fn read_bytes(fs: &Filesystem, path: &[String], session: &Session) -> Result<Vec<u8>, FsError> {
+    let (_, node) = permissions::resolve(fs, path, session)?;
+    match node {
+        Node::File { inode } if permissions::check(fs, node, session, Access::Read) => {
+            Ok(fs.read_bytes(*inode))
+        }
+        Node::File { .. } => Err(FsError::PermissionDenied),
+        _ => Err(FsError::IsADirectory),
+    }
+}
+
+/// Replaces the content of the file at `path`, or creates it with mode
+/// 0644 if it does not exist yet.
+pub fn write_file(
+    fs: &mut Filesystem,
+    path: &[String],
+    content: Vec<u8>,
+    session: &Session,
+) -> Result<(), FsError> {
+    match permissions::resolve(fs, path, session) {
+        Ok((canonical, node @ Node::File { inode })) => {
+            fs.writable(&canonical)?;
+            if !permissions::check(fs, node, session, Access::Write) {
+                return Err(FsError::PermissionDenied);
+            }
+            let inode = *inode;
+            fs.inodes.write(inode, content);
+            Ok(())
+        }
+        Ok(_) => Err(FsError::IsADirectory),
+        Err(FsError::NotFound) => {
+            let (parent, name) = path.split_at(path.len().saturating_sub(1));
+            let name = name.first().ok_or(FsError::NotFound)?;
+            let parent = permissions::can_create(fs, parent, session)?;
+            let (children, inodes) = match fs.split_mut(&parent) {
+                Some((Node::Directory { children, .. }, inodes)) => (children, inodes),
+                _ => return Err(FsError::NotFound),
+            };
+            children.insert(
+                name.clone(),
+                inodes.create(Inode {
+                    content,
+                    permissions: 0o644,
+                    owner: session.user.clone(),
+                    group: session.primary_group(),
+                }),
+            );
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The inputs of a text filter: each file operand (`-` meaning stdin), or
+/// stdin alone when there are none. Failures are already worded for
+/// `command`.
+pub fn inputs(
+    command: &str,
+    operands: &[&str],
+    stdin: Option<&str>,
+) -> Vec<Result<(String, String), String>> {
+    if operands.is_empty() {
+        return vec![Ok(("-".to_string(), stdin.unwrap_or("").to_string()))];
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    operands
+        .iter()
+        .map(|&operand| {
+            if operand == "-" {
+                return Ok((operand.to_string(), stdin.unwrap_or("").to_string()));
+            }
+            let path = normalize_path(operand, &current_path);
+            read_file(&filesystem, &path, &session)
+                .map(|content| (operand.to_string(), content))
+                .map_err(|err| format!("{}: {}: {}", command, operand, err))
+        })
+        .collect()
+}
+
+/// Piped bytes as the text tools see them: decoded, without the newline
+/// that ends the last line, as command output is kept.
+pub fn decode(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    text.strip_suffix('\n').unwrap_or(&text).to_string()
+}
+
+/// Like `inputs`, but as bytes for the tools that work on binary files.
+pub fn binary_inputs(
+    command: &str,
+    operands: &[&str],
+    stdin: Option<&[u8]>,
+) -> Vec<Result<(String, Vec<u8>), String>> {
+    if operands.is_empty() {
+        return vec![Ok(("-".to_string(), stdin.unwrap_or(b"").to_vec()))];
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    operands
+        .iter()
+        .map(|&operand| {
+            if operand == "-" {
+                return Ok((operand.to_string(), stdin.unwrap_or(b"").to_vec()));
+            }
+            let path = normalize_path(operand, &current_path);
+            read_bytes(&filesystem, &path, &session)
+                .map(|content| (operand.to_string(), content))
+                .map_err(|err| format!("{}: {}: {}", command, operand, err))
+        })
+        .collect()
+}
+
+/// A head/tail count: an optional `+`/`-` sign and the amount.
+type Count = (Option<char>, usize);
+
+/// Parses a line or byte count such as `10`, `-5` or `+3`, returning the
+/// sign separately.
+fn parse_count(value: &str) -> Option<Count> {
+    match value.chars().next()? {
+        sign @ ('+' | '-') => Some((Some(sign), value[1..].parse().ok()?)),
+        _ => Some((None, value.parse().ok()?)),
+    }
+}
+
+/// Splits `-n 5`, `-n5` and `-5` style options shared by head and tail.
+/// Returns (count, by_bytes, operands).
+fn split_count_args<'a>(
+    command: &str,
+    args: &[&'a str],
+) -> Result<(Option<Count>, bool, Vec<&'a str>), String> {
+    let mut count = None;
+    let mut bytes = false;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let (flag, attached) = match arg.strip_prefix('-') {
+            Some(option) if !option.is_empty() => {
+                let mut chars = option.chars();
+                let flag = chars.next().unwrap();
+                (flag, chars.as_str())
+            }
+            _ => {
+                operands.push(arg);
+                continue;
+            }
+        };
+
+        match flag {
+            'n' | 'c' => {
+                let value = if !attached.is_empty() {
+                    attached
+                } else if let Some((&next, tail)) = rest.split_first() {
+                    rest = tail;
+                    next
+                } else {
+                    return Err(format!(
+                        "{}: option requires an argument -- '{}'",
+                        command, flag
+                    ));
+                };
+                let what = if flag == 'n' { "lines" } else { "bytes" };
+                count = Some(parse_count(value).ok_or_else(|| {
+                    format!("{}: invalid number of {}: '{}'", command, what, value)
+                })?);
+                bytes = flag == 'c';
+            }
+            c if c.is_ascii_digit() => {
+                let value = &arg[1..];
+                let lines = value
+                    .parse()
+                    .map_err(|_| format!("{}: invalid number of lines: '{}'", command, value))?;
+                count = Some((None, lines));
+            }
+            _ => return Err(format!("{}: invalid option -- '{}'", command, flag)),
+        }
+    }
+
+    Ok((count, bytes, operands))
+}
+
+/// Shared body of head and tail: picks a slice of each input and adds
+/// `==> name <==` headers when there is more than one. Works on bytes so
+/// `head -c` can cut binary files.
+fn head_or_tail(
+    command: &str,
+    args: &[&str],
+    stdin: Option<&[u8]>,
+    pick: fn(&[u8], Option<Count>, bool) -> &[u8],
+) -> Vec<u8> {
+    let (count, bytes, operands) = match split_count_args(command, args) {
+        Ok(parsed) => parsed,
+        Err(err) => return format!("{}\n", err).into_bytes(),
+    };
+
+    let inputs = binary_inputs(command, &operands, stdin);
+    let headers = inputs.len() > 1;
+    let mut output = Vec::new();
+
+    for input in inputs {
+        match input {
+            Ok((name, content)) => {
+                if headers {
+                    if !output.is_empty() {
+                        output.push(b'\n');
+                    }
+                    let name = if name == "-" { "standard input" } else { &name };
+                    output.extend(format!("==> {} <==\n", name).into_bytes());
+                }
+                output.extend_from_slice(pick(&content, count, bytes));
+                if headers && !output.ends_with(b"\n") {
+                    output.push(b'\n');
+                }
+            }
+            Err(err) => output.extend(format!("{}\n", err).into_bytes()),
+        }
+    }
+
+    output
+}
+
+/// Splits `content` into lines, each keeping its newline.
+fn raw_lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+/// `head [-n [-]N | -c N | -N] [file...]`: the first ten lines by default;
+/// `-n -N` prints all but the last N.
+pub fn head(args: &[&str], stdin: Option<&[u8]>) -> Vec<u8> {
+    head_or_tail("head", args, stdin, |content, count, bytes| {
+        let (sign, amount) = count.unwrap_or((None, 10));
+        if bytes {
+            let end = match sign {
+                Some('-') => content.len().saturating_sub(amount),
+                _ => amount.min(content.len()),
+            };
+            return &content[..end];
+        }
+
+        let lines = raw_lines(content);
+        let keep = match sign {
+            Some('-') => lines.len().saturating_sub(amount),
+            _ => amount.min(lines.len()),
+        };
+        let end = lines[..keep].iter().map(|line| line.len()).sum();
+        &content[..end]
+    })
+}
+
+/// `tail [-n [+]N | -c N | -N] [file...]`: the last ten lines by default;
+/// `-n +N` starts at line N.
+pub fn tail(args: &[&str], stdin: Option<&[u8]>) -> Vec<u8> {
+    head_or_tail("tail", args, stdin, |content, count, bytes| {
+        let (sign, amount) = count.unwrap_or((None, 10));
+        if bytes {
+            let start = match sign {
+                Some('+') => amount.saturating_sub(1).min(content.len()),
+                _ => content.len().saturating_sub(amount),
+            };
+            return &content[start..];
+        }
+
+        let lines = raw_lines(content);
+        let skip = match sign {
+            Some('+') => amount.saturating_sub(1).min(lines.len()),
+            _ => lines.len().saturating_sub(amount),
+        };
+        let start = lines[..skip].iter().map(|line| line.len()).sum();
+        &content[start..]
+    })
+}
+
+/// `wc [-l] [-w] [-c] [-m] [file...]`, counting the bytes as they are,
+/// piped ones included. A last line without a trailing newline still
+/// counts as a line, since files here rarely end in one.
+pub fn wc(args: &[&str], stdin: Option<&[u8]>) -> String {
+    let mut show = [false; 4];
+    let mut operands = Vec::new();
+
+    for &arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'l' => show[0] = true,
+                        'w' => show[1] = true,
+                        'm' => show[2] = true,
+                        'c' => show[3] = true,
+                        _ => return format!("wc: invalid option -- '{}'", flag),
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if !show.contains(&true) {
+        show = [true, true, false, true];
+    }
+
+    let mut rows: Vec<([usize; 4], Option<String>)> = Vec::new();
+    let mut errors = Vec::new();
+    let mut total = [0; 4];
+    for input in binary_inputs("wc", &operands, stdin) {
+        match input {
+            Ok((name, data)) => {
+                let text = String::from_utf8_lossy(&data);
+                let unterminated = data.last().is_some_and(|&b| b != b'\n');
+                let counts = [
+                    data.iter().filter(|&&b| b == b'\n').count() + unterminated as usize,
+                    text.split_whitespace().count(),
+                    text.chars().count(),
+                    data.len(),
+                ];
+                for (sum, count) in total.iter_mut().zip(counts) {
+                    *sum += count;
+                }
+                rows.push((counts, (!operands.is_empty()).then_some(name)));
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    if rows.len() > 1 {
+        rows.push((total, Some("total".to_string())));
+    }
+
+    let columns = show.iter().filter(|&&shown| shown).count();
+    let width = if columns == 1 && rows.len() == 1 {
+        1
+    } else {
+        rows.iter()
+            .flat_map(|(counts, _)| counts.iter())
+            .map(|count| count.to_string().len())
+            .max()
+            .unwrap_or(1)
+    };
+
+    let mut output = errors;
+    for (counts, name) in rows {
+        let mut fields: Vec<String> = counts
+            .iter()
+            .zip(show)
+            .filter(|(_, shown)| *shown)
+            .map(|(count, _)| format!("{:>width$}", count, width = width))
+            .collect();
+        if let Some(name) = name {
+            fields.push(name);
+        }
+        output.push(fields.join(" "));
+    }
+    output.join("\n")
+}
+
+/// `sort [-r] [-n] [-u] [-f] [-t sep] [-k start[,end]] [file...]`. Ties on
+/// the key fall back to comparing whole lines, as GNU sort does.
+pub fn sort(args: &[&str], stdin: Option<&str>) -> String {
+    let mut reverse = false;
+    let mut numeric = false;
+    let mut unique = false;
+    let mut fold_case = false;
+    let mut separator: Option<char> = None;
+    let mut key: Option<(usize, Option<usize>)> = None;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => flags,
+            _ => {
+                operands.push(arg);
+                continue;
+            }
+        };
+
+        for (index, flag) in flags.char_indices() {
+            match flag {
+                'r' => reverse = true,
+                'n' => numeric = true,
+                'u' => unique = true,
+                'f' => fold_case = true,
+                't' | 'k' => {
+                    let attached = &flags[index + 1..];
+                    let value = if !attached.is_empty() {
+                        attached
+                    } else if let Some((&next, tail)) = rest.split_first() {
+                        rest = tail;
+                        next
+                    } else {
+                        return format!("sort: option requires an argument -- '{}'", flag);
+                    };
+
+                    if flag == 't' {
+                        let mut chars = value.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => separator = Some(c),
+                            _ => return "sort: multi-character tab".to_string(),
+                        }
+                    } else {
+                        let parse = |field: &str| {
+                            // Ignore per-key modifiers such as `2n` or `1.3`.
+                            let digits: String =
+                                field.chars().take_while(|c| c.is_ascii_digit()).collect();
+                            digits.parse::<usize>().ok().filter(|&n| n > 0)
+                        };
+                        let (start, end) = match value.split_once(',') {
+                            Some((start, end)) => (parse(start), Some(parse(end))),
+                            None => (parse(value), None),
+                        };
+                        match (start, end) {
+                            (Some(start), None) => key = Some((start, None)),
+                            (Some(start), Some(Some(end))) => key = Some((start, Some(end))),
+                            _ => return format!("sort: invalid number at field start: invalid count at start of '{}'", value),
+                        }
+                        if value.contains('n') {
+                            numeric = true;
+                        }
+                        if value.contains('r') {
+                            reverse = true;
+                        }
+                    }
+                    break;
+                }
+                _ => return format!("sort: invalid option -- '{}'", flag),
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+    for input in inputs("sort", &operands, stdin) {
+        match input {
+            Ok((_, content)) => lines.extend(content.lines().map(str::to_string)),
+            Err(err) => output.push(err),
+        }
+    }
+
+    let key_of = |line: &str| -> String {
+        let (start, end) = match key {
+            Some(key) => key,
+            None => return line.to_string(),
+        };
+        let fields = split_fields(line, separator);
+        let end = end.unwrap_or(fields.len()).min(fields.len());
+        if start > end {
+            return String::new();
+        }
+        let joiner = separator.map_or(String::new(), String::from);
+        fields[start - 1..end].join(&joiner)
+    };
+
+    let compare = |a: &String, b: &String| {
+        let (ka, kb) = (key_of(a), key_of(b));
+        let by_key = if numeric {
+            leading_number(&ka)
+                .partial_cmp(&leading_number(&kb))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        } else if fold_case {
+            ka.to_lowercase().cmp(&kb.to_lowercase())
+        } else {
+            ka.cmp(&kb)
+        };
+        let ordering = by_key.then_with(|| {
+            if unique {
+                std::cmp::Ordering::Equal
+            } else {
+                a.cmp(b)
+            }
+        });
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    };
+
+    lines.sort_by(compare);
+    if unique {
+        lines.dedup_by(|a, b| compare(a, b) == std::cmp::Ordering::Equal);
+    }
+
+    output.extend(lines);
+    output.join("\n")
+}
+
+/// Fields for `sort -k`: split on `separator`, or else at each run of
+/// blanks, where (as in GNU sort) the blanks belong to the field after.
+fn split_fields(line: &str, separator: Option<char>) -> Vec<String> {
+    if let Some(separator) = separator {
+        return line.split(separator).map(str::to_string).collect();
+    }
+
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_blanks = true;
+    for c in line.chars() {
+        let blank = c == ' ' || c == '\t';
+        if blank && !in_blanks && !field.is_empty() {
+            fields.push(std::mem::take(&mut field));
+        }
+        in_blanks = blank;
+        field.push(c);
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// The number at the start of `text` for `sort -n`; anything else is 0.
+fn leading_number(text: &str) -> f64 {
+    let text = text.trim_start();
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map_or(text.len(), |(i, _)| i);
+    text[..end].parse().unwrap_or(0.0)
+}
+
+/// `uniq [-c] [-d] [-u] [-i] [input]`: collapses adjacent repeated lines.
+pub fn uniq(args: &[&str], stdin: Option<&str>) -> String {
+    let mut count = false;
+    let mut repeated_only = false;
+    let mut unique_only = false;
+    let mut fold_case = false;
+    let mut operands = Vec::new();
+
+    for &arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'c' => count = true,
+                        'd' => repeated_only = true,
+                        'u' => unique_only = true,
+                        'i' => fold_case = true,
+                        _ => return format!("uniq: invalid option -- '{}'", flag),
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if operands.len() > 1 {
+        return format!("uniq: extra operand '{}'", operands[1]);
+    }
+
+    let content = match inputs("uniq", &operands, stdin).pop() {
+        Some(Ok((_, content))) => content,
+        Some(Err(err)) => return err,
+        None => String::new(),
+    };
+
+    let same = |a: &str, b: &str| {
+        if fold_case {
+            a.to_lowercase() == b.to_lowercase()
+        } else {
+            a == b
+        }
+    };
+
+    let mut groups: Vec<(&str, usize)> = Vec::new();
+    for line in content.lines() {
+        match groups.last_mut() {
+            Some((last, n)) if same(last, line) => *n += 1,
+            _ => groups.push((line, 1)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|&(_, n)| (n > 1 || !repeated_only) && (n == 1 || !unique_only))
+        .map(|(line, n)| {
+            if count {
+                format!("{:>7} {}", n, line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses a `cut` list such as `1,3-5,7-` into inclusive 1-based ranges.
+fn parse_ranges(list: &str) -> Option<Vec<(usize, usize)>> {
+    list.split(',')
+        .map(|part| {
+            let (start, end) = match part.split_once('-') {
+                Some((start, end)) => (
+                    if start.is_empty() {
+                        1
+                    } else {
+                        start.parse().ok()?
+                    },
+                    if end.is_empty() {
+                        usize::MAX
+                    } else {
+                        end.parse().ok()?
+                    },
+                ),
+                None => {
+                    let n = part.parse().ok()?;
+                    (n, n)
+                }
+            };
+            (start > 0 && start <= end).then_some((start, end))
+        })
+        .collect()
+}
+
+/// `cut -f list [-d delim] [-s] | -c list | -b list [file...]`.
+pub fn cut(args: &[&str], stdin: Option<&str>) -> String {
+    let mut mode: Option<(char, Vec<(usize, usize)>)> = None;
+    let mut delimiter = '\t';
+    let mut only_delimited = false;
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => flags,
+            _ => {
+                operands.push(arg);
+                continue;
+            }
+        };
+
+        let mut chars = flags.chars();
+        let flag = chars.next().unwrap();
+        if flag == 's' && chars.as_str().is_empty() {
+            only_delimited = true;
+            continue;
+        }
+        if !matches!(flag, 'f' | 'c' | 'b' | 'd') {
+            return format!("cut: invalid option -- '{}'", flag);
+        }
+
+        let attached = chars.as_str();
+        let value = if !attached.is_empty() {
+            attached
+        } else if let Some((&next, tail)) = rest.split_first() {
+            rest = tail;
+            next
+        } else {
+            return format!("cut: option requires an argument -- '{}'", flag);
+        };
+
+        if flag == 'd' {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => delimiter = c,
+                _ => return "cut: the delimiter must be a single character".to_string(),
+            }
+            continue;
+        }
+        if mode.is_some() {
+            return "cut: only one type of list may be specified".to_string();
+        }
+        match parse_ranges(value) {
+            Some(ranges) => mode = Some((if flag == 'f' { 'f' } else { 'c' }, ranges)),
+            None => return format!("cut: invalid field value '{}'", value),
+        }
+    }
+
+    let (mode, ranges) = match mode {
+        Some(mode) => mode,
+        None => return "cut: you must specify a list of bytes, characters, or fields".to_string(),
+    };
+    let selected = |n: usize| ranges.iter().any(|&(start, end)| start <= n && n <= end);
+
+    let mut output = Vec::new();
+    for input in inputs("cut", &operands, stdin) {
+        let content = match input {
+            Ok((_, content)) => content,
+            Err(err) => {
+                output.push(err);
+                continue;
+            }
+        };
+
+        for line in content.lines() {
+            if mode == 'c' {
+                output.push(
+                    line.chars()
+                        .enumerate()
+                        .filter(|(i, _)| selected(i + 1))
+                        .map(|(_, c)| c)
+                        .collect(),
+                );
+            } else if !line.contains(delimiter) {
+                if !only_delimited {
+                    output.push(line.to_string());
+                }
+            } else {
+                output.push(
+                    line.split(delimiter)
+                        .enumerate()
+                        .filter(|(i, _)| selected(i + 1))
+                        .map(|(_, field)| field)
+                        .collect::<Vec<_>>()
+                        .join(&delimiter.to_string()),
+                );
+            }
+        }
+    }
+
+    output.join("\n")
+}
+
+/// Expands a `tr` set: ranges (`a-z`), escapes (`\n`, `\t`, `\\`) and
+/// classes (`[:upper:]` and friends).
+fn expand_set(set: &str) -> Result<Vec<char>, String> {
+    let mut chars: Vec<char> = Vec::new();
+    let mut source = set;
+
+    while !source.is_empty() {
+        if let Some(rest) = source.strip_prefix("[:") {
+            if let Some(end) = rest.find(":]") {
+                let class: Vec<char> = match &rest[..end] {
+                    "upper" => ('A'..='Z').collect(),
+                    "lower" => ('a'..='z').collect(),
+                    "digit" => ('0'..='9').collect(),
+                    "alpha" => ('A'..='Z').chain('a'..='z').collect(),
+                    "alnum" => ('0'..='9').chain('A'..='Z').chain('a'..='z').collect(),
+                    "space" => vec![' ', '\t', '\n', '\r', '\x0b', '\x0c'],
+                    "blank" => vec![' ', '\t'],
+                    "punct" => (' '..='~').filter(|c| c.is_ascii_punctuation()).collect(),
+                    name => return Err(format!("tr: invalid character class '{}'", name)),
+                };
+                chars.extend(class);
+                source = &rest[end + 2..];
+                continue;
+            }
+        }
+
+        let mut iter = source.chars();
+        let c = match iter.next().unwrap() {
+            '\\' => match iter.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some(other) => other,
+                None => '\\',
+            },
+            c => c,
+        };
+        source = iter.as_str();
+
+        // `c-d` is a range unless the dash is the last character.
+        let mut lookahead = source.chars();
+        if lookahead.next() == Some('-') {
+            if let Some(end) = lookahead.next() {
+                if end < c {
+                    return Err(format!(
+                        "tr: range-endpoints of '{}-{}' are in reverse collating sequence order",
+                        c, end
+                    ));
+                }
+                chars.extend(c..=end);
+                source = lookahead.as_str();
+                continue;
+            }
+        }
+        chars.push(c);
+    }
+
+    Ok(chars)
+}
+
+/// `tr [-d] [-s] [-c] set1 [set2]`, reading stdin only, like the real one.
+pub fn tr(args: &[&str], stdin: Option<&str>) -> String {
+    let mut delete = false;
+    let mut squeeze = false;
+    let mut complement = false;
+    let mut sets = Vec::new();
+
+    for &arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && sets.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'd' => delete = true,
+                        's' => squeeze = true,
+                        'c' | 'C' => complement = true,
+                        _ => return format!("tr: invalid option -- '{}'", flag),
+                    }
+                }
+            }
+            _ => sets.push(arg),
+        }
+    }
+
+    // With -d, set2 only exists to be squeezed; -s alone takes one or two.
+    let needed = match (delete, squeeze) {
+        (true, true) => 2..=2,
+        (true, false) => 1..=1,
+        (false, true) => 1..=2,
+        (false, false) => 2..=2,
+    };
+    if sets.len() < *needed.start() {
+        return match sets.first() {
+            Some(set) => format!("tr: missing operand after '{}'", set),
+            None => "tr: missing operand".to_string(),
+        };
+    }
+    if sets.len() > *needed.end() {
+        return format!("tr: extra operand '{}'", sets[*needed.end()]);
+    }
+
+    let set1 = match expand_set(sets[0]) {
+        Ok(set) => set,
+        Err(err) => return err,
+    };
+    let set2 = match sets.get(1).map(|set| expand_set(set)).transpose() {
+        Ok(set) => set.unwrap_or_default(),
+        Err(err) => return err,
+    };
+    let in_set1 = |c: char| set1.contains(&c) != complement;
+    let squeeze_only = set2.is_empty();
+
+    let mut output = String::new();
+    let mut last: Option<char> = None;
+    for c in stdin.unwrap_or("").chars() {
+        let mapped = if delete {
+            if in_set1(c) {
+                continue;
+            }
+            c
+        } else if !squeeze_only && in_set1(c) {
+            // Set2 is padded with its last character, as in GNU tr.
+            let index = if complement {
+                set2.len() - 1
+            } else {
+                set1.iter().position(|&x| x == c).unwrap()
+            };
+            set2[index.min(set2.len() - 1)]
+        } else {
+            c
+        };
+
+        let squeezable = if set2.is_empty() {
+            in_set1(mapped)
+        } else {
+            set2.contains(&mapped)
+        };
+        if squeeze && squeezable && last == Some(mapped) {
+            continue;
+        }
+        last = Some(mapped);
+        output.push(mapped);
+    }
+
+    output
+}
+
+/// `rev [file...]`: reverses every line character by character.
+pub fn rev(args: &[&str], stdin: Option<&str>) -> String {
+    let mut output = Vec::new();
+    for input in inputs("rev", args, stdin) {
+        match input {
+            Ok((_, content)) => output.extend(
+                content
+                    .lines()
+                    .map(|line| line.chars().rev().collect::<String>()),
+            ),
+            Err(err) => output.push(err),
+        }
+    }
+    output.join("\n")
+}
+
+/// `nl [-b a|t|n] [-n ln|rn|rz] [-w width] [-s sep] [file...]`. Only
+/// non-empty lines are numbered unless `-b a` says otherwise.
+pub fn nl(args: &[&str], stdin: Option<&str>) -> String {
+    let mut style = 't';
+    let mut format = "rn".to_string();
+    let mut width = 6;
+    let mut separator = "\t".to_string();
+    let mut operands = Vec::new();
+
+    let mut rest = args;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => flags,
+            _ => {
+                operands.push(arg);
+                continue;
+            }
+        };
+
+        let mut chars = flags.chars();
+        let flag = chars.next().unwrap();
+        let attached = chars.as_str();
+        let value = if !attached.is_empty() {
+            attached
+        } else if let Some((&next, tail)) = rest.split_first() {
+            rest = tail;
+            next
+        } else {
+            return format!("nl: option requires an argument -- '{}'", flag);
+        };
+
+        match flag {
+            'b' => match value {
+                "a" | "t" | "n" => style = value.chars().next().unwrap(),
+                _ => return format!("nl: invalid body numbering style: '{}'", value),
+            },
+            'n' => match value {
+                "ln" | "rn" | "rz" => format = value.to_string(),
+                _ => return format!("nl: invalid line numbering format: '{}'", value),
+            },
+            'w' => match value.parse() {
+                Ok(n) if n > 0 => width = n,
+                _ => return format!("nl: invalid line number field width: '{}'", value),
+            },
+            's' => separator = value.to_string(),
+            _ => return format!("nl: invalid option -- '{}'", flag),
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut number = 1;
+    for input in inputs("nl", &operands, stdin) {
+        let content = match input {
+            Ok((_, content)) => content,
+            Err(err) => {
+                output.push(err);
+                continue;
+            }
+        };
+
+        for line in content.lines() {
+            let numbered = match style {
+                'a' => true,
+                't' => !line.is_empty(),
+                _ => false,
+            };
+            if !numbered {
+                output.push(format!(
+                    "{:width$}{}",
+                    "",
+                    line,
+                    width = width + separator.len()
+                ));
+                continue;
+            }
+            let label = match format.as_str() {
+                "ln" => format!("{:<width$}", number, width = width),
+                "rz" => format!("{:0>width$}", number, width = width),
+                _ => format!("{:>width$}", number, width = width),
+            };
+            output.push(format!("{}{}{}", label, separator, line));
+            number += 1;
+        }
+    }
+
+    output.join("\n")
+}
+
+struct GrepOptions {
+    patterns: Vec<Regex>,
+    invert: bool,
+    line_numbers: bool,
+    count: bool,
+    files_only: bool,
+    with_filename: bool,
+    before: usize,
+    after: usize,
+    color: bool,
+    text: bool,
+}
+
+/// `grep [options] pattern [file...]`. Reads stdin when no files are
+/// given (and `-r` isn't), highlighting matches like `grep --color`.
+pub fn grep(args: &[&str], stdin: Option<&str>) -> String {
+    let mut syntax = Syntax::Basic;
+    let mut fixed = false;
+    let mut ignore_case = false;
+    let mut recursive = false;
+    let mut filename_flag = None;
+    let mut patterns: Vec<String> = Vec::new();
+    let mut operands: Vec<&str> = Vec::new();
+    let mut options = GrepOptions {
+        patterns: Vec::new(),
+        invert: false,
+        line_numbers: false,
+        count: false,
+        files_only: false,
+        with_filename: false,
+        before: 0,
+        after: 0,
+        color: true,
+        text: false,
+    };
+
+    let mut rest = args;
+    let mut options_done = false;
+    while let Some((&arg, tail)) = rest.split_first() {
+        rest = tail;
+
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            operands.push(arg);
+            continue;
+        }
+        if arg == "--" {
+            options_done = true;
+            continue;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            match long {
+                "color" | "colour" | "color=always" | "colour=always" | "color=auto"
+                | "colour=auto" => options.color = true,
+                "color=never" | "colour=never" => options.color = false,
+                "ignore-case" => ignore_case = true,
+                "invert-match" => options.invert = true,
+                "line-number" => options.line_numbers = true,
+                "count" => options.count = true,
+                "recursive" => recursive = true,
+                "text" | "binary-files=text" => options.text = true,
+                "binary-files=binary" => options.text = false,
+                _ => return format!("grep: unrecognized option '{}'", arg),
+            }
+            continue;
+        }
+
+        let flags: Vec<char> = arg[1..].chars().collect();
+        for (index, &flag) in flags.iter().enumerate() {
+            match flag {
+                'a' => options.text = true,
+                'E' => syntax = Syntax::Extended,
+                'G' => syntax = Syntax::Basic,
+                'F' => fixed = true,
+                'i' => ignore_case = true,
+                'v' => options.invert = true,
+                'n' => options.line_numbers = true,
+                'c' => options.count = true,
+                'l' => options.files_only = true,
+                'r' | 'R' => recursive = true,
+                'h' => filename_flag = Some(false),
+                'H' => filename_flag = Some(true),
+                'A' | 'B' | 'C' | 'e' => {
+                    // The value is the rest of this word, or the next one.
+                    let attached: String = flags[index + 1..].iter().collect();
+                    let value = if !attached.is_empty() {
+                        attached
+                    } else if let Some((&next, tail)) = rest.split_first() {
+                        rest = tail;
+                        next.to_string()
+                    } else {
+                        return format!("grep: option requires an argument -- '{}'", flag);
+                    };
+
+                    if flag == 'e' {
+                        patterns.push(value);
+                    } else {
+                        let lines = match value.parse::<usize>() {
+                            Ok(lines) => lines,
+                            Err(_) => {
+                                return format!("grep: {}: invalid context length argument", value)
+                            }
+                        };
+                        if flag != 'A' {
+                            options.before = lines;
+                        }
+                        if flag != 'B' {
+                            options.after = lines;
+                        }
+                    }
+                    break;
+                }
+                _ => return format!("grep: invalid option -- '{}'", flag),
+            }
+        }
+    }
+
+    if patterns.is_empty() {
+        if operands.is_empty() {
+            return "Usage: grep [OPTION]... PATTERNS [FILE]...".into();
+        }
+        patterns.push(operands.remove(0).to_string());
+    }
+
+    for pattern in &patterns {
+        let regex = if fixed {
+            Regex::literal(pattern, ignore_case)
+        } else {
+            Regex::new(pattern, syntax, ignore_case)
+        };
+        match regex {
+            Ok(regex) => options.patterns.push(regex),
+            Err(err) => return format!("grep: {}", err),
+        }
+    }
+
+    if operands.is_empty() && recursive {
+        operands.push(".");
+    }
+    options.with_filename = filename_flag.unwrap_or(recursive || operands.len() > 1);
+
+    let mut output = Vec::new();
+    let mut printed_any = false;
+
+    if operands.is_empty() {
+        if let Err(err) = grep_input(
+            &options,
+            "(standard input)",
+            stdin.unwrap_or("").as_bytes(),
+            &mut output,
+            &mut printed_any,
+        ) {
+            output.push(format!("grep: {}", err));
+        }
+        return output.join("\n");
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+
+    let mut inputs: Vec<(String, Result<Vec<u8>, FsError>)> = Vec::new();
+    for &operand in &operands {
+        if operand == "-" {
+            inputs.push((
+                "(standard input)".into(),
+                Ok(stdin.unwrap_or("").as_bytes().to_vec()),
+            ));
+            continue;
+        }
+
+        let path = normalize_path(operand, &current_path);
+        match permissions::resolve(&filesystem, &path, &session) {
+            Ok((canonical, Node::Directory { .. })) if recursive => {
+                collect_files(&filesystem, &session, operand, &canonical, &mut inputs)
+            }
+            _ => inputs.push((
+                operand.to_string(),
+                read_bytes(&filesystem, &path, &session),
+            )),
+        }
+    }
+
+    for (name, content) in inputs {
+        match content {
+            Ok(content) => {
+                if let Err(err) =
+                    grep_input(&options, &name, &content, &mut output, &mut printed_any)
+                {
+                    output.push(format!("grep: {}", err));
+                    break;
+                }
+            }
+            Err(err) => output.push(format!("grep: {}: {}", name, err)),
+        }
+    }
+
+    output.join("\n")
+}
+
+/// Every readable file below `path` for `grep -r`, in name order.
+/// Symlinks inside the tree are skipped, as GNU grep does.
+fn collect_files(
+    fs: &Filesystem,
+    session: &Session,
+    display: &str,
+    path: &[String],
+    inputs: &mut Vec<(String, Result<Vec<u8>, FsError>)>,
+) {
+    let node = match permissions::resolve(fs, path, session) {
+        Ok((_, node)) => node,
+        Err(err) => return inputs.push((display.to_string(), Err(err))),
+    };
+    let children = match node {
+        Node::Directory { children, .. } => children,
+        _ => return inputs.push((display.to_string(), read_bytes(fs, path, session))),
+    };
+
+    if !permissions::check(fs, node, session, Access::Read)
+        || !permissions::check(fs, node, session, Access::Execute)
+    {
+        return inputs.push((display.to_string(), Err(FsError::PermissionDenied)));
+    }
+
+    for (name, child) in children {
+        if let Node::Symlink { .. } = child {
+            continue;
+        }
+        let mut child_path = path.to_vec();
+        child_path.push(name.clone());
+        let child_display = format!("{}/{}", display.trim_end_matches('/'), name);
+        collect_files(fs, session, &child_display, &child_path, inputs);
+    }
+}
+
+/// Greps one input. Binary content only reports whether it matched,
+/// unless `-a` asks for it to be treated as text.
+fn grep_input(
+    options: &GrepOptions,
+    name: &str,
+    content: &[u8],
+    output: &mut Vec<String>,
+    printed_any: &mut bool,
+) -> Result<(), String> {
+    let text = String::from_utf8_lossy(content);
+    if options.text || options.count || options.files_only || !is_binary(content) {
+        return grep_text(options, name, &text, output, printed_any);
+    }
+
+    let mut lines = Vec::new();
+    grep_text(options, name, &text, &mut lines, &mut false)?;
+    if !lines.is_empty() {
+        output.push(format!("grep: {}: binary file matches", name));
+    }
+    Ok(())
+}
+
+fn grep_text(
+    options: &GrepOptions,
+    name: &str,
+    content: &str,
+    output: &mut Vec<String>,
+    printed_any: &mut bool,
+) -> Result<(), String> {
+    let paint = |text: &str, code: &str| {
+        if options.color {
+            ansi::paint(text, code)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut selected = Vec::with_capacity(lines.len());
+    for line in &lines {
+        let mut hit = false;
+        for re in &options.patterns {
+            if re.is_match(line)? {
+                hit = true;
+                break;
+            }
+        }
+        selected.push(hit != options.invert);
+    }
+    let matches = selected.iter().filter(|&&s| s).count();
+
+    if options.files_only {
+        if matches > 0 {
+            output.push(paint(name, "35"));
+        }
+        return Ok(());
+    }
+    if options.count {
+        output.push(if options.with_filename {
+            format!("{}{}{}", paint(name, "35"), paint(":", "36"), matches)
+        } else {
+            matches.to_string()
+        });
+        return Ok(());
+    }
+
+    // Lines to show: every selected one plus its context window.
+    let mut shown = vec![false; lines.len()];
+    for (index, _) in selected.iter().enumerate().filter(|(_, &s)| s) {
+        let from = index.saturating_sub(options.before);
+        let to = (index + options.after).min(lines.len().saturating_sub(1));
+        for slot in &mut shown[from..=to] {
+            *slot = true;
+        }
+    }
+
+    let has_context = options.before > 0 || options.after > 0;
+    let mut previous: Option<usize> = None;
+    for (index, line) in lines.iter().enumerate() {
+        if !shown[index] {
+            continue;
+        }
+        let gap = previous.is_none_or(|previous| index > previous + 1);
+        if has_context && gap && *printed_any {
+            output.push(paint("--", "36"));
+        }
+        previous = Some(index);
+        *printed_any = true;
+
+        let separator = if selected[index] { ":" } else { "-" };
+        let mut prefix = String::new();
+        if options.with_filename {
+            prefix.push_str(&paint(name, "35"));
+            prefix.push_str(&paint(separator, "36"));
+        }
+        if options.line_numbers {
+            prefix.push_str(&paint(&(index + 1).to_string(), "32"));
+            prefix.push_str(&paint(separator, "36"));
+        }
+
+        let body = if selected[index] && !options.invert && options.color {
+            highlight(line, &options.patterns)?
+        } else {
+            line.to_string()
+        };
+        output.push(format!("{}{}", prefix, body));
+    }
+    Ok(())
+}
+
+/// Paints every match of any pattern in bold red.
+fn highlight(line: &str, patterns: &[Regex]) -> Result<String, String> {
+    let mut ranges = Vec::new();
+    for re in patterns {
+        for found in re.find_iter(line)? {
+            if found.end > found.start {
+                ranges.push((found.start, found.end));
+            }
+        }
+    }
+    ranges.sort();
+
+    let mut result = String::new();
+    let mut position = 0;
+    for (start, end) in ranges {
+        if end <= position {
+            continue;
+        }
+        let start = start.max(position);
+        result.push_str(&line[position..start]);
+        result.push_str(&ansi::paint(&line[start..end], "1;31"));
+        position = end;
+    }
+    result.push_str(&line[position..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grep_reports_binary_matches() {
+        let wav = "RIFF\u{0}\u{0}WAVEfmt \nRIFF";
+        assert_eq!(
+            grep(&["RIFF"], Some(wav)),
+            "grep: (standard input): binary file matches"
+        );
+        assert_eq!(grep(&["MP3"], Some(wav)), "");
+        assert_eq!(grep(&["-c", "RIFF"], Some(wav)), "2");
+        assert_eq!(
+            grep(&["-a", "--color=never", "WAVE"], Some(wav)),
+            "RIFF\u{0}\u{0}WAVEfmt "
+        );
+    }
+
+    #[test]
+    fn grep_reads_text_lines() {
+        let text = "alpha\nbeta\ngamma";
+        assert_eq!(
+            grep(&["--color=never", "-n", "a$"], Some(text)),
+            "1:alpha\n2:beta\n3:gamma"
+        );
+        assert_eq!(grep(&["-v", "-c", "e"], Some(text)), "2");
+        assert_eq!(grep(&["-E", "("], Some(text)), "grep: Unmatched ( or \\(");
+    }
+}
@@ -0,0 +1,220 @@
+//! `upload` and `download`: moving files between the visitor's machine
+//! and the filesystem, by way of the host bridge.
+
+use crate::commands::archive;
+use crate::commands::disk;
+use crate::commands::filesystem::{normalize_path, FsError, Node, CURRENT_PATH, FILESYSTEM};
+use crate::commands::permissions::{self, Access};
+use crate::commands::text;
+use crate::commands::users;
+use crate::utils::host::{self, HostFile};
+
+fn size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} bytes", bytes)
+    } else {
+        disk::human(bytes as u64)
+    }
+}
+
+/// `upload`: opens the browser's file picker and stores the chosen file
+/// in the working directory, replacing one of the same name.
+pub async fn upload(args: &[String]) -> String {
+    if let Some(extra) = args.first() {
+        return format!("upload: extra operand '{}'", extra);
+    }
+    match host::bridge().pick_file().await {
+        Ok(Some(file)) => store(file),
+        Ok(None) => "upload: cancelled".to_string(),
+        Err(err) => format!("upload: {}", err),
+    }
+}
+
+/// Writes a picked file into the working directory.
+pub fn store(file: HostFile) -> String {
+    // Browsers only hand over the base name, but a fake might not.
+    let name = file.name.rsplit(['/', '\\']).next().unwrap_or("");
+    if matches!(name, "" | "." | "..") {
+        return format!("upload: '{}': invalid file name", file.name);
+    }
+
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap().clone();
+    let session = users::current_session();
+
+    if disk::used(&filesystem) + file.content.len() as u64 > disk::CAPACITY {
+        return format!("upload: {}: No space left on device", name);
+    }
+    let bytes = file.content.len();
+    let path = normalize_path(name, &current_path);
+    match text::write_file(&mut filesystem, &path, file.content, &session) {
+        Ok(()) => format!(
+            "Uploaded {} ({}) to {}",
+            name,
+            size(bytes),
+            users::display_path(&format!("/{}", current_path.join("/")))
+        ),
+        Err(err) => format!("upload: {}: {}", name, err),
+    }
+}
+
+/// `download FILE...`: hands each file to the browser as a download. A
+/// directory goes as a tar archive of everything below it.
+pub fn download(args: &[&str]) -> String {
+    if args.is_empty() {
+        return "download: missing file operand".to_string();
+    }
+
+    let mut output = Vec::new();
+    let mut files = Vec::new();
+    {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        let current_path = CURRENT_PATH.lock().unwrap();
+        let session = users::current_session();
+
+        for &operand in args {
+            let path = normalize_path(operand, &current_path);
+            let (canonical, node) = match permissions::resolve(&filesystem, &path, &session) {
+                Ok(found) => found,
+                Err(err) => {
+                    output.push(format!("download: {}: {}", operand, err));
+                    continue;
+                }
+            };
+            let name = canonical
+                .last()
+                .cloned()
+                .unwrap_or_else(|| "root".to_string());
+
+            match node {
+                Node::Directory { .. }
+                    if !permissions::check(&filesystem, node, &session, Access::Read)
+                        || !permissions::check(&filesystem, node, &session, Access::Execute) =>
+                {
+                    output.push(format!(
+                        "download: {}: {}",
+                        operand,
+                        FsError::PermissionDenied
+                    ))
+                }
+                Node::Directory { .. } => {
+                    match archive::archive_tree(&filesystem, &name, node, &session) {
+                        Ok((content, errors)) => {
+                            output.extend(errors.iter().map(|err| {
+                                format!("download: {}", err.trim_start_matches("tar: "))
+                            }));
+                            files.push(HostFile {
+                                name: format!("{}.tar", name),
+                                content,
+                            });
+                        }
+                        Err(err) => output.push(format!("download: {}: {}", operand, err)),
+                    }
+                }
+                _ => match text::read_bytes(&filesystem, &path, &session) {
+                    Ok(content) => files.push(HostFile { name, content }),
+                    Err(err) => output.push(format!("download: {}: {}", operand, err)),
+                },
+            }
+        }
+    }
+
+    let bridge = host::bridge();
+    for file in files {
+        match bridge.download(&file.name, &file.content) {
+            Ok(()) => output.push(format!(
+                "Downloading {} ({})",
+                file.name,
+                size(file.content.len())
+            )),
+            Err(err) => output.push(format!("download: {}: {}", file.name, err)),
+        }
+    }
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::filesystem;
+    use crate::utils::host::FakeBridge;
+    use std::future::Future;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+
+    /// Runs a future the fake bridge settles straight away.
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        let mut future = Box::pin(future);
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(value) => value,
+            Poll::Pending => panic!("the fake bridge never waits"),
+        }
+    }
+
+    fn fake(picks: Vec<HostFile>) -> Rc<FakeBridge> {
+        let fake = Rc::new(FakeBridge::default());
+        fake.picks.borrow_mut().extend(picks);
+        host::set_bridge(fake.clone());
+        fake
+    }
+
+    fn file(name: &str, content: &[u8]) -> HostFile {
+        HostFile {
+            name: name.to_string(),
+            content: content.to_vec(),
+        }
+    }
+
+    #[test]
+    fn uploads_round_trip_through_downloads() {
+        let _lock = filesystem::shared_for_test();
+        let content = [0u8, 159, 146, 150, 255];
+        let fake = fake(vec![file("upload-test.bin", &content)]);
+        let uploaded = ready(upload(&[]));
+        assert!(
+            uploaded.starts_with("Uploaded upload-test.bin (5 bytes) to "),
+            "{}",
+            uploaded
+        );
+        assert_eq!(ready(upload(&[])), "upload: cancelled");
+
+        assert_eq!(
+            download(&["upload-test.bin"]),
+            "Downloading upload-test.bin (5 bytes)"
+        );
+        assert_eq!(
+            *fake.downloads.borrow(),
+            vec![file("upload-test.bin", &content)]
+        );
+    }
+
+    #[test]
+    fn store_keeps_only_the_base_name() {
+        let _lock = filesystem::shared_for_test();
+        assert_eq!(store(file("..", b"x")), "upload: '..': invalid file name");
+        let stored = store(file("C:\\Users\\me\\store-test.txt", b"hi"));
+        assert!(
+            stored.starts_with("Uploaded store-test.txt (2 bytes)"),
+            "{}",
+            stored
+        );
+    }
+
+    #[test]
+    fn directories_download_as_tar_archives() {
+        let _lock = filesystem::shared_for_test();
+        let fake = fake(Vec::new());
+        let output = download(&["/etc", "/nowhere"]);
+        assert!(output.contains("download: /nowhere: No such file or directory"));
+        assert!(output.contains("Downloading etc.tar"));
+
+        let downloads = fake.downloads.borrow();
+        assert_eq!(downloads.len(), 1);
+        assert_eq!(downloads[0].name, "etc.tar");
+        assert_eq!(&downloads[0].content[257..262], b"ustar");
+        assert_eq!(downloads[0].content.len() % 512, 0);
+    }
+}
@@ -0,0 +1,208 @@
+//! The freedesktop.org trash: `rm --trash` moves entries into
+//! `~/.local/share/Trash/files` and records where each came from in
+//! `~/.local/share/Trash/info`, from where `trash restore` puts them back.
+
+use crate::commands::filesystem::{
+    get_node, normalize_path, Children, Filesystem, FsError, Inode, Node, FILESYSTEM,
+};
+use crate::commands::permissions;
+use crate::commands::users::{self, Session};
+
+const TRASH_DIR: [&str; 3] = [".local", "share", "Trash"];
+
+fn trash_dir(session: &Session, sub: &str) -> Vec<String> {
+    let mut path = session.home.clone();
+    path.extend(TRASH_DIR.iter().map(|part| part.to_string()));
+    path.push(sub.to_string());
+    path
+}
+
+fn children_mut<'a>(fs: &'a mut Filesystem, path: &[String]) -> Option<&'a mut Children> {
+    match fs.split_mut(path) {
+        Some((Node::Directory { children, .. }, _)) => Some(children),
+        _ => None,
+    }
+}
+
+/// Creates whatever is missing of `path`, private to the session's user.
+fn ensure_dir(fs: &mut Filesystem, path: &[String], session: &Session) -> Result<(), FsError> {
+    for depth in 1..=path.len() {
+        match get_node(fs, &path[..depth]) {
+            Some(Node::Directory { .. }) => continue,
+            Some(_) => return Err(FsError::NotADirectory),
+            None => {}
+        }
+        let parent = permissions::can_create(fs, &path[..depth - 1], session)?;
+        children_mut(fs, &parent).ok_or(FsError::NotFound)?.insert(
+            path[depth - 1].clone(),
+            Node::Directory {
+                children: Children::new(),
+                permissions: 0o700,
+                owner: session.user.clone(),
+                group: session.primary_group(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Moves the entry at `canonical` into the session user's trash under a
+/// name not taken yet there (`notes`, `notes.2`, ...). The caller has
+/// already checked that it may be removed.
+pub fn move_to_trash(
+    fs: &mut Filesystem,
+    canonical: &[String],
+    session: &Session,
+) -> Result<(), String> {
+    let files = trash_dir(session, "files");
+    let info = trash_dir(session, "info");
+    if canonical.starts_with(&files[..files.len() - 1]) || files.starts_with(canonical) {
+        return Err("it holds the trash itself".to_string());
+    }
+    ensure_dir(fs, &files, session).map_err(|err| err.to_string())?;
+    ensure_dir(fs, &info, session).map_err(|err| err.to_string())?;
+
+    let (name, parent) = canonical.split_last().ok_or("Permission denied")?;
+    let taken = |fs: &Filesystem, candidate: &str| {
+        let mut file = files.clone();
+        file.push(candidate.to_string());
+        let mut record = info.clone();
+        record.push(format!("{}.trashinfo", candidate));
+        get_node(fs, &file).is_some() || get_node(fs, &record).is_some()
+    };
+    let trashed = (1..)
+        .map(|n| match n {
+            1 => name.clone(),
+            n => format!("{}.{}", name, n),
+        })
+        .find(|candidate| !taken(fs, candidate))
+        .unwrap_or_default();
+
+    let node = children_mut(fs, parent)
+        .and_then(|children| children.remove(name))
+        .ok_or_else(|| FsError::NotFound.to_string())?;
+    children_mut(fs, &files)
+        .ok_or_else(|| FsError::NotFound.to_string())?
+        .insert(trashed.clone(), node);
+
+    let record = fs.inodes.create(Inode {
+        content: format!("[Trash Info]\nPath=/{}\n", canonical.join("/")).into_bytes(),
+        permissions: 0o600,
+        owner: session.user.clone(),
+        group: session.primary_group(),
+    });
+    children_mut(fs, &info)
+        .ok_or_else(|| FsError::NotFound.to_string())?
+        .insert(format!("{}.trashinfo", trashed), record);
+    Ok(())
+}
+
+/// Trashed names with the absolute path each was removed from.
+fn entries(fs: &Filesystem, session: &Session) -> Vec<(String, String)> {
+    let files = match get_node(fs, &trash_dir(session, "files")) {
+        Some(Node::Directory { children, .. }) => children,
+        _ => return Vec::new(),
+    };
+    let mut info = trash_dir(session, "info");
+    info.push(String::new());
+
+    files
+        .keys()
+        .map(|name| {
+            *info.last_mut().unwrap() = format!("{}.trashinfo", name);
+            let origin = match get_node(fs, &info).and_then(|node| fs.file(node)) {
+                Some(record) => record
+                    .text()
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Path="))
+                    .unwrap_or("?")
+                    .to_string(),
+                None => "?".to_string(),
+            };
+            (name.clone(), origin)
+        })
+        .collect()
+}
+
+fn restore(fs: &mut Filesystem, name: &str, session: &Session) -> Result<(), String> {
+    let origin = entries(fs, session)
+        .into_iter()
+        .find(|(trashed, _)| trashed == name)
+        .map(|(_, origin)| origin)
+        .ok_or("not in the trash")?;
+    let destination = normalize_path(&origin, &[]);
+    let (original_name, parent) = destination.split_last().ok_or("no place to go back to")?;
+
+    if get_node(fs, &destination).is_some() {
+        return Err(format!("{}: {}", origin, FsError::AlreadyExists));
+    }
+    let parent = permissions::can_create(fs, parent, session)
+        .map_err(|err| format!("{}: {}", origin, err))?;
+
+    let node = children_mut(fs, &trash_dir(session, "files"))
+        .and_then(|children| children.remove(name))
+        .ok_or("not in the trash")?;
+    children_mut(fs, &parent)
+        .ok_or_else(|| format!("{}: {}", origin, FsError::NotFound))?
+        .insert(original_name.clone(), node);
+
+    let info = trash_dir(session, "info");
+    if let Some(record) =
+        children_mut(fs, &info).and_then(|children| children.remove(&format!("{}.trashinfo", name)))
+    {
+        fs.inodes.release(&record);
+    }
+    Ok(())
+}
+
+/// `trash [list]`, `trash restore NAME...` and `trash empty`.
+pub fn trash(args: &[&str]) -> String {
+    let mut filesystem = FILESYSTEM.lock().unwrap();
+    let session = users::current_session();
+
+    match args {
+        [] | ["list"] | ["-l"] => {
+            let entries = entries(&filesystem, &session);
+            if entries.is_empty() {
+                return "trash: the trash is empty".to_string();
+            }
+            let width = entries
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or(0);
+            entries
+                .iter()
+                .map(|(name, origin)| format!("{:<width$}  {}", name, origin, width = width))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        ["restore"] => "trash: missing operand after 'restore'".to_string(),
+        ["restore", names @ ..] => names
+            .iter()
+            .filter_map(|name| {
+                restore(&mut filesystem, name, &session)
+                    .err()
+                    .map(|err| format!("trash: cannot restore '{}': {}", name, err))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ["empty"] => {
+            for sub in ["files", "info"] {
+                let path = trash_dir(&session, sub);
+                let removed: Vec<Node> = match children_mut(&mut filesystem, &path) {
+                    Some(children) => children.drain().map(|(_, node)| node).collect(),
+                    None => continue,
+                };
+                for node in &removed {
+                    filesystem.inodes.release(node);
+                }
+            }
+            String::new()
+        }
+        [other, ..] => format!(
+            "trash: unknown action '{}' (try list, restore or empty)",
+            other
+        ),
+    }
+}
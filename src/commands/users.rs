@@ -0,0 +1,486 @@
+use crate::commands::filesystem::{get_node, normalize_path, Filesystem, CURRENT_PATH, FILESYSTEM};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// One line of `/etc/passwd`.
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+/// One line of `/etc/group`.
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+/// The identity a shell runs as. Group membership is resolved once when
+/// the session starts, so permission checks never need to re-read
+/// `/etc/group` while the filesystem is locked.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub user: String,
+    pub groups: Vec<String>,
+    pub home: Vec<String>,
+    saved_path: Vec<String>,
+}
+
+impl Session {
+    pub fn is_root(&self) -> bool {
+        self.user == "root"
+    }
+
+    pub fn primary_group(&self) -> String {
+        self.groups.first().unwrap_or(&self.user).clone()
+    }
+
+    pub fn in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|name| name == group)
+    }
+
+    /// A session for `user` in `groups`, without a home.
+    #[cfg(test)]
+    pub fn for_test(user: &str, groups: &[&str]) -> Session {
+        Session {
+            user: user.to_string(),
+            groups: groups.iter().map(|group| group.to_string()).collect(),
+            home: Vec::new(),
+            saved_path: Vec::new(),
+        }
+    }
+}
+
+/// How long a successful sudo authentication is remembered, in milliseconds.
+const SUDO_TIMEOUT_MS: f64 = 5.0 * 60.0 * 1000.0;
+
+/// The account the terminal is logged in as when it boots.
+pub const BOOT_USER: &str = "objz";
+
+lazy_static! {
+    /// Starts with the boot session, which `FILESYSTEM` leaves in
+    /// `BOOT_SESSION` as it is built; locking it here instead would
+    /// deadlock callers that already hold the filesystem.
+    static ref SESSIONS: Mutex<Vec<Session>> = {
+        lazy_static::initialize(&FILESYSTEM);
+        Mutex::new(BOOT_SESSION.lock().unwrap().take().into_iter().collect())
+    };
+    static ref SUDO_TIMESTAMPS: Mutex<HashMap<String, f64>> = Mutex::new(HashMap::new());
+}
+
+static BOOT_SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Resolves `BOOT_USER` against the filesystem being built at boot.
+pub fn prepare_boot_session(fs: &Filesystem) {
+    *BOOT_SESSION.lock().unwrap() = find_user(fs, BOOT_USER).map(|user| session_for(fs, &user));
+}
+
+fn session_for(fs: &Filesystem, user: &User) -> Session {
+    Session {
+        user: user.name.clone(),
+        groups: groups_of(fs, user).into_iter().map(|g| g.name).collect(),
+        home: normalize_path(&user.home, &[]),
+        saved_path: Vec::new(),
+    }
+}
+
+fn read_table(fs: &Filesystem, file: &str) -> String {
+    let path = ["etc".to_string(), file.to_string()];
+    match get_node(fs, &path).and_then(|node| fs.file(node)) {
+        Some(inode) => inode.text().into_owned(),
+        None => String::new(),
+    }
+}
+
+pub fn passwd(fs: &Filesystem) -> Vec<User> {
+    read_table(fs, "passwd")
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some(User {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().ok()?,
+                gid: fields[3].parse().ok()?,
+                home: fields[5].to_string(),
+                shell: fields[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn group(fs: &Filesystem) -> Vec<Group> {
+    read_table(fs, "group")
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 4 {
+                return None;
+            }
+            Some(Group {
+                name: fields[0].to_string(),
+                gid: fields[2].parse().ok()?,
+                members: fields[3]
+                    .split(',')
+                    .filter(|member| !member.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+pub fn find_user(fs: &Filesystem, name: &str) -> Option<User> {
+    passwd(fs).into_iter().find(|user| user.name == name)
+}
+
+pub fn find_group(fs: &Filesystem, name: &str) -> Option<Group> {
+    group(fs).into_iter().find(|group| group.name == name)
+}
+
+/// Primary group first, then every supplementary group listing `user`.
+pub fn groups_of(fs: &Filesystem, user: &User) -> Vec<Group> {
+    let all = group(fs);
+    let mut result: Vec<Group> = all.iter().filter(|g| g.gid == user.gid).cloned().collect();
+    for g in all {
+        if g.gid != user.gid && g.members.contains(&user.name) {
+            result.push(g);
+        }
+    }
+    result
+}
+
+pub fn current_session() -> Session {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .last()
+        .expect("the boot user is in /etc/passwd")
+        .clone()
+}
+
+pub fn current_user() -> String {
+    current_session().user
+}
+
+pub fn home_dir() -> Vec<String> {
+    current_session().home
+}
+
+/// Formats an absolute path the way the prompt shows it, with `~` for home.
+pub fn display_path(path: &str) -> String {
+    let home = format!("/{}", home_dir().join("/"));
+    if home == "/" {
+        path.to_string()
+    } else if path == home {
+        "~".to_string()
+    } else if let Some(rest) = path.strip_prefix(&format!("{}/", home)) {
+        format!("~/{}", rest)
+    } else {
+        path.to_string()
+    }
+}
+
+fn start_session(user: &User, login: bool) -> Session {
+    let session = session_for(&FILESYSTEM.lock().unwrap(), user);
+
+    let mut current_path = CURRENT_PATH.lock().unwrap();
+    let mut sessions = SESSIONS.lock().unwrap();
+    if let Some(parent) = sessions.last_mut() {
+        parent.saved_path = current_path.clone();
+    }
+    if login {
+        *current_path = session.home.clone();
+    }
+    sessions.push(session.clone());
+    session
+}
+
+/// Runs `f` as `name` for the duration of the call, the way sudo does.
+/// Sessions `f` itself starts (`sudo su`) outlive the call.
+pub fn run_as<T>(name: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    let user = {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        find_user(&filesystem, name).ok_or_else(|| format!("unknown user {}", name))?
+    };
+
+    start_session(&user, false);
+    let depth = SESSIONS.lock().unwrap().len() - 1;
+
+    let result = f();
+
+    let mut sessions = SESSIONS.lock().unwrap();
+    if depth < sessions.len() {
+        sessions.remove(depth);
+    }
+    Ok(result)
+}
+
+/// Parses `su [-] [user]` into the account to switch to and whether to
+/// start a login shell in its home directory.
+pub fn parse_su(args: &[&str]) -> Result<(User, bool), String> {
+    let mut login = false;
+    let mut target = None;
+
+    for &arg in args {
+        match arg {
+            "-" | "-l" | "--login" => login = true,
+            _ => match arg.strip_prefix('-') {
+                Some(option) => return Err(format!("su: invalid option -- '{}'", option)),
+                None => target = Some(arg),
+            },
+        }
+    }
+
+    let target = target.unwrap_or("root");
+    let user = {
+        let filesystem = FILESYSTEM.lock().unwrap();
+        match find_user(&filesystem, target) {
+            Some(user) => user,
+            None => return Err(format!("su: user {} does not exist", target)),
+        }
+    };
+
+    if user.shell.ends_with("nologin") {
+        return Err("This account is currently not available.".into());
+    }
+
+    Ok((user, login))
+}
+
+pub fn switch_user(user: &User, login: bool) -> String {
+    start_session(user, login);
+    String::new()
+}
+
+fn hash_password(password: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in password.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("$fnv1a${:016x}", hash)
+}
+
+/// Checks `password` against `/etc/shadow`. Locked (`!`) and disabled
+/// (`*`) accounts never authenticate.
+pub fn verify_password(name: &str, password: &str) -> bool {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let path = ["etc".to_string(), "shadow".to_string()];
+    let shadow = match get_node(&filesystem, &path).and_then(|node| filesystem.file(node)) {
+        Some(inode) => inode.text(),
+        None => return false,
+    };
+
+    shadow.lines().any(|line| {
+        let mut fields = line.split(':');
+        fields.next() == Some(name)
+            && fields
+                .next()
+                .is_some_and(|hash| hash.starts_with('$') && hash == hash_password(password))
+    })
+}
+
+/// A line of `/etc/sudoers` that applies to a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SudoRule {
+    /// Who the commands may run as, `ALL:ALL` style, if the rule says.
+    pub runas: Option<String>,
+    pub commands: String,
+    pub password: bool,
+}
+
+impl SudoRule {
+    /// The rule as `sudo -l` lists it: `(ALL : ALL) ALL`.
+    pub fn describe(&self) -> String {
+        let runas = self.runas.as_ref().map(|runas| {
+            let parts: Vec<&str> = runas.split(':').map(str::trim).collect();
+            format!("({}) ", parts.join(" : "))
+        });
+        format!("{}{}", runas.unwrap_or_default(), self.commands)
+    }
+}
+
+/// What `/etc/sudoers` grants the session: the first rule naming it or one
+/// of its groups, or `None` when it may not sudo at all.
+pub fn sudo_rule(session: &Session) -> Option<SudoRule> {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let path = ["etc".to_string(), "sudoers".to_string()];
+    let sudoers = get_node(&filesystem, &path).and_then(|node| filesystem.file(node))?;
+    parse_sudo_rule(&sudoers.text(), session)
+}
+
+fn parse_sudo_rule(sudoers: &str, session: &Session) -> Option<SudoRule> {
+    sudoers
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find_map(|line| {
+            let (who, spec) = line.split_once(char::is_whitespace)?;
+            let applies = match who.strip_prefix('%') {
+                Some(group) => session.in_group(group),
+                None => who == session.user,
+            };
+            if !applies {
+                return None;
+            }
+            // `HOST=(RUNAS) COMMANDS`; the host is always this machine.
+            let spec = spec.split_once('=').map_or(spec, |(_, rest)| rest).trim();
+            let (runas, commands) = match spec.strip_prefix('(') {
+                Some(rest) => {
+                    let (runas, commands) = rest.split_once(')')?;
+                    (Some(runas.trim().to_string()), commands.trim())
+                }
+                None => (None, spec),
+            };
+            Some(SudoRule {
+                runas,
+                commands: commands.to_string(),
+                password: !commands.contains("NOPASSWD:"),
+            })
+        })
+}
+
+pub fn sudo_cached(user: &str) -> bool {
+    SUDO_TIMESTAMPS
+        .lock()
+        .unwrap()
+        .get(user)
+        .is_some_and(|stamp| js_sys::Date::now() - stamp < SUDO_TIMEOUT_MS)
+}
+
+pub fn touch_sudo(user: &str) {
+    SUDO_TIMESTAMPS
+        .lock()
+        .unwrap()
+        .insert(user.to_string(), js_sys::Date::now());
+}
+
+pub fn reset_sudo(user: &str) {
+    SUDO_TIMESTAMPS.lock().unwrap().remove(user);
+}
+
+pub fn logout(_args: &[&str]) -> String {
+    let mut current_path = CURRENT_PATH.lock().unwrap();
+    let mut sessions = SESSIONS.lock().unwrap();
+
+    if sessions.len() <= 1 {
+        return "logout: not in a su session".into();
+    }
+
+    sessions.pop();
+    if let Some(parent) = sessions.last() {
+        *current_path = parent.saved_path.clone();
+    }
+    String::new()
+}
+
+pub fn whoami(_args: &[&str]) -> String {
+    current_user()
+}
+
+pub fn id(args: &[&str]) -> String {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let name = args
+        .first()
+        .map_or_else(current_user, |arg| arg.to_string());
+
+    let user = match find_user(&filesystem, &name) {
+        Some(user) => user,
+        None => return format!("id: '{}': no such user", name),
+    };
+
+    let groups = groups_of(&filesystem, &user);
+    let primary = groups.first().map_or_else(
+        || user.gid.to_string(),
+        |g| format!("{}({})", g.gid, g.name),
+    );
+
+    format!(
+        "uid={}({}) gid={} groups={}",
+        user.uid,
+        user.name,
+        primary,
+        groups
+            .iter()
+            .map(|g| format!("{}({})", g.gid, g.name))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+pub fn groups(args: &[&str]) -> String {
+    if args.is_empty() {
+        return current_session().groups.join(" ");
+    }
+
+    let filesystem = FILESYSTEM.lock().unwrap();
+    args.iter()
+        .map(|&name| match find_user(&filesystem, name) {
+            Some(user) => format!(
+                "{} : {}",
+                name,
+                groups_of(&filesystem, &user)
+                    .into_iter()
+                    .map(|g| g.name)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            None => format!("groups: '{}': no such user", name),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn users(_args: &[&str]) -> String {
+    let sessions = SESSIONS.lock().unwrap();
+    let mut names: Vec<&str> = sessions.iter().map(|s| s.user.as_str()).collect();
+    names.sort();
+    names.dedup();
+    names.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_session_comes_from_passwd_and_group() {
+        let filesystem = FILESYSTEM.lock().unwrap().clone();
+        let user = find_user(&filesystem, BOOT_USER).unwrap();
+        let session = session_for(&filesystem, &user);
+        assert_eq!(session.user, "objz");
+        assert_eq!(session.groups, ["objz", "sudo"]);
+        assert_eq!(session.home, ["home", "objz"]);
+        assert_eq!(SESSIONS.lock().unwrap()[0].groups, session.groups);
+    }
+
+    #[test]
+    fn sudoers_rules_match_users_and_groups() {
+        let sudoers = "# comment\nroot ALL=(ALL:ALL) ALL\n%wheel ALL=(ALL) NOPASSWD: ALL\nguest ALL=/usr/bin/ls";
+
+        let root = parse_sudo_rule(sudoers, &Session::for_test("root", &["root"])).unwrap();
+        assert_eq!(root.describe(), "(ALL : ALL) ALL");
+        assert!(root.password);
+
+        let wheel = parse_sudo_rule(sudoers, &Session::for_test("ada", &["ada", "wheel"])).unwrap();
+        assert_eq!(wheel.describe(), "(ALL) NOPASSWD: ALL");
+        assert!(!wheel.password);
+
+        let guest = parse_sudo_rule(sudoers, &Session::for_test("guest", &["guest"])).unwrap();
+        assert_eq!(guest.describe(), "/usr/bin/ls");
+
+        assert_eq!(
+            parse_sudo_rule(sudoers, &Session::for_test("objz", &["objz", "sudo"])),
+            None
+        );
+    }
+}
@@ -26,7 +26,7 @@ impl CommandHistory {
 
         let new_index = match self.current_index {
             None => self.history.len() - 1,
-            Some(0) => return self.history.get(0),
+            Some(0) => return self.history.first(),
             Some(i) => i - 1,
         };
 
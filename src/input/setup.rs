@@ -1,5 +1,8 @@
+use crate::commands::processor::{CommandResult, PendingPrompt};
+use crate::commands::text;
 use crate::commands::CommandHandler;
 use crate::input::history::CommandHistory;
+use crate::programs::{self, less::Less, Key, Program};
 use crate::terminal::autocomplete::{find_common_prefix, AutoComplete, CompletionResult};
 use crate::terminal::buffer::{self, InputMode};
 use crate::terminal::Terminal;
@@ -10,9 +13,10 @@ use wasm_bindgen_futures::spawn_local;
 use web_sys::{window, HtmlInputElement, KeyboardEvent};
 
 thread_local! {
-    static CURRENT_INPUT: RefCell<String> = RefCell::new(String::new());
-    static IS_FOCUSED: RefCell<bool> = RefCell::new(false);
+    static CURRENT_INPUT: RefCell<String> = const { RefCell::new(String::new()) };
+    static IS_FOCUSED: RefCell<bool> = const { RefCell::new(false) };
     static AUTOCOMPLETE: RefCell<AutoComplete> = RefCell::new(AutoComplete::new());
+    static PENDING_PROMPT: RefCell<Option<PendingPrompt>> = const { RefCell::new(None) };
 }
 
 pub struct InputHandler;
@@ -34,6 +38,23 @@ impl InputHandler {
                 if state.input_mode == InputMode::Disabled {
                     return;
                 }
+                // On-screen keyboards often report keys as "Unidentified"
+                // and only deliver the text here, so programs get it as keys.
+                if state.input_mode == InputMode::Program {
+                    let typed = hidden_input.value();
+                    hidden_input.set_value("");
+                    for c in typed.chars() {
+                        let key = Key {
+                            key: c.to_string(),
+                            ctrl: false,
+                            alt: false,
+                        };
+                        if !Self::send_program_key(&key, &terminal, &hidden_input) {
+                            break;
+                        }
+                    }
+                    return;
+                }
                 let current_value = hidden_input.value();
                 CURRENT_INPUT.with(|input| {
                     *input.borrow_mut() = current_value.clone();
@@ -66,6 +87,16 @@ impl InputHandler {
                     event.prevent_default();
                     return;
                 }
+                if state.input_mode == InputMode::Program {
+                    Self::handle_program_key(&event, &terminal, &hidden_input);
+                    return;
+                }
+                if state.input_mode == InputMode::Password
+                    && matches!(event.key().as_str(), "ArrowUp" | "ArrowDown" | "Tab")
+                {
+                    event.prevent_default();
+                    return;
+                }
                 let current_input = CURRENT_INPUT.with(|input| input.borrow().clone());
 
                 match event.key().as_str() {
@@ -261,7 +292,10 @@ impl InputHandler {
         let scroll_up_callback = {
             let terminal = terminal_clone.clone();
             Closure::wrap(Box::new(move |_event: web_sys::Event| {
-                if buffer::scroll_up(3) {
+                if programs::is_running() {
+                    programs::scroll(-3, terminal.renderer.program_size());
+                    terminal.render();
+                } else if buffer::scroll_up(3) {
                     terminal.render();
                 }
             }) as Box<dyn FnMut(_)>)
@@ -278,7 +312,10 @@ impl InputHandler {
         let scroll_down_callback = {
             let terminal = terminal_clone.clone();
             Closure::wrap(Box::new(move |_event: web_sys::Event| {
-                if buffer::scroll_down(3) {
+                if programs::is_running() {
+                    programs::scroll(3, terminal.renderer.program_size());
+                    terminal.render();
+                } else if buffer::scroll_down(3) {
                     terminal.render();
                 }
             }) as Box<dyn FnMut(_)>)
@@ -320,26 +357,11 @@ impl InputHandler {
         if state.input_mode == InputMode::Disabled {
             return;
         }
-        let trimmed_input = current_input.trim();
-
-        if panic::should_panic(trimmed_input) {
-            history.add(trimmed_input.to_string());
-            let prompt = terminal.get_current_prompt();
-            buffer::add_command_line(&prompt, trimmed_input);
-
-            hidden_input.set_value("");
-            CURRENT_INPUT.with(|input| input.borrow_mut().clear());
-            buffer::update_input_state(String::new(), 0);
-            buffer::set_input_mode(InputMode::Disabled);
-
-            let terminal_clone = terminal.clone();
-            let hidden_input_clone = hidden_input.clone();
-            spawn_local(async move {
-                panic::trigger(&terminal_clone).await;
-                Self::handle_input(&terminal_clone, &hidden_input_clone);
-            });
+        if state.input_mode == InputMode::Password {
+            Self::handle_password(current_input, processor, terminal, hidden_input);
             return;
         }
+        let trimmed_input = current_input.trim();
 
         if !trimmed_input.is_empty() {
             history.add(trimmed_input.to_string());
@@ -354,37 +376,152 @@ impl InputHandler {
 
         if !trimmed_input.is_empty() {
             let (result, _directory_changed) = processor.handle(trimmed_input);
+            Self::handle_result(result, terminal, hidden_input);
+        } else {
+            Self::handle_input(terminal, hidden_input);
+        }
+    }
 
-            // Extract the String from CommandResult::Output before treating it like text
-            if let crate::commands::processor::CommandResult::Output(s) = result {
-                match s.as_str() {
-                    "CLEAR_SCREEN" => {
-                        buffer::clear_buffer();
-                        Self::handle_input(terminal, hidden_input);
-                    }
-                    "SYSTEM_PANIC" => {
-                        let terminal_clone = terminal.clone();
-                        let hidden_input_clone = hidden_input.clone();
-                        spawn_local(async move {
-                            panic::trigger(&terminal_clone).await;
-                            Self::handle_input(&terminal_clone, &hidden_input_clone);
-                        });
-                    }
-                    other if !other.is_empty() => {
-                        buffer::add_output_lines(other, None);
-                        Self::handle_input(terminal, hidden_input);
-                    }
-                    _ => {
-                        // empty output: just redraw prompt
-                        Self::handle_input(terminal, hidden_input);
-                    }
+    /// Hands a masked line back to the command that asked for it. The
+    /// password itself is never echoed or added to history.
+    fn handle_password(
+        password: &str,
+        processor: &mut CommandHandler,
+        terminal: &Terminal,
+        hidden_input: &HtmlInputElement,
+    ) {
+        let prompt = buffer::get_terminal_state().prompt;
+        buffer::add_command_line(&prompt, "");
+
+        hidden_input.set_value("");
+        CURRENT_INPUT.with(|input| input.borrow_mut().clear());
+        buffer::update_input_state(String::new(), 0);
+        buffer::set_input_mode(InputMode::Disabled);
+
+        match PENDING_PROMPT.with(|pending| pending.borrow_mut().take()) {
+            Some(pending) => {
+                let (result, _directory_changed) = processor.resume(pending, password);
+                Self::handle_result(result, terminal, hidden_input);
+            }
+            None => Self::handle_input(terminal, hidden_input),
+        }
+    }
+
+    fn handle_result(result: CommandResult, terminal: &Terminal, hidden_input: &HtmlInputElement) {
+        match result {
+            CommandResult::Output(s) => match s.as_str() {
+                "CLEAR_SCREEN" => {
+                    buffer::clear_buffer();
+                    Self::handle_input(terminal, hidden_input);
+                }
+                // Anything taller than the screen opens in the pager
+                // rather than scrolling past.
+                other
+                    if buffer::visual_line_count(other) > terminal.renderer.max_visible_lines() =>
+                {
+                    Self::run_program(Box::new(Less::paging(s)), terminal, hidden_input);
+                }
+                other if !other.is_empty() => {
+                    buffer::add_output_lines(other, None);
+                    Self::handle_input(terminal, hidden_input);
+                }
+                _ => {
+                    // empty output: just redraw prompt
+                    Self::handle_input(terminal, hidden_input);
+                }
+            },
+            CommandResult::Bytes(bytes) => {
+                Self::handle_result(
+                    CommandResult::Output(text::decode(&bytes)),
+                    terminal,
+                    hidden_input,
+                );
+            }
+            CommandResult::Animated(animation) => {
+                let terminal_clone = terminal.clone();
+                let hidden_input_clone = hidden_input.clone();
+                spawn_local(async move {
+                    animation(terminal_clone.renderer.clone()).await;
+                    Self::handle_input(&terminal_clone, &hidden_input_clone);
+                });
+            }
+            CommandResult::Program(program) => {
+                Self::run_program(program, terminal, hidden_input);
+            }
+            CommandResult::Panic => {
+                let terminal_clone = terminal.clone();
+                let hidden_input_clone = hidden_input.clone();
+                spawn_local(async move {
+                    panic::trigger(&terminal_clone).await;
+                    Self::handle_input(&terminal_clone, &hidden_input_clone);
+                });
+            }
+            CommandResult::Prompt(pending) => {
+                if let Some(notice) = &pending.notice {
+                    buffer::add_output_lines(notice, None);
+                }
+                buffer::set_current_prompt(pending.prompt.clone());
+                PENDING_PROMPT.with(|slot| *slot.borrow_mut() = Some(pending));
+                buffer::set_input_mode(InputMode::Password);
+                buffer::auto_scroll_to_bottom();
+
+                terminal.render();
+                let _ = hidden_input.focus();
+            }
+        }
+    }
+
+    fn run_program(
+        program: Box<dyn Program>,
+        terminal: &Terminal,
+        hidden_input: &HtmlInputElement,
+    ) {
+        programs::start(program);
+        buffer::set_input_mode(InputMode::Program);
+        hidden_input.set_value("");
+        CURRENT_INPUT.with(|input| input.borrow_mut().clear());
+
+        terminal.render();
+        let _ = hidden_input.focus();
+    }
+
+    /// Passes a key to the running program, and hands the terminal back
+    /// to the prompt once it exits.
+    fn handle_program_key(
+        event: &KeyboardEvent,
+        terminal: &Terminal,
+        hidden_input: &HtmlInputElement,
+    ) {
+        let key = event.key();
+        // Reloading and the developer tools stay with the browser, and
+        // unidentified keys arrive through the input event instead.
+        if matches!(key.as_str(), "F5" | "F12" | "Unidentified") {
+            return;
+        }
+        event.prevent_default();
+
+        let key = Key {
+            key,
+            ctrl: event.ctrl_key(),
+            alt: event.alt_key(),
+        };
+        Self::send_program_key(&key, terminal, hidden_input);
+    }
+
+    /// Returns whether the program is still running.
+    fn send_program_key(key: &Key, terminal: &Terminal, hidden_input: &HtmlInputElement) -> bool {
+        match programs::key(key, terminal.renderer.program_size()) {
+            Some(output) => {
+                if !output.is_empty() {
+                    buffer::add_output_lines(&output, None);
                 }
-            } else {
-                // Animated command: redraw prompt (animation is handled elsewhere)
                 Self::handle_input(terminal, hidden_input);
+                false
+            }
+            None => {
+                terminal.render();
+                true
             }
-        } else {
-            Self::handle_input(terminal, hidden_input);
         }
     }
 
@@ -411,20 +548,19 @@ impl InputHandler {
         let trimmed = current_input.trim();
         let parts: Vec<&str> = trimmed.split_whitespace().collect();
 
-        let (command_prefix, completion_target) = if parts.is_empty() {
-            ("", current_input)
-        } else if parts.len() == 1 && !trimmed.ends_with(' ') {
-            ("", current_input)
-        } else {
-            if parts.len() == 1 {
-                (trimmed, "")
+        let (command_prefix, completion_target) =
+            if parts.is_empty() || (parts.len() == 1 && !trimmed.ends_with(' ')) {
+                ("", current_input)
             } else {
-                let last_space_idx = current_input.rfind(' ').unwrap_or(0);
-                let prefix = &current_input[..=last_space_idx];
-                let target = &current_input[last_space_idx + 1..];
-                (prefix, target)
-            }
-        };
+                if parts.len() == 1 {
+                    (trimmed, "")
+                } else {
+                    let last_space_idx = current_input.rfind(' ').unwrap_or(0);
+                    let prefix = &current_input[..=last_space_idx];
+                    let target = &current_input[last_space_idx + 1..];
+                    (prefix, target)
+                }
+            };
 
         let completion_result = AUTOCOMPLETE.with(|autocomplete| {
             autocomplete
@@ -518,7 +654,7 @@ impl InputHandler {
             let is_focused = IS_FOCUSED.with(|focused| *focused.borrow());
             let state = buffer::get_terminal_state();
 
-            if is_focused && state.input_mode == InputMode::Normal {
+            if is_focused && state.input_mode != InputMode::Disabled {
                 terminal_clone.renderer.toggle_cursor();
                 terminal_clone.render();
             }
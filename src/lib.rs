@@ -10,6 +10,7 @@ mod ascii;
 mod boot;
 mod commands;
 mod input;
+mod programs;
 mod terminal;
 mod utils;
 
@@ -26,6 +27,7 @@ pub fn main() {
     let document = window.document().expect("should have a document on window");
 
     commands::system::init();
+    commands::mounts::restore();
 
     let terminal = Terminal::new(&document);
 
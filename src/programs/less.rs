@@ -0,0 +1,468 @@
+//! `less` (and `more`): a pager over files, stdin, or any command output
+//! too tall for the screen.
+
+use crate::commands::regex::{Regex, Syntax};
+use crate::commands::text;
+use crate::programs::{Flow, Key, Program, Screen, Size};
+use crate::terminal::{ansi, buffer};
+
+/// The `-N` column, printed as `%7d ` the way less does.
+const NUMBER_WIDTH: usize = 8;
+
+enum Prompt {
+    None,
+    Search {
+        forward: bool,
+        input: String,
+    },
+    /// After `-`, waiting for the option letter to toggle.
+    Option,
+}
+
+/// One screen row: visible characters `start..end` of a line.
+struct Row {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+pub struct Less {
+    name: Option<String>,
+    lines: Vec<String>,
+    numbers: bool,
+    ignore_case: bool,
+    /// `more` exits when paging past the end instead of waiting for `q`.
+    quit_at_end: bool,
+    /// Left in the scrollback on exit, for output that was paged
+    /// automatically.
+    keep: String,
+    top: usize,
+    rows: Vec<Row>,
+    /// The width and numbering `rows` were laid out for.
+    layout: Option<(usize, bool)>,
+    pattern: Option<Regex>,
+    forward: bool,
+    prompt: Prompt,
+    message: Option<String>,
+    count: String,
+}
+
+impl Less {
+    fn new(name: Option<String>, text: &str) -> Self {
+        Self {
+            name,
+            lines: text.lines().map(str::to_string).collect(),
+            numbers: false,
+            ignore_case: false,
+            quit_at_end: false,
+            keep: String::new(),
+            top: 0,
+            rows: Vec::new(),
+            layout: None,
+            pattern: None,
+            forward: true,
+            prompt: Prompt::None,
+            message: None,
+            count: String::new(),
+        }
+    }
+
+    /// Pages a command's output that would not fit on the screen; the
+    /// output stays in the scrollback once the pager is closed.
+    pub fn paging(output: String) -> Self {
+        let mut less = Self::new(None, &output);
+        less.keep = output;
+        less
+    }
+
+    fn lay_out(&mut self, width: usize) {
+        if self.layout == Some((width, self.numbers)) {
+            return;
+        }
+        let top_line = self.rows.get(self.top).map_or(0, |row| row.line);
+        let text_width = if self.numbers {
+            width.saturating_sub(NUMBER_WIDTH).max(1)
+        } else {
+            width.max(1)
+        };
+
+        self.rows = self
+            .lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line, content)| {
+                buffer::wrap_ranges(content, text_width)
+                    .into_iter()
+                    .map(move |(start, end)| Row { line, start, end })
+            })
+            .collect();
+        self.layout = Some((width, self.numbers));
+        self.top = self.line_row(top_line);
+    }
+
+    /// The first row of line `line` (0-based).
+    fn line_row(&self, line: usize) -> usize {
+        self.rows.partition_point(|row| row.line < line)
+    }
+
+    fn page(size: Size) -> usize {
+        size.height.saturating_sub(1).max(1)
+    }
+
+    fn last_top(&self, size: Size) -> usize {
+        self.rows.len().saturating_sub(Self::page(size))
+    }
+
+    fn at_end(&self, size: Size) -> bool {
+        self.top >= self.last_top(size)
+    }
+
+    fn go_to(&mut self, top: usize, size: Size) {
+        self.top = top.min(self.last_top(size));
+    }
+
+    fn move_by(&mut self, rows: isize, size: Size) {
+        let top = if rows < 0 {
+            self.top.saturating_sub(rows.unsigned_abs())
+        } else {
+            self.top + rows as usize
+        };
+        self.go_to(top, size);
+    }
+
+    /// Where `pattern` matches `line`, as ranges of visible characters.
+    fn matches(&self, line: usize) -> Vec<(usize, usize)> {
+        let pattern = match &self.pattern {
+            Some(pattern) => pattern,
+            None => return Vec::new(),
+        };
+        let plain = ansi::strip(&self.lines[line]);
+        let column = |offset: usize| plain[..offset].chars().count();
+        pattern
+            .find_iter(&plain)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|found| found.end > found.start)
+            .map(|found| (column(found.start), column(found.end)))
+            .collect()
+    }
+
+    /// Brings the next line matching the pattern to the top. Searches
+    /// start from the second line on screen, as less's do.
+    fn search(&mut self, forward: bool, size: Size) {
+        let pattern = match &self.pattern {
+            Some(pattern) => pattern,
+            None => {
+                self.message = Some("No previous regular expression".to_string());
+                return;
+            }
+        };
+        let current = self.rows.get(self.top).map_or(0, |row| row.line);
+        let order: Vec<usize> = if forward {
+            (current + 1..self.lines.len()).collect()
+        } else {
+            (0..current).rev().collect()
+        };
+        for line in order {
+            match pattern.is_match(&ansi::strip(&self.lines[line])) {
+                Ok(true) => return self.go_to(self.line_row(line), size),
+                Ok(false) => {}
+                Err(err) => {
+                    self.message = Some(err);
+                    return;
+                }
+            }
+        }
+        self.message = Some("Pattern not found".to_string());
+    }
+
+    fn submit_search(&mut self, forward: bool, input: &str, size: Size) {
+        if !input.is_empty() {
+            match Regex::new(input, Syntax::Extended, self.ignore_case) {
+                Ok(pattern) => self.pattern = Some(pattern),
+                Err(err) => {
+                    self.message = Some(err);
+                    return;
+                }
+            }
+        }
+        self.forward = forward;
+        self.search(forward, size);
+    }
+
+    fn toggle(&mut self, option: char) {
+        self.message = Some(match option {
+            'N' => {
+                self.numbers = !self.numbers;
+                if self.numbers {
+                    "Constantly display line numbers"
+                } else {
+                    "Don't use line numbers"
+                }
+                .to_string()
+            }
+            'i' => {
+                self.ignore_case = !self.ignore_case;
+                if self.ignore_case {
+                    "Ignore case in searches"
+                } else {
+                    "Case is significant in searches"
+                }
+                .to_string()
+            }
+            other => format!("There is no -{} option", other),
+        });
+    }
+
+    /// What `=` shows: the name, the lines on screen and how far in.
+    fn info(&self, size: Size) -> String {
+        let first = self.rows.get(self.top).map_or(0, |row| row.line + 1);
+        let bottom = (self.top + Self::page(size)).min(self.rows.len());
+        let last = bottom
+            .checked_sub(1)
+            .and_then(|row| self.rows.get(row))
+            .map_or(0, |row| row.line + 1);
+        let percent = (bottom * 100).checked_div(self.rows.len()).unwrap_or(100);
+        format!(
+            "{} lines {}-{}/{} {}%",
+            self.name.as_deref().unwrap_or("Standard input"),
+            first,
+            last,
+            self.lines.len(),
+            percent
+        )
+    }
+
+    fn render_row(&self, row: &Row) -> String {
+        let line = &self.lines[row.line];
+        let marks: Vec<(usize, usize)> = self
+            .matches(row.line)
+            .into_iter()
+            .filter(|&(start, end)| start < row.end && end > row.start)
+            .map(|(start, end)| {
+                (
+                    start.max(row.start) - row.start,
+                    end.min(row.end) - row.start,
+                )
+            })
+            .collect();
+        let text = ansi::mark(&ansi::slice(line, row.start, row.end), &marks);
+
+        match (self.numbers, row.start) {
+            (false, _) => text,
+            (true, 0) => format!("{:>7} {}", row.line + 1, text),
+            (true, _) => format!("{}{}", " ".repeat(NUMBER_WIDTH), text),
+        }
+    }
+
+    fn status(&self, size: Size) -> String {
+        let reverse = |text: &str| format!("{}{}{}", ansi::REVERSE, text, ansi::RESET);
+        match &self.prompt {
+            Prompt::Search { forward, input } => {
+                format!("{}{}", if *forward { '/' } else { '?' }, input)
+            }
+            Prompt::Option => "-".to_string(),
+            Prompt::None => match &self.message {
+                Some(message) => reverse(message),
+                None if self.quit_at_end => {
+                    let bottom = (self.top + Self::page(size)).min(self.rows.len());
+                    let percent = (bottom * 100).checked_div(self.rows.len()).unwrap_or(100);
+                    reverse(&format!("--More--({}%)", percent))
+                }
+                None if self.at_end(size) => reverse("(END)"),
+                None => match &self.name {
+                    Some(name) if self.top == 0 => reverse(name),
+                    _ => ":".to_string(),
+                },
+            },
+        }
+    }
+}
+
+impl Program for Less {
+    fn key(&mut self, key: &Key, size: Size) -> Flow {
+        self.lay_out(size.width);
+        self.message = None;
+
+        match std::mem::replace(&mut self.prompt, Prompt::None) {
+            Prompt::Search { forward, mut input } => {
+                match key.key.as_str() {
+                    "Enter" => self.submit_search(forward, &input, size),
+                    "Escape" => {}
+                    "c" if key.ctrl => {}
+                    "Backspace" if input.is_empty() => {}
+                    "Backspace" => {
+                        input.pop();
+                        self.prompt = Prompt::Search { forward, input };
+                    }
+                    _ => {
+                        if let Some(c) = key.char() {
+                            input.push(c);
+                        }
+                        self.prompt = Prompt::Search { forward, input };
+                    }
+                }
+                return Flow::Continue;
+            }
+            Prompt::Option => {
+                if let Some(option) = key.char() {
+                    self.toggle(option);
+                }
+                return Flow::Continue;
+            }
+            Prompt::None => {}
+        }
+
+        if let Some(digit @ '0'..='9') = key.char() {
+            self.count.push(digit);
+            return Flow::Continue;
+        }
+        let count = self.count.parse::<usize>().ok();
+        self.count.clear();
+        let page = Self::page(size) as isize;
+        let lines = count.map_or(1, |count| count as isize);
+
+        match (key.key.as_str(), key.ctrl) {
+            ("q" | "Q", false) | ("c", true) => return Flow::Exit(std::mem::take(&mut self.keep)),
+            (" " | "f" | "PageDown", false) | ("f" | "v", true) => {
+                if self.quit_at_end && self.at_end(size) {
+                    return Flow::Exit(std::mem::take(&mut self.keep));
+                }
+                self.move_by(count.map_or(page, |count| count as isize), size);
+            }
+            ("b" | "PageUp", false) | ("b", true) => {
+                self.move_by(-count.map_or(page, |count| count as isize), size)
+            }
+            ("j" | "e" | "Enter" | "ArrowDown", false) | ("n" | "e" | "j", true) => {
+                self.move_by(lines, size)
+            }
+            ("k" | "y" | "ArrowUp", false) | ("p" | "y" | "k", true) => self.move_by(-lines, size),
+            ("d", _) => self.move_by(page / 2, size),
+            ("u", _) => self.move_by(-page / 2, size),
+            ("g" | "<" | "Home", false) => {
+                let top = count.map_or(0, |line| self.line_row(line.saturating_sub(1)));
+                self.go_to(top, size);
+            }
+            ("G" | ">" | "End", false) => {
+                let top = count.map_or(self.rows.len(), |line| {
+                    self.line_row(line.saturating_sub(1))
+                });
+                self.go_to(top, size);
+            }
+            ("/", false) => {
+                self.prompt = Prompt::Search {
+                    forward: true,
+                    input: String::new(),
+                }
+            }
+            ("?", false) => {
+                self.prompt = Prompt::Search {
+                    forward: false,
+                    input: String::new(),
+                }
+            }
+            ("n", false) => self.search(self.forward, size),
+            ("N", false) => self.search(!self.forward, size),
+            ("-", false) => self.prompt = Prompt::Option,
+            ("=", false) | ("g", true) => self.message = Some(self.info(size)),
+            _ => {}
+        }
+        Flow::Continue
+    }
+
+    fn view(&mut self, size: Size) -> Screen {
+        self.lay_out(size.width);
+        self.go_to(self.top, size);
+
+        let page = Self::page(size);
+        let mut lines: Vec<String> = self.rows[self.top..]
+            .iter()
+            .take(page)
+            .map(|row| self.render_row(row))
+            .collect();
+        lines.resize(page, "~".to_string());
+
+        let status = ansi::slice(&self.status(size), 0, size.width);
+        let cursor = (page, ansi::visible_chars(&status).len());
+        lines.push(status);
+        Screen {
+            lines,
+            cursor: Some(cursor),
+        }
+    }
+
+    fn scroll(&mut self, rows: isize, size: Size) {
+        self.lay_out(size.width);
+        self.move_by(rows, size);
+    }
+}
+
+/// `less [-N] [-i] [FILE...]`, and `more` with the same options: pages
+/// the files, or stdin when there are none. Several files are shown one
+/// after another under `more`-style headers.
+pub fn open(command: &str, args: &[&str], stdin: Option<&str>) -> Result<Less, String> {
+    let mut numbers = false;
+    let mut ignore_case = false;
+    let mut operands = Vec::new();
+    let mut options_done = false;
+
+    for &arg in args {
+        match arg {
+            "--" if !options_done => options_done = true,
+            "--LINE-NUMBERS" if !options_done => numbers = true,
+            "--ignore-case" if !options_done => ignore_case = true,
+            "--RAW-CONTROL-CHARS" if !options_done => {}
+            long if long.starts_with("--") && !options_done => {
+                return Err(format!("{}: unrecognized option '{}'", command, long))
+            }
+            short if short.starts_with('-') && short.len() > 1 && !options_done => {
+                for flag in short[1..].chars() {
+                    match flag {
+                        'N' => numbers = true,
+                        'i' => ignore_case = true,
+                        // Colours always come through.
+                        'R' | 'r' => {}
+                        other => return Err(format!("{}: invalid option -- '{}'", command, other)),
+                    }
+                }
+            }
+            operand => operands.push(operand),
+        }
+    }
+
+    if operands.is_empty() && stdin.is_none() {
+        return Err(format!(
+            "{}: missing filename (\"{} --help\" for help)",
+            command, command
+        ));
+    }
+
+    let mut errors = Vec::new();
+    let mut documents = Vec::new();
+    for input in text::inputs(command, &operands, stdin) {
+        match input {
+            Ok(document) => documents.push(document),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    let mut less = match documents.as_slice() {
+        [] => return Err(errors.join("\n")),
+        [(name, content)] => Less::new(Some(name.clone()).filter(|name| name != "-"), content),
+        several => {
+            let text: String = several
+                .iter()
+                .map(|(name, content)| {
+                    format!("::::::::::::::\n{}\n::::::::::::::\n{}", name, content)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Less::new(Some(several[0].0.clone()), &text)
+        }
+    };
+    less.numbers = numbers;
+    less.ignore_case = ignore_case;
+    less.quit_at_end = command == "more";
+    less.message = errors.into_iter().next();
+    Ok(less)
+}
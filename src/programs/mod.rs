@@ -0,0 +1,177 @@
+//! Full-screen programs such as `less` and `nano`. While one runs it owns
+//! the whole canvas and every key press; the scrollback and prompt come
+//! back when it exits.
+
+pub mod less;
+pub mod nano;
+pub mod vi;
+
+use std::cell::RefCell;
+
+use crate::commands::filesystem::{normalize_path, FsError, Node, CURRENT_PATH, FILESYSTEM};
+use crate::commands::permissions::{self, Access};
+use crate::commands::{mounts, snapshot, text, users};
+use crate::terminal::{ansi, buffer};
+
+/// One key press, named the way `KeyboardEvent.key` names it (`"j"`,
+/// `" "`, `"Enter"`, `"ArrowDown"`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub key: String,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Key {
+    /// The typed character, if this is a plain printable key.
+    pub fn char(&self) -> Option<char> {
+        let mut chars = self.key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if !self.ctrl && !self.alt => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// The canvas in character cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Size {
+    pub width: usize,
+    pub height: usize,
+}
+
+/// What a program draws: one string per row (ANSI styles allowed) and
+/// where the cursor sits, as row and column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Screen {
+    pub lines: Vec<String>,
+    pub cursor: Option<(usize, usize)>,
+}
+
+pub enum Flow {
+    Continue,
+    /// The program is done; the text is left in the scrollback.
+    Exit(String),
+}
+
+pub trait Program {
+    fn key(&mut self, key: &Key, size: Size) -> Flow;
+
+    fn view(&mut self, size: Size) -> Screen;
+
+    /// The mouse wheel, in rows; negative is up.
+    fn scroll(&mut self, _rows: isize, _size: Size) {}
+}
+
+thread_local! {
+    static RUNNING: RefCell<Option<Box<dyn Program>>> = RefCell::new(None);
+}
+
+/// Hands the screen and keyboard to `program`.
+pub fn start(program: Box<dyn Program>) {
+    RUNNING.with(|running| *running.borrow_mut() = Some(program));
+}
+
+pub fn is_running() -> bool {
+    RUNNING.with(|running| running.borrow().is_some())
+}
+
+/// Passes a key press to the running program. Returns the text it left
+/// behind once it exits, `None` while it keeps running.
+pub fn key(key: &Key, size: Size) -> Option<String> {
+    RUNNING.with(|running| {
+        let mut running = running.borrow_mut();
+        match running.as_mut()?.key(key, size) {
+            Flow::Continue => None,
+            Flow::Exit(output) => {
+                *running = None;
+                Some(output)
+            }
+        }
+    })
+}
+
+pub fn scroll(rows: isize, size: Size) {
+    RUNNING.with(|running| {
+        if let Some(program) = running.borrow_mut().as_mut() {
+            program.scroll(rows, size);
+        }
+    })
+}
+
+/// The running program's screen, if there is one.
+pub fn view(size: Size) -> Option<Screen> {
+    RUNNING.with(|running| {
+        running
+            .borrow_mut()
+            .as_mut()
+            .map(|program| program.view(size))
+    })
+}
+
+/// Reads `path`, relative to the working directory, into an editor.
+/// `Ok(None)` means the file does not exist yet.
+pub fn load_file(path: &str) -> Result<Option<String>, FsError> {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    match text::read_file(&filesystem, &normalize_path(path, &current_path), &session) {
+        Ok(content) => Ok(Some(content)),
+        Err(FsError::NotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether saving over the existing file at `path` would be refused.
+pub fn is_unwritable(path: &str) -> bool {
+    let filesystem = FILESYSTEM.lock().unwrap();
+    let current_path = CURRENT_PATH.lock().unwrap();
+    let session = users::current_session();
+    match permissions::resolve(&filesystem, &normalize_path(path, &current_path), &session) {
+        Ok((canonical, node @ Node::File { .. })) => {
+            filesystem.writable(&canonical).is_err()
+                || !permissions::check(&filesystem, node, &session, Access::Write)
+        }
+        _ => false,
+    }
+}
+
+/// Saves an editor's buffer to `path` the way a command would change the
+/// filesystem: `undo` can take it back as `command`, and overlay mounts
+/// are persisted. Should storage refuse them, the terminal says so once the
+/// editor closes.
+pub fn save_file(command: &str, path: &str, content: &str) -> Result<(), FsError> {
+    let before = snapshot::take();
+    {
+        let mut filesystem = FILESYSTEM.lock().unwrap();
+        let current_path = CURRENT_PATH.lock().unwrap().clone();
+        let session = users::current_session();
+        text::write_file(
+            &mut filesystem,
+            &normalize_path(path, &current_path),
+            content.as_bytes().to_vec(),
+            &session,
+        )?;
+    }
+    snapshot::record(before, command);
+    if let Err(err) = mounts::sync() {
+        buffer::add_output_lines(&err, None);
+    }
+    Ok(())
+}
+
+/// A buffer line as drawn: control characters show as their caret
+/// letter in reverse video, one cell wide.
+pub fn display(chars: &[char]) -> String {
+    chars
+        .iter()
+        .map(|&c| match c {
+            '\t' => " ".to_string(),
+            c if (c as u32) < 0x20 || c == '\x7f' => {
+                let letter = ((c as u8) ^ 0x40) as char;
+                ansi::paint(&letter.to_string(), "7")
+            }
+            c => c.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,782 @@
+//! `nano`: a small full-screen editor for files in the filesystem. Long
+//! lines wrap onto the following screen rows rather than scrolling
+//! sideways.
+
+use crate::programs::{self, Flow, Key, Program, Screen, Size};
+use crate::terminal::ansi;
+
+/// Spaces inserted for Tab; the canvas has no tab stops to align to.
+const TAB_WIDTH: usize = 4;
+
+/// The title bar, the status line and the two shortcut lines.
+const CHROME_ROWS: usize = 4;
+
+enum Prompt {
+    None,
+    WriteOut { input: String, exit: bool },
+    Search { input: String },
+    SaveChanges,
+}
+
+pub struct Nano {
+    name: Option<String>,
+    lines: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    /// The column within a screen row that up and down aim for.
+    goal: usize,
+    /// The first screen row shown, counting wrapped rows.
+    top: usize,
+    modified: bool,
+    prompt: Prompt,
+    message: Option<String>,
+    cut: Vec<Vec<char>>,
+    /// Whether the previous key cut a line, so the next cut adds to it.
+    cutting: bool,
+    last_search: String,
+}
+
+impl Nano {
+    fn new(name: Option<String>, content: &str) -> Self {
+        let mut lines: Vec<Vec<char>> = content.lines().map(|l| l.chars().collect()).collect();
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        Self {
+            name,
+            lines,
+            row: 0,
+            col: 0,
+            goal: 0,
+            top: 0,
+            modified: false,
+            prompt: Prompt::None,
+            message: None,
+            cut: Vec::new(),
+            cutting: false,
+            last_search: String::new(),
+        }
+    }
+
+    fn content(&self) -> String {
+        if self.lines.len() == 1 && self.lines[0].is_empty() {
+            return String::new();
+        }
+        self.lines
+            .iter()
+            .map(|line| line.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    fn edit_rows(size: Size) -> usize {
+        size.height.saturating_sub(CHROME_ROWS).max(1)
+    }
+
+    /// Screen rows line `line` takes; a line exactly filling its last
+    /// row gets one more for the cursor to sit at its end.
+    fn rows_of(&self, line: usize, width: usize) -> usize {
+        self.lines[line].len() / width + 1
+    }
+
+    /// The screen row, counted from the top of the file, the cursor is on.
+    fn cursor_row(&self, width: usize) -> usize {
+        (0..self.row)
+            .map(|line| self.rows_of(line, width))
+            .sum::<usize>()
+            + self.col / width
+    }
+
+    fn scroll_into_view(&mut self, size: Size) {
+        let width = size.width.max(1);
+        let cursor = self.cursor_row(width);
+        let rows = Self::edit_rows(size);
+        if cursor < self.top {
+            self.top = cursor;
+        } else if cursor >= self.top + rows {
+            self.top = cursor + 1 - rows;
+        }
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].len()
+    }
+
+    fn set_col(&mut self, col: usize, width: usize) {
+        self.col = col;
+        self.goal = col % width;
+    }
+
+    fn up(&mut self, width: usize) {
+        let chunk = self.col / width;
+        if chunk > 0 {
+            self.col = (chunk - 1) * width + self.goal;
+        } else if self.row > 0 {
+            self.row -= 1;
+            let last = self.line_len() / width;
+            self.col = (last * width + self.goal).min(self.line_len());
+        }
+    }
+
+    fn down(&mut self, width: usize) {
+        let next = (self.col / width + 1) * width;
+        if next <= self.line_len() {
+            self.col = (next + self.goal).min(self.line_len());
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.goal.min(self.line_len());
+        }
+    }
+
+    fn insert(&mut self, text: &str, width: usize) {
+        for c in text.chars() {
+            self.lines[self.row].insert(self.col, c);
+            self.col += 1;
+        }
+        self.goal = self.col % width;
+        self.modified = true;
+    }
+
+    fn newline(&mut self) {
+        let rest = self.lines[self.row].split_off(self.col);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+        self.goal = 0;
+        self.modified = true;
+    }
+
+    fn backspace(&mut self, width: usize) {
+        if self.col > 0 {
+            self.lines[self.row].remove(self.col - 1);
+            self.set_col(self.col - 1, width);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            let col = self.line_len();
+            self.lines[self.row].extend(line);
+            self.set_col(col, width);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    fn delete(&mut self) {
+        if self.col < self.line_len() {
+            self.lines[self.row].remove(self.col);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(next);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    /// `^K`: cuts the current line. Consecutive cuts collect together.
+    fn cut_line(&mut self) {
+        if !self.cutting {
+            self.cut.clear();
+        }
+        if self.lines.len() == 1 {
+            self.cut.push(std::mem::take(&mut self.lines[0]));
+        } else {
+            self.cut.push(self.lines.remove(self.row));
+            self.row = self.row.min(self.lines.len() - 1);
+        }
+        self.col = 0;
+        self.goal = 0;
+        self.modified = true;
+    }
+
+    /// `^U`: puts the cut lines back above the cursor.
+    fn paste(&mut self) {
+        if self.cut.is_empty() {
+            return;
+        }
+        for (offset, line) in self.cut.iter().enumerate() {
+            self.lines.insert(self.row + offset, line.clone());
+        }
+        self.row += self.cut.len();
+        self.col = 0;
+        self.goal = 0;
+        self.modified = true;
+    }
+
+    /// `^C`: where the cursor is, as nano reports it.
+    fn location(&self) -> String {
+        let percent = |part: usize, whole: usize| (part * 100).checked_div(whole).unwrap_or(0);
+        let chars_before: usize = self.lines[..self.row]
+            .iter()
+            .map(|l| l.len() + 1)
+            .sum::<usize>()
+            + self.col;
+        let chars: usize = self.lines.iter().map(|l| l.len() + 1).sum::<usize>() - 1;
+        format!(
+            "line {}/{} ({}%), col {}/{} ({}%), char {}/{} ({}%)",
+            self.row + 1,
+            self.lines.len(),
+            percent(self.row + 1, self.lines.len()),
+            self.col + 1,
+            self.line_len() + 1,
+            percent(self.col + 1, self.line_len() + 1),
+            chars_before,
+            chars,
+            percent(chars_before, chars)
+        )
+    }
+
+    /// Moves to the next case-insensitive match after the cursor,
+    /// wrapping around the end of the buffer.
+    fn search(&mut self, needle: &str, width: usize) {
+        let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+        let needle: Vec<char> = needle.chars().map(fold).collect();
+        let find = |line: &[char], from: usize| {
+            let line: Vec<char> = line.iter().copied().map(fold).collect();
+            (from..=line.len().saturating_sub(needle.len()))
+                .find(|&start| line.get(start..start + needle.len()) == Some(&needle[..]))
+        };
+
+        let count = self.lines.len();
+        for step in 0..=count {
+            let row = (self.row + step) % count;
+            let from = if step == 0 { self.col + 1 } else { 0 };
+            let found = match find(&self.lines[row], from) {
+                // Past the cursor on its own line means we wrapped all
+                // the way around.
+                Some(col) if step == count && col > self.col => None,
+                found => found,
+            };
+            if let Some(col) = found {
+                self.message = if step == count && col == self.col {
+                    Some("This is the only occurrence".to_string())
+                } else if row < self.row || (row == self.row && step > 0) {
+                    Some("Search Wrapped".to_string())
+                } else {
+                    None
+                };
+                self.row = row;
+                self.set_col(col, width);
+                return;
+            }
+        }
+        self.message = Some(format!(
+            "\"{}\" not found",
+            needle.iter().collect::<String>()
+        ));
+    }
+
+    /// Writes the buffer out; returns whether it worked.
+    fn write(&mut self, name: &str) -> bool {
+        let command = format!("nano {}", name);
+        match programs::save_file(&command, name, &self.content()) {
+            Ok(()) => {
+                let lines = if self.content().is_empty() {
+                    0
+                } else {
+                    self.lines.len()
+                };
+                self.message = Some(format!(
+                    "Wrote {} line{}",
+                    lines,
+                    if lines == 1 { "" } else { "s" }
+                ));
+                self.name = Some(name.to_string());
+                self.modified = false;
+                true
+            }
+            Err(err) => {
+                self.message = Some(format!("Error writing {}: {}", name, err));
+                false
+            }
+        }
+    }
+
+    fn prompt_key(&mut self, prompt: Prompt, key: &Key, width: usize) -> Flow {
+        let cancel = key.key == "Escape" || (key.ctrl && key.key == "c");
+        match prompt {
+            Prompt::None => {}
+            _ if cancel => self.message = Some("Cancelled".to_string()),
+            Prompt::SaveChanges => match key.char() {
+                Some('y' | 'Y') => match self.name.clone() {
+                    Some(name) => {
+                        if self.write(&name) {
+                            return Flow::Exit(String::new());
+                        }
+                    }
+                    None => {
+                        self.prompt = Prompt::WriteOut {
+                            input: String::new(),
+                            exit: true,
+                        }
+                    }
+                },
+                Some('n' | 'N') => return Flow::Exit(String::new()),
+                _ => self.prompt = Prompt::SaveChanges,
+            },
+            Prompt::WriteOut { mut input, exit } => match key.key.as_str() {
+                "Enter" if input.is_empty() => self.message = Some("Cancelled".to_string()),
+                "Enter" => {
+                    if self.write(&input) && exit {
+                        return Flow::Exit(String::new());
+                    }
+                }
+                "Backspace" => {
+                    input.pop();
+                    self.prompt = Prompt::WriteOut { input, exit };
+                }
+                _ => {
+                    input.extend(key.char());
+                    self.prompt = Prompt::WriteOut { input, exit };
+                }
+            },
+            Prompt::Search { mut input } => match key.key.as_str() {
+                "Enter" => {
+                    if !input.is_empty() {
+                        self.last_search = input;
+                    }
+                    if self.last_search.is_empty() {
+                        self.message = Some("Cancelled".to_string());
+                    } else {
+                        let needle = self.last_search.clone();
+                        self.search(&needle, width);
+                    }
+                }
+                "Backspace" => {
+                    input.pop();
+                    self.prompt = Prompt::Search { input };
+                }
+                _ => {
+                    input.extend(key.char());
+                    self.prompt = Prompt::Search { input };
+                }
+            },
+        }
+        Flow::Continue
+    }
+
+    fn title(&self, width: usize) -> String {
+        let left = "  GNU nano 7.2";
+        let name = self.name.as_deref().unwrap_or("New Buffer");
+        let right = if self.modified { "Modified  " } else { "" };
+
+        let mut bar: Vec<char> = vec![' '; width];
+        let mut put = |at: usize, text: &str| {
+            for (offset, c) in text.chars().enumerate() {
+                if let Some(cell) = bar.get_mut(at + offset) {
+                    *cell = c;
+                }
+            }
+        };
+        // The name wins over the version when the bar is too narrow for both.
+        let name_at = width.saturating_sub(name.chars().count()) / 2;
+        if name_at > left.len() {
+            put(0, left);
+        }
+        put(name_at, name);
+        put(width.saturating_sub(right.len()), right);
+        format!(
+            "{}{}{}",
+            ansi::REVERSE,
+            bar.into_iter().collect::<String>(),
+            ansi::RESET
+        )
+    }
+
+    fn status(&self, width: usize) -> (String, Option<usize>) {
+        let reverse = |text: &str| format!("{}{}{}", ansi::REVERSE, text, ansi::RESET);
+        // Text too long for the line scrolls so its end, where the cursor
+        // is, stays in view.
+        let field = |label: &str, input: &str| {
+            let text = format!("{}{}", label, input);
+            let len = text.chars().count();
+            let skip = (len + 1).saturating_sub(width);
+            let shown: String = text.chars().skip(skip).collect();
+            let padded = format!("{}{}", shown, " ".repeat(width.saturating_sub(len - skip)));
+            (reverse(&padded), Some(len - skip))
+        };
+        match &self.prompt {
+            Prompt::WriteOut { input, .. } => field("File Name to Write: ", input),
+            Prompt::Search { input } if self.last_search.is_empty() => field("Search: ", input),
+            Prompt::Search { input } => field(&format!("Search [{}]: ", self.last_search), input),
+            Prompt::SaveChanges => field(
+                "Save modified buffer?  (Answering \"No\" will DISCARD changes.) ",
+                "",
+            ),
+            Prompt::None => match &self.message {
+                Some(message) => {
+                    let text = format!("[ {} ]", message);
+                    let pad = width.saturating_sub(text.chars().count()) / 2;
+                    (format!("{}{}", " ".repeat(pad), reverse(&text)), None)
+                }
+                None => (String::new(), None),
+            },
+        }
+    }
+
+    fn shortcuts(&self, width: usize) -> [String; 2] {
+        let rows: [&[(&str, &str)]; 2] = match self.prompt {
+            Prompt::None => [
+                &[
+                    ("^X", "Exit"),
+                    ("^O", "Write Out"),
+                    ("^F", "Where Is"),
+                    ("^K", "Cut"),
+                    ("^C", "Location"),
+                ],
+                &[
+                    ("^S", "Save"),
+                    ("^U", "Paste"),
+                    ("^A", "Line Start"),
+                    ("^E", "Line End"),
+                    ("M-W", "Find Next"),
+                ],
+            ],
+            Prompt::SaveChanges => [&[(" Y", "Yes")], &[(" N", "No"), ("^C", "Cancel")]],
+            _ => [&[("^C", "Cancel")], &[]],
+        };
+        let cell = (width / 5).max(1);
+        rows.map(|row| {
+            row.iter()
+                .map(|(key, label)| {
+                    let text = format!(" {}", label);
+                    let pad = cell.saturating_sub(key.len() + text.chars().count()).max(1);
+                    format!("{}{}{}", ansi::paint(key, "7"), text, " ".repeat(pad))
+                })
+                .collect()
+        })
+    }
+}
+
+impl Program for Nano {
+    fn key(&mut self, key: &Key, size: Size) -> Flow {
+        let width = size.width.max(1);
+        self.message = None;
+
+        let prompt = std::mem::replace(&mut self.prompt, Prompt::None);
+        if !matches!(prompt, Prompt::None) {
+            let flow = self.prompt_key(prompt, key, width);
+            self.scroll_into_view(size);
+            return flow;
+        }
+
+        let cutting = std::mem::take(&mut self.cutting);
+        if let Some(c) = key.char() {
+            self.insert(&c.to_string(), width);
+            self.scroll_into_view(size);
+            return Flow::Continue;
+        }
+
+        let page = Self::edit_rows(size);
+        match (key.key.as_str(), key.ctrl, key.alt) {
+            ("x", true, _) | ("F2", ..) => {
+                if !self.modified {
+                    return Flow::Exit(String::new());
+                }
+                self.prompt = Prompt::SaveChanges;
+            }
+            ("o", true, _) | ("F3", ..) => {
+                self.prompt = Prompt::WriteOut {
+                    input: self.name.clone().unwrap_or_default(),
+                    exit: false,
+                }
+            }
+            ("s", true, _) => match self.name.clone() {
+                Some(name) => {
+                    self.write(&name);
+                }
+                None => {
+                    self.prompt = Prompt::WriteOut {
+                        input: String::new(),
+                        exit: false,
+                    }
+                }
+            },
+            ("f" | "w", true, _) | ("F6", ..) => {
+                self.prompt = Prompt::Search {
+                    input: String::new(),
+                }
+            }
+            ("w", false, true) => {
+                if self.last_search.is_empty() {
+                    self.message = Some("No current search pattern".to_string());
+                } else {
+                    let needle = self.last_search.clone();
+                    self.search(&needle, width);
+                }
+            }
+            ("k", true, _) | ("F9", ..) => {
+                self.cutting = cutting;
+                self.cut_line();
+                self.cutting = true;
+            }
+            ("u", true, _) | ("F10", ..) => self.paste(),
+            ("c", true, _) | ("F11", ..) => self.message = Some(self.location()),
+            ("Enter", ..) => self.newline(),
+            ("Tab", ..) => self.insert(&" ".repeat(TAB_WIDTH), width),
+            ("Backspace", ..) | ("h", true, _) => self.backspace(width),
+            ("Delete", ..) | ("d", true, _) => self.delete(),
+            ("ArrowLeft", ..) | ("b", true, _) => {
+                if self.col > 0 {
+                    self.set_col(self.col - 1, width);
+                } else if self.row > 0 {
+                    self.row -= 1;
+                    self.set_col(self.line_len(), width);
+                }
+            }
+            ("ArrowRight", ..) => {
+                if self.col < self.line_len() {
+                    self.set_col(self.col + 1, width);
+                } else if self.row + 1 < self.lines.len() {
+                    self.row += 1;
+                    self.set_col(0, width);
+                }
+            }
+            ("ArrowUp", ..) | ("p", true, _) => self.up(width),
+            ("ArrowDown", ..) | ("n", true, _) => self.down(width),
+            ("Home", ..) | ("a", true, _) => self.set_col(0, width),
+            ("End", ..) | ("e", true, _) => self.set_col(self.line_len(), width),
+            ("PageUp", ..) | ("y", true, _) => (0..page).for_each(|_| self.up(width)),
+            ("PageDown", ..) | ("v", true, _) => (0..page).for_each(|_| self.down(width)),
+            _ => {}
+        }
+        self.scroll_into_view(size);
+        Flow::Continue
+    }
+
+    fn view(&mut self, size: Size) -> Screen {
+        let width = size.width.max(1);
+        let rows = Self::edit_rows(size);
+        self.scroll_into_view(size);
+
+        let mut lines = vec![self.title(width)];
+        let mut skip = self.top;
+        for line in &self.lines {
+            let chunks = line.len() / width + 1;
+            if skip >= chunks {
+                skip -= chunks;
+                continue;
+            }
+            for chunk in skip..chunks {
+                let end = ((chunk + 1) * width).min(line.len());
+                lines.push(programs::display(&line[chunk * width..end]));
+            }
+            skip = 0;
+            if lines.len() > rows {
+                break;
+            }
+        }
+        lines.truncate(rows + 1);
+        lines.resize(rows + 1, String::new());
+
+        let (status, prompt_cursor) = self.status(width);
+        lines.push(status);
+        lines.extend(self.shortcuts(width));
+
+        let (row, col) = match prompt_cursor {
+            Some(col) => (rows + 1, col),
+            None => (1 + self.cursor_row(width) - self.top, self.col % width),
+        };
+        Screen {
+            lines: lines
+                .iter()
+                .map(|line| ansi::slice(line, 0, width))
+                .collect(),
+            cursor: Some((row.min(lines.len() - 1), col.min(width - 1))),
+        }
+    }
+
+    fn scroll(&mut self, rows: isize, size: Size) {
+        let width = size.width.max(1);
+        for _ in 0..rows.unsigned_abs() {
+            if rows < 0 {
+                self.up(width);
+            } else {
+                self.down(width);
+            }
+        }
+    }
+}
+
+/// `nano [+LINE] [FILE]`: edits `FILE`, or a new buffer that is named
+/// when it is first written out.
+pub fn open(args: &[&str]) -> Result<Nano, String> {
+    let mut line = None;
+    let mut files = Vec::new();
+    for &arg in args {
+        if let Some(number) = arg.strip_prefix('+') {
+            match number.parse::<usize>() {
+                Ok(number) => line = Some(number),
+                Err(_) => return Err(format!("nano: invalid line number '{}'", number)),
+            }
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("nano: invalid option -- '{}'", &arg[1..]));
+        } else {
+            files.push(arg);
+        }
+    }
+
+    let mut nano = match files.as_slice() {
+        [] => Nano::new(None, ""),
+        [name] => match programs::load_file(name) {
+            Ok(Some(content)) => {
+                let mut nano = Nano::new(Some(name.to_string()), &content);
+                let count = content.lines().count();
+                nano.message = Some(if programs::is_unwritable(name) {
+                    format!("File '{}' is unwritable", name)
+                } else {
+                    format!("Read {} line{}", count, if count == 1 { "" } else { "s" })
+                });
+                nano
+            }
+            Ok(None) => {
+                let mut nano = Nano::new(Some(name.to_string()), "");
+                nano.message = Some("New File".to_string());
+                nano
+            }
+            Err(err) => return Err(format!("nano: {}: {}", name, err)),
+        },
+        _ => return Err("nano: only one file can be edited at a time".to_string()),
+    };
+    if let Some(line) = line {
+        nano.row = line.saturating_sub(1).min(nano.lines.len() - 1);
+    }
+    Ok(nano)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Size = Size {
+        width: 80,
+        height: 24,
+    };
+
+    /// Presses a named key, or a letter with Ctrl held when `ctrl` is set.
+    fn press(nano: &mut Nano, key: &str, ctrl: bool) -> Flow {
+        let key = Key {
+            key: key.to_string(),
+            ctrl,
+            alt: false,
+        };
+        nano.key(&key, SIZE)
+    }
+
+    fn type_text(nano: &mut Nano, text: &str) {
+        for c in text.chars() {
+            press(nano, &c.to_string(), false);
+        }
+    }
+
+    #[test]
+    fn edits_at_the_cursor() {
+        let mut nano = Nano::new(None, "one\ntwo\n");
+        type_text(&mut nano, "zero ");
+        press(&mut nano, "End", false);
+        press(&mut nano, "Enter", false);
+        type_text(&mut nano, "x");
+        press(&mut nano, "Backspace", false);
+        press(&mut nano, "Backspace", false);
+        press(&mut nano, "ArrowDown", false);
+        press(&mut nano, "Home", false);
+        press(&mut nano, "Delete", false);
+        press(&mut nano, "Tab", false);
+        assert_eq!(nano.content(), "zero one\n    wo\n");
+        assert!(nano.modified);
+    }
+
+    #[test]
+    fn consecutive_cuts_paste_together() {
+        let mut nano = Nano::new(None, "a\nb\nc\nd\n");
+        press(&mut nano, "k", true);
+        press(&mut nano, "k", true);
+        press(&mut nano, "ArrowDown", false);
+        press(&mut nano, "u", true);
+        assert_eq!(nano.content(), "c\na\nb\nd\n");
+
+        // A key in between starts a fresh cut.
+        press(&mut nano, "k", true);
+        press(&mut nano, "ArrowUp", false);
+        press(&mut nano, "k", true);
+        press(&mut nano, "u", true);
+        assert_eq!(nano.content(), "c\na\nb\n");
+    }
+
+    #[test]
+    fn searches_wrap_around() {
+        let mut nano = Nano::new(None, "Foo bar\nbaz foo\n");
+        press(&mut nano, "w", true);
+        type_text(&mut nano, "foo");
+        press(&mut nano, "Enter", false);
+        assert_eq!((nano.row, nano.col, nano.message.clone()), (1, 4, None));
+
+        nano.prompt = Prompt::None;
+        nano.key(
+            &Key {
+                key: "w".to_string(),
+                ctrl: false,
+                alt: true,
+            },
+            SIZE,
+        );
+        assert_eq!((nano.row, nano.col), (0, 0));
+        assert_eq!(nano.message.as_deref(), Some("Search Wrapped"));
+
+        press(&mut nano, "w", true);
+        type_text(&mut nano, "qux");
+        press(&mut nano, "Enter", false);
+        assert_eq!(nano.message.as_deref(), Some("\"qux\" not found"));
+    }
+
+    #[test]
+    fn asks_before_discarding_changes() {
+        let mut nano = Nano::new(None, "text\n");
+        assert!(matches!(press(&mut nano, "x", true), Flow::Exit(_)));
+
+        type_text(&mut nano, "more ");
+        assert!(matches!(press(&mut nano, "x", true), Flow::Continue));
+        assert!(matches!(nano.prompt, Prompt::SaveChanges));
+        assert!(matches!(press(&mut nano, "Escape", false), Flow::Continue));
+        assert_eq!(nano.message.as_deref(), Some("Cancelled"));
+
+        press(&mut nano, "x", true);
+        assert!(matches!(press(&mut nano, "n", false), Flow::Exit(_)));
+    }
+    #[test]
+    fn every_row_fits_a_narrow_screen() {
+        let size = Size {
+            width: 12,
+            height: 8,
+        };
+        let fits = |nano: &mut Nano| {
+            let screen = nano.view(size);
+            for line in &screen.lines {
+                assert!(
+                    ansi::strip(line).chars().count() <= size.width,
+                    "{:?}",
+                    line
+                );
+            }
+            let (row, col) = screen.cursor.unwrap();
+            assert!(row < screen.lines.len() && col < size.width);
+            screen
+        };
+
+        let mut nano = Nano::new(Some("a-rather-long-name.txt".to_string()), "text\n");
+        let title = ansi::strip(&fits(&mut nano).lines[0]);
+        assert!(!title.contains("GNU nano"));
+
+        type_text(&mut nano, "x");
+        press(&mut nano, "x", true);
+        assert!(matches!(nano.prompt, Prompt::SaveChanges));
+        fits(&mut nano);
+
+        press(&mut nano, "Escape", false);
+        press(&mut nano, "w", true);
+        type_text(&mut nano, "a search longer than the screen");
+        let screen = fits(&mut nano);
+        assert_eq!(screen.cursor, Some((5, 11)));
+        assert_eq!(ansi::strip(&screen.lines[5]), " the screen ");
+    }
+}
@@ -0,0 +1,2041 @@
+//! `vi`/`vim`: a modal editor for files in the filesystem. Normal mode
+//! reads counts, motions and operators; `i`, `v` and `:` switch to
+//! insert, visual and command-line mode, and Escape comes back.
+
+use crate::commands::regex::{Regex, Syntax};
+use crate::commands::sed;
+use crate::programs::{self, Flow, Key, Program, Screen, Size};
+use crate::terminal::ansi;
+
+/// Spaces inserted for Tab; the canvas has no tab stops to align to.
+const TAB_WIDTH: usize = 4;
+
+/// Counts beyond this are clamped so `99999999p` cannot hang the page.
+const MAX_COUNT: usize = 10_000;
+
+/// A buffer position: line, then character within the line.
+type Pos = (usize, usize);
+
+/// A span of buffer lines, first and last.
+type Lines = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Normal,
+    Insert,
+    Visual { line: bool },
+    Command,
+    Search { forward: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `w`/`W`, `b`/`B` and `e`/`E`; true for the blank-separated kind.
+    WordForward(bool),
+    WordBackward(bool),
+    WordEnd(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    NextLine,
+    PrevLine,
+    /// `G`: the last line, or line COUNT.
+    Goto,
+    /// `gg`: the first line, or line COUNT.
+    GotoFirst,
+    Find {
+        target: char,
+        forward: bool,
+        till: bool,
+    },
+    /// `n` (true) or `N` (false).
+    SearchNext(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// How much of the text between the cursor and a motion's target an
+/// operator takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reach {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Move(Motion),
+    /// `None` is the doubled form (`dd`, `cc`, `yy`).
+    Operate(Operator, Option<Motion>),
+    Replace(char),
+    Quit {
+        write: bool,
+    },
+    Other(char),
+}
+
+impl Action {
+    /// Whether `.` repeats this and `u` takes it back.
+    fn is_change(&self) -> bool {
+        match self {
+            Action::Operate(op, _) => *op != Operator::Yank,
+            Action::Replace(_) => true,
+            Action::Other(c) => "xXDCsSpPiaIAoOJ~".contains(*c),
+            Action::Move(_) | Action::Quit { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Command {
+    count: Option<usize>,
+    action: Action,
+}
+
+enum Parse<T> {
+    Incomplete,
+    Invalid,
+    Done(T),
+}
+
+/// The unnamed register `d`, `c` and `y` fill and `p` puts back.
+#[derive(Debug, Clone, Default)]
+struct Register {
+    text: Vec<Vec<char>>,
+    linewise: bool,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    lines: Vec<Vec<char>>,
+    cursor: Pos,
+}
+
+/// The keys of the last change, for `.`: the command after its count,
+/// then anything typed in the insert mode it started.
+#[derive(Debug, Clone)]
+struct Change {
+    count: Option<usize>,
+    keys: Vec<Key>,
+}
+
+/// Text typed after a counted insert (`3ix<Esc>`, `2o`) is typed again
+/// `count - 1` more times on Escape.
+struct InsertRepeat {
+    count: usize,
+    newline: bool,
+    /// Where the typed keys start in the change being recorded.
+    start: usize,
+}
+
+pub struct Vi {
+    /// `vi` or `vim`, as invoked; names the change for `undo`.
+    program: String,
+    name: Option<String>,
+    lines: Vec<Vec<char>>,
+    /// The buffer as last read or written, to tell whether it changed.
+    saved: Vec<Vec<char>>,
+    row: usize,
+    col: usize,
+    /// The column `j` and `k` aim for; `usize::MAX` after `$`.
+    goal: usize,
+    /// The first buffer line on screen.
+    top: usize,
+    mode: Mode,
+    /// Where visual mode started.
+    anchor: Pos,
+    /// The lines of the last visual selection, for `'<,'>`.
+    marks: Option<Lines>,
+    pending: Vec<char>,
+    /// What has been typed after `:`, `/` or `?`.
+    input: String,
+    message: Option<String>,
+    error: bool,
+    register: Register,
+    undo: Vec<State>,
+    redo: Vec<State>,
+    /// The buffer before the change in progress.
+    before: Option<State>,
+    recording: Option<Change>,
+    insert_repeat: Option<InsertRepeat>,
+    last_change: Option<Change>,
+    /// The last pattern searched for and whether that was forwards.
+    last_search: Option<(String, bool)>,
+    number: bool,
+    size: Size,
+}
+
+fn typed(c: char) -> Key {
+    Key {
+        key: c.to_string(),
+        ctrl: false,
+        alt: false,
+    }
+}
+
+/// Reads a count at `keys[*i..]`. A leading `0` is the `0` motion, not a
+/// count.
+fn count(keys: &[char], i: &mut usize) -> Option<usize> {
+    let start = *i;
+    while let Some(&c) = keys.get(*i) {
+        if !c.is_ascii_digit() || (c == '0' && *i == start) {
+            break;
+        }
+        *i += 1;
+    }
+    let digits: String = keys[start..*i].iter().collect();
+    digits.parse::<usize>().ok().map(|n| n.min(MAX_COUNT))
+}
+
+fn parse_motion(keys: &[char]) -> Parse<Motion> {
+    let motion = match keys[0] {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'j' => Motion::Down,
+        'k' => Motion::Up,
+        'w' => Motion::WordForward(false),
+        'W' => Motion::WordForward(true),
+        'b' => Motion::WordBackward(false),
+        'B' => Motion::WordBackward(true),
+        'e' => Motion::WordEnd(false),
+        'E' => Motion::WordEnd(true),
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        '+' => Motion::NextLine,
+        '-' => Motion::PrevLine,
+        'G' => Motion::Goto,
+        'n' => Motion::SearchNext(true),
+        'N' => Motion::SearchNext(false),
+        'g' => {
+            return match keys.get(1) {
+                None => Parse::Incomplete,
+                Some('g') => Parse::Done(Motion::GotoFirst),
+                Some(_) => Parse::Invalid,
+            }
+        }
+        c @ ('f' | 't' | 'F' | 'T') => {
+            return match keys.get(1) {
+                None => Parse::Incomplete,
+                Some(&target) => Parse::Done(Motion::Find {
+                    target,
+                    forward: c.is_lowercase(),
+                    till: c == 't' || c == 'T',
+                }),
+            }
+        }
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(motion)
+}
+
+/// Parses the keys typed so far in normal mode.
+fn parse(keys: &[char]) -> Parse<Command> {
+    let mut i = 0;
+    let count = count(keys, &mut i);
+    let c = match keys.get(i) {
+        Some(&c) => c,
+        None => return Parse::Incomplete,
+    };
+    let rest = &keys[i + 1..];
+
+    let action = match c {
+        'd' | 'c' | 'y' => {
+            let op = match c {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            let mut j = 0;
+            let inner = self::count(rest, &mut j);
+            let count = match (count, inner) {
+                (Some(a), Some(b)) => Some((a * b).min(MAX_COUNT)),
+                (a, b) => a.or(b),
+            };
+            let action = match rest.get(j) {
+                None => return Parse::Incomplete,
+                Some(&d) if d == c => Action::Operate(op, None),
+                Some(_) => match parse_motion(&rest[j..]) {
+                    Parse::Done(motion) => Action::Operate(op, Some(motion)),
+                    Parse::Incomplete => return Parse::Incomplete,
+                    Parse::Invalid => return Parse::Invalid,
+                },
+            };
+            return Parse::Done(Command { count, action });
+        }
+        'r' => match rest.first() {
+            Some(&with) => Action::Replace(with),
+            None => return Parse::Incomplete,
+        },
+        'Z' => match rest.first() {
+            Some('Z') => Action::Quit { write: true },
+            Some('Q') => Action::Quit { write: false },
+            Some(_) => return Parse::Invalid,
+            None => return Parse::Incomplete,
+        },
+        'x' | 'X' | 'D' | 'C' | 's' | 'S' | 'Y' | 'p' | 'P' | 'i' | 'a' | 'I' | 'A' | 'o' | 'O'
+        | 'u' | 'J' | '~' | '.' | 'v' | 'V' | ':' | '/' | '?' => Action::Other(c),
+        _ => match parse_motion(&keys[i..]) {
+            Parse::Done(motion) => Action::Move(motion),
+            Parse::Incomplete => return Parse::Incomplete,
+            Parse::Invalid => return Parse::Invalid,
+        },
+    };
+    Parse::Done(Command { count, action })
+}
+
+/// What a word motion sees at each position: 0 blank, 1 word
+/// characters, 2 punctuation, 3 an empty line.
+fn class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+/// Splits `text` at the first `delimiter` not escaped with a backslash.
+/// An escaped delimiter loses its backslash; other escapes are kept.
+fn split_delimited(text: &str, delimiter: char) -> (String, &str) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, d)) if d == delimiter => part.push(d),
+                Some((_, d)) => {
+                    part.push('\\');
+                    part.push(d);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter => return (part, &text[i + c.len_utf8()..]),
+            c => part.push(c),
+        }
+    }
+    (part, "")
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+impl Vi {
+    fn new(program: &str, name: Option<String>, content: &str) -> Self {
+        let mut lines: Vec<Vec<char>> = content.lines().map(|l| l.chars().collect()).collect();
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        Self {
+            program: program.to_string(),
+            name,
+            saved: lines.clone(),
+            lines,
+            row: 0,
+            col: 0,
+            goal: 0,
+            top: 0,
+            mode: Mode::Normal,
+            anchor: (0, 0),
+            marks: None,
+            pending: Vec::new(),
+            input: String::new(),
+            message: None,
+            error: false,
+            register: Register::default(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            before: None,
+            recording: None,
+            insert_repeat: None,
+            last_change: None,
+            last_search: None,
+            number: false,
+            size: Size {
+                width: 80,
+                height: 24,
+            },
+        }
+    }
+
+    fn content(&self) -> String {
+        if self.lines.len() == 1 && self.lines[0].is_empty() {
+            return String::new();
+        }
+        self.lines
+            .iter()
+            .map(|line| line.iter().collect::<String>() + "\n")
+            .collect()
+    }
+
+    fn modified(&self) -> bool {
+        self.lines != self.saved
+    }
+
+    fn len(&self, row: usize) -> usize {
+        self.lines[row].len()
+    }
+
+    fn last_line(&self) -> usize {
+        self.lines.len() - 1
+    }
+
+    /// `^`: the first non-blank, or the last character of a blank line.
+    fn first_non_blank(&self, row: usize) -> usize {
+        self.lines[row]
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or_else(|| self.len(row).saturating_sub(1))
+    }
+
+    /// The column `j` and `k` land on in `row`.
+    fn goal_col(&self, row: usize) -> usize {
+        self.goal.min(self.len(row).saturating_sub(1))
+    }
+
+    /// Keeps the cursor in the buffer. Only insert mode may sit just past
+    /// the end of a line.
+    fn clamp(&mut self) {
+        self.row = self.row.min(self.last_line());
+        let len = self.len(self.row);
+        let max = if self.mode == Mode::Insert {
+            len
+        } else {
+            len.saturating_sub(1)
+        };
+        self.col = self.col.min(max);
+    }
+
+    fn go_to(&mut self, (row, col): Pos) {
+        self.row = row;
+        self.col = col;
+        self.clamp();
+        self.goal = self.col;
+    }
+
+    fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+        self.error = false;
+    }
+
+    fn set_error(&mut self, message: String) {
+        self.message = Some(message);
+        self.error = true;
+    }
+
+    fn state(&self) -> State {
+        State {
+            lines: self.lines.clone(),
+            cursor: (self.row, self.col),
+        }
+    }
+
+    fn restore(&mut self, state: State) {
+        self.lines = state.lines;
+        self.row = state.cursor.0;
+        self.col = state.cursor.1;
+        self.clamp();
+        self.goal = self.col;
+    }
+
+    /// Ends the change in progress: remembers it for `.` and, if the
+    /// buffer changed, for `u`.
+    fn finish_change(&mut self) {
+        if let Some(change) = self.recording.take() {
+            self.last_change = Some(change);
+        }
+        if let Some(before) = self.before.take() {
+            if before.lines != self.lines {
+                self.undo.push(before);
+                self.redo.clear();
+            }
+        }
+    }
+
+    fn undo(&mut self, count: usize) {
+        self.before = None;
+        for _ in 0..count {
+            match self.undo.pop() {
+                Some(state) => {
+                    self.redo.push(self.state());
+                    self.restore(state);
+                }
+                None => return self.set_error("Already at oldest change".to_string()),
+            }
+        }
+    }
+
+    fn redo(&mut self, count: usize) {
+        self.before = None;
+        for _ in 0..count {
+            match self.redo.pop() {
+                Some(state) => {
+                    self.undo.push(self.state());
+                    self.restore(state);
+                }
+                None => return self.set_error("Already at newest change".to_string()),
+            }
+        }
+    }
+
+    /// Every position word motions step through, with the index of the
+    /// cursor's. Each line ends in a blank standing for the line break.
+    fn stops(&self, big: bool) -> (Vec<(Pos, u8)>, usize) {
+        let mut stops = Vec::new();
+        let mut cursor = 0;
+        for (row, line) in self.lines.iter().enumerate() {
+            if row == self.row {
+                cursor = stops.len() + self.col.min(line.len());
+            }
+            if line.is_empty() {
+                stops.push(((row, 0), 3));
+            }
+            stops.extend(
+                line.iter()
+                    .enumerate()
+                    .map(|(col, &c)| ((row, col), class(c, big))),
+            );
+            stops.push(((row, line.len()), 0));
+        }
+        (stops, cursor)
+    }
+
+    fn word(&self, motion: Motion, count: usize) -> Pos {
+        let big = matches!(
+            motion,
+            Motion::WordForward(true) | Motion::WordBackward(true) | Motion::WordEnd(true)
+        );
+        let (stops, mut i) = self.stops(big);
+        let last = stops.len() - 1;
+        for _ in 0..count {
+            match motion {
+                Motion::WordForward(_) => {
+                    let class = stops[i].1;
+                    if class == 3 {
+                        i += 1;
+                    } else if class != 0 {
+                        while i < last && stops[i].1 == class {
+                            i += 1;
+                        }
+                    }
+                    while i < last && stops[i].1 == 0 {
+                        i += 1;
+                    }
+                }
+                Motion::WordEnd(_) => {
+                    i = (i + 1).min(last);
+                    while i < last && matches!(stops[i].1, 0 | 3) {
+                        i += 1;
+                    }
+                    let class = stops[i].1;
+                    while i < last && class != 0 && stops[i + 1].1 == class {
+                        i += 1;
+                    }
+                }
+                _ => {
+                    i = i.saturating_sub(1);
+                    while i > 0 && stops[i].1 == 0 {
+                        i -= 1;
+                    }
+                    let class = stops[i].1;
+                    if class != 3 {
+                        while i > 0 && stops[i - 1].1 == class {
+                            i -= 1;
+                        }
+                    }
+                }
+            }
+        }
+        stops[i].0
+    }
+
+    /// Where `motion` goes from the cursor, or None if it cannot move.
+    fn target(&mut self, motion: Motion, count: Option<usize>) -> Option<(Pos, Reach)> {
+        let n = count.unwrap_or(1).max(1);
+        let (row, col) = (self.row, self.col);
+        let last = self.last_line();
+        let line_number = |count: Option<usize>, default: usize| {
+            count.map_or(default, |n| n.saturating_sub(1).min(last))
+        };
+
+        Some(match motion {
+            Motion::Left => ((row, col.saturating_sub(n)), Reach::Exclusive),
+            Motion::Right => ((row, (col + n).min(self.len(row))), Reach::Exclusive),
+            Motion::Up if row > 0 => {
+                let row = row.saturating_sub(n);
+                ((row, self.goal_col(row)), Reach::Linewise)
+            }
+            Motion::Down if row < last => {
+                let row = (row + n).min(last);
+                ((row, self.goal_col(row)), Reach::Linewise)
+            }
+            Motion::Up | Motion::Down => return None,
+            Motion::WordForward(_) | Motion::WordBackward(_) => {
+                (self.word(motion, n), Reach::Exclusive)
+            }
+            Motion::WordEnd(_) => (self.word(motion, n), Reach::Inclusive),
+            Motion::LineStart => ((row, 0), Reach::Exclusive),
+            Motion::FirstNonBlank => ((row, self.first_non_blank(row)), Reach::Exclusive),
+            Motion::LineEnd => {
+                let row = (row + n - 1).min(last);
+                ((row, self.len(row).saturating_sub(1)), Reach::Inclusive)
+            }
+            Motion::NextLine if row < last => {
+                let row = (row + n).min(last);
+                ((row, self.first_non_blank(row)), Reach::Linewise)
+            }
+            Motion::PrevLine if row > 0 => {
+                let row = row.saturating_sub(n);
+                ((row, self.first_non_blank(row)), Reach::Linewise)
+            }
+            Motion::NextLine | Motion::PrevLine => return None,
+            Motion::Goto => {
+                let row = line_number(count, last);
+                ((row, self.first_non_blank(row)), Reach::Linewise)
+            }
+            Motion::GotoFirst => {
+                let row = line_number(count, 0);
+                ((row, self.first_non_blank(row)), Reach::Linewise)
+            }
+            Motion::Find {
+                target,
+                forward,
+                till,
+            } => {
+                let line = &self.lines[row];
+                let found = if forward {
+                    (col + 1..line.len())
+                        .filter(|&i| line[i] == target)
+                        .nth(n - 1)?
+                } else {
+                    (0..col).rev().filter(|&i| line[i] == target).nth(n - 1)?
+                };
+                let col = match (forward, till) {
+                    (true, true) => found - 1,
+                    (false, true) => found + 1,
+                    _ => found,
+                };
+                let reach = if forward {
+                    Reach::Inclusive
+                } else {
+                    Reach::Exclusive
+                };
+                ((row, col), reach)
+            }
+            Motion::SearchNext(same) => {
+                let (pattern, forward) = match self.last_search.clone() {
+                    Some(search) => search,
+                    None => {
+                        self.set_error("E35: No previous regular expression".to_string());
+                        return None;
+                    }
+                };
+                let pos = self.search(&pattern, forward == same, n)?;
+                (pos, Reach::Exclusive)
+            }
+        })
+    }
+
+    fn go(&mut self, motion: Motion, count: Option<usize>) {
+        if let Some(((row, col), _)) = self.target(motion, count) {
+            self.row = row;
+            self.col = col;
+            self.clamp();
+            match motion {
+                Motion::Up | Motion::Down => {}
+                Motion::LineEnd => self.goal = usize::MAX,
+                _ => self.goal = self.col,
+            }
+        }
+    }
+
+    /// Copies `from..to`, or the lines `from.0..=to.0` when linewise.
+    fn extract(&self, from: Pos, to: Pos, linewise: bool) -> Register {
+        let text = if linewise {
+            self.lines[from.0..=to.0].to_vec()
+        } else if from.0 == to.0 {
+            vec![self.lines[from.0][from.1..to.1].to_vec()]
+        } else {
+            let mut text = vec![self.lines[from.0][from.1..].to_vec()];
+            text.extend(self.lines[from.0 + 1..to.0].iter().cloned());
+            text.push(self.lines[to.0][..to.1].to_vec());
+            text
+        };
+        Register { text, linewise }
+    }
+
+    fn remove(&mut self, from: Pos, to: Pos, linewise: bool) {
+        if linewise {
+            self.lines.drain(from.0..=to.0);
+            if self.lines.is_empty() {
+                self.lines.push(Vec::new());
+            }
+        } else {
+            let tail = self.lines[to.0].split_off(to.1);
+            self.lines[from.0].truncate(from.1);
+            self.lines[from.0].extend(tail);
+            self.lines.drain(from.0 + 1..=to.0);
+        }
+    }
+
+    /// Applies an operator to `from..to` (exclusive), or to whole lines.
+    fn apply(&mut self, op: Operator, from: Pos, to: Pos, linewise: bool) {
+        let register = self.extract(from, to, linewise);
+        let count = register.text.len();
+        match op {
+            Operator::Yank => {
+                if linewise {
+                    self.row = from.0;
+                    self.clamp();
+                } else {
+                    self.go_to(from);
+                }
+                if linewise && count > 2 {
+                    self.set_message(format!("{} lines yanked", count));
+                }
+            }
+            Operator::Delete => {
+                self.remove(from, to, linewise);
+                if linewise {
+                    self.row = from.0.min(self.last_line());
+                    let col = self.first_non_blank(self.row);
+                    self.go_to((self.row, col));
+                    if count > 2 {
+                        self.set_message(format!("{} fewer lines", count));
+                    }
+                } else {
+                    self.go_to(from);
+                }
+            }
+            Operator::Change => {
+                self.remove(from, to, linewise);
+                if linewise {
+                    self.lines.insert(from.0, Vec::new());
+                    self.row = from.0;
+                    self.col = 0;
+                } else {
+                    self.row = from.0;
+                    self.col = from.1;
+                }
+                self.start_insert(1, false);
+            }
+        }
+        self.register = register;
+    }
+
+    fn operate(&mut self, op: Operator, motion: Option<Motion>, count: Option<usize>) {
+        let n = count.unwrap_or(1).max(1);
+        let start = (self.row, self.col);
+        let motion = match motion {
+            Some(motion) => motion,
+            None => {
+                let end = (self.row + n - 1).min(self.last_line());
+                return self.apply(op, (self.row, 0), (end, 0), true);
+            }
+        };
+
+        // `cw` on a word changes only to its end, like `ce`.
+        let on_word = matches!(self.lines[self.row].get(self.col), Some(c) if !c.is_whitespace());
+        let target = match motion {
+            Motion::WordForward(big) if op == Operator::Change && on_word => {
+                let line = &self.lines[self.row];
+                let at_end = line
+                    .get(self.col + 1)
+                    .is_none_or(|&c| class(c, big) != class(line[self.col], big));
+                if at_end && n == 1 {
+                    Some((start, Reach::Inclusive))
+                } else {
+                    let count = if at_end { n - 1 } else { n };
+                    Some((self.word(Motion::WordEnd(big), count), Reach::Inclusive))
+                }
+            }
+            _ => self.target(motion, count),
+        };
+        let (target, reach) = match target {
+            Some(target) => target,
+            None => return,
+        };
+
+        match reach {
+            Reach::Linewise => {
+                let (from, to) = (start.min(target), start.max(target));
+                self.apply(op, from, to, true);
+            }
+            _ => {
+                let (from, mut to) = (start.min(target), start.max(target));
+                if reach == Reach::Inclusive {
+                    to.1 = (to.1 + 1).min(self.len(to.0));
+                } else if matches!(motion, Motion::WordForward(_))
+                    && to.0 > from.0
+                    && to.1 <= self.first_non_blank(to.0)
+                {
+                    // A word motion stops at the end of the last line it
+                    // moved over rather than taking the line break.
+                    to = (to.0 - 1, self.len(to.0 - 1));
+                } else if to.0 > from.0 && to.1 == 0 {
+                    // An exclusive motion ending in column 0 leaves that line
+                    // alone, and takes whole lines if it started at the indent.
+                    if from.1 <= self.first_non_blank(from.0) {
+                        return self.apply(op, (from.0, 0), (to.0 - 1, 0), true);
+                    }
+                    to = (to.0 - 1, self.len(to.0 - 1));
+                }
+                self.apply(op, from, to, false);
+            }
+        }
+    }
+
+    fn start_insert(&mut self, count: usize, newline: bool) {
+        self.mode = Mode::Insert;
+        self.message = None;
+        self.insert_repeat = if count > 1 {
+            Some(InsertRepeat {
+                count,
+                newline,
+                start: self.recording.as_ref().map_or(0, |r| r.keys.len()),
+            })
+        } else {
+            None
+        };
+    }
+
+    fn open_line(&mut self, below: bool) {
+        let row = if below { self.row + 1 } else { self.row };
+        self.lines.insert(row, Vec::new());
+        self.row = row;
+        self.col = 0;
+    }
+
+    /// `p`/`P`: puts the register after or before the cursor.
+    fn put(&mut self, count: usize, after: bool) {
+        if self.register.text.is_empty() {
+            return self.set_error("E353: Nothing in register \"".to_string());
+        }
+        if self.register.linewise {
+            let at = if after { self.row + 1 } else { self.row };
+            let text: Vec<Vec<char>> = (0..count)
+                .flat_map(|_| self.register.text.iter().cloned())
+                .collect();
+            let added = text.len();
+            self.lines.splice(at..at, text);
+            let col = self.first_non_blank(at);
+            self.go_to((at, col));
+            if added > 2 {
+                self.set_message(format!("{} more lines", added));
+            }
+            return;
+        }
+
+        let mut text = self.register.text.clone();
+        for _ in 1..count {
+            let mut next = self.register.text.clone();
+            let first = next.remove(0);
+            text.last_mut().unwrap().extend(first);
+            text.extend(next);
+        }
+        let at = if after && !self.lines[self.row].is_empty() {
+            self.col + 1
+        } else {
+            self.col
+        };
+        let tail = self.lines[self.row].split_off(at);
+        let rows = text.len();
+        let last_len = text[rows - 1].len();
+        let mut text = text.into_iter();
+        self.lines[self.row].extend(text.next().unwrap());
+        let rest: Vec<Vec<char>> = text.collect();
+        self.lines.splice(self.row + 1..self.row + 1, rest);
+        let end = self.row + rows - 1;
+        self.lines[end].extend(tail);
+        if rows == 1 {
+            self.go_to((self.row, (at + last_len).saturating_sub(1)));
+        } else {
+            self.go_to((self.row, at));
+        }
+    }
+
+    /// `J`: joins `count` lines, at least two, with single spaces.
+    fn join(&mut self, row: usize, count: usize) {
+        let joins = count.max(2) - 1;
+        if row + 1 > self.last_line() {
+            return;
+        }
+        for _ in 0..joins {
+            if row + 1 > self.last_line() {
+                break;
+            }
+            let next = self.lines.remove(row + 1);
+            let next: Vec<char> = next.into_iter().skip_while(|c| c.is_whitespace()).collect();
+            let line = &mut self.lines[row];
+            while line.last().is_some_and(|c| c.is_whitespace()) {
+                line.pop();
+            }
+            let at = line.len();
+            if !next.is_empty() && !line.is_empty() && next[0] != ')' {
+                line.push(' ');
+            }
+            line.extend(next);
+            self.row = row;
+            self.col = at;
+        }
+        self.clamp();
+        self.goal = self.col;
+    }
+
+    fn toggle_case(c: char) -> char {
+        if c.is_uppercase() {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c.to_uppercase().next().unwrap_or(c)
+        }
+    }
+
+    /// Runs a complete normal-mode command.
+    fn run(&mut self, command: Command) -> Flow {
+        let n = command.count.unwrap_or(1).max(1);
+        match command.action {
+            Action::Move(motion) => self.go(motion, command.count),
+            Action::Operate(op, motion) => self.operate(op, motion, command.count),
+            Action::Replace(with) => {
+                let end = self.col + n;
+                if end <= self.len(self.row) {
+                    for c in &mut self.lines[self.row][self.col..end] {
+                        *c = with;
+                    }
+                    self.go_to((self.row, end - 1));
+                }
+            }
+            Action::Quit { write } => {
+                let command = if write { "x" } else { "q!" };
+                return self.ex(command);
+            }
+            Action::Other(c) => match c {
+                'x' if !self.lines[self.row].is_empty() => {
+                    self.operate(Operator::Delete, Some(Motion::Right), command.count)
+                }
+                'X' => self.operate(Operator::Delete, Some(Motion::Left), command.count),
+                'D' => self.operate(Operator::Delete, Some(Motion::LineEnd), command.count),
+                'C' => self.operate(Operator::Change, Some(Motion::LineEnd), command.count),
+                's' => self.operate(Operator::Change, Some(Motion::Right), command.count),
+                'S' => self.operate(Operator::Change, None, command.count),
+                'Y' => self.operate(Operator::Yank, None, command.count),
+                'p' | 'P' => self.put(n, c == 'p'),
+                'i' => self.start_insert(n, false),
+                'a' => {
+                    self.col = (self.col + 1).min(self.len(self.row));
+                    self.start_insert(n, false);
+                }
+                'I' => {
+                    self.col = self.first_non_blank(self.row);
+                    self.start_insert(n, false);
+                }
+                'A' => {
+                    self.col = self.len(self.row);
+                    self.start_insert(n, false);
+                }
+                'o' | 'O' => {
+                    self.open_line(c == 'o');
+                    self.start_insert(n, true);
+                }
+                'u' => self.undo(n),
+                'J' => self.join(self.row, n),
+                '~' => {
+                    let end = (self.col + n).min(self.len(self.row));
+                    for c in &mut self.lines[self.row][self.col..end] {
+                        *c = Self::toggle_case(*c);
+                    }
+                    self.go_to((self.row, end));
+                }
+                '.' => return self.repeat(command.count),
+                'v' | 'V' => {
+                    self.anchor = (self.row, self.col);
+                    self.mode = Mode::Visual { line: c == 'V' };
+                    self.message = None;
+                }
+                ':' => {
+                    self.mode = Mode::Command;
+                    self.input = match command.count {
+                        Some(1) => ".".to_string(),
+                        Some(n) => format!(".,.+{}", n - 1),
+                        None => String::new(),
+                    };
+                }
+                '/' | '?' => {
+                    self.mode = Mode::Search { forward: c == '/' };
+                    self.input.clear();
+                }
+                _ => {}
+            },
+        }
+        Flow::Continue
+    }
+
+    /// `.`: types the last change again, with a new count if one is given.
+    fn repeat(&mut self, count: Option<usize>) -> Flow {
+        let change = match self.last_change.clone() {
+            Some(change) => change,
+            None => return Flow::Continue,
+        };
+        let mut keys: Vec<Key> = count
+            .or(change.count)
+            .map(|n| n.to_string().chars().map(typed).collect())
+            .unwrap_or_default();
+        keys.extend(change.keys);
+        let size = self.size;
+        for key in &keys {
+            if let Flow::Exit(output) = self.key(key, size) {
+                return Flow::Exit(output);
+            }
+        }
+        Flow::Continue
+    }
+
+    /// The visual selection, ordered, as an exclusive range or whole lines.
+    fn selection(&self) -> (Pos, Pos, bool) {
+        let cursor = (self.row, self.col);
+        let (from, to) = (self.anchor.min(cursor), self.anchor.max(cursor));
+        match self.mode {
+            Mode::Visual { line: true } => (from, to, true),
+            _ => (from, (to.0, (to.1 + 1).min(self.len(to.0))), false),
+        }
+    }
+
+    fn leave_visual(&mut self) {
+        let (from, to, _) = self.selection();
+        self.marks = Some((from.0, to.0));
+        self.mode = Mode::Normal;
+    }
+
+    /// A key in visual mode that acts on the selection rather than
+    /// moving; returns false for anything else.
+    fn visual_command(&mut self, c: char) -> bool {
+        let (from, to, linewise) = self.selection();
+        let whole_lines = linewise || "XDCSYR".contains(c);
+        let op = match c {
+            'd' | 'x' | 'X' | 'D' => Some(Operator::Delete),
+            'c' | 's' | 'C' | 'S' | 'R' => Some(Operator::Change),
+            'y' | 'Y' => Some(Operator::Yank),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.leave_visual();
+            self.before = Some(self.state());
+            self.apply(op, from, to, whole_lines);
+            if self.mode != Mode::Insert {
+                self.finish_change();
+            }
+            return true;
+        }
+
+        match c {
+            '~' | 'u' | 'U' => {
+                self.leave_visual();
+                self.before = Some(self.state());
+                for row in from.0..=to.0 {
+                    let start = if linewise || row > from.0 { 0 } else { from.1 };
+                    let end = if linewise || row < to.0 {
+                        self.len(row)
+                    } else {
+                        to.1
+                    };
+                    for ch in &mut self.lines[row][start..end] {
+                        *ch = match c {
+                            'u' => ch.to_lowercase().next().unwrap_or(*ch),
+                            'U' => ch.to_uppercase().next().unwrap_or(*ch),
+                            _ => Self::toggle_case(*ch),
+                        };
+                    }
+                }
+                self.go_to(from);
+                self.finish_change();
+            }
+            'p' | 'P' => {
+                self.leave_visual();
+                self.before = Some(self.state());
+                let mut register = self.register.clone();
+                register.linewise |= whole_lines;
+                self.apply(Operator::Delete, from, to, whole_lines);
+                let deleted = std::mem::replace(&mut self.register, register);
+                let after = if whole_lines {
+                    from.0 > self.last_line()
+                } else {
+                    from.1 > 0 && from.1 >= self.len(from.0)
+                };
+                self.put(1, after);
+                self.register = deleted;
+                self.finish_change();
+            }
+            'J' => {
+                self.leave_visual();
+                self.before = Some(self.state());
+                self.join(from.0, to.0 - from.0 + 1);
+                self.finish_change();
+            }
+            'o' => {
+                let cursor = (self.row, self.col);
+                self.row = self.anchor.0;
+                self.col = self.anchor.1;
+                self.anchor = cursor;
+                self.goal = self.col;
+            }
+            'v' | 'V' => {
+                let line = c == 'V';
+                if self.mode == (Mode::Visual { line }) {
+                    self.leave_visual();
+                } else {
+                    self.mode = Mode::Visual { line };
+                }
+            }
+            ':' => {
+                self.leave_visual();
+                self.mode = Mode::Command;
+                self.input = "'<,'>".to_string();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn key_char(&mut self, c: char) -> Flow {
+        let visual = matches!(self.mode, Mode::Visual { .. });
+        if visual && self.pending.iter().all(|c| c.is_ascii_digit()) && self.visual_command(c) {
+            self.pending.clear();
+            return Flow::Continue;
+        }
+
+        self.pending.push(c);
+        let command = match parse(&self.pending) {
+            Parse::Incomplete => return Flow::Continue,
+            Parse::Invalid => {
+                self.pending.clear();
+                return Flow::Continue;
+            }
+            Parse::Done(command) => command,
+        };
+        let keys = std::mem::take(&mut self.pending);
+        if visual {
+            if let Action::Move(motion) = command.action {
+                self.go(motion, command.count);
+            }
+            return Flow::Continue;
+        }
+
+        if command.action.is_change() {
+            let mut skip = 0;
+            count(&keys, &mut skip);
+            self.before = Some(self.state());
+            self.recording = Some(Change {
+                count: command.count,
+                keys: keys[skip..].iter().copied().map(typed).collect(),
+            });
+        }
+        let flow = self.run(command);
+        if self.mode != Mode::Insert {
+            self.finish_change();
+        }
+        flow
+    }
+
+    /// Keys other than plain characters in normal and visual mode.
+    fn special_key(&mut self, key: &Key) -> Flow {
+        let page = self.size.height.saturating_sub(1).max(1);
+        let pending: String = self.pending.iter().collect();
+        let count = pending.parse::<usize>().ok();
+        let mapped = match (key.key.as_str(), key.ctrl) {
+            ("ArrowLeft" | "Backspace", _) | ("h", true) => Some('h'),
+            ("ArrowRight", _) => Some('l'),
+            ("ArrowUp", _) | ("p", true) => Some('k'),
+            ("ArrowDown", _) | ("n", true) => Some('j'),
+            ("Home", _) => Some('0'),
+            ("End", _) => Some('$'),
+            ("Enter", _) => Some('+'),
+            ("Delete", _) => Some('x'),
+            _ => None,
+        };
+        if let Some(c) = mapped {
+            return self.key_char(c);
+        }
+
+        self.pending.clear();
+        let scroll = |this: &mut Self, rows: isize| {
+            let row = (this.row as isize + rows).clamp(0, this.last_line() as isize) as usize;
+            let col = this.first_non_blank(row);
+            this.go_to((row, col));
+        };
+        match (key.key.as_str(), key.ctrl) {
+            ("Escape", _) | ("[", true) => {
+                if matches!(self.mode, Mode::Visual { .. }) {
+                    self.leave_visual();
+                }
+            }
+            ("c", true) if self.mode == Mode::Normal && pending.is_empty() => self.set_message(
+                "Type  :qa!  and press <Enter> to abandon all changes and exit Vim".to_string(),
+            ),
+            ("c", true) => {
+                if matches!(self.mode, Mode::Visual { .. }) {
+                    self.leave_visual();
+                }
+            }
+            ("r", true) if self.mode == Mode::Normal => self.redo(count.unwrap_or(1)),
+            ("d", true) => scroll(self, (page / 2) as isize),
+            ("u", true) => scroll(self, -((page / 2) as isize)),
+            ("f", true) | ("PageDown", _) => scroll(self, page.saturating_sub(2).max(1) as isize),
+            ("b", true) | ("PageUp", _) => scroll(self, -(page.saturating_sub(2).max(1) as isize)),
+            ("g", true) => {
+                let lines = self.lines.len();
+                self.set_message(format!(
+                    "\"{}\"{} {} line{} --{}%--",
+                    self.name.as_deref().unwrap_or("[No Name]"),
+                    if self.modified() { " [Modified]" } else { "" },
+                    lines,
+                    plural(lines),
+                    (self.row + 1) * 100 / lines
+                ));
+            }
+            _ => {}
+        }
+        Flow::Continue
+    }
+
+    /// Edits the buffer for one key typed in insert mode.
+    fn type_key(&mut self, key: &Key) {
+        if let Some(c) = key.char() {
+            self.lines[self.row].insert(self.col, c);
+            self.col += 1;
+            return;
+        }
+        match (key.key.as_str(), key.ctrl) {
+            ("Enter", _) | ("j" | "m", true) => {
+                let rest = self.lines[self.row].split_off(self.col);
+                self.lines.insert(self.row + 1, rest);
+                self.row += 1;
+                self.col = 0;
+            }
+            ("Tab", _) => {
+                for _ in 0..TAB_WIDTH {
+                    self.lines[self.row].insert(self.col, ' ');
+                }
+                self.col += TAB_WIDTH;
+            }
+            ("Backspace", _) | ("h", true) => {
+                if self.col > 0 {
+                    self.col -= 1;
+                    self.lines[self.row].remove(self.col);
+                } else if self.row > 0 {
+                    let line = self.lines.remove(self.row);
+                    self.row -= 1;
+                    self.col = self.len(self.row);
+                    self.lines[self.row].extend(line);
+                }
+            }
+            ("Delete", _) => {
+                if self.col < self.len(self.row) {
+                    self.lines[self.row].remove(self.col);
+                } else if self.row < self.last_line() {
+                    let next = self.lines.remove(self.row + 1);
+                    self.lines[self.row].extend(next);
+                }
+            }
+            ("w", true) => {
+                let line = &self.lines[self.row];
+                let mut start = self.col;
+                while start > 0 && line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                if start > 0 {
+                    let kind = class(line[start - 1], false);
+                    while start > 0 && class(line[start - 1], false) == kind {
+                        start -= 1;
+                    }
+                }
+                self.lines[self.row].drain(start..self.col);
+                self.col = start;
+            }
+            ("u", true) => {
+                self.lines[self.row].drain(..self.col);
+                self.col = 0;
+            }
+            ("ArrowLeft", _) => self.col = self.col.saturating_sub(1),
+            ("ArrowRight", _) => self.col = (self.col + 1).min(self.len(self.row)),
+            ("ArrowUp", _) if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.col.min(self.len(self.row));
+            }
+            ("ArrowDown", _) if self.row < self.last_line() => {
+                self.row += 1;
+                self.col = self.col.min(self.len(self.row));
+            }
+            ("Home", _) => self.col = 0,
+            ("End", _) => self.col = self.len(self.row),
+            _ => {}
+        }
+    }
+
+    fn insert_key(&mut self, key: &Key) {
+        if let Some(recording) = &mut self.recording {
+            recording.keys.push(key.clone());
+        }
+        let leave = key.key == "Escape" || (key.ctrl && matches!(key.key.as_str(), "c" | "["));
+        if !leave {
+            return self.type_key(key);
+        }
+
+        if let Some(repeat) = self.insert_repeat.take() {
+            let keys: Vec<Key> = self.recording.as_ref().map_or_else(Vec::new, |r| {
+                r.keys[repeat.start..r.keys.len() - 1].to_vec()
+            });
+            for _ in 1..repeat.count {
+                if repeat.newline {
+                    self.open_line(true);
+                }
+                for key in &keys {
+                    self.type_key(key);
+                }
+            }
+        }
+        self.mode = Mode::Normal;
+        self.col = self.col.saturating_sub(1);
+        self.clamp();
+        self.goal = self.col;
+        self.finish_change();
+    }
+
+    /// Keys on the `:`, `/` or `?` line.
+    fn line_key(&mut self, key: &Key) -> Flow {
+        match (key.key.as_str(), key.ctrl) {
+            ("Escape", _) | ("c" | "[", true) => {
+                self.mode = Mode::Normal;
+                self.input.clear();
+            }
+            ("Enter", _) => {
+                let input = std::mem::take(&mut self.input);
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                match mode {
+                    Mode::Search { forward } => self.search_command(&input, forward),
+                    _ => {
+                        self.before = Some(self.state());
+                        let flow = self.ex(&input);
+                        self.finish_change();
+                        return flow;
+                    }
+                }
+            }
+            ("Backspace", _) | ("h", true) => {
+                if self.input.pop().is_none() {
+                    self.mode = Mode::Normal;
+                }
+            }
+            ("u", true) => self.input.clear(),
+            _ => self.input.extend(key.char()),
+        }
+        Flow::Continue
+    }
+
+    /// The next match of `pattern` from `from`, wrapping around the
+    /// buffer; the flag says whether it wrapped.
+    fn find(&self, regex: &Regex, from: Pos, forward: bool) -> Option<(Pos, bool)> {
+        let count = self.lines.len();
+        let starts = |row: usize| -> Vec<usize> {
+            let text: String = self.lines[row].iter().collect();
+            regex
+                .find_iter(&text)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|found| text[..found.start].chars().count())
+                .collect()
+        };
+        for step in 0..=count {
+            let (row, wrapped) = if forward {
+                ((from.0 + step) % count, from.0 + step >= count)
+            } else {
+                ((from.0 + count * 2 - step) % count, step > from.0)
+            };
+            let mut starts = starts(row).into_iter();
+            let found = match (forward, step) {
+                (true, 0) => starts.find(|&col| col > from.1),
+                (true, _) if step == count => starts.find(|&col| col <= from.1),
+                (true, _) => starts.next(),
+                (false, 0) => starts.rev().find(|&col| col < from.1),
+                (false, _) if step == count => starts.rev().find(|&col| col >= from.1),
+                (false, _) => starts.last(),
+            };
+            if let Some(col) = found {
+                return Some(((row, col), wrapped));
+            }
+        }
+        None
+    }
+
+    /// Finds the `count`th match of `pattern` and reports it the way vim
+    /// does; None (with an error shown) if there is none.
+    fn search(&mut self, pattern: &str, forward: bool, count: usize) -> Option<Pos> {
+        let regex = match Regex::new(pattern, Syntax::Basic, false) {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.set_error(format!(
+                    "E383: Invalid search string: {} ({})",
+                    pattern, err
+                ));
+                return None;
+            }
+        };
+        let mut at = (self.row, self.col);
+        let mut wrapped = false;
+        for _ in 0..count {
+            match self.find(&regex, at, forward) {
+                Some((pos, wrap)) => {
+                    at = pos;
+                    wrapped |= wrap;
+                }
+                None => {
+                    self.set_error(format!("E486: Pattern not found: {}", pattern));
+                    return None;
+                }
+            }
+        }
+        if !wrapped {
+            self.set_message(format!("{}{}", if forward { '/' } else { '?' }, pattern));
+        } else if forward {
+            self.set_error("search hit BOTTOM, continuing at TOP".to_string());
+        } else {
+            self.set_error("search hit TOP, continuing at BOTTOM".to_string());
+        }
+        Some(at)
+    }
+
+    fn search_command(&mut self, input: &str, forward: bool) {
+        let pattern = if input.is_empty() {
+            match &self.last_search {
+                Some((pattern, _)) => pattern.clone(),
+                None => return self.set_error("E35: No previous regular expression".to_string()),
+            }
+        } else {
+            input.to_string()
+        };
+        self.last_search = Some((pattern.clone(), forward));
+        if let Some(pos) = self.search(&pattern, forward, 1) {
+            self.go_to(pos);
+        }
+    }
+
+    /// One ex line address (`.`, `$`, `12`, `'<`, `'>`) with any `+N` or
+    /// `-N` after it, as a 0-based line.
+    fn address<'a>(&self, input: &'a str) -> Result<(Option<usize>, &'a str), String> {
+        let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (mut line, mut rest): (Option<isize>, &str) =
+            if let Some(rest) = input.strip_prefix('.') {
+                (Some(self.row as isize), rest)
+            } else if let Some(rest) = input.strip_prefix('$') {
+                (Some(self.last_line() as isize), rest)
+            } else if let Some(rest) = input.strip_prefix("'<") {
+                let (start, _) = self.marks.ok_or("E20: Mark not set")?;
+                (Some(start as isize), rest)
+            } else if let Some(rest) = input.strip_prefix("'>") {
+                let (_, end) = self.marks.ok_or("E20: Mark not set")?;
+                (Some(end as isize), rest)
+            } else if digits > 0 {
+                let number: isize = input[..digits].parse().map_err(|_| "E16: Invalid range")?;
+                (Some((number - 1).max(0)), &input[digits..])
+            } else {
+                (None, input)
+            };
+
+        while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+            let after = &rest[1..];
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let offset: isize = after[..digits].parse().unwrap_or(1);
+            let base = line.unwrap_or(self.row as isize);
+            line = Some(if sign == '+' {
+                base + offset
+            } else {
+                base - offset
+            });
+            rest = &after[digits..];
+        }
+
+        match line {
+            Some(line) if line < 0 || line > self.last_line() as isize => {
+                Err("E16: Invalid range".to_string())
+            }
+            line => Ok((line.map(|line| line as usize), rest)),
+        }
+    }
+
+    /// The line range in front of an ex command, if there is one.
+    fn range<'a>(&self, input: &'a str) -> Result<(Option<Lines>, &'a str), String> {
+        if let Some(rest) = input.strip_prefix('%') {
+            return Ok((Some((0, self.last_line())), rest));
+        }
+        let (first, rest) = self.address(input)?;
+        match rest.strip_prefix(',') {
+            Some(after) => {
+                let first = first.unwrap_or(self.row);
+                let (second, rest) = self.address(after)?;
+                let second = second.unwrap_or(self.row);
+                Ok((Some((first.min(second), first.max(second))), rest))
+            }
+            None => Ok((first.map(|line| (line, line)), rest)),
+        }
+    }
+
+    fn ex(&mut self, input: &str) -> Flow {
+        match self.ex_command(input) {
+            Ok(flow) => flow,
+            Err(err) => {
+                self.set_error(err);
+                Flow::Continue
+            }
+        }
+    }
+
+    fn ex_command(&mut self, input: &str) -> Result<Flow, String> {
+        let input = input.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+        let (range, rest) = self.range(input)?;
+        let rest = rest.trim_start();
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let (name, after) = rest.split_at(name_len);
+        let (bang, arg) = match after.strip_prefix('!') {
+            Some(arg) => (true, arg.trim()),
+            None => (false, after.trim()),
+        };
+        let file = if arg.is_empty() { None } else { Some(arg) };
+        let lines = range.unwrap_or((self.row, self.row));
+
+        match name {
+            "" if arg.is_empty() => {
+                if let Some((_, end)) = range {
+                    let col = self.first_non_blank(end);
+                    self.go_to((end, col));
+                }
+            }
+            "w" | "write" => self.write(file)?,
+            "q" | "quit" => {
+                if self.modified() && !bang {
+                    return Err("E37: No write since last change (add ! to override)".to_string());
+                }
+                return Ok(Flow::Exit(String::new()));
+            }
+            "wq" | "x" | "xit" | "exit" => {
+                if name == "wq" || self.modified() || file.is_some() {
+                    self.write(file)?;
+                }
+                return Ok(Flow::Exit(String::new()));
+            }
+            "s" | "substitute" => self.substitute(lines, after)?,
+            "d" | "delete" => {
+                self.apply(Operator::Delete, (lines.0, 0), (lines.1, 0), true);
+            }
+            "se" | "set" => {
+                for option in arg.split_whitespace() {
+                    match option {
+                        "nu" | "number" => self.number = true,
+                        "nonu" | "nonumber" => self.number = false,
+                        "invnu" | "invnumber" | "nu!" | "number!" => self.number = !self.number,
+                        _ => return Err(format!("E518: Unknown option: {}", option)),
+                    }
+                }
+            }
+            "u" | "undo" => self.undo(1),
+            "red" | "redo" => self.redo(1),
+            "noh" | "nohlsearch" => {}
+            _ => return Err(format!("E492: Not an editor command: {}", input)),
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// `:[range]s/pattern/replacement/[flags]`, with sed's replacement
+    /// syntax.
+    fn substitute(&mut self, (start, end): Lines, spec: &str) -> Result<(), String> {
+        let delimiter = match spec.chars().next() {
+            None => return Err("E35: No previous regular expression".to_string()),
+            Some(c)
+                if c.is_alphanumeric() || c.is_whitespace() || matches!(c, '\\' | '"' | '|') =>
+            {
+                return Err("E146: Regular expressions can't be delimited by letters".to_string())
+            }
+            Some(c) => c,
+        };
+        let (pattern, rest) = split_delimited(&spec[delimiter.len_utf8()..], delimiter);
+        let (replacement, flags) = split_delimited(rest, delimiter);
+
+        let mut global = false;
+        let mut ignore_case = false;
+        let mut quiet = false;
+        for flag in flags.trim_end().chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                'I' => ignore_case = false,
+                'e' => quiet = true,
+                _ => return Err(format!("E488: Trailing characters: {}", flags)),
+            }
+        }
+
+        let pattern = if pattern.is_empty() {
+            match &self.last_search {
+                Some((pattern, _)) => pattern.clone(),
+                None => return Err("E35: No previous regular expression".to_string()),
+            }
+        } else {
+            pattern
+        };
+        self.last_search = Some((pattern.clone(), true));
+        let regex = Regex::new(&pattern, Syntax::Basic, ignore_case)
+            .map_err(|err| format!("E383: Invalid search string: {} ({})", pattern, err))?;
+
+        let mut substitutions = 0;
+        let mut changed = 0;
+        let mut last = None;
+        for row in start..=end {
+            let text: String = self.lines[row].iter().collect();
+            if let Some(new) = sed::substitute_line(&text, &regex, &replacement, global)? {
+                substitutions += if global {
+                    regex.find_iter(&text)?.len()
+                } else {
+                    1
+                };
+                changed += 1;
+                self.lines[row] = new.chars().collect();
+                last = Some(row);
+            }
+        }
+
+        match last {
+            Some(row) => {
+                let col = self.first_non_blank(row);
+                self.go_to((row, col));
+                if changed > 2 {
+                    self.set_message(format!(
+                        "{} substitution{} on {} line{}",
+                        substitutions,
+                        plural(substitutions),
+                        changed,
+                        plural(changed)
+                    ));
+                }
+                Ok(())
+            }
+            None if quiet => Ok(()),
+            None => Err(format!("E486: Pattern not found: {}", pattern)),
+        }
+    }
+
+    /// `:w [file]`: writes the buffer, to its own file unless named.
+    fn write(&mut self, file: Option<&str>) -> Result<(), String> {
+        let name = match file.map(str::to_string).or_else(|| self.name.clone()) {
+            Some(name) => name,
+            None => return Err("E32: No file name".to_string()),
+        };
+        let new = matches!(programs::load_file(&name), Ok(None));
+        let content = self.content();
+        let command = format!("{} {}", self.program, name);
+        if programs::save_file(&command, &name, &content).is_err() {
+            return Err(format!("\"{}\" E212: Can't open file for writing", name));
+        }
+
+        if self.name.is_none() {
+            self.name = Some(name.clone());
+        }
+        if self.name.as_deref() == Some(name.as_str()) {
+            self.saved = self.lines.clone();
+        }
+        let lines = if content.is_empty() {
+            0
+        } else {
+            self.lines.len()
+        };
+        self.set_message(format!(
+            "\"{}\" {}{}L, {}B written",
+            name,
+            if new { "[New] " } else { "" },
+            lines,
+            content.len()
+        ));
+        Ok(())
+    }
+
+    fn gutter(&self) -> usize {
+        if self.number {
+            self.lines.len().to_string().len().max(3) + 1
+        } else {
+            0
+        }
+    }
+
+    /// Screen rows line `row` takes; in insert mode the cursor may need
+    /// one more just past the end.
+    fn rows_of(&self, row: usize, width: usize) -> usize {
+        let len = self.len(row);
+        if self.mode == Mode::Insert && row == self.row {
+            len / width + 1
+        } else {
+            len.max(1).div_ceil(width)
+        }
+    }
+
+    fn scroll_into_view(&mut self, rows: usize, width: usize) {
+        let mut top = self.row;
+        let mut used = self.rows_of(top, width);
+        while top > self.top && used + self.rows_of(top - 1, width) <= rows {
+            top -= 1;
+            used += self.rows_of(top, width);
+        }
+        self.top = top;
+    }
+
+    /// The selected columns of line `row` in visual mode.
+    fn selected(&self, row: usize) -> Option<(usize, usize)> {
+        if !matches!(self.mode, Mode::Visual { .. }) {
+            return None;
+        }
+        let (from, to, linewise) = self.selection();
+        if row < from.0 || row > to.0 {
+            return None;
+        }
+        if linewise {
+            return Some((0, self.len(row)));
+        }
+        let start = if row == from.0 { from.1 } else { 0 };
+        let end = if row == to.0 { to.1 } else { self.len(row) };
+        Some((start, end))
+    }
+
+    /// The bottom line: the mode or last message, any pending keys, and
+    /// the ruler.
+    fn status(&self, width: usize, below: usize) -> String {
+        let left = match self.mode {
+            Mode::Command => return ansi::slice(&format!(":{}", self.input), 0, width),
+            Mode::Search { forward } => {
+                let prompt = if forward { '/' } else { '?' };
+                return ansi::slice(&format!("{}{}", prompt, self.input), 0, width);
+            }
+            Mode::Insert => ansi::paint("-- INSERT --", "1"),
+            Mode::Visual { line: false } => ansi::paint("-- VISUAL --", "1"),
+            Mode::Visual { line: true } => ansi::paint("-- VISUAL LINE --", "1"),
+            Mode::Normal => match &self.message {
+                Some(message) if self.error => ansi::paint(message, "31"),
+                Some(message) => message.clone(),
+                None => String::new(),
+            },
+        };
+        let left_len = ansi::visible_chars(&left).len();
+        let ruler_col = width.saturating_sub(18);
+        let pending_col = ruler_col.saturating_sub(11);
+        if width < 40 || left_len >= pending_col {
+            return ansi::slice(&left, 0, width);
+        }
+
+        let above = self.top;
+        let percent = match (above, below) {
+            (0, 0) => "All".to_string(),
+            (0, _) => "Top".to_string(),
+            (_, 0) => "Bot".to_string(),
+            _ => format!("{}%", above * 100 / (above + below)),
+        };
+        let position = if self.lines[self.row].is_empty() {
+            format!("{},0-1", self.row + 1)
+        } else {
+            format!("{},{}", self.row + 1, self.col + 1)
+        };
+        let pending: String = self.pending.iter().collect();
+        format!(
+            "{}{}{:<11}{:<14}{}",
+            left,
+            " ".repeat(pending_col - left_len),
+            pending,
+            position,
+            percent
+        )
+    }
+}
+
+impl Program for Vi {
+    fn key(&mut self, key: &Key, size: Size) -> Flow {
+        self.size = size;
+        match self.mode {
+            Mode::Insert => {
+                self.insert_key(key);
+                Flow::Continue
+            }
+            Mode::Command | Mode::Search { .. } => self.line_key(key),
+            Mode::Normal | Mode::Visual { .. } => match key.char() {
+                Some(c) => self.key_char(c),
+                None => self.special_key(key),
+            },
+        }
+    }
+
+    fn view(&mut self, size: Size) -> Screen {
+        self.size = size;
+        let rows = size.height.saturating_sub(1).max(1);
+        let gutter = self.gutter();
+        let width = size.width.saturating_sub(gutter).max(1);
+        self.scroll_into_view(rows, width);
+
+        let mut lines = Vec::new();
+        let mut cursor = (0, gutter);
+        let mut row = self.top;
+        while lines.len() < rows && row < self.lines.len() {
+            let chunks = self.rows_of(row, width);
+            if lines.len() + chunks > rows && row != self.top {
+                // Like vim, a line that does not fit is not shown in part.
+                while lines.len() < rows {
+                    lines.push(ansi::paint("@", "94"));
+                }
+                break;
+            }
+            if row == self.row {
+                cursor = (
+                    (lines.len() + self.col / width).min(rows - 1),
+                    gutter + self.col % width,
+                );
+            }
+
+            let line = &self.lines[row];
+            let selected = self.selected(row);
+            for chunk in 0..chunks {
+                let start = (chunk * width).min(line.len());
+                let end = ((chunk + 1) * width).min(line.len());
+                let mut text = programs::display(&line[start..end]);
+                match selected {
+                    Some(_) if line.is_empty() => text = ansi::paint(" ", "7"),
+                    Some((from, to)) if from.max(start) < to.min(end) => {
+                        text = ansi::mark(&text, &[(from.max(start) - start, to.min(end) - start)])
+                    }
+                    _ => {}
+                }
+                let number = if gutter == 0 {
+                    String::new()
+                } else if chunk == 0 {
+                    ansi::paint(&format!("{:>1$} ", row + 1, gutter - 1), "33")
+                } else {
+                    " ".repeat(gutter)
+                };
+                lines.push(number + &text);
+                if lines.len() == rows {
+                    break;
+                }
+            }
+            row += 1;
+        }
+        let below = self.lines.len().saturating_sub(row);
+        while lines.len() < rows {
+            lines.push(ansi::paint("~", "94"));
+        }
+        lines.push(self.status(size.width, below));
+
+        if matches!(self.mode, Mode::Command | Mode::Search { .. }) {
+            cursor = (rows, (1 + self.input.chars().count()).min(size.width));
+        }
+        Screen {
+            lines,
+            cursor: Some(cursor),
+        }
+    }
+
+    fn scroll(&mut self, rows: isize, _size: Size) {
+        if self.mode == Mode::Insert {
+            return;
+        }
+        let row = (self.row as isize + rows).clamp(0, self.last_line() as isize) as usize;
+        self.row = row;
+        self.col = self.goal_col(row);
+    }
+}
+
+/// `vi [+LINE] [FILE]`: edits `FILE`, or an unnamed buffer that `:w
+/// NAME` names. A bare `+` starts on the last line.
+pub fn open(program: &str, args: &[&str]) -> Result<Vi, String> {
+    let mut line = None;
+    let mut files = Vec::new();
+    for &arg in args {
+        if let Some(number) = arg.strip_prefix('+') {
+            if number.is_empty() {
+                line = Some(usize::MAX);
+                continue;
+            }
+            match number.parse::<usize>() {
+                Ok(number) => line = Some(number),
+                Err(_) => return Err(format!("{}: invalid line number '{}'", program, number)),
+            }
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("{}: Unknown option argument: \"{}\"", program, arg));
+        } else {
+            files.push(arg);
+        }
+    }
+
+    let mut vi = match files.as_slice() {
+        [] => Vi::new(program, None, ""),
+        [name] => match programs::load_file(name) {
+            Ok(Some(content)) => {
+                let mut vi = Vi::new(program, Some(name.to_string()), &content);
+                let lines = content.lines().count();
+                vi.set_message(format!(
+                    "\"{}\" {}{}L, {}B",
+                    name,
+                    if programs::is_unwritable(name) {
+                        "[readonly] "
+                    } else {
+                        ""
+                    },
+                    lines,
+                    content.len()
+                ));
+                vi
+            }
+            Ok(None) => {
+                let mut vi = Vi::new(program, Some(name.to_string()), "");
+                vi.set_message(format!("\"{}\" [New]", name));
+                vi
+            }
+            Err(err) => return Err(format!("{}: {}: {}", program, name, err)),
+        },
+        _ => {
+            return Err(format!(
+                "{}: only one file can be edited at a time",
+                program
+            ))
+        }
+    };
+    if let Some(line) = line {
+        vi.row = line.saturating_sub(1).min(vi.last_line());
+        vi.col = vi.first_non_blank(vi.row);
+        vi.goal = vi.col;
+    }
+    Ok(vi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "alpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line";
+    const SIZE: Size = Size {
+        width: 80,
+        height: 24,
+    };
+
+    /// Feeds `keys` to `vi`: `\u{1b}` is Escape, `\r` Enter and `\u{12}`
+    /// Ctrl-R; anything else is typed as is.
+    fn type_keys(vi: &mut Vi, keys: &str) {
+        for c in keys.chars() {
+            let key = match c {
+                '\u{1b}' => Key {
+                    key: "Escape".to_string(),
+                    ctrl: false,
+                    alt: false,
+                },
+                '\r' => Key {
+                    key: "Enter".to_string(),
+                    ctrl: false,
+                    alt: false,
+                },
+                '\u{12}' => Key {
+                    key: "r".to_string(),
+                    ctrl: true,
+                    alt: false,
+                },
+                c => Key {
+                    key: c.to_string(),
+                    ctrl: false,
+                    alt: false,
+                },
+            };
+            vi.key(&key, SIZE);
+        }
+    }
+
+    fn text(vi: &Vi) -> String {
+        vi.lines
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Key sequences and the buffer Vim leaves behind for them on `TEXT`.
+    #[test]
+    fn edits_like_vim() {
+        let cases: &[(&str, &str)] = &[
+            ("ggdd", "second line here\nthird\n  indented fourth\nlast line"),
+            ("ggddp", "second line here\nalpha beta gamma\nthird\n  indented fourth\nlast line"),
+            ("gg3x", "ha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggdwu", "alpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggcwALPHA\u{1b}", "ALPHA beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggyyjp", "alpha beta gamma\nsecond line here\nalpha beta gamma\nthird\n  indented fourth\nlast line"),
+            ("ggJ", "alpha beta gamma second line here\nthird\n  indented fourth\nlast line"),
+            ("ggjdd.", "alpha beta gamma\n  indented fourth\nlast line"),
+            ("gg2dd", "third\n  indented fourth\nlast line"),
+            ("ggdGu", "alpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggA!\u{1b}", "alpha beta gamma!\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggI> \u{1b}j.", "> alpha beta gamma\n> second line here\nthird\n  indented fourth\nlast line"),
+            ("ggofresh\u{1b}", "alpha beta gamma\nfresh\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggxp", "lapha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggde", " beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggd$", "\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggwD", "alpha \nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg:%s/line/LINE/g\r", "alpha beta gamma\nsecond LINE here\nthird\n  indented fourth\nlast LINE"),
+            ("gg:2,3d\r", "alpha beta gamma\n  indented fourth\nlast line"),
+            ("gg/third\rdd", "alpha beta gamma\nsecond line here\n  indented fourth\nlast line"),
+            ("ggGdgg", ""),
+            ("ggvjd", "econd line here\nthird\n  indented fourth\nlast line"),
+            ("ggVjd", "third\n  indented fourth\nlast line"),
+            ("gg~~~", "ALPha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggrZ", "Zlpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg3J", "alpha beta gamma second line here third\n  indented fourth\nlast line"),
+            ("ggylp", "aalpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggdta", "a beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggctm\u{1b}", "mma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggxxxuu", "lpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggxxxuu\u{12}", "pha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggYp", "alpha beta gamma\nalpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggddjP", "second line here\nalpha beta gamma\nthird\n  indented fourth\nlast line"),
+            ("ggwcwone\u{1b}w.", "alpha one one\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg:3\rdd", "alpha beta gamma\nsecond line here\n  indented fourth\nlast line"),
+            ("ggjdk", "third\n  indented fourth\nlast line"),
+            ("ggjdj", "alpha beta gamma\n  indented fourth\nlast line"),
+            ("ggGJ", "alpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg$x", "alpha beta gamm\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggwi\r\u{1b}", "alpha \nbeta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggd2w", "gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg3wdb", "alpha beta \nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggjwd0", "alpha beta gamma\nline here\nthird\n  indented fourth\nlast line"),
+            ("ggdf ", "beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggdtg", "gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("ggwdFa", "alphbeta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+            ("gg/last\rggjjdn", "alpha beta gamma\nsecond line here\nlast line"),
+            ("gg/last\rggjjldn", "alpha beta gamma\nsecond line here\nt\nlast line"),
+            ("ggjdw", "alpha beta gamma\nline here\nthird\n  indented fourth\nlast line"),
+            ("gg2wjdb", "alpha beta gamma\nsecond  here\nthird\n  indented fourth\nlast line"),
+            ("gg2jdd2ku", "alpha beta gamma\nsecond line here\nthird\n  indented fourth\nlast line"),
+        ];
+        let mut failures = Vec::new();
+        for (keys, expected) in cases {
+            let mut vi = Vi::new("vi", None, TEXT);
+            type_keys(&mut vi, keys);
+            if text(&vi) != *expected {
+                failures.push(format!("{:?}: {:?} != {:?}", keys, text(&vi), expected));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
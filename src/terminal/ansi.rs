@@ -0,0 +1,230 @@
+//! Just enough of ANSI SGR (`ESC [ ... m`) for commands to colour parts of
+//! a line, the way `grep --color` does on a real terminal, plus OSC 8
+//! (`ESC ] 8 ; ; URL ESC \\`) to make a run of text a link.
+
+use super::buffer::Span;
+
+pub const RESET: &str = "\x1b[0m";
+pub const REVERSE: &str = "\x1b[7m";
+pub const NO_REVERSE: &str = "\x1b[27m";
+/// Ends an OSC 8 link.
+pub const LINK_END: &str = "\x1b]8;;\x1b\\";
+
+/// Wraps `text` in the SGR sequence `code` (e.g. `"1;31"`) and a reset.
+pub fn paint(text: &str, code: &str) -> String {
+    format!("\x1b[{}m{}{}", code, text, RESET)
+}
+
+/// Starts an OSC 8 link to `url`; the text up to `LINK_END` is the link.
+pub fn link(url: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\", url)
+}
+
+/// The target of an OSC 8 escape: `Some("")` for one that ends a link,
+/// None if `escape` is not OSC 8.
+fn link_target(escape: &str) -> Option<&str> {
+    let body = escape.strip_prefix("\x1b]8;")?;
+    let body = body
+        .strip_suffix("\x1b\\")
+        .or_else(|| body.strip_suffix('\x07'))
+        .unwrap_or(body);
+    // Parameters come before the second `;`.
+    body.split_once(';').map(|(_, url)| url)
+}
+
+/// Splits `text` into runs of visible text and the escape sequences
+/// between them. Escapes are `Err`, visible runs `Ok`.
+fn tokens(text: &str) -> Vec<Result<&str, &str>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('\x1b') {
+        if start > 0 {
+            tokens.push(Ok(&rest[..start]));
+        }
+        let escape = &rest[start..];
+        let end = match escape.get(1..2) {
+            Some("[") => escape[2..]
+                .find(|c: char| c.is_ascii_alphabetic())
+                .map_or(escape.len(), |end| end + 3),
+            // OSC runs to BEL or to ST (`ESC \\`).
+            Some("]") => match escape.find(['\x07', '\\']) {
+                Some(end) => end + 1,
+                None => escape.len(),
+            },
+            _ => 1,
+        };
+        tokens.push(Err(&escape[..end]));
+        rest = &escape[end..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Ok(rest));
+    }
+    tokens
+}
+
+/// `text` without any escape sequences.
+pub fn strip(text: &str) -> String {
+    if !text.contains('\x1b') {
+        return text.to_string();
+    }
+    tokens(text).into_iter().filter_map(Result::ok).collect()
+}
+
+/// Visible characters of `text` with their byte offsets.
+pub fn visible_chars(text: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut offset = 0;
+    for token in tokens(text) {
+        if let Ok(run) = token {
+            chars.extend(run.char_indices().map(|(i, c)| (offset + i, c)));
+        }
+        offset += token.unwrap_or_else(|escape| escape).len();
+    }
+    chars
+}
+
+/// The escape sequences still in effect at the end of `text`, so a
+/// wrapped continuation line can pick up the same colour and link.
+pub fn active_style(text: &str) -> String {
+    let mut style = String::new();
+    let mut link = "";
+    for token in tokens(text) {
+        if let Err(escape) = token {
+            if let Some(target) = link_target(escape) {
+                link = if target.is_empty() { "" } else { escape };
+            } else if escape == RESET || escape == "\x1b[m" {
+                style.clear();
+            } else if escape.ends_with('m') {
+                style.push_str(escape);
+            }
+        }
+    }
+    style + link
+}
+
+/// Visible characters `start..end` of `text`, opened with the styles in
+/// effect at `start` and closed with a reset if any are open, so the slice
+/// stands on its own as a line.
+pub fn slice(text: &str, start: usize, end: usize) -> String {
+    let chars = visible_chars(text);
+    let offset = |index: usize| chars.get(index).map_or(text.len(), |&(o, _)| o);
+    let (from, to) = (offset(start), offset(end));
+    let style = active_style(&text[..from]);
+    let reset = if style.is_empty() { "" } else { RESET };
+    format!("{}{}{}", style, &text[from..to], reset)
+}
+
+/// Puts each `start..end` range of visible characters in `text` in
+/// reverse video, the way `less` marks search matches.
+pub fn mark(text: &str, ranges: &[(usize, usize)]) -> String {
+    if ranges.is_empty() {
+        return text.to_string();
+    }
+    let chars = visible_chars(text);
+    let offset = |index: usize| chars.get(index).map_or(text.len(), |&(o, _)| o);
+
+    let mut out = String::new();
+    let mut copied = 0;
+    for &(start, end) in ranges {
+        let (from, to) = (offset(start), offset(end));
+        if from < copied || from >= to {
+            continue;
+        }
+        out.push_str(&text[copied..from]);
+        out.push_str(REVERSE);
+        out.push_str(&text[from..to]);
+        out.push_str(NO_REVERSE);
+        copied = to;
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Visible runs of `text` as spans, each with the style it should be
+/// drawn in.
+pub fn spans(text: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut style = Span::default();
+
+    for token in tokens(text) {
+        match token {
+            Ok(run) => match spans.last_mut() {
+                Some(last) if last.same_style(&style) => last.text.push_str(run),
+                _ => spans.push(Span {
+                    text: run.to_string(),
+                    ..style.clone()
+                }),
+            },
+            Err(escape) => {
+                if let Some(target) = link_target(escape) {
+                    style.link = Some(target.to_string()).filter(|url| !url.is_empty());
+                } else if let Some(params) = escape
+                    .strip_prefix("\x1b[")
+                    .and_then(|rest| rest.strip_suffix('m'))
+                {
+                    apply_sgr(params, &mut style);
+                }
+            }
+        }
+    }
+    spans
+}
+
+/// Applies an SGR escape to `style`. A reset leaves any link open, as
+/// terminals do.
+fn apply_sgr(params: &str, style: &mut Span) {
+    let codes: Vec<u16> = params
+        .split(';')
+        .map(|code| code.parse().unwrap_or(0))
+        .collect();
+
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => {
+                *style = Span {
+                    link: style.link.take(),
+                    ..Span::default()
+                }
+            }
+            1 => style.bold = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            7 => style.inverse = true,
+            22 => style.bold = false,
+            23 => style.italic = false,
+            24 => style.underline = false,
+            27 => style.inverse = false,
+            39 => style.fg = None,
+            49 => style.bg = None,
+            code @ 30..=37 => style.fg = Some(BASIC[(code - 30) as usize].to_string()),
+            code @ 40..=47 => style.bg = Some(BASIC[(code - 40) as usize].to_string()),
+            code @ 90..=97 => style.fg = Some(BRIGHT[(code - 90) as usize].to_string()),
+            code @ 100..=107 => style.bg = Some(BRIGHT[(code - 100) as usize].to_string()),
+            code @ (38 | 48) if codes.get(i + 1) == Some(&2) && i + 4 < codes.len() => {
+                let color = format!(
+                    "#{:02x}{:02x}{:02x}",
+                    codes[i + 2],
+                    codes[i + 3],
+                    codes[i + 4]
+                );
+                if code == 38 {
+                    style.fg = Some(color);
+                } else {
+                    style.bg = Some(color);
+                }
+                i += 4;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+const BASIC: [&str; 8] = [
+    "#000000", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+const BRIGHT: [&str; 8] = [
+    "gray", "#ff5555", "#55ff55", "#ffff55", "#5555ff", "#ff55ff", "#55ffff", "#ffffff",
+];
@@ -5,9 +5,82 @@ pub struct AutoComplete {
 impl AutoComplete {
     pub fn new() -> Self {
         let commands = vec![
-            "help", "clear", "history", "echo", "date", "uptime", "neofetch", "uname", "ls", "ll",
-            "cd", "cat", "pwd", "tree", "mkdir", "touch", "rm", "ln", "sudo", "cowsay", "sl",
-            "lolcat", "calc",
+            "help",
+            "clear",
+            "history",
+            "echo",
+            "date",
+            "uptime",
+            "neofetch",
+            "uname",
+            "ls",
+            "ll",
+            "cd",
+            "cat",
+            "pwd",
+            "tree",
+            "mkdir",
+            "touch",
+            "rm",
+            "ln",
+            "sudo",
+            "cowsay",
+            "sl",
+            "lolcat",
+            "calc",
+            "chmod",
+            "chown",
+            "chgrp",
+            "su",
+            "logout",
+            "exit",
+            "whoami",
+            "id",
+            "groups",
+            "users",
+            "readlink",
+            "realpath",
+            "find",
+            "grep",
+            "head",
+            "tail",
+            "wc",
+            "sort",
+            "uniq",
+            "cut",
+            "tr",
+            "rev",
+            "nl",
+            "sed",
+            "awk",
+            "diff",
+            "patch",
+            "du",
+            "df",
+            "file",
+            "stat",
+            "mount",
+            "umount",
+            "undo",
+            "trash",
+            "tar",
+            "gzip",
+            "upload",
+            "download",
+            "gunzip",
+            "zcat",
+            "base64",
+            "xxd",
+            "hexdump",
+            "sha256sum",
+            "md5sum",
+            "less",
+            "more",
+            "nano",
+            "view",
+            "bat",
+            "vi",
+            "vim",
         ]
         .into_iter()
         .map(|s| s.to_string())
@@ -25,15 +98,19 @@ impl AutoComplete {
         let parts: Vec<&str> = trimmed.split_whitespace().collect();
 
         if parts.len() == 1 {
-            self.complete_command(&parts[0])
+            self.complete_command(parts[0])
         } else {
             let command = parts[0];
             let partial_path = parts.last().map_or("", |v| v);
 
             match command {
-                "cd" | "ls" | "cat" | "tree" | "rm" | "mkdir" | "touch" | "ln" => {
-                    self.complete_path(partial_path, current_path, command == "cd")
-                }
+                "cd" | "ls" | "cat" | "tree" | "rm" | "mkdir" | "touch" | "ln" | "chmod"
+                | "chown" | "chgrp" | "readlink" | "realpath" | "find" | "grep" | "head"
+                | "tail" | "wc" | "sort" | "uniq" | "cut" | "rev" | "nl" | "sed" | "awk"
+                | "diff" | "patch" | "du" | "df" | "file" | "stat" | "mount" | "umount" | "tar"
+                | "gzip" | "gunzip" | "zcat" | "download" | "base64" | "xxd" | "hexdump"
+                | "sha256sum" | "md5sum" | "less" | "more" | "nano" | "view" | "bat" | "vi"
+                | "vim" => self.complete_path(partial_path, current_path, command == "cd"),
                 _ => CompletionResult::None,
             }
         }
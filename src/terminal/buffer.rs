@@ -1,3 +1,4 @@
+use super::ansi;
 use std::cell::RefCell;
 use std::collections::VecDeque;
 #[derive(Debug, Clone, PartialEq)]
@@ -9,72 +10,214 @@ pub enum LineType {
     Typing,
 }
 
+/// A run of a line drawn in one style: foreground and background colour
+/// (`None` for the defaults), font style, whether the two colours are
+/// swapped, and the URL it links to. Lines are drawn span by span, so one
+/// line can mix colours; escapes in command output become spans.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+    pub link: Option<String>,
+}
+
+impl Span {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_fg(mut self, color: &str) -> Self {
+        self.fg = Some(color.to_string());
+        self
+    }
+
+    /// Whether `other` is drawn the same way, whatever its text.
+    pub fn same_style(&self, other: &Span) -> bool {
+        Span {
+            text: String::new(),
+            ..self.clone()
+        } == Span {
+            text: String::new(),
+            ..other.clone()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BufferLine {
-    pub content: String,
+    pub spans: Vec<Span>,
     pub line_type: LineType,
-    pub color: Option<String>,
     pub _timestamp: f64,
-    pub wrapped_lines: Vec<String>,
+    /// The spans of each screen row, once wrapped to the terminal.
+    pub rows: Vec<Vec<Span>>,
 }
 
 impl BufferLine {
-    pub fn new(content: String, line_type: LineType, color: Option<String>) -> Self {
+    pub fn new(spans: Vec<Span>, line_type: LineType) -> Self {
         Self {
-            content,
+            spans,
             line_type,
-            color,
             _timestamp: js_sys::Date::now(),
-            wrapped_lines: Vec::new(),
+            rows: Vec::new(),
         }
     }
 
     pub fn calc_wrapping(&mut self, max_width: usize) {
-        self.wrapped_lines.clear();
+        self.rows = wrap_spans(&self.spans, max_width);
+    }
 
-        let chars: Vec<char> = self.content.chars().collect();
+    pub fn get_line_count(&self) -> usize {
+        self.rows.len().max(1)
+    }
+}
 
-        if chars.len() <= max_width {
-            self.wrapped_lines.push(self.content.clone());
-            return;
+/// A line of output as spans: its escapes turned into styles, bare URLs
+/// into links, and `color` given to any text without a colour of its own.
+pub fn spans(content: &str, color: Option<&str>) -> Vec<Span> {
+    let mut spans = linkify(ansi::spans(content));
+    if let Some(color) = color {
+        for span in spans.iter_mut().filter(|span| span.link.is_none()) {
+            span.fg.get_or_insert_with(|| color.to_string());
         }
+    }
+    spans
+}
+
+/// Splits bare `http://` and `https://` URLs out of `spans` into spans
+/// linking to them. A URL runs up to whitespace or a `]`.
+pub fn linkify(spans: Vec<Span>) -> Vec<Span> {
+    let mut out = Vec::new();
+    for span in spans {
+        if span.link.is_some() {
+            out.push(span);
+            continue;
+        }
+        let piece = |text: &str, link: Option<&str>| Span {
+            text: text.to_string(),
+            link: link.map(str::to_string),
+            ..span.clone()
+        };
+        let mut rest = span.text.as_str();
+        while let Some(start) = rest
+            .match_indices("http")
+            .map(|(start, _)| start)
+            .find(|&start| {
+                rest[start..].starts_with("http://") || rest[start..].starts_with("https://")
+            })
+        {
+            let end = rest[start..]
+                .find(|c: char| c.is_whitespace() || c == ']')
+                .map_or(rest.len(), |end| start + end);
+            if start > 0 {
+                out.push(piece(&rest[..start], None));
+            }
+            out.push(piece(&rest[start..end], Some(&rest[start..end])));
+            rest = &rest[end..];
+        }
+        if !rest.is_empty() {
+            out.push(piece(rest, None));
+        }
+    }
+    out
+}
+
+/// Where a line of `chars` breaks to fit `max_width` columns. Breaks fall
+/// on spaces where possible, and the space itself is dropped.
+fn break_ranges(chars: &[char], max_width: usize) -> Vec<(usize, usize)> {
+    if chars.len() <= max_width || max_width == 0 {
+        return vec![(0, chars.len())];
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + max_width).min(chars.len());
 
-        let mut start = 0;
-        while start < chars.len() {
-            let end = (start + max_width).min(chars.len());
+        if end >= chars.len() {
+            ranges.push((start, chars.len()));
+            break;
+        }
 
-            if end >= chars.len() {
-                let chunk: String = chars[start..].iter().collect();
-                self.wrapped_lines.push(chunk);
+        let mut break_point = end;
+        for i in (start..end).rev() {
+            if chars[i] == ' ' {
+                break_point = i;
                 break;
             }
+        }
 
-            let mut break_point = end;
-            for i in (start..end).rev() {
-                if chars[i] == ' ' {
-                    break_point = i;
-                    break;
-                }
-            }
+        ranges.push((start, break_point));
+
+        start = if break_point < end && chars[break_point] == ' ' {
+            break_point + 1
+        } else {
+            break_point
+        };
+    }
+    ranges
+}
 
-            let chunk: String = chars[start..break_point].iter().collect();
-            self.wrapped_lines.push(chunk);
+/// Where `content` breaks to fit `max_width` columns, as ranges of visible
+/// characters. Colour escapes take no width.
+pub fn wrap_ranges(content: &str, max_width: usize) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = ansi::visible_chars(content)
+        .into_iter()
+        .map(|(_, c)| c)
+        .collect();
+    break_ranges(&chars, max_width)
+}
 
-            start = if break_point < end && chars[break_point] == ' ' {
-                break_point + 1
-            } else {
-                break_point
-            };
-        }
+/// `content` split into lines of at most `max_width` columns, each
+/// carrying the colour it started in.
+pub fn wrap(content: &str, max_width: usize) -> Vec<String> {
+    match wrap_ranges(content, max_width).as_slice() {
+        [_] => vec![content.to_string()],
+        ranges => ranges
+            .iter()
+            .map(|&(start, end)| ansi::slice(content, start, end))
+            .collect(),
     }
+}
 
-    pub fn get_line_count(&self) -> usize {
-        if self.wrapped_lines.is_empty() {
-            1
-        } else {
-            self.wrapped_lines.len()
+/// A line of spans split into rows of at most `max_width` columns. A span
+/// cut by a break keeps its style, and its link, on both rows.
+pub fn wrap_spans(spans: &[Span], max_width: usize) -> Vec<Vec<Span>> {
+    let chars: Vec<char> = spans.iter().flat_map(|span| span.text.chars()).collect();
+    break_ranges(&chars, max_width)
+        .into_iter()
+        .map(|(start, end)| slice_spans(spans, start, end))
+        .collect()
+}
+
+/// Characters `start..end` of a line of spans.
+pub fn slice_spans(spans: &[Span], start: usize, end: usize) -> Vec<Span> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let len = span.text.chars().count();
+        let (from, to) = (start.max(offset), end.min(offset + len));
+        if from < to {
+            out.push(Span {
+                text: span
+                    .text
+                    .chars()
+                    .skip(from - offset)
+                    .take(to - from)
+                    .collect(),
+                ..span.clone()
+            });
         }
+        offset += len;
     }
+    out
 }
 
 #[derive(Debug, Clone)]
@@ -89,6 +232,10 @@ pub struct TerminalState {
 #[derive(Debug, Clone, PartialEq)]
 pub enum InputMode {
     Normal,
+    /// Reading a password: input is masked and kept out of history.
+    Password,
+    /// A full-screen program such as `less` has the keyboard.
+    Program,
     Disabled,
 }
 
@@ -136,8 +283,16 @@ impl LineBuffer {
         }
     }
 
+    pub fn width(&self) -> usize {
+        *self.terminal_width.borrow()
+    }
+
     pub fn add_line(&self, content: String, line_type: LineType, color: Option<String>) {
-        let mut line = BufferLine::new(content, line_type, color);
+        self.add_spans(spans(&content, color.as_deref()), line_type);
+    }
+
+    pub fn add_spans(&self, spans: Vec<Span>, line_type: LineType) {
+        let mut line = BufferLine::new(spans, line_type);
         let width = *self.terminal_width.borrow();
         line.calc_wrapping(width);
 
@@ -265,6 +420,11 @@ pub fn add_line(content: String, line_type: LineType, color: Option<String>) {
     LINE_BUFFER.with(|buffer| buffer.add_line(content, line_type, color));
 }
 
+/// Adds a line already split into styled spans.
+pub fn add_spans(spans: Vec<Span>, line_type: LineType) {
+    LINE_BUFFER.with(|buffer| buffer.add_spans(spans, line_type));
+}
+
 pub fn add_command_line(prompt: &str, input: &str) {
     LINE_BUFFER.with(|buffer| buffer.add_command(prompt, input));
 }
@@ -281,6 +441,19 @@ pub fn set_terminal_dimensions(width: usize, height: usize) {
     LINE_BUFFER.with(|buffer| buffer.set_dimensions(width, height));
 }
 
+/// How many screen rows `text` takes once wrapped to the terminal.
+pub fn visual_line_count(text: &str) -> usize {
+    let width = terminal_width();
+    text.lines()
+        .map(|line| wrap_ranges(line, width).len())
+        .sum()
+}
+
+/// Characters per line at the last render, for commands laying out columns.
+pub fn terminal_width() -> usize {
+    LINE_BUFFER.with(|buffer| buffer.width())
+}
+
 pub fn get_visible_lines(max_lines: usize) -> Vec<BufferLine> {
     LINE_BUFFER.with(|buffer| buffer.get_visible_lines(max_lines))
 }
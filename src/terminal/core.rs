@@ -1,6 +1,6 @@
 use super::buffer;
 use super::renderer::{LineOptions, TerminalRenderer};
-use crate::commands::CommandHandler;
+use crate::commands::{users, CommandHandler};
 use js_sys::Promise;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
@@ -10,7 +10,7 @@ use web_sys::{window, CanvasRenderingContext2d, Document, HtmlCanvasElement};
 pub struct Terminal {
     pub renderer: TerminalRenderer,
     pub command_handler: CommandHandler,
-    pub base_prompt: String,
+    pub hostname: String,
 }
 
 impl Terminal {
@@ -36,7 +36,7 @@ impl Terminal {
 
         let renderer = TerminalRenderer::new(canvas.clone(), context);
         let command_handler = CommandHandler::new();
-        let base_prompt = "objz@portfolio".to_string();
+        let hostname = "portfolio".to_string();
 
         buffer::set_terminal_dimensions(
             renderer.max_chars_per_line(),
@@ -46,7 +46,7 @@ impl Terminal {
         let terminal = Self {
             renderer,
             command_handler,
-            base_prompt,
+            hostname,
         };
 
         terminal.setup_events(&canvas);
@@ -84,15 +84,16 @@ impl Terminal {
 
     pub fn get_current_prompt(&self) -> String {
         let cwd = self.command_handler.get_working_dir();
-        let display_path = if cwd == "/home/objz" {
-            "~".to_string()
-        } else if cwd.starts_with("/home/objz/") {
-            format!("~{}", &cwd["/home/objz".len()..])
-        } else {
-            cwd
-        };
-
-        format!("{}:{}$ ", self.base_prompt, display_path)
+        let session = users::current_session();
+        let sigil = if session.is_root() { '#' } else { '$' };
+
+        format!(
+            "{}@{}:{}{} ",
+            session.user,
+            self.hostname,
+            users::display_path(&cwd),
+            sigil
+        )
     }
 
     pub async fn sleep(&self, ms: i32) {
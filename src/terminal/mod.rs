@@ -1,3 +1,4 @@
+pub mod ansi;
 pub mod autocomplete;
 pub mod buffer;
 pub mod core;
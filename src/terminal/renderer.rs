@@ -1,5 +1,6 @@
-use super::buffer::{self, BufferLine, InputMode, LineType, TerminalState};
+use super::buffer::{self, BufferLine, InputMode, LineType, Span, TerminalState};
 use super::linkmap::LinkMap;
+use crate::programs::{self, Screen, Size};
 use js_sys::Promise;
 use std::cell::{Cell, RefCell};
 use wasm_bindgen::prelude::*;
@@ -97,10 +98,12 @@ impl TerminalRenderer {
             self.sleep(60).await;
         }
 
-        let final_text = format!("{} [OK]", task);
+        let mut task = Span::new(task);
+        task.fg = opts.color.clone();
+        let spans = vec![task, Span::new(" [OK]").with_fg("#00ff00")];
         self.clear_line_at_y(y);
-        self.draw_boot_line(&final_text, y, opts.color.as_deref());
-        buffer::add_line(final_text, LineType::Boot, opts.color.clone());
+        self.draw_spans(&spans, 10.0, y, None);
+        buffer::add_spans(spans, LineType::Boot);
         self.advance_y();
         self.handle_scroll_if_needed();
     }
@@ -148,8 +151,20 @@ impl TerminalRenderer {
         ((self.width as f64 - 20.0) / self.char_width) as usize
     }
 
+    /// The cells a full-screen program gets: every row, no prompt.
+    pub fn program_size(&self) -> Size {
+        Size {
+            width: self.max_chars_per_line(),
+            height: self.max_visible_lines(),
+        }
+    }
+
     pub fn render(&self) {
         self.clear_screen();
+        if let Some(screen) = programs::view(self.program_size()) {
+            self.render_screen(&screen);
+            return;
+        }
         buffer::set_terminal_dimensions(self.max_chars_per_line(), self.max_visible_lines());
         let visible_lines = buffer::get_visible_lines(self.max_visible_lines() - 2);
         let state = buffer::get_terminal_state();
@@ -161,32 +176,28 @@ impl TerminalRenderer {
 
         self.y.set(y_offset);
 
-        if state.input_mode == InputMode::Normal {
+        if state.input_mode != InputMode::Disabled {
             self.render_input_line(&state, y_offset);
         }
     }
 
     fn render_line(&self, line: &BufferLine, y: f64) -> f64 {
-        if line.line_type == LineType::Boot {
-            self.draw_boot_line(&line.content, y, line.color.as_deref());
-            if line.wrapped_lines.is_empty() {
-                self.line_height
-            } else {
-                self.line_height * line.wrapped_lines.len() as f64
-            }
-        } else {
-            let color = self.get_color(&line.line_type, line.color.as_deref());
+        let color = self.get_color(&line.line_type);
+        for (row, spans) in line.rows.iter().enumerate() {
+            let row_y = y + row as f64 * self.line_height;
+            self.draw_spans(spans, 10.0, row_y, Some(color));
+        }
+        self.line_height * line.get_line_count() as f64
+    }
 
-            if line.wrapped_lines.is_empty() {
-                self.draw_text(&line.content, 10.0, y, Some(&color));
-                self.line_height
-            } else {
-                let mut current_y = y;
-                for wrapped_line in &line.wrapped_lines {
-                    self.draw_text(wrapped_line, 10.0, current_y, Some(&color));
-                    current_y += self.line_height;
-                }
-                self.line_height * line.wrapped_lines.len() as f64
+    fn render_screen(&self, screen: &Screen) {
+        let row_y = |row: usize| 20.0 + row as f64 * self.line_height;
+        for (row, line) in screen.lines.iter().enumerate() {
+            self.draw_text(line, 10.0, row_y(row), None);
+        }
+        if let Some((row, column)) = screen.cursor {
+            if self.cursor_blink_state.get() {
+                self.draw_cursor(10.0 + column as f64 * self.char_width, row_y(row));
             }
         }
     }
@@ -199,7 +210,12 @@ impl TerminalRenderer {
         let input_x = 10.0 + prompt_width;
 
         if !state.current_input.is_empty() {
-            self.draw_text(&state.current_input, input_x, y, Some("#ffffff"));
+            let input = if state.input_mode == InputMode::Password {
+                "•".repeat(state.current_input.chars().count())
+            } else {
+                state.current_input.clone()
+            };
+            self.draw_text(&input, input_x, y, Some("#ffffff"));
         }
 
         if self.cursor_blink_state.get() {
@@ -208,96 +224,73 @@ impl TerminalRenderer {
         }
     }
 
+    /// Draws `text` from `x`: escapes become styles and URLs links, and
+    /// anything without a colour of its own takes `color`.
     pub fn draw_text(&self, text: &str, x: f64, y: f64, color: Option<&str>) {
-        self.linkmap
-            .borrow_mut()
-            .detect_links(text, x, y, self.char_width, self.line_height);
-
-        self.context.save();
-        self.setup_font();
+        self.draw_spans(&buffer::spans(text, None), x, y, color);
+    }
 
+    /// Draws a row of spans from `x`; spans without a colour of their own
+    /// take `color`.
+    fn draw_spans(&self, spans: &[Span], x: f64, y: f64, color: Option<&str>) {
         let mut current_x = x;
-        let mut pos = 0;
-
-        while pos < text.len() {
-            if let Some(http_start) = text[pos..].find("http") {
-                let absolute_start = pos + http_start;
-
-                let before_text = &text[pos..absolute_start];
-                if !before_text.is_empty() {
-                    self.set_fill_color(&self.get_color_value(color.unwrap_or("#ffffff")));
-                    let _ = self.context.fill_text(before_text, current_x, y);
-                    current_x += before_text.len() as f64 * self.char_width;
-                }
-
-                let remaining = &text[absolute_start..];
-                let url_end = remaining
-                    .find(|c: char| c.is_whitespace() || c == '\n' || c == ']')
-                    .unwrap_or(remaining.len());
-                let potential_url = &remaining[..url_end];
-
-                if potential_url.starts_with("http://") || potential_url.starts_with("https://") {
-                    self.set_fill_color("#00ffff");
-                    let _ = self.context.fill_text(potential_url, current_x, y);
-
-                    self.context.save();
-                    self.context.set_stroke_style_str("#00ffff");
-                    self.context.set_line_width(1.0);
-                    self.context.begin_path();
-                    self.context.move_to(current_x, y + self.line_height - 2.0);
-                    self.context.line_to(
-                        current_x + (potential_url.len() as f64 * self.char_width),
-                        y + self.line_height - 2.0,
-                    );
-                    let _ = self.context.stroke();
-                    self.context.restore();
-
-                    current_x += potential_url.len() as f64 * self.char_width;
-                    pos = absolute_start + url_end;
-                } else {
-                    self.set_fill_color(&self.get_color_value(color.unwrap_or("#ffffff")));
-                    let _ = self.context.fill_text(
-                        &text[absolute_start..absolute_start + 4],
-                        current_x,
-                        y,
-                    );
-                    current_x += 4.0 * self.char_width;
-                    pos = absolute_start + 4;
-                }
-            } else {
-                let remaining_text = &text[pos..];
-                self.set_fill_color(&self.get_color_value(color.unwrap_or("#ffffff")));
-                let _ = self.context.fill_text(remaining_text, current_x, y);
-                break;
-            }
+        for span in spans {
+            current_x += self.draw_span(span, current_x, y, color);
         }
-
-        self.context.restore();
     }
 
-    pub fn draw_boot_line(&self, text: &str, y: f64, color: Option<&str>) {
-        self.context.save();
-        self.setup_font();
-
-        if let Some(ok_pos) = text.rfind(" [OK]") {
-            let main_text = &text[..ok_pos];
-            let ok_text = " [OK]";
-
-            self.set_fill_color(&self.get_color_value(color.unwrap_or("#ffffff")));
-            self.context.fill_text(main_text, 10.0, y).unwrap();
-
-            let main_width = main_text.len() as f64 * self.char_width;
+    /// Draws one span: its background, its text in its font style, and an
+    /// underline if asked for or if it is a link, which is made clickable.
+    /// Returns how wide it was.
+    fn draw_span(&self, span: &Span, x: f64, y: f64, color: Option<&str>) -> f64 {
+        let width = span.text.chars().count() as f64 * self.char_width;
+        let fg = match (&span.fg, &span.link, color) {
+            (Some(fg), _, _) => self.get_color_value(fg),
+            (None, Some(_), _) => "#00ffff".to_string(),
+            (None, None, color) => self.get_color_value(color.unwrap_or("#ffffff")),
+        };
+        let bg = span.bg.as_deref().map(|bg| self.get_color_value(bg));
+        let (fg, bg) = if span.inverse {
+            (bg.unwrap_or_else(|| "#000000".to_string()), Some(fg))
+        } else {
+            (fg, bg)
+        };
 
-            self.set_fill_color("#00ff00");
+        self.context.save();
+        if let Some(bg) = bg {
+            self.set_fill_color(&bg);
             self.context
-                .fill_text(ok_text, 10.0 + main_width, y)
-                .unwrap();
-        } else {
-            self.set_fill_color(&self.get_color_value(color.unwrap_or("#ffffff")));
-            self.context.fill_text(text, 10.0, y).unwrap();
+                .fill_rect(x, y - 2.0, width, self.line_height - 2.0);
         }
 
+        let mut font = String::new();
+        if span.italic {
+            font.push_str("italic ");
+        }
+        if span.bold {
+            font.push_str("bold ");
+        }
+        self.context.set_font(&format!("{}14px monospace", font));
+        self.context.set_text_baseline("top");
+        self.set_fill_color(&fg);
+        let _ = self.context.fill_text(&span.text, x, y);
+
+        if span.underline || span.link.is_some() {
+            self.context.set_stroke_style_str(&fg);
+            self.context.set_line_width(1.0);
+            self.context.begin_path();
+            self.context.move_to(x, y + self.line_height - 2.0);
+            self.context.line_to(x + width, y + self.line_height - 2.0);
+            self.context.stroke();
+        }
         self.context.restore();
+
+        if let Some(url) = &span.link {
+            self.linkmap
+                .borrow_mut()
+                .add_link(url, x, x + width, y, self.line_height);
+        }
+        width
     }
 
     fn clear_line_at_y(&self, y: f64) {
@@ -328,16 +321,7 @@ impl TerminalRenderer {
         );
     }
 
-    fn setup_font(&self) {
-        self.context.set_font("14px monospace");
-        self.context.set_text_baseline("top");
-    }
-
-    fn get_color(&self, line_type: &LineType, custom_color: Option<&str>) -> String {
-        if let Some(color) = custom_color {
-            return self.get_color_value(color);
-        }
-
+    fn get_color(&self, line_type: &LineType) -> &'static str {
         match line_type {
             LineType::Command => "#00ffff",
             LineType::Output => "#ffffff",
@@ -345,7 +329,6 @@ impl TerminalRenderer {
             LineType::Typing => "#ffffff",
             LineType::Normal => "#ffffff",
         }
-        .to_string()
     }
 
     pub fn get_color_value(&self, color: &str) -> String {
@@ -0,0 +1,159 @@
+//! The page around the terminal. Commands that need the browser itself,
+//! a file picker or a download, go through `HostBridge`, so native
+//! builds can swap in `FakeBridge` and script what the visitor "picks".
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use js_sys::{Array, Promise, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, Url};
+
+/// A file crossing between the visitor's machine and the filesystem.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostFile {
+    pub name: String,
+    pub content: Vec<u8>,
+}
+
+/// What the picker resolves to: `None` when the visitor cancels it.
+pub type PickFuture = Pin<Box<dyn Future<Output = Result<Option<HostFile>, String>>>>;
+
+pub trait HostBridge {
+    /// Opens a file picker and reads whatever the visitor chooses.
+    fn pick_file(&self) -> PickFuture;
+    /// Saves `content` on the visitor's machine as `name`.
+    fn download(&self, name: &str, content: &[u8]) -> Result<(), String>;
+}
+
+thread_local! {
+    static BRIDGE: RefCell<Rc<dyn HostBridge>> = RefCell::new(default_bridge());
+}
+
+fn default_bridge() -> Rc<dyn HostBridge> {
+    if cfg!(target_arch = "wasm32") {
+        Rc::new(Browser)
+    } else {
+        Rc::new(FakeBridge::default())
+    }
+}
+
+/// The bridge commands should use.
+pub fn bridge() -> Rc<dyn HostBridge> {
+    BRIDGE.with(|bridge| bridge.borrow().clone())
+}
+
+/// Replaces the bridge, returning the previous one. Only native tests
+/// do this; the page keeps the bridge it started with.
+#[cfg(test)]
+pub fn set_bridge(bridge: Rc<dyn HostBridge>) -> Rc<dyn HostBridge> {
+    BRIDGE.with(|slot| slot.replace(bridge))
+}
+
+fn js_error(err: JsValue) -> String {
+    err.as_string()
+        .or_else(|| {
+            err.dyn_ref::<js_sys::Error>()
+                .map(|err| String::from(err.message()))
+        })
+        .unwrap_or_else(|| "unknown browser error".to_string())
+}
+
+/// The real page: a hidden `<input type=file>` for picking and a blob URL
+/// behind an `<a download>` for saving.
+pub struct Browser;
+
+impl Browser {
+    async fn pick() -> Result<Option<HostFile>, JsValue> {
+        let document = window()
+            .and_then(|window| window.document())
+            .ok_or("no document")?;
+        let input: HtmlInputElement = document.create_element("input")?.dyn_into()?;
+        input.set_type("file");
+
+        // Both outcomes settle the same promise; afterwards the input
+        // tells us which one it was.
+        let settled = Promise::new(&mut |resolve, _reject| {
+            let settle = Closure::wrap(Box::new(move || {
+                let _ = resolve.call0(&JsValue::UNDEFINED);
+            }) as Box<dyn FnMut()>);
+            let callback = settle.as_ref().unchecked_ref();
+            let _ = input.add_event_listener_with_callback("change", callback);
+            let _ = input.add_event_listener_with_callback("cancel", callback);
+            settle.forget();
+        });
+        input.click();
+        JsFuture::from(settled).await?;
+
+        let file = match input.files().and_then(|files| files.get(0)) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let buffer = JsFuture::from(file.array_buffer()).await?;
+        Ok(Some(HostFile {
+            name: file.name(),
+            content: Uint8Array::new(&buffer).to_vec(),
+        }))
+    }
+
+    fn save(name: &str, content: &[u8]) -> Result<(), JsValue> {
+        let window = window().ok_or("no window")?;
+        let document = window.document().ok_or("no document")?;
+
+        let options = BlobPropertyBag::new();
+        options.set_type("application/octet-stream");
+        let parts = Array::of1(&Uint8Array::from(content));
+        let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+        let url = Url::create_object_url_with_blob(&blob)?;
+
+        let anchor: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+        anchor.set_href(&url);
+        anchor.set_download(name);
+        anchor.click();
+
+        // Revoking straight away can cancel the download in some browsers.
+        let revoke = Closure::once_into_js(move || {
+            let _ = Url::revoke_object_url(&url);
+        });
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000)?;
+        Ok(())
+    }
+}
+
+impl HostBridge for Browser {
+    fn pick_file(&self) -> PickFuture {
+        Box::pin(async { Self::pick().await.map_err(js_error) })
+    }
+
+    fn download(&self, name: &str, content: &[u8]) -> Result<(), String> {
+        Self::save(name, content).map_err(js_error)
+    }
+}
+
+/// A scripted stand-in: `pick_file` hands out `picks` in order (and
+/// cancels once they run out), `download` records into `downloads`.
+#[derive(Default)]
+pub struct FakeBridge {
+    pub picks: RefCell<VecDeque<HostFile>>,
+    pub downloads: RefCell<Vec<HostFile>>,
+}
+
+impl HostBridge for FakeBridge {
+    fn pick_file(&self) -> PickFuture {
+        let picked = self.picks.borrow_mut().pop_front();
+        Box::pin(async move { Ok(picked) })
+    }
+
+    fn download(&self, name: &str, content: &[u8]) -> Result<(), String> {
+        self.downloads.borrow_mut().push(HostFile {
+            name: name.to_string(),
+            content: content.to_vec(),
+        });
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::commands::{mounts, snapshot};
 use crate::terminal::buffer::InputMode;
 use crate::terminal::renderer::LineOptions;
 use crate::terminal::{buffer, Terminal};
@@ -22,7 +23,10 @@ pub async fn trigger(terminal: &Terminal) {
         ("Emergency shutdown in 2...", "warning"),
         ("Emergency shutdown in 1...", "warning"),
         ("", ""),
-        ("Shutdown failed. Recovery was possible.", "success"),
+        (
+            "Shutdown failed. Restoring filesystem from snapshot...",
+            "warning",
+        ),
     ];
 
     for (line, color) in panic_lines {
@@ -37,42 +41,28 @@ pub async fn trigger(terminal: &Terminal) {
         terminal.sleep(700).await;
     }
 
-    terminal.sleep(1000).await;
-    buffer::clear_buffer();
-    terminal.render();
-}
-
-pub fn should_panic(input: &str) -> bool {
-    let parts: Vec<&str> = input.split_whitespace().collect();
-
-    if parts.len() >= 4 && parts[0] == "sudo" && parts[1] == "rm" {
-        let has_rf_flag = parts.iter().any(|&part| {
-            part == "-rf" || part == "-fr" || part.contains("rf") || part.contains("fr")
-        });
-
-        if has_rf_flag {
-            for &part in &parts[3..] {
-                match part {
-                    "/" => return true,
-                    "./" => {
-                        use crate::commands::filesystem::CURRENT_PATH;
-                        let current_path = CURRENT_PATH.lock().unwrap();
-                        if current_path.is_empty() {
-                            return true;
-                        }
-                    }
-                    path if path.starts_with("./") => {
-                        use crate::commands::filesystem::CURRENT_PATH;
-                        let current_path = CURRENT_PATH.lock().unwrap();
-                        if current_path.is_empty() {
-                            return true;
-                        }
-                    }
-                    _ => {}
-                }
+    let (outcome, color) = match snapshot::revert() {
+        Some(_) => {
+            // The command left saving to us so the wipe never reaches storage.
+            match mounts::sync() {
+                Ok(()) => ("Recovery complete. Nothing was lost.", "success"),
+                Err(_) => (
+                    "Recovery complete, but the changes could not be saved.",
+                    "warning",
+                ),
             }
         }
-    }
+        None => ("Recovery failed: no snapshot to restore.", "error"),
+    };
+    terminal
+        .add_line(
+            outcome,
+            Some(LineOptions::new().with_typing(20).with_color(color)),
+        )
+        .await;
+    terminal.render();
 
-    false
+    terminal.sleep(1000).await;
+    buffer::clear_buffer();
+    terminal.render();
 }